@@ -18,14 +18,19 @@
 // To contact SUSE LLC about this file by physical or electronic mail, you may
 // find current contact information at www.suse.com.
 
-use agama_lib::{auth::AuthToken, error::ServiceError};
+use agama_lib::{
+    auth::{AuthToken, TokenScope},
+    error::ServiceError,
+};
 use clap::Subcommand;
+use serde_json::json;
 
 use crate::error::CliError;
 use agama_lib::base_http_client::BaseHTTPClient;
 use inquire::Password;
 use std::collections::HashMap;
 use std::io::{self, IsTerminal};
+use std::time::Duration;
 
 /// HTTP client to handle authentication
 struct AuthHTTPClient {
@@ -53,6 +58,28 @@ impl AuthHTTPClient {
             None => Err(anyhow::anyhow!("Failed to get authentication token")),
         }
     }
+
+    /// Asks the web server for a new token
+    pub async fn create_token(
+        &self,
+        scope: TokenScope,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<String> {
+        let body = json!({
+            "scope": scope,
+            "ttl": ttl.map(|t| t.as_secs())
+        });
+
+        let response = self
+            .api
+            .post::<HashMap<String, String>>("/auth/tokens", &body)
+            .await?;
+
+        match response.get("token") {
+            Some(token) => Ok(token.clone()),
+            None => Err(anyhow::anyhow!("Failed to create the token")),
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -67,6 +94,26 @@ pub enum AuthCommands {
     Logout,
     /// Print the used token to the standard output.
     Show,
+    /// Manage additional tokens (e.g., to give limited access to other tools).
+    #[command(subcommand)]
+    Token(TokenCommands),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TokenCommands {
+    /// Create a new token and print it to the standard output.
+    ///
+    /// The token can be restricted to a scope: "full" (full access), "read-only" (cannot change
+    /// anything) or "questions" (can follow the events and answer the questions). Creating a token
+    /// requires full access.
+    Create {
+        /// What the token grants access to.
+        #[arg(long, default_value_t = TokenScope::Full)]
+        scope: TokenScope,
+        /// Time to live of the token (e.g., "30m", "2h" or "7days"). One day by default.
+        #[arg(long, value_parser = humantime::parse_duration)]
+        ttl: Option<Duration>,
+    },
 }
 
 /// Main entry point called from agama CLI main loop
@@ -77,6 +124,9 @@ pub async fn run(client: BaseHTTPClient, subcommand: AuthCommands) -> anyhow::Re
         AuthCommands::Login => login(auth_client, read_password()?).await,
         AuthCommands::Logout => logout(),
         AuthCommands::Show => show(),
+        AuthCommands::Token(TokenCommands::Create { scope, ttl }) => {
+            create_token(auth_client, scope, ttl).await
+        }
     }
 }
 
@@ -120,6 +170,23 @@ fn logout() -> anyhow::Result<()> {
     Ok(AuthToken::remove_user_token()?)
 }
 
+/// Creates a new token and prints it on stdout.
+async fn create_token(
+    client: AuthHTTPClient,
+    scope: TokenScope,
+    ttl: Option<Duration>,
+) -> anyhow::Result<()> {
+    if ttl.is_some_and(|t| t.as_secs() == 0) {
+        return Err(anyhow::anyhow!(
+            "The time to live must be at least one second"
+        ));
+    }
+
+    let token = client.create_token(scope, ttl).await?;
+    println!("{}", token);
+    Ok(())
+}
+
 /// Shows stored JWT on stdout
 fn show() -> anyhow::Result<()> {
     // we do not care if jwt() fails or not. If there is something to print, show it otherwise
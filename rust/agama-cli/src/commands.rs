@@ -20,8 +20,9 @@
 
 use crate::auth::AuthCommands;
 use crate::config::ConfigCommands;
+use crate::events::EventsArgs;
 use crate::logs::LogsCommands;
-use crate::profile::ProfileCommands;
+use crate::profile::{IntegrityArgs, ProfileCommands, TransferArgs};
 use crate::questions::QuestionsCommands;
 use crate::FinishMethod;
 use clap::Subcommand;
@@ -79,6 +80,13 @@ pub enum Commands {
     #[command(subcommand)]
     Logs(LogsCommands),
 
+    /// Display the events emitted by the installer.
+    ///
+    /// It follows the events stream of Agama's server, writing each event as a JSON document in its
+    /// own line. Use the "--type" and "--service" options to display only the events you are
+    /// interested in.
+    Events(EventsArgs),
+
     /// Authenticate with Agama's server.
     ///
     /// Unless you are executing this program as root, you need to authenticate with Agama's server
@@ -105,6 +113,10 @@ pub enum Commands {
     Download {
         /// URL pointing to file for download
         url: String,
+        #[command(flatten)]
+        integrity: IntegrityArgs,
+        #[command(flatten)]
+        transfer: TransferArgs,
     },
     /// Finish the installation rebooting the system by default.
     ///
@@ -20,16 +20,19 @@
 
 use std::{
     io::{self, Read},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::Command,
 };
 
 use crate::show_progress;
 use agama_lib::{
-    base_http_client::BaseHTTPClient, install_settings::InstallSettings, Store as SettingsStore,
+    base_http_client::BaseHTTPClient,
+    dry_run::SystemSnapshot,
+    install_settings::{self, InstallSettings, Section},
+    Store as SettingsStore,
 };
 use anyhow::anyhow;
-use clap::Subcommand;
+use clap::{Subcommand, ValueEnum};
 use std::io::Write;
 use tempfile::Builder;
 
@@ -46,7 +49,41 @@ pub enum ConfigCommands {
     Show,
 
     /// Read and load a profile from the standard input.
-    Load,
+    ///
+    /// By default, all the sections of the profile are applied. Use --only or --except to apply
+    /// just some of them (users, network, product, localization, software, storage, bootloader
+    /// and scripts). The pre-scripts only run if the "scripts" section is applied.
+    Load {
+        /// Apply only these sections (comma-separated).
+        #[arg(long, value_delimiter = ',', conflicts_with = "except")]
+        only: Vec<Section>,
+        /// Apply all sections except these ones (comma-separated).
+        #[arg(long, value_delimiter = ',')]
+        except: Vec<Section>,
+    },
+
+    /// Record a snapshot of the system, including the current settings.
+    ///
+    /// The snapshot contains the settings and the inventory of the system (products, patterns,
+    /// locales, devices, etc.). It can be used to check profiles offline with
+    /// "agama profile check".
+    Snapshot,
+
+    /// Compare two installation profiles.
+    ///
+    /// The profiles are compared section by section, ignoring the differences that do not
+    /// change the meaning of the settings (e.g., the order of the patterns, the order of the
+    /// network connections or the formatting of the storage settings). The secret values are
+    /// masked.
+    Diff {
+        /// Path to the original profile (JSON).
+        a: PathBuf,
+        /// Path to the modified profile (JSON).
+        b: PathBuf,
+        /// Output format.
+        #[arg(short, long, value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+    },
 
     /// Edit and update installation option using an external editor.
     ///
@@ -61,17 +98,32 @@ pub enum ConfigCommands {
     },
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    /// Human readable list of changes grouped by section.
+    Text,
+    /// JSON Patch (RFC 6902) operations.
+    JsonPatch,
+}
+
 pub async fn run(http_client: BaseHTTPClient, subcommand: ConfigCommands) -> anyhow::Result<()> {
-    let store = SettingsStore::new(http_client).await?;
+    let store = SettingsStore::new(http_client.clone()).await?;
 
     match subcommand {
+        ConfigCommands::Diff { a, b, format } => diff(&a, &b, format),
         ConfigCommands::Show => {
             let model = store.load().await?;
             let json = serde_json::to_string_pretty(&model)?;
             println!("{}", json);
             Ok(())
         }
-        ConfigCommands::Load => {
+        ConfigCommands::Snapshot => {
+            let snapshot = SystemSnapshot::record(http_client).await?;
+            let json = serde_json::to_string_pretty(&snapshot)?;
+            println!("{}", json);
+            Ok(())
+        }
+        ConfigCommands::Load { only, except } => {
             let mut stdin = io::stdin();
             let mut contents = String::new();
             stdin.read_to_string(&mut contents)?;
@@ -79,7 +131,9 @@ pub async fn run(http_client: BaseHTTPClient, subcommand: ConfigCommands) -> any
             tokio::spawn(async move {
                 show_progress().await.unwrap();
             });
-            store.store(&result).await?;
+            store
+                .store_sections(&result, &selected_sections(&only, &except))
+                .await?;
             Ok(())
         }
         ConfigCommands::Edit { editor } => {
@@ -97,6 +151,50 @@ pub async fn run(http_client: BaseHTTPClient, subcommand: ConfigCommands) -> any
     }
 }
 
+/// Return the sections to apply, keeping the order in which they are stored.
+///
+/// * `only`: sections to include (all of them if empty).
+/// * `except`: sections to exclude.
+fn selected_sections(only: &[Section], except: &[Section]) -> Vec<Section> {
+    Section::ALL
+        .into_iter()
+        .filter(|s| only.is_empty() || only.contains(s))
+        .filter(|s| !except.contains(s))
+        .collect()
+}
+
+/// Compare two installation profiles and print the differences.
+///
+/// * `a`: path to the original profile.
+/// * `b`: path to the modified profile.
+/// * `format`: output format.
+pub fn diff(a: &Path, b: &Path, format: DiffFormat) -> anyhow::Result<()> {
+    let old = InstallSettings::from_file(a)
+        .map_err(|e| anyhow!("Could not read the profile {}: {}", a.display(), e))?;
+    let new = InstallSettings::from_file(b)
+        .map_err(|e| anyhow!("Could not read the profile {}: {}", b.display(), e))?;
+    let diff = install_settings::diff(&old, &new);
+
+    match format {
+        DiffFormat::Text => {
+            if diff.is_empty() {
+                println!("The profiles are equivalent");
+            }
+            for section in &diff.sections {
+                println!("{}:", section.section);
+                for change in &section.changes {
+                    println!("  {}", change);
+                }
+            }
+        }
+        DiffFormat::JsonPatch => {
+            let json = serde_json::to_string_pretty(&diff.to_patch())?;
+            println!("{}", json);
+        }
+    }
+    Ok(())
+}
+
 /// Edit the installation settings using an external editor.
 ///
 /// If the editor does not return a successful error code, it returns an error.
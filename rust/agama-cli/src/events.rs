@@ -0,0 +1,68 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+use agama_lib::base_http_client::BaseHTTPClient;
+use agama_lib::events::{EventsHTTPClient, EventsQuery};
+use clap::Args;
+use std::io::Write;
+use std::time::Duration;
+
+/// Time to wait before reconnecting when the server closes the stream.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// definition of "agama events" arguments, see clap crate for details
+#[derive(Args, Debug)]
+pub struct EventsArgs {
+    /// Event type to display (e.g., "Progress"). It can be repeated.
+    #[arg(long = "type", value_name = "TYPE")]
+    types: Vec<String>,
+    /// Service whose events to display (e.g., "storage"). It can be repeated.
+    #[arg(long = "service", value_name = "SERVICE")]
+    services: Vec<String>,
+    /// Display the events after the given sequence number, if the server still keeps them.
+    #[arg(long)]
+    since: Option<u64>,
+}
+
+/// Main entry point called from agama CLI main loop
+///
+/// It writes each event as a JSON document in its own line, reconnecting (and resuming from the
+/// last received event) when the server closes the stream.
+pub async fn run(client: BaseHTTPClient, args: EventsArgs) -> anyhow::Result<()> {
+    let client = EventsHTTPClient::new(client);
+    let mut query = EventsQuery {
+        types: args.types,
+        services: args.services,
+        since: args.since,
+    };
+
+    loop {
+        let mut stream = client.subscribe(&query).await?;
+        while let Some(event) = stream.next().await? {
+            if let Some(seq) = event.id.as_ref().and_then(|id| id.parse().ok()) {
+                query.since = Some(seq);
+            }
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{}", event.data)?;
+            stdout.flush()?;
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
@@ -25,6 +25,7 @@ mod auth;
 mod commands;
 mod config;
 mod error;
+mod events;
 mod logs;
 mod profile;
 mod progress;
@@ -33,18 +34,23 @@ mod questions;
 use crate::error::CliError;
 use agama_lib::base_http_client::BaseHTTPClient;
 use agama_lib::{
-    error::ServiceError, manager::ManagerClient, progress::ProgressMonitor, utils::Transfer,
+    error::ServiceError,
+    manager::ManagerClient,
+    progress::ProgressMonitor,
+    utils::{Integrity, Transfer, TransferOptions, TrustedKeys},
 };
-use auth::run as run_auth_cmd;
+use auth::{run as run_auth_cmd, AuthCommands};
 use commands::Commands;
-use config::run as run_config_cmd;
+use config::{diff as diff_profiles, run as run_config_cmd, ConfigCommands};
+use events::run as run_events_cmd;
 use inquire::Confirm;
 use logs::run as run_logs_cmd;
-use profile::run as run_profile_cmd;
+use profile::{run as run_profile_cmd, ProfileCommands};
 use progress::InstallerProgress;
 use questions::run as run_questions_cmd;
 use std::{
     collections::HashMap,
+    io::{stdout, IsTerminal},
     process::{ExitCode, Termination},
     thread::sleep,
     time::Duration,
@@ -198,7 +204,42 @@ async fn allowed_insecure_api(use_insecure: bool, api_url: String) -> Result<boo
     }
 }
 
+/// Downloads a file to the standard output, reporting the progress if it runs on a terminal.
+fn download(url: &str, integrity: Integrity, options: TransferOptions) -> Result<(), ServiceError> {
+    let mut transfer = Transfer::new(options);
+    let interactive = std::io::stderr().is_terminal();
+    if interactive {
+        transfer = transfer.with_progress(|now, total| {
+            if total > 0 {
+                eprint!("\rDownloaded {} of {} bytes", now, total);
+            }
+        });
+    }
+    let result = transfer.fetch_verified(url, &integrity, &TrustedKeys::default(), stdout());
+    if interactive {
+        eprintln!();
+    }
+    Ok(result?)
+}
+
 pub async fn run_command(cli: Cli) -> Result<(), ServiceError> {
+    // checking, composing, evaluating or comparing profiles works on local files, so it does not need the
+    // API at all
+    match cli.command {
+        Commands::Profile(
+            subcommand @ (ProfileCommands::Check { .. }
+            | ProfileCommands::Compose { .. }
+            | ProfileCommands::Evaluate { .. }
+            | ProfileCommands::Hardware),
+        ) => {
+            return Ok(run_profile_cmd(subcommand).await?);
+        }
+        Commands::Config(ConfigCommands::Diff { a, b, format }) => {
+            return Ok(diff_profiles(&a, &b, format)?);
+        }
+        _ => {}
+    }
+
     // somehow check whether we need to ask user for self-signed certificate acceptance
     let api_url = cli.opts.api.trim_end_matches('/').to_string();
 
@@ -212,12 +253,15 @@ pub async fn run_command(cli: Cli) -> Result<(), ServiceError> {
 
     // we need to distinguish commands on those which assume that authentication JWT is already
     // available and those which not (or don't need it)
-    client = if let Commands::Auth(_) = cli.command {
-        client.unauthenticated()?
-    } else {
-        // this deals with authentication need inside
-        client.authenticated()?
-    };
+    client =
+        if let Commands::Auth(AuthCommands::Login | AuthCommands::Logout | AuthCommands::Show) =
+            cli.command
+        {
+            client.unauthenticated()?
+        } else {
+            // this deals with authentication need inside
+            client.authenticated()?
+        };
 
     match cli.command {
         Commands::Config(subcommand) => run_config_cmd(client, subcommand).await?,
@@ -238,7 +282,12 @@ pub async fn run_command(cli: Cli) -> Result<(), ServiceError> {
         }
         Commands::Questions(subcommand) => run_questions_cmd(client, subcommand).await?,
         Commands::Logs(subcommand) => run_logs_cmd(client, subcommand).await?,
-        Commands::Download { url } => Transfer::get(&url, std::io::stdout())?,
+        Commands::Events(args) => run_events_cmd(client, args).await?,
+        Commands::Download {
+            url,
+            integrity,
+            transfer,
+        } => download(&url, integrity.into(), transfer.into())?,
         Commands::Auth(subcommand) => {
             run_auth_cmd(client, subcommand).await?;
         }
@@ -21,14 +21,18 @@
 use crate::show_progress;
 use agama_lib::{
     base_http_client::BaseHTTPClient,
+    dry_run::{DryRunStore, IssueSeverity, SystemSnapshot},
+    error::ProfileError,
     install_settings::InstallSettings,
-    profile::{AutoyastProfileImporter, ProfileEvaluator, ProfileValidator, ValidationResult},
-    utils::FileFormat,
-    utils::Transfer,
+    profile::{
+        AutoyastProfileImporter, FixtureHardwareProvider, HardwareProvider, ProfileComposer,
+        ProfileEvaluator, ProfileValidator, SysfsHardwareProvider, ValidationResult,
+    },
+    utils::{FileFormat, Integrity, Transfer, TransferOptions, TrustedKeys},
     Store as SettingsStore,
 };
 use anyhow::Context;
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 use console::style;
 use std::os::unix::{fs::PermissionsExt, process::CommandExt};
 use std::{
@@ -36,6 +40,7 @@ use std::{
     io::stdout,
     path::{Path, PathBuf},
     process::Command,
+    time::Duration,
 };
 use tempfile::TempDir;
 use url::Url;
@@ -47,6 +52,10 @@ pub enum ProfileCommands {
         /// AutoYaST profile's URL. Any AutoYaST scheme, ERB and rules/classes are supported.
         /// all schemas that autoyast supports.
         url: String,
+        /// Print the conversion report (supported, partially supported and ignored elements)
+        /// instead of the resulting json
+        #[arg(long)]
+        report: bool,
     },
 
     /// Validate a profile using JSON Schema
@@ -57,13 +66,46 @@ pub enum ProfileCommands {
         path: PathBuf,
     },
 
-    /// Evaluate a profile, injecting the hardware information from D-Bus
+    /// Evaluate a profile, injecting the hardware information
     ///
     /// For an example of Jsonnet-based profile, see
     /// https://github.com/openSUSE/agama/blob/master/rust/agama-lib/share/examples/profile.jsonnet
     Evaluate {
         /// Path to jsonnet file.
         path: PathBuf,
+        /// Read the hardware information from a JSON file (see "agama profile hardware")
+        /// instead of the running system. The lshw information is not available in that case.
+        #[arg(long)]
+        hardware: Option<PathBuf>,
+    },
+
+    /// Print a profile after merging its includes and overlays
+    ///
+    /// The "include" and "overlays" keys of the profile list the profiles to merge before and
+    /// after it. Network connections are merged by "id", scripts by "name" and software
+    /// patterns are handled as a set.
+    Compose {
+        /// Profile's URL or path. Supports the same schemas as the "download" command.
+        url: String,
+    },
+
+    /// Print the hardware information that is available to Jsonnet profiles
+    ///
+    /// The output can be saved and used later to evaluate a profile on a different system
+    /// (see the --hardware option of "agama profile evaluate").
+    Hardware,
+
+    /// Check what loading a profile would do, without a running installer
+    ///
+    /// The profile is applied to a recorded system snapshot (see "agama config snapshot")
+    /// instead of the installer. It reports which settings would change and which issues the
+    /// installer would raise.
+    Check {
+        /// Local path to the JSON profile to check
+        path: PathBuf,
+        /// Local path to the recorded system snapshot
+        #[arg(short, long)]
+        snapshot: PathBuf,
     },
 
     /// Process autoinstallation profile and loads it into agama
@@ -78,9 +120,74 @@ pub enum ProfileCommands {
         url: String,
         /// Specific directory where all processing happens. By default it uses a temporary directory
         dir: Option<PathBuf>,
+        #[command(flatten)]
+        integrity: IntegrityArgs,
+        #[command(flatten)]
+        transfer: TransferArgs,
     },
 }
 
+/// Integrity checks for downloaded files
+#[derive(Args, Debug)]
+pub struct IntegrityArgs {
+    /// Expected SHA-256 checksum of the file
+    #[arg(long)]
+    pub sha256: Option<String>,
+    /// URL of a detached minisign (.minisig) or GPG signature of the file. It must be made with
+    /// one of the keys from /etc/agama.d/trusted-keys
+    #[arg(long)]
+    pub signature: Option<String>,
+}
+
+/// Settings for downloading files
+///
+/// They override the AGAMA_TRANSFER_* environment variables.
+#[derive(Args, Debug)]
+pub struct TransferArgs {
+    /// Number of retries after a transient error (default: 3)
+    #[arg(long)]
+    pub retries: Option<u32>,
+    /// Connection timeout, in seconds (default: 30)
+    #[arg(long)]
+    pub connect_timeout: Option<u64>,
+    /// Timeout for each download attempt, in seconds
+    #[arg(long)]
+    pub timeout: Option<u64>,
+    /// Proxy URL (e.g., "http://proxy.example.net:3128")
+    #[arg(long)]
+    pub proxy: Option<String>,
+    /// CA bundle to verify the server certificates
+    #[arg(long)]
+    pub ca_bundle: Option<PathBuf>,
+}
+
+impl From<TransferArgs> for TransferOptions {
+    fn from(args: TransferArgs) -> Self {
+        let mut options = TransferOptions::from_env();
+        if let Some(retries) = args.retries {
+            options.retries = retries;
+        }
+        if let Some(timeout) = args.connect_timeout {
+            options.connect_timeout = Some(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = args.timeout {
+            options.timeout = Some(Duration::from_secs(timeout));
+        }
+        options.proxy = args.proxy.or(options.proxy);
+        options.ca_bundle = args.ca_bundle.or(options.ca_bundle);
+        options
+    }
+}
+
+impl From<IntegrityArgs> for Integrity {
+    fn from(args: IntegrityArgs) -> Self {
+        Self {
+            sha256: args.sha256,
+            signature: args.signature,
+        }
+    }
+}
+
 fn validate(path: &PathBuf) -> anyhow::Result<()> {
     let validator = ProfileValidator::default_schema()?;
     let result = validator
@@ -103,15 +210,67 @@ fn validate(path: &PathBuf) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn evaluate(path: &Path) -> anyhow::Result<()> {
-    let evaluator = ProfileEvaluator {};
+fn evaluate(path: &Path, hardware: Option<PathBuf>) -> anyhow::Result<()> {
+    let evaluator = match hardware {
+        Some(hardware) => ProfileEvaluator::new(FixtureHardwareProvider::new(hardware)),
+        None => ProfileEvaluator::default(),
+    };
     evaluator
         .evaluate(path, stdout())
         .context("Could not evaluate the profile".to_string())?;
     Ok(())
 }
 
-async fn import(url_string: String, dir: Option<PathBuf>) -> anyhow::Result<()> {
+fn hardware() -> anyhow::Result<()> {
+    let facts = SysfsHardwareProvider::default()
+        .facts()
+        .context("Could not read the hardware information")?;
+    println!("{}", serde_json::to_string_pretty(&facts)?);
+    Ok(())
+}
+
+fn check(path: &Path, snapshot: &Path) -> anyhow::Result<()> {
+    let settings = InstallSettings::from_file(path)
+        .context(format!("Could not read the profile {:?}", path))?;
+    let snapshot = SystemSnapshot::from_file(snapshot)
+        .context(format!("Could not read the snapshot {:?}", snapshot))?;
+    let mut store = DryRunStore::new(snapshot);
+    let report = store.store(&settings);
+
+    for section in &report.sections {
+        println!("{}", style(section.section).bold());
+        if section.changes.is_empty() {
+            println!("\tNo changes");
+        }
+        for change in &section.changes {
+            println!("\t* {change}");
+        }
+        for effect in &section.side_effects {
+            println!("\t! {effect}");
+        }
+        for issue in &section.issues {
+            let mark = match issue.severity {
+                IssueSeverity::Warn => style("\u{26a0}").bold().yellow(),
+                IssueSeverity::Error => style("\u{2717}").bold().red(),
+            };
+            println!("\t{} {}", mark, issue.description);
+        }
+    }
+
+    if report.has_errors() {
+        return Err(anyhow::Error::msg(
+            "The installer would report errors for this profile",
+        ));
+    }
+    Ok(())
+}
+
+async fn import(
+    url_string: String,
+    dir: Option<PathBuf>,
+    integrity: Integrity,
+    options: TransferOptions,
+) -> anyhow::Result<()> {
     tokio::spawn(async move {
         show_progress().await.unwrap();
     });
@@ -124,10 +283,28 @@ async fn import(url_string: String, dir: Option<PathBuf>) -> anyhow::Result<()>
     // Specific AutoYaST handling
     let path = url.path();
     if path.ends_with(".xml") || path.ends_with(".erb") || path.ends_with('/') {
+        if !integrity.is_empty() {
+            return Err(anyhow::Error::msg(
+                "Checksums and signatures are not supported for AutoYaST profiles",
+            ));
+        }
         // AutoYaST specific download and convert to JSON
-        AutoyastProfileImporter::read(&url)?.write_file(&profile_path)?;
+        let importer = AutoyastProfileImporter::read(&url)?;
+        importer.write_file(&profile_path)?;
+        if let Some(report) = importer.report() {
+            let report_path = work_dir.join("conversion-report.json");
+            serde_json::to_writer_pretty(File::create(&report_path)?, report)?;
+            if !report.is_complete() {
+                eprintln!(
+                    "The AutoYaST profile was not fully converted (see {}).\n{}",
+                    report_path.display(),
+                    report
+                );
+            }
+        }
     } else {
-        pre_process_profile(&url_string, &profile_path)?;
+        pre_process_profile(&url_string, &integrity, &options, &profile_path)?;
+        compose_file(&profile_path, &url, &options)?;
     }
 
     validate(&profile_path)?;
@@ -143,16 +320,23 @@ async fn import(url_string: String, dir: Option<PathBuf>) -> anyhow::Result<()>
 // * If it is a JSON file, no preprocessing is needed.
 // * If it is a Jsonnet file, it is converted to JSON.
 // * If it is a script, it is executed.
-fn pre_process_profile<P: AsRef<Path>>(url_string: &str, path: P) -> anyhow::Result<()> {
+fn pre_process_profile<P: AsRef<Path>>(
+    url_string: &str,
+    integrity: &Integrity,
+    options: &TransferOptions,
+    path: P,
+) -> anyhow::Result<()> {
     let work_dir = path.as_ref().parent().unwrap();
     let tmp_profile_path = work_dir.join("profile.temp");
     let tmp_file = File::create(&tmp_profile_path)?;
-    Transfer::get(url_string, tmp_file)?;
+    Transfer::new(options.clone())
+        .fetch_verified(url_string, integrity, &TrustedKeys::default(), tmp_file)
+        .map_err(ProfileError::from)?;
 
     match FileFormat::from_file(&tmp_profile_path)? {
         FileFormat::Jsonnet => {
             let file = File::create(path)?;
-            let evaluator = ProfileEvaluator {};
+            let evaluator = ProfileEvaluator::default();
             evaluator
                 .evaluate(&tmp_profile_path, file)
                 .context("Could not evaluate the profile".to_string())?;
@@ -174,6 +358,29 @@ fn pre_process_profile<P: AsRef<Path>>(url_string: &str, path: P) -> anyhow::Res
     Ok(())
 }
 
+// Merges the includes and overlays of the profile, replacing the file.
+fn compose_file(path: &Path, url: &Url, options: &TransferOptions) -> anyhow::Result<()> {
+    let profile = serde_json::from_reader(File::open(path)?)?;
+    let composer = ProfileComposer::new(
+        ProfileEvaluator::default(),
+        TrustedKeys::default(),
+        Transfer::new(options.clone()),
+    );
+    let profile = composer
+        .compose_value(profile, url)
+        .context("Could not merge the included profiles")?;
+    serde_json::to_writer_pretty(File::create(path)?, &profile)?;
+    Ok(())
+}
+
+fn compose(url: &str) -> anyhow::Result<()> {
+    let profile = ProfileComposer::default()
+        .compose(url)
+        .context(format!("Could not compose the profile {}", url))?;
+    println!("{}", serde_json::to_string_pretty(&profile)?);
+    Ok(())
+}
+
 async fn store_settings<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
     let store = SettingsStore::new(BaseHTTPClient::default().authenticated()?).await?;
     let settings = InstallSettings::from_file(&path)?;
@@ -181,18 +388,35 @@ async fn store_settings<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn autoyast(url_string: String) -> anyhow::Result<()> {
+fn autoyast(url_string: String, report: bool) -> anyhow::Result<()> {
     let url = Url::parse(&url_string)?;
     let importer = AutoyastProfileImporter::read(&url)?;
-    importer.write(std::io::stdout())?;
+    if !report {
+        importer.write(std::io::stdout())?;
+        return Ok(());
+    }
+
+    let report = importer
+        .report()
+        .context("agama-autoyast did not write the conversion report")?;
+    serde_json::to_writer_pretty(stdout(), report)?;
+    println!();
     Ok(())
 }
 
 pub async fn run(subcommand: ProfileCommands) -> anyhow::Result<()> {
     match subcommand {
-        ProfileCommands::Autoyast { url } => autoyast(url),
+        ProfileCommands::Autoyast { url, report } => autoyast(url, report),
         ProfileCommands::Validate { path } => validate(&path),
-        ProfileCommands::Evaluate { path } => evaluate(&path),
-        ProfileCommands::Import { url, dir } => import(url, dir).await,
+        ProfileCommands::Evaluate { path, hardware } => evaluate(&path, hardware),
+        ProfileCommands::Compose { url } => compose(&url),
+        ProfileCommands::Hardware => hardware(),
+        ProfileCommands::Check { path, snapshot } => check(&path, &snapshot),
+        ProfileCommands::Import {
+            url,
+            dir,
+            integrity,
+            transfer,
+        } => import(url, dir, integrity.into(), transfer.into()).await,
     }
 }
@@ -35,6 +35,17 @@
 //! with the server. In that process, it obtains a new token that should be stored
 //! in user's home directory (`$HOME/.local/agama/token`).
 //!
+//! ## Scopes
+//!
+//! A token grants access to the whole API unless it is restricted to a given [scope](TokenScope)
+//! (e.g., a read-only token for a dashboard). Additionally, tokens can be created with a custom
+//! expiration time (see [TokenClaims::new]).
+//!
+//! ## Keys
+//!
+//! The tokens are signed with the first key of a [KeyRing], which can contain additional keys to
+//! verify the tokens signed before rotating the keys. See the [keys] module for further details.
+//!
 //! ## A simplistic API
 //!
 //! The current API is rather limited and it does not support, for instance,
@@ -52,14 +63,22 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use chrono::{Duration, Utc};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub mod keys;
+pub use keys::{KeyRing, TokenKey};
+
 #[derive(Error, Debug)]
-#[error("Invalid authentication token: {0}")]
-pub struct AuthTokenError(#[from] jsonwebtoken::errors::Error);
+pub enum AuthTokenError {
+    #[error("Invalid authentication token: {0}")]
+    Invalid(#[from] jsonwebtoken::errors::Error),
+    #[error("Unknown key to verify the token: '{0}'")]
+    UnknownKey(String),
+    #[error("There is no key to sign the token")]
+    NoSigningKey,
+}
 
 /// Represents an authentication token (JWT).
 pub struct AuthToken(String);
@@ -76,13 +95,18 @@ impl AuthToken {
     ///
     /// * `secret`: secret to encode the token.
     pub fn generate(secret: &str) -> Result<Self, AuthTokenError> {
-        let claims = TokenClaims::default();
-        let token = jsonwebtoken::encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(secret.as_ref()),
-        )?;
-        Ok(AuthToken(token))
+        Self::generate_with_claims(secret, &TokenClaims::default())
+    }
+
+    /// Generates a new token including the given claims.
+    ///
+    /// * `secret`: secret to encode the token.
+    /// * `claims`: claims to include in the token.
+    pub fn generate_with_claims(
+        secret: &str,
+        claims: &TokenClaims,
+    ) -> Result<Self, AuthTokenError> {
+        KeyRing::from_secret(secret).sign(claims)
     }
 
     /// Finds an usable token for the current user.
@@ -142,9 +166,7 @@ impl AuthToken {
     ///
     /// * `secret`: secret to decode the token.
     pub fn claims(&self, secret: &str) -> Result<TokenClaims, AuthTokenError> {
-        let decoding = DecodingKey::from_secret(secret.as_ref());
-        let token_data = jsonwebtoken::decode(&self.0, &decoding, &Validation::default())?;
-        Ok(token_data.claims)
+        KeyRing::from_secret(secret).verify(self)
     }
 
     /// Returns a reference to the token's content.
@@ -184,33 +206,86 @@ impl Display for AuthToken {
     }
 }
 
+/// Defines what a token grants access to.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+    utoipa::ToSchema,
+)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenScope {
+    /// Full access to the API.
+    #[default]
+    Full,
+    /// Read the installation status and settings, but do not change anything. The settings that
+    /// may contain secrets (e.g., passwords or the registration code) are not readable.
+    ReadOnly,
+    /// Follow the events and answer the questions.
+    Questions,
+}
+
 /// Claims that are included in the token.
 ///
 /// See https://datatracker.ietf.org/doc/html/rfc7519 for reference.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
     pub exp: i64,
+    /// Issue time.
+    #[serde(default)]
+    pub iat: i64,
+    /// Token identifier, which the web server uses to keep track of the sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// Tokens without a scope (e.g., the ones issued by previous versions) grant full access.
+    #[serde(default)]
+    pub scope: TokenScope,
 }
 
-impl Default for TokenClaims {
-    fn default() -> Self {
-        let mut exp = Utc::now();
-
-        if let Some(days) = Duration::try_days(1) {
-            exp += days;
-        }
+impl TokenClaims {
+    /// Default time to live of a token (one day).
+    pub const DEFAULT_TTL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
 
+    /// Builds the claims for a token that expires after the given time.
+    ///
+    /// * `scope`: what the token grants access to.
+    /// * `ttl`: time to live of the token.
+    pub fn new(scope: TokenScope, ttl: std::time::Duration) -> Self {
+        let now = Utc::now();
+        let ttl = Duration::from_std(ttl).unwrap_or(Duration::max_value());
+        let exp = now
+            .checked_add_signed(ttl)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
         Self {
             exp: exp.timestamp(),
+            iat: now.timestamp(),
+            jti: None,
+            scope,
         }
     }
 }
 
+impl Default for TokenClaims {
+    fn default() -> Self {
+        Self::new(TokenScope::default(), Self::DEFAULT_TTL)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use tempfile::tempdir;
 
-    use super::AuthToken;
+    use std::time::Duration;
+
+    use super::{AuthToken, TokenClaims, TokenScope};
 
     #[test]
     fn test_generate_token() {
@@ -222,6 +297,22 @@ mod tests {
         assert!(wrong.is_err())
     }
 
+    #[test]
+    fn test_generate_scoped_token() {
+        let claims = TokenClaims::new(TokenScope::ReadOnly, Duration::from_secs(300));
+        let token = AuthToken::generate_with_claims("nots3cr3t", &claims).unwrap();
+        let decoded = token.claims("nots3cr3t").unwrap();
+        assert_eq!(decoded.scope, TokenScope::ReadOnly);
+        assert_eq!(decoded.exp, claims.exp);
+
+        let expired = TokenClaims {
+            exp: claims.exp - 600,
+            ..claims
+        };
+        let token = AuthToken::generate_with_claims("nots3cr3t", &expired).unwrap();
+        assert!(token.claims("nots3cr3t").is_err());
+    }
+
     #[test]
     fn test_write_and_read_token() {
         // let token = AuthToken::from_path<P: AsRef<Path>>(path: P)
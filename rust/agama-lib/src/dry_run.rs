@@ -0,0 +1,34 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Offline checking of the installation settings
+//!
+//! This module allows finding out what applying a profile would do without a running installer.
+//! Instead of calling the HTTP API, the [DryRunStore] applies the settings to an in-memory copy of
+//! the system which is seeded from a [SystemSnapshot]. The snapshot can be recorded from a running
+//! installer (see [SystemSnapshot::record]) and checked later on any machine.
+
+mod report;
+mod snapshot;
+mod store;
+
+pub use report::{DryRunReport, Issue, IssueSeverity, SectionReport};
+pub use snapshot::{SnapshotProduct, SystemSnapshot};
+pub use store::DryRunStore;
@@ -0,0 +1,101 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Result of a dry-run.
+
+use crate::install_settings::{Change, Section};
+use serde::Serialize;
+
+/// Outcome of applying the settings to a snapshot.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunReport {
+    /// Reports for the sections included in the settings, in the order they are applied.
+    pub sections: Vec<SectionReport>,
+}
+
+impl DryRunReport {
+    /// Whether any section would raise an error.
+    pub fn has_errors(&self) -> bool {
+        self.sections
+            .iter()
+            .flat_map(|s| &s.issues)
+            .any(|i| i.severity == IssueSeverity::Error)
+    }
+
+    /// Returns the report for the given section, if any.
+    ///
+    /// * `section`: section to search for.
+    pub fn section(&self, section: Section) -> Option<&SectionReport> {
+        self.sections.iter().find(|s| s.section == section)
+    }
+}
+
+/// What applying a single section would do.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SectionReport {
+    pub section: Section,
+    /// Settings that would change.
+    pub changes: Vec<Change>,
+    /// Problems that the installer would report.
+    pub issues: Vec<Issue>,
+    /// Additional actions triggered by the section (e.g., running scripts or probing the
+    /// system).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub side_effects: Vec<String>,
+}
+
+impl SectionReport {
+    pub fn new(section: Section) -> Self {
+        Self {
+            section,
+            changes: vec![],
+            issues: vec![],
+            side_effects: vec![],
+        }
+    }
+
+    /// Adds an issue with the given severity.
+    pub fn add_issue(&mut self, severity: IssueSeverity, description: String) {
+        self.issues.push(Issue {
+            description,
+            severity,
+        });
+    }
+}
+
+/// Problem that the installer would report.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Issue {
+    pub description: String,
+    pub severity: IssueSeverity,
+}
+
+/// Issue severity, following the values used by the installer services.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, strum::Display)]
+#[strum(serialize_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub enum IssueSeverity {
+    /// The installation can continue, but the result might not be the expected one.
+    Warn,
+    /// The installer would refuse to apply the settings or to install.
+    Error,
+}
@@ -0,0 +1,139 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Recorded state of an installer.
+
+use crate::base_http_client::BaseHTTPClient;
+use crate::error::ServiceError;
+use crate::install_settings::InstallSettings;
+use crate::storage::model::DeviceInfo;
+use crate::Store;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Snapshot of a system as seen by the installer.
+///
+/// It contains the current settings and the inventory of the system (products, locales, devices,
+/// etc.). The inventory lists are optional: when one of them is missing, the checks that depend
+/// on it are skipped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemSnapshot {
+    /// Current installation settings (as written by "agama config show").
+    #[serde(default)]
+    pub settings: InstallSettings,
+    /// Available products.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub products: Option<Vec<SnapshotProduct>>,
+    /// Names of the available patterns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patterns: Option<Vec<String>>,
+    /// Known locales (e.g., "en_US.UTF-8").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locales: Option<Vec<String>>,
+    /// Known keymaps (e.g., "us").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keymaps: Option<Vec<String>>,
+    /// Known timezones (e.g., "Europe/Berlin").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezones: Option<Vec<String>>,
+    /// Names of the network devices (e.g., "eth0").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_devices: Option<Vec<String>>,
+    /// Names of the storage devices (e.g., "/dev/vda").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_devices: Option<Vec<String>>,
+}
+
+/// Product as recorded in the snapshot
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotProduct {
+    /// Product ID (e.g., "Tumbleweed")
+    pub id: String,
+    /// Whether the product requires registration
+    #[serde(default)]
+    pub registration: bool,
+}
+
+#[derive(Deserialize)]
+struct NamedEntry {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct IdEntry {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct TimezoneEntry {
+    code: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StorageDevice {
+    device_info: DeviceInfo,
+}
+
+impl SystemSnapshot {
+    /// Records a snapshot from a running installer.
+    ///
+    /// * `http_client`: client to connect to the installer's HTTP API.
+    pub async fn record(http_client: BaseHTTPClient) -> Result<Self, ServiceError> {
+        let store = Store::new(http_client.clone()).await?;
+        let settings = store.load().await?;
+
+        let products: Vec<SnapshotProduct> = http_client.get("/software/products").await?;
+        let patterns: Vec<NamedEntry> = http_client.get("/software/patterns").await?;
+        let locales: Vec<IdEntry> = http_client.get("/l10n/locales").await?;
+        let keymaps: Vec<IdEntry> = http_client.get("/l10n/keymaps").await?;
+        let timezones: Vec<TimezoneEntry> = http_client.get("/l10n/timezones").await?;
+        let network_devices: Vec<NamedEntry> = http_client.get("/network/devices").await?;
+        let storage_devices: Vec<StorageDevice> =
+            http_client.get("/storage/devices/system").await?;
+
+        Ok(Self {
+            settings,
+            products: Some(products),
+            patterns: Some(patterns.into_iter().map(|p| p.name).collect()),
+            locales: Some(locales.into_iter().map(|l| l.id).collect()),
+            keymaps: Some(keymaps.into_iter().map(|k| k.id).collect()),
+            timezones: Some(timezones.into_iter().map(|t| t.code).collect()),
+            network_devices: Some(network_devices.into_iter().map(|d| d.name).collect()),
+            storage_devices: Some(
+                storage_devices
+                    .into_iter()
+                    .map(|d| d.device_info.name)
+                    .collect(),
+            ),
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        let data = serde_json::from_reader(reader)?;
+        Ok(data)
+    }
+}
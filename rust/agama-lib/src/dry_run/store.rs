@@ -0,0 +1,667 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Implements the in-memory counterpart of [crate::Store].
+
+use super::{DryRunReport, IssueSeverity, SectionReport, SystemSnapshot};
+use crate::bootloader::model::BootloaderSettings;
+use crate::install_settings::{json_changes, section_value, to_value, InstallSettings, Section};
+use crate::localization::LocalizationSettings;
+use crate::network::{default_connection, find_connection, ordered_connections, NetworkSettings};
+use crate::product::ProductSettings;
+use crate::scripts::ScriptsConfig;
+use crate::software::SoftwareSettings;
+use crate::storage::StorageSettings;
+use crate::users::{FirstUserSettings, UserSettings};
+use serde_json::Value;
+
+/// Applies the installation settings to a [SystemSnapshot] instead of a running installer.
+///
+/// It mimics the behavior of each sub-store of [crate::Store] (e.g., connections are added or
+/// updated by their ID, the scripts are replaced, etc.) and applies the sections in the same
+/// order. Instead of stopping at the first failure, it records the problems that the installer
+/// would report and keeps going, so a single run reports all of them.
+pub struct DryRunStore {
+    snapshot: SystemSnapshot,
+}
+
+impl DryRunStore {
+    pub fn new(snapshot: SystemSnapshot) -> Self {
+        Self { snapshot }
+    }
+
+    /// Returns the settings of the in-memory system.
+    pub fn load(&self) -> &InstallSettings {
+        &self.snapshot.settings
+    }
+
+    /// Applies the given settings and reports what each section would change.
+    ///
+    /// * `settings`: installation settings.
+    pub fn store(&mut self, settings: &InstallSettings) -> DryRunReport {
+        let mut report = DryRunReport::default();
+
+        if let Some(scripts) = &settings.scripts {
+            self.apply(Section::Scripts, &mut report, |s| s.store_scripts(scripts));
+        }
+        if let Some(user) = settings
+            .user
+            .as_ref()
+            .filter(|_| settings.has_section(Section::Users))
+        {
+            self.apply(Section::Users, &mut report, |s| s.store_users(user));
+        }
+        if let Some(network) = &settings.network {
+            self.apply(Section::Network, &mut report, |s| s.store_network(network));
+        }
+        if let Some(product) = &settings.product {
+            self.apply(Section::Product, &mut report, |s| s.store_product(product));
+        }
+        if let Some(localization) = &settings.localization {
+            self.apply(Section::Localization, &mut report, |s| {
+                s.store_localization(localization)
+            });
+        }
+        if let Some(software) = &settings.software {
+            self.apply(Section::Software, &mut report, |s| {
+                s.store_software(software)
+            });
+        }
+        if settings.has_section(Section::Storage) {
+            self.apply(Section::Storage, &mut report, |s| {
+                s.store_storage(&settings.into())
+            });
+        }
+        if let Some(bootloader) = &settings.bootloader {
+            self.apply(Section::Bootloader, &mut report, |s| {
+                s.store_bootloader(bootloader)
+            });
+        }
+
+        report
+    }
+
+    /// Applies a section and adds its report, including the changes in the settings.
+    ///
+    /// * `section`: section to apply.
+    /// * `report`: report to add the section report to.
+    /// * `store_fn`: function that applies the section.
+    fn apply<F>(&mut self, section: Section, report: &mut DryRunReport, store_fn: F)
+    where
+        F: FnOnce(&mut Self) -> SectionReport,
+    {
+        let old = section_value(&self.snapshot.settings, section);
+        let mut section_report = store_fn(self);
+        let new = section_value(&self.snapshot.settings, section);
+        json_changes(&[], &old, &new, &mut section_report.changes);
+        report.sections.push(section_report);
+    }
+
+    fn store_scripts(&mut self, scripts: &ScriptsConfig) -> SectionReport {
+        let mut report = SectionReport::new(Section::Scripts);
+        self.snapshot.settings.scripts = Some(scripts.clone());
+
+        let pre: Vec<&str> = scripts
+            .pre
+            .iter()
+            .flatten()
+            .map(|s| s.base.name.as_str())
+            .collect();
+        if !pre.is_empty() {
+            report
+                .side_effects
+                .push(format!("The pre-scripts would run: {}", pre.join(", ")));
+            report.side_effects.push(
+                "The system would be probed again, resetting the storage proposal".to_string(),
+            );
+        }
+        report
+    }
+
+    fn store_users(&mut self, settings: &UserSettings) -> SectionReport {
+        let mut report = SectionReport::new(Section::Users);
+        let current = self
+            .snapshot
+            .settings
+            .user
+            .get_or_insert_with(Default::default);
+
+        if let Some(first_user) = &settings.first_user {
+            current.first_user = Some(FirstUserSettings {
+                full_name: Some(first_user.full_name.clone().unwrap_or_default()),
+                user_name: Some(first_user.user_name.clone().unwrap_or_default()),
+                password: Some(first_user.password.clone().unwrap_or_default()),
+                hashed_password: Some(first_user.hashed_password.unwrap_or_default()),
+            });
+        }
+
+        if let Some(root) = &settings.root {
+            let current_root = current.root.get_or_insert_with(Default::default);
+            if let Some(password) = &root.password {
+                current_root.password = Some(password.clone());
+                current_root.hashed_password = Some(root.hashed_password.unwrap_or_default());
+            }
+            if let Some(ssh_public_key) = &root.ssh_public_key {
+                current_root.ssh_public_key = Some(ssh_public_key.clone());
+            }
+        }
+
+        let has_first_user = current
+            .first_user
+            .as_ref()
+            .is_some_and(|u| u.user_name.as_ref().is_some_and(|n| !n.is_empty()));
+        let has_root_auth = current.root.as_ref().is_some_and(|r| {
+            r.password.as_ref().is_some_and(|p| !p.is_empty())
+                || r.ssh_public_key.as_ref().is_some_and(|k| !k.is_empty())
+        });
+        if !has_first_user && !has_root_auth {
+            report.add_issue(
+                IssueSeverity::Error,
+                "Defining a user, setting the root password or a SSH public key is required"
+                    .to_string(),
+            );
+        }
+        report
+    }
+
+    fn store_network(&mut self, settings: &NetworkSettings) -> SectionReport {
+        let mut report = SectionReport::new(Section::Network);
+        let current = self
+            .snapshot
+            .settings
+            .network
+            .get_or_insert_with(Default::default);
+
+        for id in ordered_connections(&settings.connections) {
+            let fallback = default_connection(&id);
+            let conn = find_connection(&id, &settings.connections).unwrap_or(&fallback);
+            match current.connections.iter_mut().find(|c| c.id == conn.id) {
+                Some(existing) => *existing = conn.clone(),
+                None => current.connections.push(conn.clone()),
+            }
+        }
+        if let Some(nmstate) = &settings.nmstate {
+            current.nmstate = Some(nmstate.clone());
+        }
+        report
+            .side_effects
+            .push("The network configuration would be applied".to_string());
+
+        if let Some(devices) = &self.snapshot.network_devices {
+            // virtual devices (e.g., bonds or VLANs) are created by the connections themselves
+            let virtual_devices: Vec<&str> = current
+                .connections
+                .iter()
+                .filter(|c| {
+                    c.bond.is_some()
+                        || c.vlan.is_some()
+                        || c.bridge.is_some()
+                        || c.tun.is_some()
+                        || c.wireguard.is_some()
+                        || c.infiniband.as_ref().is_some_and(|i| i.p_key.is_some())
+                })
+                .map(|c| c.interface.as_deref().unwrap_or(&c.id))
+                .collect();
+            let is_missing =
+                |name: &str| !devices.iter().any(|d| d == name) && !virtual_devices.contains(&name);
+            for conn in &settings.connections {
+                if let Some(interface) = &conn.interface {
+                    if is_missing(interface) {
+                        report.add_issue(
+                            IssueSeverity::Warn,
+                            format!(
+                                "The network device '{}' used by the connection '{}' is not found in the system",
+                                interface, conn.id
+                            ),
+                        );
+                    }
+                }
+                let ports = conn.bond.iter().flat_map(|b| &b.ports);
+                for port in ports.filter(|p| is_missing(p)) {
+                    report.add_issue(
+                        IssueSeverity::Warn,
+                        format!(
+                            "The network device '{}' used as a port by the connection '{}' is not found in the system",
+                            port, conn.id
+                        ),
+                    );
+                }
+            }
+        }
+        report
+    }
+
+    fn store_product(&mut self, settings: &ProductSettings) -> SectionReport {
+        let mut report = SectionReport::new(Section::Product);
+        let current = self
+            .snapshot
+            .settings
+            .product
+            .get_or_insert_with(Default::default);
+        let mut probe = false;
+
+        if let Some(id) = &settings.id {
+            if current.id.as_ref() != Some(id) {
+                current.id = Some(id.clone());
+                probe = true;
+            }
+        }
+        if let Some(reg_code) = &settings.registration_code {
+            current.registration_code = Some(reg_code.clone());
+            current.registration_email = settings.registration_email.clone();
+            report
+                .side_effects
+                .push("The product would be registered".to_string());
+            probe = true;
+        }
+        if probe {
+            report
+                .side_effects
+                .push("The system would be probed again".to_string());
+        }
+
+        let Some(id) = current.id.as_ref().filter(|i| !i.is_empty()) else {
+            report.add_issue(IssueSeverity::Error, "Product not selected yet".to_string());
+            return report;
+        };
+        if let Some(products) = &self.snapshot.products {
+            match products.iter().find(|p| &p.id == id) {
+                Some(product) => {
+                    if product.registration && current.registration_code.is_none() {
+                        report.add_issue(
+                            IssueSeverity::Error,
+                            "Product must be registered".to_string(),
+                        );
+                    }
+                }
+                None => report.add_issue(IssueSeverity::Error, format!("Unknown product: {}", id)),
+            }
+        }
+        report
+    }
+
+    fn store_localization(&mut self, settings: &LocalizationSettings) -> SectionReport {
+        let mut report = SectionReport::new(Section::Localization);
+        let current = self
+            .snapshot
+            .settings
+            .localization
+            .get_or_insert_with(Default::default);
+
+        if let Some(language) = &settings.language {
+            current.language = Some(language.clone());
+            if !known(&self.snapshot.locales, language) {
+                report.add_issue(
+                    IssueSeverity::Error,
+                    format!("Unknown locale code: {}", language),
+                );
+            }
+        }
+        if let Some(keyboard) = &settings.keyboard {
+            current.keyboard = Some(keyboard.clone());
+            if !known(&self.snapshot.keymaps, keyboard) {
+                report.add_issue(
+                    IssueSeverity::Error,
+                    format!("Unknown keymap: {}", keyboard),
+                );
+            }
+        }
+        if let Some(timezone) = &settings.timezone {
+            current.timezone = Some(timezone.clone());
+            if !known(&self.snapshot.timezones, timezone) {
+                report.add_issue(
+                    IssueSeverity::Error,
+                    format!("Unknown timezone: {}", timezone),
+                );
+            }
+        }
+        report
+    }
+
+    fn store_software(&mut self, settings: &SoftwareSettings) -> SectionReport {
+        let mut report = SectionReport::new(Section::Software);
+        let current = self
+            .snapshot
+            .settings
+            .software
+            .get_or_insert_with(Default::default);
+        current.patterns = settings.patterns.clone();
+
+        if let Some(patterns) = &self.snapshot.patterns {
+            let unknown: Vec<&String> = settings
+                .patterns
+                .iter()
+                .filter(|p| !patterns.contains(p))
+                .collect();
+            if !unknown.is_empty() {
+                report.add_issue(
+                    IssueSeverity::Error,
+                    format!("Failed to find these patterns: {:?}", unknown),
+                );
+            }
+        }
+        report
+    }
+
+    fn store_storage(&mut self, settings: &StorageSettings) -> SectionReport {
+        let mut report = SectionReport::new(Section::Storage);
+        let current = &mut self.snapshot.settings;
+        current.storage = settings.storage.clone();
+        current.storage_autoyast = settings.storage_autoyast.clone();
+
+        if let Some(devices) = &self.snapshot.storage_devices {
+            let mut searched = vec![];
+            searched_devices(&to_value(settings), &mut searched);
+            let missing: Vec<String> = searched
+                .into_iter()
+                .filter(|d| !devices.contains(d))
+                .collect();
+            if !missing.is_empty() {
+                report.add_issue(
+                    IssueSeverity::Error,
+                    format!(
+                        "The following selected devices are not found in the system: {}",
+                        missing.join(", ")
+                    ),
+                );
+            }
+        }
+        report
+    }
+
+    fn store_bootloader(&mut self, settings: &BootloaderSettings) -> SectionReport {
+        self.snapshot.settings.bootloader = Some(settings.clone());
+        SectionReport::new(Section::Bootloader)
+    }
+}
+
+/// Whether the value is included in the list.
+///
+/// If the list was not recorded, any value is considered as known.
+fn known(list: &Option<Vec<String>>, value: &str) -> bool {
+    match list {
+        Some(list) => list.iter().any(|v| v == value),
+        None => true,
+    }
+}
+
+/// Collects the device names that the storage settings look for.
+fn searched_devices(value: &Value, devices: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("search" | "device", Value::String(name)) => devices.push(name.clone()),
+                    ("condition", Value::Object(condition)) => {
+                        if let Some(Value::String(name)) = condition.get("name") {
+                            devices.push(name.clone());
+                        }
+                    }
+                    _ => searched_devices(value, devices),
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|v| searched_devices(v, devices)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dry_run::SnapshotProduct;
+    use crate::install_settings::Change;
+    use crate::network::settings::{BondSettings, NetworkConnection};
+    use serde_json::json;
+
+    fn snapshot() -> SystemSnapshot {
+        let settings: InstallSettings = serde_json::from_value(json!({
+            "product": { "id": "Tumbleweed" },
+            "software": { "patterns": ["base"] },
+            "localization": { "language": "en_US.UTF-8", "keyboard": "us" },
+            "network": { "connections": [{ "id": "eth0", "method4": "auto" }] }
+        }))
+        .unwrap();
+
+        SystemSnapshot {
+            settings,
+            products: Some(vec![
+                SnapshotProduct {
+                    id: "Tumbleweed".to_string(),
+                    registration: false,
+                },
+                SnapshotProduct {
+                    id: "SLES".to_string(),
+                    registration: true,
+                },
+            ]),
+            patterns: Some(vec!["base".to_string(), "gnome".to_string()]),
+            locales: Some(vec!["en_US.UTF-8".to_string(), "es_ES.UTF-8".to_string()]),
+            network_devices: Some(vec!["eth0".to_string(), "eth1".to_string()]),
+            storage_devices: Some(vec!["/dev/vda".to_string()]),
+            ..Default::default()
+        }
+    }
+
+    fn settings(value: Value) -> InstallSettings {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_store_reports_changes() {
+        let mut store = DryRunStore::new(snapshot());
+        let report = store.store(&settings(json!({
+            "localization": { "language": "es_ES.UTF-8" },
+            "network": { "connections": [{ "id": "eth0", "method4": "manual" }] }
+        })));
+
+        assert_eq!(report.sections.len(), 2);
+        assert!(!report.has_errors());
+
+        let network = report.section(Section::Network).unwrap();
+        assert_eq!(
+            network.changes,
+            vec![Change {
+                path: vec![
+                    "network".to_string(),
+                    "connections".to_string(),
+                    "eth0".to_string(),
+                    "method4".to_string()
+                ],
+                old: Some(json!("auto")),
+                new: Some(json!("manual")),
+            }]
+        );
+
+        let localization = report.section(Section::Localization).unwrap();
+        assert_eq!(
+            localization.changes,
+            vec![Change {
+                path: vec!["localization".to_string(), "language".to_string()],
+                old: Some(json!("en_US.UTF-8")),
+                new: Some(json!("es_ES.UTF-8")),
+            }]
+        );
+        assert_eq!(
+            store.load().localization.as_ref().unwrap().keyboard,
+            Some("us".to_string())
+        );
+    }
+
+    #[test]
+    fn test_store_reports_issues() {
+        let mut store = DryRunStore::new(snapshot());
+        let report = store.store(&settings(json!({
+            "product": { "id": "SLES" },
+            "software": { "patterns": ["gnome", "kde"] },
+            "localization": { "timezone": "Mars/Olympus" },
+            "storage": { "drives": [{ "search": "/dev/sda" }] }
+        })));
+
+        assert!(report.has_errors());
+        let issues: Vec<&str> = report
+            .sections
+            .iter()
+            .flat_map(|s| &s.issues)
+            .map(|i| i.description.as_str())
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                "Product must be registered",
+                "Failed to find these patterns: [\"kde\"]",
+                "The following selected devices are not found in the system: /dev/sda",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_store_network_bond() {
+        let mut store = DryRunStore::new(snapshot());
+        let bond = NetworkConnection {
+            id: "bond0".to_string(),
+            interface: Some("bond0".to_string()),
+            bond: Some(BondSettings {
+                ports: vec!["eth1".to_string(), "eth2".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let report = store.store(&InstallSettings {
+            network: Some(NetworkSettings {
+                connections: vec![bond],
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        let ids: Vec<&str> = store
+            .load()
+            .network
+            .as_ref()
+            .unwrap()
+            .connections
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["eth0", "eth1", "eth2", "bond0"]);
+
+        let network = report.section(Section::Network).unwrap();
+        assert_eq!(network.issues.len(), 1);
+        assert!(network.issues[0].description.contains("'eth2'"));
+    }
+
+    fn store_virtual_device(connection: Value) -> Vec<String> {
+        let mut store = DryRunStore::new(snapshot());
+        let report = store.store(&settings(json!({
+            "network": { "connections": [connection] }
+        })));
+        let network = report.section(Section::Network).unwrap();
+        network
+            .issues
+            .iter()
+            .map(|i| i.description.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_store_network_vlan() {
+        let issues = store_virtual_device(json!({
+            "id": "eth0.10",
+            "interface": "eth0.10",
+            "vlan": { "parent": "eth0", "id": 10 }
+        }));
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn test_store_network_bridge() {
+        let issues = store_virtual_device(json!({
+            "id": "br0",
+            "interface": "br0",
+            "bridge": { "ports": ["eth1"] }
+        }));
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn test_store_network_tun() {
+        let issues = store_virtual_device(json!({
+            "id": "tun0",
+            "interface": "tun0",
+            "tun": { "mode": "tun" }
+        }));
+        assert!(issues.is_empty());
+
+        let issues = store_virtual_device(json!({ "id": "tun1", "interface": "tun1" }));
+        assert_eq!(issues.len(), 1);
+    }
+
+    #[test]
+    fn test_store_masks_secrets() {
+        let mut store = DryRunStore::new(snapshot());
+        let report = store.store(&settings(json!({
+            "user": { "userName": "tux", "password": "fish" },
+            "root": { "password": "nots3cr3t" }
+        })));
+
+        let users = report.section(Section::Users).unwrap();
+        assert!(users.issues.is_empty());
+        let output = serde_json::to_string(&users.changes).unwrap();
+        assert!(!output.contains("fish"));
+        assert!(!output.contains("nots3cr3t"));
+    }
+
+    #[test]
+    fn test_store_masks_wireguard_secrets() {
+        let mut store = DryRunStore::new(snapshot());
+        let report = store.store(&settings(json!({
+            "network": { "connections": [{
+                "id": "wg0",
+                "interface": "wg0",
+                "wireguard": {
+                    "privateKey": "QkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkI=",
+                    "peers": [{
+                        "publicKey": "QUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUE=",
+                        "presharedKey": "Q0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0M="
+                    }]
+                }
+            }] }
+        })));
+
+        let network = report.section(Section::Network).unwrap();
+        let output = serde_json::to_string(&network.changes).unwrap();
+        assert!(output.contains("wg0"));
+        assert!(!output.contains("QkJCQkJC"));
+        assert!(!output.contains("Q0NDQ0ND"));
+    }
+
+    #[test]
+    fn test_store_pre_scripts() {
+        let mut store = DryRunStore::new(snapshot());
+        let report = store.store(&settings(json!({
+            "scripts": { "pre": [{ "name": "hello", "body": "echo hello" }] }
+        })));
+
+        let scripts = report.section(Section::Scripts).unwrap();
+        assert_eq!(scripts.changes[0].path, vec!["scripts".to_string()]);
+        assert_eq!(scripts.side_effects.len(), 2);
+    }
+}
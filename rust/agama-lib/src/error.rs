@@ -23,6 +23,8 @@ use std::io;
 use thiserror::Error;
 use zbus::{self, zvariant};
 
+use crate::install_settings::Section;
+use crate::store::Rollback;
 use crate::utils::TransferError;
 
 #[derive(Error, Debug)]
@@ -69,18 +71,42 @@ pub enum ServiceError {
     InternalError(String),
     #[error("Could not read the file: '{0}'")]
     CouldNotTransferFile(#[from] TransferError),
+    #[error("Could not store the {section} settings ({rollback}): {source}")]
+    StoreSettings {
+        section: Section,
+        #[source]
+        source: Box<ServiceError>,
+        rollback: Rollback,
+    },
 }
 
 #[derive(Error, Debug)]
 pub enum ProfileError {
     #[error("Could not read the profile")]
-    Unreachable(#[from] TransferError),
+    Unreachable(#[source] TransferError),
+    #[error("Refusing to use an untrusted profile. {0}")]
+    Untrusted(#[source] TransferError),
     #[error("Jsonnet evaluation failed:\n{0}")]
     EvaluationError(String),
     #[error("I/O error")]
     InputOutputError(#[from] io::Error),
     #[error("The profile is not a valid JSON file")]
     FormatError(#[from] serde_json::Error),
+    #[error("The profile {0} includes itself")]
+    CircularInclude(String),
+    #[error("Invalid profile reference: {0}")]
+    InvalidReference(String),
+    #[error("Unsupported format of the included profile {0}")]
+    UnsupportedInclude(String),
     #[error("Error: {0}")]
     Anyhow(#[from] anyhow::Error),
 }
+
+impl From<TransferError> for ProfileError {
+    fn from(error: TransferError) -> Self {
+        match error {
+            TransferError::VerificationFailed(..) => Self::Untrusted(error),
+            _ => Self::Unreachable(error),
+        }
+    }
+}
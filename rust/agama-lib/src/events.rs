@@ -0,0 +1,125 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! This module implements support for the events stream that Agama's web server publishes as
+//! [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html) at
+//! `/api/events`.
+
+pub mod http_client;
+pub use http_client::{EventsHTTPClient, EventsQuery, EventsStream};
+
+/// Event received through the events stream.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerSentEvent {
+    /// Event identifier (the sequence number of the event in the server journal).
+    pub id: Option<String>,
+    /// Event type (e.g., "Progress" or "Gap").
+    pub event: Option<String>,
+    /// Event data (a JSON document).
+    pub data: String,
+}
+
+/// Incremental parser for a `text/event-stream` body.
+///
+/// The body is received in chunks that do not necessarily match line boundaries, so the parser
+/// keeps any incomplete line until the next chunk arrives.
+#[derive(Default)]
+pub struct EventStreamParser {
+    buffer: Vec<u8>,
+    current: ServerSentEvent,
+    has_data: bool,
+}
+
+impl EventStreamParser {
+    /// Feeds a chunk of the body and returns the events that were completed.
+    ///
+    /// * `chunk`: chunk of the response body.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<ServerSentEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = vec![];
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line[..pos]);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if let Some(event) = self.process_line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<ServerSentEvent> {
+        if line.is_empty() {
+            let event = std::mem::take(&mut self.current);
+            let has_data = std::mem::take(&mut self.has_data);
+            return has_data.then_some(event);
+        }
+
+        // lines starting with a colon are comments (e.g., keep-alive messages)
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "data" => {
+                if self.has_data {
+                    self.current.data.push('\n');
+                }
+                self.current.data.push_str(value);
+                self.has_data = true;
+            }
+            "id" => self.current.id = Some(value.to_string()),
+            "event" => self.current.event = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventStreamParser, ServerSentEvent};
+
+    #[test]
+    fn test_parse_event_stream() {
+        let mut parser = EventStreamParser::default();
+        let events = parser.feed(b": keep-alive\n\ndata: {\"seq\":1}\nid: 1\nevent: Prog");
+        assert!(events.is_empty());
+
+        let events = parser.feed(b"ress\n\ndata: {\"from\":2,\ndata: \"to\":3}\r\nevent: Gap\n\n");
+        assert_eq!(
+            events,
+            vec![
+                ServerSentEvent {
+                    id: Some("1".to_string()),
+                    event: Some("Progress".to_string()),
+                    data: "{\"seq\":1}".to_string()
+                },
+                ServerSentEvent {
+                    id: None,
+                    event: Some("Gap".to_string()),
+                    data: "{\"from\":2,\n\"to\":3}".to_string()
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Implements a client to access Agama's events stream.
+
+use std::collections::VecDeque;
+
+use reqwest::Response;
+
+use crate::base_http_client::BaseHTTPClient;
+use crate::events::{EventStreamParser, ServerSentEvent};
+use crate::ServiceError;
+
+/// Filters to apply when subscribing to the events stream.
+#[derive(Clone, Debug, Default)]
+pub struct EventsQuery {
+    /// Event types to receive (e.g., "Progress"). All of them if empty.
+    pub types: Vec<String>,
+    /// Services whose events should be received (e.g., "storage"). All of them if empty.
+    pub services: Vec<String>,
+    /// Receive the events after the given sequence number, if they are still in the journal.
+    pub since: Option<u64>,
+}
+
+impl EventsQuery {
+    fn to_query_string(&self) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        if !self.types.is_empty() {
+            query.append_pair("types", &self.types.join(","));
+        }
+        if !self.services.is_empty() {
+            query.append_pair("services", &self.services.join(","));
+        }
+        if let Some(since) = self.since {
+            query.append_pair("since", &since.to_string());
+        }
+        query.finish()
+    }
+}
+
+pub struct EventsHTTPClient {
+    client: BaseHTTPClient,
+}
+
+impl EventsHTTPClient {
+    pub fn new(base: BaseHTTPClient) -> Self {
+        Self { client: base }
+    }
+
+    /// Subscribes to the events stream.
+    ///
+    /// * `query`: events to receive.
+    pub async fn subscribe(&self, query: &EventsQuery) -> Result<EventsStream, ServiceError> {
+        let query = query.to_query_string();
+        let path = if query.is_empty() {
+            "/events".to_string()
+        } else {
+            format!("/events?{}", query)
+        };
+        let response = self.client.get_raw(&path).await?;
+        Ok(EventsStream::new(response))
+    }
+}
+
+/// Stream of events coming from the server.
+pub struct EventsStream {
+    response: Response,
+    parser: EventStreamParser,
+    pending: VecDeque<ServerSentEvent>,
+}
+
+impl EventsStream {
+    fn new(response: Response) -> Self {
+        Self {
+            response,
+            parser: EventStreamParser::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Returns the next event or `None` if the server closed the stream.
+    pub async fn next(&mut self) -> Result<Option<ServerSentEvent>, ServiceError> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+
+            let Some(chunk) = self.response.chunk().await? else {
+                return Ok(None);
+            };
+            self.pending.extend(self.parser.feed(&chunk));
+        }
+    }
+}
@@ -33,6 +33,12 @@ use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 
+mod diff;
+pub use diff::{diff, Change, PatchOperation, PatchOperationKind, SectionDiff, SettingsDiff};
+pub(crate) use diff::{json_changes, section_value, to_value};
+mod merge;
+pub use merge::merge;
+
 /// Installation settings
 ///
 /// This struct represents installation settings. It serves as an entry point and it is composed of
@@ -63,6 +69,26 @@ pub struct InstallSettings {
 }
 
 impl InstallSettings {
+    /// Whether the given section is present in the settings.
+    ///
+    /// * `section`: section to check.
+    pub fn has_section(&self, section: Section) -> bool {
+        match section {
+            Section::Bootloader => self.bootloader.is_some(),
+            // the users settings are flattened, so they are always deserialized
+            Section::Users => self
+                .user
+                .as_ref()
+                .is_some_and(|u| u.first_user.is_some() || u.root.is_some()),
+            Section::Software => self.software.is_some(),
+            Section::Product => self.product.is_some(),
+            Section::Storage => self.storage.is_some() || self.storage_autoyast.is_some(),
+            Section::Network => self.network.is_some(),
+            Section::Localization => self.localization.is_some(),
+            Section::Scripts => self.scripts.is_some(),
+        }
+    }
+
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
         let file = File::open(path)?;
         let reader = BufReader::new(file);
@@ -70,3 +96,50 @@ impl InstallSettings {
         Ok(data)
     }
 }
+
+/// Top-level sections of the installation settings.
+///
+/// Each section is handled by its own store (see [crate::Store]).
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+)]
+#[strum(serialize_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub enum Section {
+    Users,
+    Network,
+    Product,
+    Localization,
+    Software,
+    Storage,
+    Bootloader,
+    Scripts,
+}
+
+impl Section {
+    /// All the sections, in the same order that [crate::Store] applies them.
+    pub const ALL: [Section; 8] = [
+        Section::Scripts,
+        // import the users (esp. the root password) before initializing software,
+        // if software fails the Web UI would be stuck in the root password dialog
+        Section::Users,
+        // order is important here as network can be critical for connection
+        // to registration server and selecting product is important for rest
+        Section::Network,
+        Section::Product,
+        // ordering: localization after product as some product may miss some locales
+        Section::Localization,
+        Section::Software,
+        Section::Storage,
+        Section::Bootloader,
+    ];
+}
@@ -0,0 +1,598 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Semantic comparison of installation settings.
+//!
+//! Comparing the JSON documents directly reports many spurious changes (e.g., reordered keys in
+//! the storage settings or reordered network connections). Instead, each section is converted
+//! to a normalized representation before comparing them:
+//!
+//! * The software patterns are handled as a set (`{ "gnome": true }`).
+//! * The network connections are indexed by their `id`.
+//! * The scripts are indexed by their `name`.
+//! * The storage settings are parsed, so the formatting and the keys order do not matter.
+//! * Missing, `null` and empty values are equivalent.
+//!
+//! The paths of the changes refer to that normalized representation (e.g.,
+//! `network.connections.eth0.method4`).
+
+use super::{InstallSettings, Section};
+use crate::network::NetworkSettings;
+use crate::scripts::ScriptsConfig;
+use crate::users::UserSettings;
+use crate::utils::{is_secret, mask_secrets};
+use serde::Serialize;
+use serde_json::value::RawValue;
+use serde_json::{Map, Value};
+use std::fmt;
+
+const MASKED_VALUE: &str = "********";
+
+/// Differences between two sets of installation settings.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsDiff {
+    /// Sections that changed, in the order they are applied.
+    pub sections: Vec<SectionDiff>,
+}
+
+impl SettingsDiff {
+    /// Whether both settings are equivalent.
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Returns the differences for the given section, if any.
+    ///
+    /// * `section`: section to search for.
+    pub fn section(&self, section: Section) -> Option<&SectionDiff> {
+        self.sections.iter().find(|s| s.section == section)
+    }
+
+    /// Converts the differences into a list of JSON Patch (RFC 6902) operations.
+    ///
+    /// The operations apply to the normalized representation of the settings.
+    pub fn to_patch(&self) -> Vec<PatchOperation> {
+        self.sections
+            .iter()
+            .flat_map(|s| &s.changes)
+            .map(PatchOperation::from)
+            .collect()
+    }
+}
+
+/// Differences within a single section.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SectionDiff {
+    pub section: Section,
+    pub changes: Vec<Change>,
+}
+
+/// Change of a single setting.
+///
+/// The `path` is the route to the setting (e.g., `["network", "connections", "eth0",
+/// "method4"]`). A missing `old` value means that the setting is added and a missing `new` value
+/// that it is removed. The secret values (e.g., passwords) are masked.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Change {
+    pub path: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<Value>,
+}
+
+impl Change {
+    /// Returns the path as a JSON Pointer (RFC 6901).
+    pub fn pointer(&self) -> String {
+        self.path
+            .iter()
+            .map(|s| format!("/{}", s.replace('~', "~0").replace('/', "~1")))
+            .collect()
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.join(".");
+        match (&self.old, &self.new) {
+            (None, Some(new)) => write!(f, "{}: added {}", path, new),
+            (Some(old), None) => write!(f, "{}: removed {}", path, old),
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", path, old, new),
+            (None, None) => write!(f, "{}", path),
+        }
+    }
+}
+
+/// JSON Patch operation.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PatchOperation {
+    pub op: PatchOperationKind,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PatchOperationKind {
+    Add,
+    Remove,
+    Replace,
+}
+
+impl From<&Change> for PatchOperation {
+    fn from(change: &Change) -> Self {
+        let op = match (&change.old, &change.new) {
+            (None, _) => PatchOperationKind::Add,
+            (Some(_), None) => PatchOperationKind::Remove,
+            (Some(_), Some(_)) => PatchOperationKind::Replace,
+        };
+        Self {
+            op,
+            path: change.pointer(),
+            value: change.new.clone(),
+        }
+    }
+}
+
+/// Compares two sets of installation settings section by section.
+///
+/// * `old`: settings to compare from.
+/// * `new`: settings to compare to.
+pub fn diff(old: &InstallSettings, new: &InstallSettings) -> SettingsDiff {
+    let sections = Section::ALL
+        .into_iter()
+        .filter_map(|section| {
+            let mut changes = vec![];
+            json_changes(
+                &[],
+                &section_value(old, section),
+                &section_value(new, section),
+                &mut changes,
+            );
+            (!changes.is_empty()).then_some(SectionDiff { section, changes })
+        })
+        .collect();
+    SettingsDiff { sections }
+}
+
+/// Returns the normalized representation of a section.
+///
+/// The result is an object containing the top-level keys of the section (e.g., `user` and
+/// `root` for the users section).
+///
+/// * `settings`: installation settings.
+/// * `section`: section to convert.
+pub(crate) fn section_value(settings: &InstallSettings, section: Section) -> Value {
+    let mut map = Map::new();
+    match section {
+        Section::Users => {
+            if let Some(user) = &settings.user {
+                map = users_value(user);
+            }
+        }
+        Section::Network => {
+            if let Some(network) = &settings.network {
+                map.insert("network".to_string(), network_value(network));
+            }
+        }
+        Section::Product => {
+            map.insert("product".to_string(), to_value(&settings.product));
+        }
+        Section::Localization => {
+            map.insert("localization".to_string(), to_value(&settings.localization));
+        }
+        Section::Software => {
+            if let Some(software) = &settings.software {
+                let patterns = software
+                    .patterns
+                    .iter()
+                    .map(|p| (p.clone(), Value::Bool(true)))
+                    .collect::<Map<String, Value>>();
+                map.insert(
+                    "software".to_string(),
+                    Value::Object(Map::from_iter([(
+                        "patterns".to_string(),
+                        Value::Object(patterns),
+                    )])),
+                );
+            }
+        }
+        Section::Storage => {
+            map.insert("storage".to_string(), raw_value(&settings.storage));
+            map.insert(
+                "legacyAutoyastStorage".to_string(),
+                raw_value(&settings.storage_autoyast),
+            );
+        }
+        Section::Bootloader => {
+            map.insert("bootloader".to_string(), to_value(&settings.bootloader));
+        }
+        Section::Scripts => {
+            if let Some(scripts) = &settings.scripts {
+                map.insert("scripts".to_string(), scripts_value(scripts));
+            }
+        }
+    }
+    strip_empty(Value::Object(map))
+}
+
+/// Serializes the users settings, including the root password.
+fn users_value(settings: &UserSettings) -> Map<String, Value> {
+    let Value::Object(mut map) = to_value(settings) else {
+        return Map::new();
+    };
+    // the root password is never serialized, so add it explicitly
+    if let Some(root) = &settings.root {
+        let root_map = map
+            .entry("root")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let (Value::Object(root_map), Some(password)) = (root_map, &root.password) {
+            root_map.insert("password".to_string(), Value::String(password.clone()));
+            if let Some(hashed) = root.hashed_password {
+                root_map.insert("hashedPassword".to_string(), Value::Bool(hashed));
+            }
+        }
+    }
+    map
+}
+
+/// Serializes the network settings indexing the connections by their ID.
+fn network_value(settings: &NetworkSettings) -> Value {
+    let connections = settings
+        .connections
+        .iter()
+        .map(|c| (c.id.clone(), to_value(c)))
+        .collect::<Map<String, Value>>();
+    let mut map = Map::from_iter([("connections".to_string(), Value::Object(connections))]);
+    if let Some(nmstate) = &settings.nmstate {
+        map.insert("nmstate".to_string(), nmstate.clone());
+    }
+    Value::Object(map)
+}
+
+/// Serializes the scripts settings indexing each group by the script's name.
+fn scripts_value(settings: &ScriptsConfig) -> Value {
+    let Value::Object(groups) = to_value(settings) else {
+        return Value::Null;
+    };
+    let groups = groups
+        .into_iter()
+        .map(|(group, scripts)| {
+            let Value::Array(scripts) = scripts else {
+                return (group, scripts);
+            };
+            let scripts = scripts
+                .into_iter()
+                .map(|s| {
+                    let name = s.get("name").and_then(Value::as_str).unwrap_or_default();
+                    (name.to_string(), s)
+                })
+                .collect::<Map<String, Value>>();
+            (group, Value::Object(scripts))
+        })
+        .collect();
+    Value::Object(groups)
+}
+
+/// Parses a raw JSON value (e.g., the storage settings).
+fn raw_value(value: &Option<Box<RawValue>>) -> Value {
+    value
+        .as_ref()
+        .and_then(|v| serde_json::from_str(v.get()).ok())
+        .map(strip_empty)
+        .unwrap_or_default()
+}
+
+pub(crate) fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value)
+        .map(strip_empty)
+        .unwrap_or_default()
+}
+
+/// Removes the null and empty members from the objects.
+///
+/// A setting set to null (or to an empty object) is equivalent to a missing one.
+fn strip_empty(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (k, strip_empty(v)))
+                .filter(|(_, v)| !(v.is_null() || v.as_object().is_some_and(|o| o.is_empty())))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(strip_empty).collect()),
+        value => value,
+    }
+}
+
+/// Replaces the values of the secret keys (see [is_secret]).
+fn mask(key: &str, value: &Value) -> Value {
+    if is_secret(key, value) {
+        return Value::String(MASKED_VALUE.to_string());
+    }
+    mask_secrets(value.clone(), MASKED_VALUE)
+}
+
+/// Collects the differences between two JSON values.
+///
+/// Objects are compared member by member and arrays of the same length element by element. Any
+/// other value (including arrays of different length) is compared as a whole.
+///
+/// * `path`: path of the values.
+/// * `old`: old value.
+/// * `new`: new value.
+/// * `changes`: list to add the changes to.
+pub(crate) fn json_changes(path: &[String], old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    let key = path.last().map(String::as_str).unwrap_or_default();
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let child = child_path(path, key);
+                match new_map.get(key) {
+                    Some(new_value) => json_changes(&child, old_value, new_value, changes),
+                    None => changes.push(Change {
+                        path: child,
+                        old: Some(mask(key, old_value)),
+                        new: None,
+                    }),
+                }
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    changes.push(Change {
+                        path: child_path(path, key),
+                        old: None,
+                        new: Some(mask(key, new_value)),
+                    });
+                }
+            }
+        }
+        (Value::Array(old_values), Value::Array(new_values))
+            if old_values.len() == new_values.len() =>
+        {
+            for (index, (old_value, new_value)) in old_values.iter().zip(new_values).enumerate() {
+                json_changes(
+                    &child_path(path, &index.to_string()),
+                    old_value,
+                    new_value,
+                    changes,
+                );
+            }
+        }
+        (Value::Null, Value::Object(_)) => {
+            json_changes(path, &Value::Object(Map::new()), new, changes)
+        }
+        (Value::Object(_), Value::Null) => {
+            json_changes(path, old, &Value::Object(Map::new()), changes)
+        }
+        _ if old == new => {}
+        _ => changes.push(Change {
+            path: path.to_vec(),
+            old: Some(mask(key, old)).filter(|v| !v.is_null()),
+            new: Some(mask(key, new)).filter(|v| !v.is_null()),
+        }),
+    }
+}
+
+fn child_path(path: &[String], key: &str) -> Vec<String> {
+    let mut child = path.to_vec();
+    child.push(key.to_string());
+    child
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings(value: Value) -> InstallSettings {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn path(path: &str) -> Vec<String> {
+        path.split('.').map(String::from).collect()
+    }
+
+    #[test]
+    fn test_diff_equivalent_settings() {
+        let old = settings(json!({
+            "software": { "patterns": ["base", "gnome"] },
+            "network": {
+                "connections": [
+                    { "id": "eth0", "method4": "auto" },
+                    { "id": "eth1", "method4": "manual" }
+                ]
+            },
+            "storage": { "drives": [{ "search": "/dev/vda", "alias": "root" }] },
+            "localization": { "language": "en_US.UTF-8", "timezone": null }
+        }));
+        let new = settings(json!({
+            "localization": { "language": "en_US.UTF-8" },
+            "storage": { "drives": [{ "alias": "root", "search": "/dev/vda" }] },
+            "network": {
+                "connections": [
+                    { "id": "eth1", "method4": "manual" },
+                    { "id": "eth0", "method4": "auto" }
+                ]
+            },
+            "software": { "patterns": ["gnome", "base"] }
+        }));
+
+        assert!(diff(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_diff_sections() {
+        let old = settings(json!({
+            "software": { "patterns": ["base"] },
+            "network": { "connections": [{ "id": "eth0", "method4": "auto" }] },
+            "scripts": { "post": [{ "name": "hello", "body": "echo hello" }] },
+            "storage": { "drives": [{ "search": "/dev/vda" }] }
+        }));
+        let new = settings(json!({
+            "software": { "patterns": ["base", "gnome"] },
+            "network": { "connections": [{ "id": "eth0", "method4": "manual" }] },
+            "scripts": { "post": [{ "name": "hello", "body": "echo bye" }] },
+            "storage": { "drives": [{ "search": "/dev/vdb" }] }
+        }));
+
+        let diff = diff(&old, &new);
+        let sections: Vec<Section> = diff.sections.iter().map(|s| s.section).collect();
+        assert_eq!(
+            sections,
+            vec![
+                Section::Scripts,
+                Section::Network,
+                Section::Software,
+                Section::Storage
+            ]
+        );
+
+        let changes = &diff.section(Section::Network).unwrap().changes;
+        assert_eq!(
+            changes,
+            &vec![Change {
+                path: path("network.connections.eth0.method4"),
+                old: Some(json!("auto")),
+                new: Some(json!("manual")),
+            }]
+        );
+
+        let changes = &diff.section(Section::Scripts).unwrap().changes;
+        assert_eq!(changes[0].path, path("scripts.post.hello.body"));
+
+        let changes = &diff.section(Section::Software).unwrap().changes;
+        assert_eq!(
+            changes[0].to_string(),
+            "software.patterns.gnome: added true"
+        );
+
+        let changes = &diff.section(Section::Storage).unwrap().changes;
+        assert_eq!(changes[0].path, path("storage.drives.0.search"));
+    }
+
+    #[test]
+    fn test_diff_masks_secrets() {
+        let old = settings(json!({
+            "root": { "password": "nots3cr3t" },
+            "product": { "id": "SLES", "registrationCode": "1234" }
+        }));
+        let new = settings(json!({
+            "root": { "password": "s3cr3t" },
+            "product": { "id": "SLES", "registrationCode": "5678" }
+        }));
+
+        let diff = diff(&old, &new);
+        assert_eq!(diff.sections.len(), 2);
+        let output = serde_json::to_string(&diff).unwrap();
+        assert!(!output.contains("s3cr3t"));
+        assert!(!output.contains("5678"));
+    }
+
+    #[test]
+    fn test_diff_masks_8021x_secrets() {
+        let old = settings(json!({
+            "network": { "connections": [{ "id": "eth0" }] }
+        }));
+        let new = settings(json!({
+            "network": { "connections": [{
+                "id": "eth0",
+                "ieee-8021x": {
+                    "eap": ["tls"],
+                    "identity": "tux",
+                    "caCertPassword": "ca-s3cr3t",
+                    "clientCertPassword": "client-s3cr3t",
+                    "privateKey": "/etc/certs/key.pem",
+                    "privateKeyPassword": "key-s3cr3t"
+                }
+            }] }
+        }));
+
+        let diff = diff(&old, &new);
+        let output = serde_json::to_string(&diff).unwrap();
+        assert!(output.contains(r#""identity":"tux""#));
+        assert!(!output.contains("s3cr3t"));
+        assert!(!output.contains("key.pem"));
+        let patch = serde_json::to_string(&diff.to_patch()).unwrap();
+        assert!(!patch.contains("s3cr3t"));
+    }
+
+    #[test]
+    fn test_diff_masks_wireguard_secrets() {
+        let wg0 = |private_key: &str, preshared_key: &str| {
+            json!({
+                "id": "wg0",
+                "wireguard": {
+                    "privateKey": private_key,
+                    "listenPort": 51820,
+                    "peers": [{
+                        "publicKey": "QUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUE=",
+                        "endpoint": "vpn.example.net:51820",
+                        "presharedKey": preshared_key
+                    }]
+                }
+            })
+        };
+        let old = settings(json!({ "network": { "connections": [] } }));
+        let new = settings(json!({
+            "network": { "connections": [wg0("QkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkJCQkI=", "Q0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0NDQ0M=")] }
+        }));
+        let output = serde_json::to_string(&diff(&old, &new)).unwrap();
+        assert!(output.contains("vpn.example.net:51820"));
+        assert!(!output.contains("QkJCQkJC"));
+        assert!(!output.contains("Q0NDQ0ND"));
+
+        // changed keys
+        let old = new;
+        let new = settings(json!({
+            "network": { "connections": [wg0("REREREREREREREREREREREREREREREREREREREREREQ=", "RUVFRUVFRUVFRUVFRUVFRUVFRUVFRUVFRUVFRUVFRUU=")] }
+        }));
+        let diff = diff(&old, &new);
+        assert_eq!(diff.to_patch().len(), 2);
+        let output = serde_json::to_string(&diff.to_patch()).unwrap();
+        assert!(!output.contains("RERERERE"));
+        assert!(!output.contains("RUVFRUVF"));
+    }
+
+    #[test]
+    fn test_diff_to_patch() {
+        let old = settings(json!({
+            "network": { "connections": [{ "id": "eth0/1", "method4": "auto" }] },
+            "localization": { "keyboard": "us" }
+        }));
+        let new = settings(json!({
+            "network": { "connections": [{ "id": "eth0/1" }] },
+            "localization": { "keyboard": "es", "timezone": "Europe/Madrid" }
+        }));
+
+        let patch = serde_json::to_value(diff(&old, &new).to_patch()).unwrap();
+        assert_eq!(
+            patch,
+            json!([
+                { "op": "remove", "path": "/network/connections/eth0~11/method4" },
+                { "op": "replace", "path": "/localization/keyboard", "value": "es" },
+                { "op": "add", "path": "/localization/timezone", "value": "Europe/Madrid" }
+            ])
+        );
+    }
+}
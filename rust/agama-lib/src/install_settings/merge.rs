@@ -0,0 +1,237 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Deep merge of installation settings.
+//!
+//! Profiles can be composed from several documents (see [crate::profile::ProfileComposer]).
+//! Merging an overlay into a base profile follows these rules:
+//!
+//! * Objects are merged recursively, so an overlay only needs to contain the values it changes.
+//! * A `null` value removes the key from the base profile.
+//! * The network connections are merged by their `id`.
+//! * The network `connections` and `nmstate` are alternatives: when the overlay sets one of them,
+//!   the other one is removed from the base profile.
+//! * The scripts are merged by their `name`.
+//! * The software patterns are handled as a set: the patterns of the overlay are added.
+//! * The storage settings are replaced as a whole, as merging two storage configurations
+//!   rarely produces a meaningful result.
+//! * Any other value (including the rest of the arrays) is replaced.
+//!
+//! When merging the elements of an array by a key, the matching elements are merged
+//! recursively and the new ones are appended.
+
+use serde_json::Value;
+
+/// Merge strategy for an array.
+#[derive(Clone, Copy)]
+enum ArrayMerge {
+    /// Elements are merged by the value of the given key.
+    ByKey(&'static str),
+    /// Elements are handled as a set.
+    Set,
+}
+
+/// Arrays that are not simply replaced.
+const ARRAY_RULES: [(&str, ArrayMerge); 7] = [
+    ("network.connections", ArrayMerge::ByKey("id")),
+    ("network.nmstate.interfaces", ArrayMerge::ByKey("name")),
+    ("scripts.pre", ArrayMerge::ByKey("name")),
+    ("scripts.postPartitioning", ArrayMerge::ByKey("name")),
+    ("scripts.post", ArrayMerge::ByKey("name")),
+    ("scripts.init", ArrayMerge::ByKey("name")),
+    ("software.patterns", ArrayMerge::Set),
+];
+
+/// Values that are replaced as a whole.
+const OPAQUE_KEYS: [&str; 2] = ["storage", "legacyAutoyastStorage"];
+
+/// Keys that cannot be used together. Setting one of them removes the others.
+const EXCLUSIVE_KEYS: [(&str, &[&str]); 1] = [("network", &["connections", "nmstate"])];
+
+/// Merges an overlay into the given profile.
+///
+/// * `base`: profile to merge the overlay into.
+/// * `overlay`: profile containing the values to add or change.
+pub fn merge(base: &mut Value, overlay: Value) {
+    merge_at("", base, overlay);
+}
+
+fn merge_at(path: &str, base: &mut Value, overlay: Value) {
+    if OPAQUE_KEYS.contains(&path) {
+        *base = overlay;
+        return;
+    }
+
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                if value.is_null() {
+                    base.remove(&key);
+                    continue;
+                }
+
+                for excluded in exclusive_keys(path, &key) {
+                    base.remove(excluded);
+                }
+
+                match base.get_mut(&key) {
+                    Some(current) => merge_at(&child_path(path, &key), current, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(overlay)) => match array_rule(path) {
+            Some(ArrayMerge::ByKey(key)) => merge_by_key(path, base, overlay, key),
+            Some(ArrayMerge::Set) => {
+                for value in overlay {
+                    if !base.contains(&value) {
+                        base.push(value);
+                    }
+                }
+            }
+            None => *base = overlay,
+        },
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Merges the elements of two arrays using the value of the given key.
+///
+/// Elements without the key are appended.
+fn merge_by_key(path: &str, base: &mut Vec<Value>, overlay: Vec<Value>, key: &str) {
+    for value in overlay {
+        let current = value
+            .get(key)
+            .and_then(|id| base.iter_mut().find(|e| e.get(key) == Some(id)));
+        match current {
+            Some(current) => merge_at(path, current, value),
+            None => base.push(value),
+        }
+    }
+}
+
+fn array_rule(path: &str) -> Option<ArrayMerge> {
+    ARRAY_RULES
+        .iter()
+        .find(|(rule_path, _)| *rule_path == path)
+        .map(|(_, rule)| *rule)
+}
+
+/// Returns the keys that cannot be used together with the given one.
+fn exclusive_keys(path: &str, key: &str) -> Vec<&'static str> {
+    EXCLUSIVE_KEYS
+        .iter()
+        .filter(|(keys_path, keys)| *keys_path == path && keys.contains(&key))
+        .flat_map(|(_, keys)| keys.iter().copied())
+        .filter(|k| *k != key)
+        .collect()
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge() {
+        let mut base = json!({
+            "localization": { "language": "en_US.UTF-8", "keyboard": "us" },
+            "software": { "patterns": ["base", "gnome"] },
+            "network": {
+                "connections": [
+                    { "id": "eth0", "method4": "auto", "gateway4": "192.168.1.1" },
+                    { "id": "eth1", "method4": "auto" }
+                ]
+            },
+            "scripts": { "post": [{ "name": "motd", "body": "echo hello" }] },
+            "storage": { "drives": [{ "search": "/dev/vda" }] },
+            "user": { "userName": "jane", "fullName": "Jane Doe" }
+        });
+        let overlay = json!({
+            "localization": { "keyboard": "de" },
+            "software": { "patterns": ["gnome", "kde"] },
+            "network": {
+                "connections": [
+                    { "id": "eth0", "method4": "manual", "gateway4": null },
+                    { "id": "wlan0", "method4": "auto" }
+                ]
+            },
+            "scripts": {
+                "post": [{ "name": "motd", "body": "echo bye" }, { "name": "ssh", "body": "" }]
+            },
+            "storage": { "boot": { "configure": false } },
+            "user": null
+        });
+
+        merge(&mut base, overlay);
+        assert_eq!(
+            base,
+            json!({
+                "localization": { "language": "en_US.UTF-8", "keyboard": "de" },
+                "software": { "patterns": ["base", "gnome", "kde"] },
+                "network": {
+                    "connections": [
+                        { "id": "eth0", "method4": "manual" },
+                        { "id": "eth1", "method4": "auto" },
+                        { "id": "wlan0", "method4": "auto" }
+                    ]
+                },
+                "scripts": {
+                    "post": [{ "name": "motd", "body": "echo bye" }, { "name": "ssh", "body": "" }]
+                },
+                "storage": { "boot": { "configure": false } }
+            })
+        );
+    }
+
+    #[test]
+    fn test_merge_exclusive_keys() {
+        let mut base = json!({
+            "network": { "connections": [{ "id": "eth0" }] }
+        });
+        let overlay = json!({
+            "network": { "nmstate": { "interfaces": [{ "name": "eth0" }] } }
+        });
+        merge(&mut base, overlay);
+        assert_eq!(
+            base,
+            json!({ "network": { "nmstate": { "interfaces": [{ "name": "eth0" }] } } })
+        );
+
+        let overlay = json!({
+            "network": { "connections": [{ "id": "eth1" }] }
+        });
+        merge(&mut base, overlay);
+        assert_eq!(
+            base,
+            json!({ "network": { "connections": [{ "id": "eth1" }] } })
+        );
+    }
+}
@@ -46,7 +46,9 @@
 pub mod auth;
 pub mod base_http_client;
 pub mod bootloader;
+pub mod dry_run;
 pub mod error;
+pub mod events;
 pub mod install_settings;
 pub mod jobs;
 pub mod localization;
@@ -63,7 +65,7 @@ pub mod dbus;
 pub mod progress;
 pub mod proxies;
 mod store;
-pub use store::Store;
+pub use store::{Rollback, Store};
 pub mod openapi;
 pub mod questions;
 pub mod scripts;
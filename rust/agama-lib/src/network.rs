@@ -29,3 +29,4 @@ pub mod types;
 pub use client::NetworkClient;
 pub use settings::NetworkSettings;
 pub use store::NetworkStore;
+pub(crate) use store::{default_connection, find_connection, ordered_connections};
@@ -79,6 +79,22 @@ impl NetworkClient {
         Ok(())
     }
 
+    /// Removes a network connection
+    ///
+    /// * `id`: connection ID.
+    pub async fn remove_connection(&self, id: &str) -> Result<(), ServiceError> {
+        self.client
+            .delete_void(format!("/network/connections/{id}").as_str())
+            .await
+    }
+
+    /// Applies a nmstate desired state to the network configuration
+    ///
+    /// * `nmstate`: nmstate document.
+    pub async fn update_nmstate(&self, nmstate: &serde_json::Value) -> Result<(), ServiceError> {
+        self.client.put_void("/network/nmstate", nmstate).await
+    }
+
     /// Returns an array of network connections
     pub async fn apply(&self) -> Result<(), ServiceError> {
         // trying to be tricky here. If something breaks then we need a put method on
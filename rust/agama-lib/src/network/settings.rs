@@ -32,7 +32,11 @@ use std::net::IpAddr;
 #[serde(rename_all = "camelCase")]
 pub struct NetworkSettings {
     /// Connections to use in the installation
+    #[serde(default)]
     pub connections: Vec<NetworkConnection>,
+    /// Network configuration in nmstate format, as an alternative to the connections list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nmstate: Option<serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
@@ -114,6 +118,176 @@ impl Default for BondSettings {
     }
 }
 
+/// VLAN configuration
+#[derive(Clone, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VlanSettings {
+    /// Interface name of the parent device (e.g., "eth0" or "bond0")
+    pub parent: String,
+    /// VLAN identifier
+    pub id: u32,
+    /// VLAN protocol ("802.1Q" or "802.1ad")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+}
+
+/// Bridge configuration
+#[derive(Clone, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeSettings {
+    /// Whether the Spanning Tree Protocol is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stp: Option<bool>,
+    /// Priority of the bridge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    /// Spanning Tree Protocol forwarding delay (in seconds)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forward_delay: Option<u32>,
+    /// Spanning Tree Protocol hello time (in seconds)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hello_time: Option<u32>,
+    /// Spanning Tree Protocol maximum message age (in seconds)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<u32>,
+    /// Ethernet MAC address aging time (in seconds)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ageing_time: Option<u32>,
+    /// Interfaces or connections to add to the bridge
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub ports: Vec<String>,
+}
+
+/// Settings of a connection which is a port of a bridge
+#[derive(Clone, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgePortSettings {
+    /// Spanning Tree Protocol priority of the port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    /// Spanning Tree Protocol cost of the port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_cost: Option<u32>,
+}
+
+/// InfiniBand configuration
+#[derive(Clone, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InfinibandSettings {
+    /// Partition key (only for partitions on top of a parent device)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p_key: Option<i32>,
+    /// Interface name of the parent device
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// IP-over-InfiniBand transport mode ("datagram" or "connected")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport_mode: Option<String>,
+}
+
+/// TUN/TAP configuration
+#[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TunSettings {
+    /// Device mode ("tun" or "tap")
+    pub mode: String,
+    /// Group ID which is allowed to use the device
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// User ID which is allowed to use the device
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+impl Default for TunSettings {
+    fn default() -> Self {
+        Self {
+            mode: "tun".to_string(),
+            group: None,
+            owner: None,
+        }
+    }
+}
+
+/// WireGuard configuration
+#[derive(Clone, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WireguardSettings {
+    /// Base64-encoded private key of the interface
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    /// UDP port to listen on (a random port is used if not set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_port: Option<u16>,
+    /// Peers of the interface
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub peers: Vec<WireguardPeerSettings>,
+}
+
+/// WireGuard peer
+#[derive(Clone, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WireguardPeerSettings {
+    /// Base64-encoded public key of the peer
+    pub public_key: String,
+    /// Networks which are routed through the peer (e.g., "10.0.0.0/8" or "0.0.0.0/0")
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    #[schema(schema_with = schemas::ip_inet_array)]
+    pub allowed_ips: Vec<IpInet>,
+    /// Endpoint of the peer ("host:port")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Interval (in seconds) to send keepalive packets to the peer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistent_keepalive: Option<u32>,
+    /// Base64-encoded pre-shared key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preshared_key: Option<String>,
+}
+
+/// Static route
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteSettings {
+    /// Destination network (e.g., "10.0.0.0/8")
+    #[schema(schema_with = schemas::ip_inet_ref)]
+    pub destination: IpInet,
+    /// Next hop (gateway) to reach the destination
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(schema_with = schemas::ip_addr_ref)]
+    pub next_hop: Option<IpAddr>,
+    /// Metric of the route
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+    /// Routing table to add the route to (the main table is used if not set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<u32>,
+}
+
+/// Routing policy rule
+///
+/// It selects the routing table to use for the traffic matching the given source and
+/// destination networks.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingRuleSettings {
+    /// Priority of the rule (lower values are evaluated first)
+    pub priority: u32,
+    /// IP family ("ipv4" or "ipv6"). It is only needed when neither "from" nor "to" are set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    /// Source network to match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(schema_with = schemas::ip_inet_ref)]
+    pub from: Option<IpInet>,
+    /// Destination network to match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(schema_with = schemas::ip_inet_ref)]
+    pub to: Option<IpInet>,
+    /// Routing table to look up
+    pub table: u32,
+}
+
 /// IEEE 802.1x (EAP) settings
 #[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -200,6 +374,12 @@ pub struct NetworkConnection {
     /// Specifies whether to ignore automatically assigned DNS settings
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ignore_auto_dns: Option<bool>,
+    /// List of static routes (IPv4 and IPv6)
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub routes: Vec<RouteSettings>,
+    /// List of routing policy rules (IPv4 and IPv6)
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub routing_rules: Vec<RoutingRuleSettings>,
     /// Wireless settings for the connection
     #[serde(skip_serializing_if = "Option::is_none")]
     pub wireless: Option<WirelessSettings>,
@@ -215,6 +395,24 @@ pub struct NetworkConnection {
     /// Bonding settings if part of a bond
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bond: Option<BondSettings>,
+    /// VLAN settings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vlan: Option<VlanSettings>,
+    /// Bridge settings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bridge: Option<BridgeSettings>,
+    /// Port settings if part of a bridge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bridge_port: Option<BridgePortSettings>,
+    /// InfiniBand settings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub infiniband: Option<InfinibandSettings>,
+    /// TUN/TAP settings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tun: Option<TunSettings>,
+    /// WireGuard settings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wireguard: Option<WireguardSettings>,
     /// MAC address of the connection's interface
     #[serde(rename = "mac-address", skip_serializing_if = "Option::is_none")]
     pub mac_address: Option<String>,
@@ -250,8 +448,40 @@ impl NetworkConnection {
             DeviceType::Wireless
         } else if self.bond.is_some() {
             DeviceType::Bond
+        } else if self.vlan.is_some() {
+            DeviceType::Vlan
+        } else if self.bridge.is_some() {
+            DeviceType::Bridge
+        } else if self.infiniband.is_some() {
+            DeviceType::Infiniband
+        } else if self.tun.is_some() {
+            DeviceType::Tun
+        } else if self.wireguard.is_some() {
+            DeviceType::Wireguard
         } else {
             DeviceType::Ethernet
         }
     }
+
+    /// Interfaces or connections controlled by this connection (bond or bridge ports).
+    pub fn ports(&self) -> &[String] {
+        if let Some(bond) = &self.bond {
+            &bond.ports
+        } else if let Some(bridge) = &self.bridge {
+            &bridge.ports
+        } else {
+            &[]
+        }
+    }
+
+    /// Interface name of the device this connection is built on top of (e.g., the VLAN parent).
+    pub fn parent_interface(&self) -> Option<&str> {
+        if let Some(vlan) = &self.vlan {
+            Some(vlan.parent.as_str())
+        } else if let Some(infiniband) = &self.infiniband {
+            infiniband.parent.as_deref()
+        } else {
+            None
+        }
+    }
 }
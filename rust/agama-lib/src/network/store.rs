@@ -39,10 +39,17 @@ impl NetworkStore {
     pub async fn load(&self) -> Result<NetworkSettings, ServiceError> {
         let connections = self.network_client.connections().await?;
 
-        Ok(NetworkSettings { connections })
+        Ok(NetworkSettings {
+            connections,
+            nmstate: None,
+        })
     }
 
     pub async fn store(&self, settings: &NetworkSettings) -> Result<(), ServiceError> {
+        if let Some(nmstate) = &settings.nmstate {
+            self.network_client.update_nmstate(nmstate).await?;
+        }
+
         for id in ordered_connections(&settings.connections) {
             let id = id.as_str();
             let fallback = default_connection(id);
@@ -55,12 +62,36 @@ impl NetworkStore {
 
         Ok(())
     }
+
+    /// Restores the previous settings after storing new ones failed.
+    ///
+    /// Besides writing the previous connections again, it removes the connections that were
+    /// added by the new settings. The ones that were not written yet are ignored.
+    ///
+    /// * `previous`: settings before storing the new ones.
+    /// * `settings`: settings that were (maybe partially) stored.
+    pub async fn rollback(
+        &self,
+        previous: &NetworkSettings,
+        settings: &NetworkSettings,
+    ) -> Result<(), ServiceError> {
+        for id in ordered_connections(&settings.connections).iter().rev() {
+            if find_connection(id, &previous.connections).is_some() {
+                continue;
+            }
+            match self.network_client.remove_connection(id).await {
+                Err(ServiceError::BackendError(404, _)) | Ok(()) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        self.store(previous).await
+    }
 }
 
 /// Returns the list of connections in the order they should be written to the D-Bus service.
 ///
 /// * `conns`: connections to write.
-fn ordered_connections(conns: &Vec<NetworkConnection>) -> Vec<String> {
+pub(crate) fn ordered_connections(conns: &Vec<NetworkConnection>) -> Vec<String> {
     let mut ordered: Vec<String> = Vec::with_capacity(conns.len());
     for conn in conns {
         add_ordered_connection(conn, conns, &mut ordered);
@@ -78,13 +109,18 @@ fn add_ordered_connection(
     conns: &Vec<NetworkConnection>,
     ordered: &mut Vec<String>,
 ) {
-    if let Some(bond) = &conn.bond {
-        for port in &bond.ports {
-            if let Some(conn) = find_connection(port, conns) {
-                add_ordered_connection(conn, conns, ordered);
-            } else if !ordered.contains(&conn.id) {
-                ordered.push(port.clone());
-            }
+    for port in conn.ports() {
+        if let Some(conn) = find_connection(port, conns) {
+            add_ordered_connection(conn, conns, ordered);
+        } else if !ordered.contains(&conn.id) {
+            ordered.push(port.clone());
+        }
+    }
+
+    // the parent is written first, but only if it is defined in the settings
+    if let Some(parent) = conn.parent_interface() {
+        if let Some(parent) = find_connection(parent, conns) {
+            add_ordered_connection(parent, conns, ordered);
         }
     }
 
@@ -96,13 +132,16 @@ fn add_ordered_connection(
 /// Finds a connection by id in the list.
 ///
 /// * `id`: connection ID.
-fn find_connection<'a>(id: &str, conns: &'a [NetworkConnection]) -> Option<&'a NetworkConnection> {
+pub(crate) fn find_connection<'a>(
+    id: &str,
+    conns: &'a [NetworkConnection],
+) -> Option<&'a NetworkConnection> {
     conns
         .iter()
         .find(|c| c.id == id || c.interface == Some(id.to_string()))
 }
 
-fn default_connection(id: &str) -> NetworkConnection {
+pub(crate) fn default_connection(id: &str) -> NetworkConnection {
     NetworkConnection {
         id: id.to_string(),
         interface: Some(id.to_string()),
@@ -113,7 +152,7 @@ fn default_connection(id: &str) -> NetworkConnection {
 #[cfg(test)]
 mod tests {
     use super::ordered_connections;
-    use crate::network::settings::{BondSettings, NetworkConnection};
+    use crate::network::settings::{BondSettings, BridgeSettings, NetworkConnection, VlanSettings};
 
     #[test]
     fn test_ordered_connections() {
@@ -153,4 +192,47 @@ mod tests {
             ]
         )
     }
+
+    #[test]
+    fn test_ordered_bridge_and_vlan_connections() {
+        let vlan = NetworkConnection {
+            id: "br0.10".to_string(),
+            vlan: Some(VlanSettings {
+                parent: "br0".to_string(),
+                id: 10,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let bridge = NetworkConnection {
+            id: "bridge".to_string(),
+            interface: Some("br0".to_string()),
+            bridge: Some(BridgeSettings {
+                ports: vec!["eth0".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let vlan_eth1 = NetworkConnection {
+            id: "eth1.20".to_string(),
+            vlan: Some(VlanSettings {
+                parent: "eth1".to_string(),
+                id: 20,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let conns = vec![vlan, bridge, vlan_eth1];
+        let ordered = ordered_connections(&conns);
+        assert_eq!(
+            ordered,
+            vec![
+                "eth0".to_string(),
+                "bridge".to_string(),
+                "br0.10".to_string(),
+                "eth1.20".to_string()
+            ]
+        )
+    }
 }
@@ -76,6 +76,9 @@ pub enum DeviceType {
     Bond = 4,
     Vlan = 5,
     Bridge = 6,
+    Infiniband = 7,
+    Tun = 8,
+    Wireguard = 9,
 }
 
 // For now this mirrors NetworkManager, because it was less mental work than coming up with
@@ -283,6 +286,9 @@ impl TryFrom<u8> for DeviceType {
             4 => Ok(DeviceType::Bond),
             5 => Ok(DeviceType::Vlan),
             6 => Ok(DeviceType::Bridge),
+            7 => Ok(DeviceType::Infiniband),
+            8 => Ok(DeviceType::Tun),
+            9 => Ok(DeviceType::Wireguard),
             _ => Err(InvalidDeviceType(value)),
         }
     }
@@ -79,6 +79,25 @@ impl ProductStore {
 
         Ok(())
     }
+
+    /// Restores the previously selected product.
+    ///
+    /// Unlike [ProductStore::store], it does not register the product again, as that has side
+    /// effects beyond the installer (e.g., in the registration server). If no product was
+    /// selected before, the current one is kept.
+    ///
+    /// * `previous`: product settings before storing the new ones.
+    pub async fn rollback(&self, previous: &ProductSettings) -> Result<(), ServiceError> {
+        let Some(product) = previous.id.as_ref().filter(|p| !p.is_empty()) else {
+            return Ok(());
+        };
+        let existing_product = self.product_client.product().await?;
+        if *product != existing_product {
+            self.product_client.select_product(product).await?;
+            self.manager_client.probe().await?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -29,12 +29,26 @@ use std::{
     path::Path,
     process::Command,
 };
-use tempfile::{tempdir, TempDir};
+use tempfile::TempDir;
 use url::Url;
 
+pub mod compose;
+pub use compose::ProfileComposer;
+pub mod conversion_report;
+pub use conversion_report::{ConversionReport, ReportElement, SupportLevel};
+pub mod hardware;
+pub use hardware::{
+    CpuFacts, DiskFacts, DmiFacts, FixtureHardwareProvider, HardwareFacts, HardwareProvider,
+    NicFacts, SysfsHardwareProvider,
+};
+
+/// Jsonnet command used by [ProfileEvaluator].
+pub const JSONNET_BIN: &str = "/usr/bin/jsonnet";
+
 /// Downloads and converts autoyast profile.
 pub struct AutoyastProfileImporter {
     content: String,
+    report: Option<ConversionReport>,
 }
 
 impl AutoyastProfileImporter {
@@ -47,6 +61,7 @@ impl AutoyastProfileImporter {
 
         const TMP_DIR_PREFIX: &str = "autoyast";
         const AUTOINST_JSON: &str = "autoinst.json";
+        const AUTOINST_REPORT: &str = "autoinst-report.json";
 
         let tmp_dir = TempDir::with_prefix(TMP_DIR_PREFIX)?;
         Command::new("agama-autoyast")
@@ -56,7 +71,20 @@ impl AutoyastProfileImporter {
 
         let autoinst_json = tmp_dir.path().join(AUTOINST_JSON);
         let content = fs::read_to_string(autoinst_json)?;
-        Ok(Self { content })
+
+        // older versions of agama-autoyast do not write the report
+        let report_path = tmp_dir.path().join(AUTOINST_REPORT);
+        let report = if report_path.exists() {
+            Some(ConversionReport::from_file(report_path)?)
+        } else {
+            None
+        };
+        Ok(Self { content, report })
+    }
+
+    /// Returns the conversion report, if available.
+    pub fn report(&self) -> Option<&ConversionReport> {
+        self.report.as_ref()
     }
 
     pub fn write(&self, mut file: impl Write) -> anyhow::Result<()> {
@@ -147,54 +175,188 @@ impl ProfileValidator {
 
 /// Evaluates a profile
 ///
-/// Evaluating a profile means injecting the hardware information (coming from D-Bus)
-/// and running the jsonnet code to generate a plain JSON file. For this struct to
-/// work, the `/usr/bin/jsonnet` command must be available.
-pub struct ProfileEvaluator {}
+/// Evaluating a profile means injecting the hardware information and running the jsonnet code
+/// to generate a plain JSON file. The hardware information, along with some helpers, is
+/// available to the profile as the `hw.libsonnet` file. See the [hardware] module for the
+/// schema of the hardware facts.
+///
+/// Profiles are evaluated with the [JSONNET_BIN] command.
+pub struct ProfileEvaluator {
+    provider: Box<dyn HardwareProvider>,
+}
+
+impl Default for ProfileEvaluator {
+    fn default() -> Self {
+        Self::new(SysfsHardwareProvider::default())
+    }
+}
 
 impl ProfileEvaluator {
-    pub fn evaluate(&self, profile_path: &Path, mut out_fd: impl Write) -> anyhow::Result<()> {
-        let dir = tempdir()?;
+    /// * `provider`: source of the hardware facts.
+    pub fn new(provider: impl HardwareProvider + 'static) -> Self {
+        Self {
+            provider: Box::new(provider),
+        }
+    }
 
-        let working_path = dir.path().join("profile.jsonnet");
-        fs::copy(profile_path, working_path)?;
+    pub fn evaluate(&self, profile_path: &Path, out_fd: impl Write) -> anyhow::Result<()> {
+        self.evaluate_with_import_path(profile_path, None, out_fd)
+    }
 
-        let hwinfo_path = dir.path().join("hw.libsonnet");
-        self.write_hwinfo(&hwinfo_path)
+    /// Evaluates a profile which was copied from another location.
+    ///
+    /// * `profile_path`: path of the profile.
+    /// * `import_path`: directory to resolve the relative imports that are not found next to
+    ///   the profile (usually, the original location of the profile).
+    /// * `out_fd`: where to write the resulting JSON.
+    pub fn evaluate_with_import_path(
+        &self,
+        profile_path: &Path,
+        import_path: Option<&Path>,
+        out_fd: impl Write,
+    ) -> anyhow::Result<()> {
+        let facts = self
+            .provider
+            .facts()
             .context("Failed to read system's hardware information")?;
+        let helpers = fs::read_to_string("share/agama.libsonnet")
+            .or_else(|_| fs::read_to_string("/usr/share/agama-cli/agama.libsonnet"))
+            .context("Failed to read agama.libsonnet")?;
+        let lshw = self.provider.lshw();
+        let hwinfo = hwinfo(&helpers, &facts, lshw.as_deref().unwrap_or("null"))?;
+        self.evaluate_with_hwinfo(profile_path, import_path, &hwinfo, out_fd)
+    }
 
-        let result = Command::new("/usr/bin/jsonnet")
-            .arg("profile.jsonnet")
-            .current_dir(&dir)
+    fn evaluate_with_hwinfo(
+        &self,
+        profile_path: &Path,
+        import_path: Option<&Path>,
+        hwinfo: &str,
+        mut out_fd: impl Write,
+    ) -> anyhow::Result<()> {
+        // The hw.libsonnet file is written to a temporary directory which is added to the
+        // library path, so relative imports are still resolved from the profile's directory.
+        let dir = TempDir::with_prefix("jsonnet")?;
+        fs::write(dir.path().join("hw.libsonnet"), hwinfo)?;
+        let mut command = Command::new(JSONNET_BIN);
+        command.arg("-J").arg(dir.path());
+        if let Some(import_path) = import_path {
+            command.arg("-J").arg(import_path);
+        }
+        let output = command
+            .arg(profile_path)
             .output()
             .context("Failed to run jsonnet")?;
-        if !result.status.success() {
-            let message =
-                String::from_utf8(result.stderr).context("Invalid UTF-8 sequence from jsonnet")?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr).to_string();
             return Err(ProfileError::EvaluationError(message).into());
         }
-        out_fd.write_all(&result.stdout)?;
+        out_fd.write_all(&output.stdout)?;
         Ok(())
     }
+}
 
-    // Write the hardware information in JSON format to a given path and also helpers to help with it
-    //
-    // TODO: we need a better way to generate this information, as lshw and hwinfo are not usable
-    // out of the box.
-    fn write_hwinfo(&self, path: &Path) -> anyhow::Result<()> {
-        let result = Command::new("/usr/sbin/lshw")
-            .args(["-json"])
-            .output()
-            .context("Failed to run lshw")?;
-        let helpers = fs::read_to_string("share/agama.libsonnet")
-            .or_else(|_| fs::read_to_string("/usr/share/agama-cli/agama.libsonnet"))
-            .context("Failed to read agama.libsonnet")?;
-        let mut file = fs::File::create(path)?;
-        file.write_all(b"{\n")?;
-        file.write_all(helpers.as_bytes())?;
-        file.write_all(b"\n\"lshw\":\n")?;
-        file.write_all(&result.stdout)?;
-        file.write_all(b"\n}")?;
-        Ok(())
+/// Builds the content of the `hw.libsonnet` file.
+///
+/// * `helpers`: content of the agama.libsonnet file.
+/// * `facts`: hardware facts.
+/// * `lshw`: output of `lshw -json` (`null` if it is not available).
+fn hwinfo(helpers: &str, facts: &HardwareFacts, lshw: &str) -> anyhow::Result<String> {
+    let facts = serde_json::to_string_pretty(facts)?;
+    Ok(format!(
+        "{{\n{}\n\"hardware\":\n{},\n\"lshw\":\n{}\n}}",
+        helpers, facts, lshw
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        hwinfo, FixtureHardwareProvider, HardwareProvider, ProfileEvaluator, ProfileValidator,
+        ValidationResult, JSONNET_BIN,
+    };
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validate_nmstate_profile() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let validator = ProfileValidator::new(&root.join("share/profile.schema.json")).unwrap();
+
+        let result = validator
+            .validate_file(&root.join("share/examples/profile_nmstate.json"))
+            .unwrap();
+        assert!(matches!(result, ValidationResult::Valid));
+
+        // the nmstate document is an alternative to the connections list
+        let profile = r#"{
+          "network": {
+            "connections": [{ "id": "eth0" }],
+            "nmstate": { "interfaces": [{ "name": "eth0" }] }
+          }
+        }"#;
+        let result = validator.validate_str(profile).unwrap();
+        assert!(matches!(result, ValidationResult::NotValid(_)));
+    }
+
+    #[test]
+    fn test_evaluate_profile_with_import_path() {
+        // jsonnet is only available when building the package
+        if !Path::new(JSONNET_BIN).exists() {
+            return;
+        }
+
+        let origin = TempDir::new().unwrap();
+        fs::write(
+            origin.path().join("site.libsonnet"),
+            "{ product: { id: 'Tumbleweed' } }",
+        )
+        .unwrap();
+        let copy = TempDir::new().unwrap();
+        let profile = copy.path().join("profile.jsonnet");
+        fs::write(&profile, "import 'site.libsonnet'").unwrap();
+
+        let mut output = vec![];
+        ProfileEvaluator::default()
+            .evaluate_with_hwinfo(&profile, Some(origin.path()), "{}", &mut output)
+            .unwrap();
+        let result: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(result["product"]["id"], "Tumbleweed");
+    }
+
+    #[test]
+    fn test_evaluate_profile() {
+        if !Path::new(JSONNET_BIN).exists() {
+            return;
+        }
+
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let helpers = fs::read_to_string(root.join("../share/agama.libsonnet")).unwrap();
+        let provider = FixtureHardwareProvider::new(root.join("share/examples/hardware.json"));
+        let facts = provider.facts().unwrap();
+        let profile = root.join("share/examples/profile.jsonnet");
+
+        let mut output = vec![];
+        ProfileEvaluator::new(provider)
+            .evaluate_with_hwinfo(
+                &profile,
+                None,
+                &hwinfo(&helpers, &facts, "null").unwrap(),
+                &mut output,
+            )
+            .unwrap();
+        let result: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(result["product"]["id"], "MicroOS");
+        assert_eq!(
+            result["storage"]["bootDevice"],
+            "/dev/disk/by-id/virtio-system"
+        );
+        assert_eq!(
+            result["network"]["connections"][1]["match"]["interface"][0],
+            "enp1s0"
+        );
     }
 }
@@ -0,0 +1,324 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Composition of profiles from several documents.
+//!
+//! A profile can be built on top of other profiles (e.g., a base profile for the whole fleet
+//! plus per-site and per-host overlays) using two top-level keys:
+//!
+//! * `include`: profiles to use as the base. They are merged in the given order and then the
+//!   profile itself is merged on top of them.
+//! * `overlays`: profiles to merge on top of the profile, in the given order.
+//!
+//! ```json
+//! {
+//!   "include": ["base.json", "https://example.net/sites/nuremberg.jsonnet"],
+//!   "overlays": ["file:///run/agama/host.json"],
+//!   "localization": { "keyboard": "de" }
+//! }
+//! ```
+//!
+//! Both keys accept a single URL or a list of them. The profiles are fetched through
+//! [Transfer], so any supported scheme can be used, and relative references are resolved
+//! against the URL of the profile that contains them. Instead of a plain URL, a reference can
+//! be an object including the expected checksum and/or signature of the profile (see
+//! [Integrity]): `{ "url": "base.json", "sha256": "..." }`. Included profiles can be JSON or Jsonnet
+//! files and they can include other profiles too. See [crate::install_settings::merge] for the
+//! merge rules.
+
+use super::ProfileEvaluator;
+use crate::error::ProfileError;
+use crate::install_settings::merge;
+use crate::utils::{FileFormat, Integrity, Transfer, TrustedKeys};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{env, fs::File, io::Write, path::Path};
+use tempfile::NamedTempFile;
+use url::Url;
+
+const INCLUDE_KEY: &str = "include";
+const OVERLAYS_KEY: &str = "overlays";
+
+/// Reference to an included profile.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Reference {
+    Url(String),
+    Verified {
+        url: String,
+        #[serde(flatten)]
+        integrity: Integrity,
+    },
+}
+
+/// Builds a profile merging its includes and overlays.
+#[derive(Default)]
+pub struct ProfileComposer {
+    evaluator: ProfileEvaluator,
+    keys: TrustedKeys,
+    transfer: Transfer,
+}
+
+impl ProfileComposer {
+    /// * `evaluator`: evaluator for the Jsonnet profiles.
+    /// * `keys`: keys that are trusted to sign the profiles.
+    /// * `transfer`: transfer API to fetch the profiles.
+    pub fn new(evaluator: ProfileEvaluator, keys: TrustedKeys, transfer: Transfer) -> Self {
+        Self {
+            evaluator,
+            keys,
+            transfer,
+        }
+    }
+
+    /// Fetches a profile and merges its includes and overlays.
+    ///
+    /// * `reference`: URL or path of the profile.
+    pub fn compose(&self, reference: &str) -> Result<Value, ProfileError> {
+        let url = resolve(reference, None)?;
+        self.compose_url(&url, &Integrity::default(), &mut vec![])
+    }
+
+    /// Merges the includes and overlays of an already fetched profile.
+    ///
+    /// * `profile`: profile to process.
+    /// * `url`: URL of the profile, used to resolve relative references.
+    pub fn compose_value(&self, profile: Value, url: &Url) -> Result<Value, ProfileError> {
+        self.compose_profile(profile, url, &mut vec![url.clone()])
+    }
+
+    fn compose_url(
+        &self,
+        url: &Url,
+        integrity: &Integrity,
+        stack: &mut Vec<Url>,
+    ) -> Result<Value, ProfileError> {
+        if stack.contains(url) {
+            return Err(ProfileError::CircularInclude(url.to_string()));
+        }
+
+        stack.push(url.clone());
+        let profile = self.fetch(url, integrity)?;
+        let result = self.compose_profile(profile, url, stack);
+        stack.pop();
+        result
+    }
+
+    fn compose_profile(
+        &self,
+        mut profile: Value,
+        url: &Url,
+        stack: &mut Vec<Url>,
+    ) -> Result<Value, ProfileError> {
+        let Some(object) = profile.as_object_mut() else {
+            return Ok(profile);
+        };
+        let includes = references(object.remove(INCLUDE_KEY), url)?;
+        let overlays = references(object.remove(OVERLAYS_KEY), url)?;
+
+        let mut result = Value::Object(Default::default());
+        for (include, integrity) in includes {
+            merge(&mut result, self.compose_url(&include, &integrity, stack)?);
+        }
+        merge(&mut result, profile);
+        for (overlay, integrity) in overlays {
+            merge(&mut result, self.compose_url(&overlay, &integrity, stack)?);
+        }
+        Ok(result)
+    }
+
+    /// Fetches a profile, evaluating it if needed.
+    fn fetch(&self, url: &Url, integrity: &Integrity) -> Result<Value, ProfileError> {
+        let mut file = NamedTempFile::new()?;
+        self.transfer
+            .fetch_verified(url.as_str(), integrity, &self.keys, &mut file)?;
+        file.flush()?;
+
+        match FileFormat::from_file(file.path())? {
+            FileFormat::Json => Ok(serde_json::from_reader(File::open(file.path())?)?),
+            FileFormat::Jsonnet => {
+                let mut output = vec![];
+                // relative imports are resolved from the original location
+                let dir = url.to_file_path().ok();
+                let import_path = dir.as_deref().and_then(Path::parent);
+                self.evaluator
+                    .evaluate_with_import_path(file.path(), import_path, &mut output)?;
+                Ok(serde_json::from_slice(&output)?)
+            }
+            _ => Err(ProfileError::UnsupportedInclude(url.to_string())),
+        }
+    }
+}
+
+/// Returns the URLs from the value of the `include` or `overlays` keys.
+///
+/// * `value`: a single reference or a list of them.
+/// * `base`: URL to resolve relative references against.
+fn references(value: Option<Value>, base: &Url) -> Result<Vec<(Url, Integrity)>, ProfileError> {
+    let values = match value {
+        None => vec![],
+        Some(Value::Array(values)) => values,
+        Some(value) => vec![value],
+    };
+    values
+        .into_iter()
+        .map(|value| {
+            let reference = serde_json::from_value(value.clone())
+                .map_err(|_| ProfileError::InvalidReference(value.to_string()))?;
+            let (url, integrity) = match reference {
+                Reference::Url(url) => (url, Integrity::default()),
+                Reference::Verified { url, integrity } => (url, integrity),
+            };
+            Ok((resolve(&url, Some(base))?, integrity))
+        })
+        .collect()
+}
+
+/// Converts a reference into an URL.
+///
+/// * `reference`: absolute URL, absolute path or relative reference.
+/// * `base`: URL to resolve relative references against. If it is missing, they are resolved
+///   from the current directory.
+fn resolve(reference: &str, base: Option<&Url>) -> Result<Url, ProfileError> {
+    let invalid = || ProfileError::InvalidReference(reference.to_string());
+    if let Ok(url) = Url::parse(reference) {
+        return Ok(url);
+    }
+    match base {
+        Some(base) => base.join(reference).map_err(|_| invalid()),
+        None => {
+            let path = env::current_dir()?.join(reference);
+            Url::from_file_path(path).map_err(|_| invalid())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProfileComposer;
+    use crate::error::ProfileError;
+    use crate::profile::{ProfileValidator, ValidationResult};
+    use serde_json::json;
+    use std::{fs, path::PathBuf};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_compose() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sites")).unwrap();
+        fs::write(
+            dir.path().join("base.json"),
+            r#"{ "localization": { "language": "en_US.UTF-8", "keyboard": "us" } }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("sites/site.json"),
+            r#"{ "include": "../base.json", "software": { "patterns": ["base"] } }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("host.json"),
+            r#"{ "localization": { "keyboard": "de" } }"#,
+        )
+        .unwrap();
+        let profile = dir.path().join("profile.json");
+        fs::write(
+            &profile,
+            r#"{
+                "include": ["sites/site.json"],
+                "overlays": ["host.json"],
+                "localization": { "keyboard": "cz" },
+                "software": { "patterns": ["gnome"] }
+            }"#,
+        )
+        .unwrap();
+
+        let result = ProfileComposer::default()
+            .compose(profile.to_str().unwrap())
+            .unwrap();
+        assert_eq!(
+            result,
+            json!({
+                "localization": { "language": "en_US.UTF-8", "keyboard": "de" },
+                "software": { "patterns": ["base", "gnome"] }
+            })
+        );
+    }
+
+    #[test]
+    fn test_compose_network_alternatives() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("base.json"),
+            r#"{ "network": { "connections": [{ "id": "eth0", "method4": "auto" }] } }"#,
+        )
+        .unwrap();
+        let profile = dir.path().join("profile.json");
+        fs::write(
+            &profile,
+            r#"{
+                "include": "base.json",
+                "network": { "nmstate": { "interfaces": [{ "name": "eth0" }] } }
+            }"#,
+        )
+        .unwrap();
+
+        let result = ProfileComposer::default()
+            .compose(profile.to_str().unwrap())
+            .unwrap();
+        assert_eq!(
+            result,
+            json!({ "network": { "nmstate": { "interfaces": [{ "name": "eth0" }] } } })
+        );
+
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let validator = ProfileValidator::new(&root.join("share/profile.schema.json")).unwrap();
+        let result = validator.validate_str(&result.to_string()).unwrap();
+        assert!(matches!(result, ValidationResult::Valid));
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("base.json"), r#"{ "software": {} }"#).unwrap();
+        let profile = r#"{
+            "include": {
+                "url": "base.json",
+                "sha256": "0000000000000000000000000000000000000000000000000000000000000000"
+            }
+        }"#;
+        fs::write(dir.path().join("profile.json"), profile).unwrap();
+
+        let result =
+            ProfileComposer::default().compose(dir.path().join("profile.json").to_str().unwrap());
+        assert!(matches!(result, Err(ProfileError::Untrusted(_))));
+    }
+
+    #[test]
+    fn test_circular_include() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.json"), r#"{ "include": "b.json" }"#).unwrap();
+        fs::write(dir.path().join("b.json"), r#"{ "include": "a.json" }"#).unwrap();
+
+        let result =
+            ProfileComposer::default().compose(dir.path().join("a.json").to_str().unwrap());
+        assert!(matches!(result, Err(ProfileError::CircularInclude(_))));
+    }
+}
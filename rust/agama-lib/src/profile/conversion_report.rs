@@ -0,0 +1,154 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Report of the conversion of an AutoYaST profile.
+//!
+//! The `agama-autoyast` tool writes the report next to the converted profile
+//! (`autoinst-report.json`), classifying the AutoYaST elements found in the original profile:
+//!
+//! ```json
+//! {
+//!   "supported": [
+//!     { "path": "software/products", "support": "yes", "agama": "software.id" }
+//!   ],
+//!   "partial": [{ "path": "networking", "support": "partial" }],
+//!   "ignored": [
+//!     {
+//!       "path": "networking/backend",
+//!       "support": "no",
+//!       "notes": "Only NetworkManager is supported."
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Paths are relative to the `profile` element of the XML document and arrays include the
+//! index of the element (e.g., `scripts/pre-scripts[0]/rerun`).
+
+use crate::error::ProfileError;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path};
+
+/// Support level of an AutoYaST element in Agama.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SupportLevel {
+    Yes,
+    Partial,
+    Planned,
+    No,
+}
+
+/// AutoYaST element included in the conversion report.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportElement {
+    /// Path of the element in the AutoYaST profile (e.g., "networking/backend").
+    pub path: String,
+    pub support: SupportLevel,
+    /// Agama equivalent (e.g., "localization.keyboard").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agama: Option<String>,
+    /// Additional information about the element.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+/// AutoYaST elements classified according to their support level.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversionReport {
+    #[serde(default)]
+    pub supported: Vec<ReportElement>,
+    #[serde(default)]
+    pub partial: Vec<ReportElement>,
+    /// Elements that are not supported (yet) and were ignored during the conversion.
+    #[serde(default)]
+    pub ignored: Vec<ReportElement>,
+}
+
+impl ConversionReport {
+    /// Reads the report from a JSON file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ProfileError> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Whether all the elements were converted.
+    pub fn is_complete(&self) -> bool {
+        self.partial.is_empty() && self.ignored.is_empty()
+    }
+}
+
+impl fmt::Display for ConversionReport {
+    /// Lists the partially supported and the ignored elements.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let groups = [
+            ("Partially supported elements", &self.partial),
+            ("Ignored elements", &self.ignored),
+        ];
+        for (title, elements) in groups {
+            if elements.is_empty() {
+                continue;
+            }
+            writeln!(f, "{}:", title)?;
+            for element in elements {
+                write!(f, "  * {}", element.path)?;
+                if element.support == SupportLevel::Planned {
+                    write!(f, " (planned)")?;
+                }
+                if let Some(notes) = &element.notes {
+                    write!(f, ": {}", notes)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConversionReport, SupportLevel};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_read_report() {
+        let mut file = NamedTempFile::new().unwrap();
+        let json = r#"{
+            "supported": [{ "path": "software/products", "support": "yes", "agama": "software.id" }],
+            "partial": [{ "path": "networking", "support": "partial" }],
+            "ignored": [
+                { "path": "networking/backend", "support": "no", "notes": "Only NetworkManager is supported." },
+                { "path": "iscsi-client", "support": "planned" }
+            ]
+        }"#;
+        file.write_all(json.as_bytes()).unwrap();
+
+        let report = ConversionReport::from_file(file.path()).unwrap();
+        assert!(!report.is_complete());
+        assert_eq!(report.supported[0].agama.as_deref(), Some("software.id"));
+        assert_eq!(report.ignored[1].support, SupportLevel::Planned);
+        assert_eq!(
+            report.to_string(),
+            "Partially supported elements:\n  * networking\nIgnored elements:\n  \
+             * networking/backend: Only NetworkManager is supported.\n  * iscsi-client (planned)\n"
+        );
+    }
+}
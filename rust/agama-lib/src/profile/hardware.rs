@@ -0,0 +1,419 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Hardware information for the Jsonnet profiles
+//!
+//! The facts are collected by a [HardwareProvider] and exposed to the profiles as the `hardware`
+//! key of the `hw.libsonnet` file. The schema is stable, so the profiles can rely on it:
+//!
+//! ```json
+//! {
+//!   "arch": "x86_64",
+//!   "memory": 8589934592,
+//!   "cpu": { "model": "AMD EPYC 7763 64-Core Processor", "count": 4 },
+//!   "dmi": { "vendor": "QEMU", "product": "Standard PC (Q35 + ICH9, 2009)", "version": null },
+//!   "disks": [
+//!     {
+//!       "name": "sda",
+//!       "path": "/dev/sda",
+//!       "size": 21474836480,
+//!       "vendor": "ATA",
+//!       "model": "QEMU HARDDISK",
+//!       "removable": false,
+//!       "rotational": true,
+//!       "byId": ["/dev/disk/by-id/ata-QEMU_HARDDISK_QM00001"]
+//!     }
+//!   ],
+//!   "nics": [
+//!     { "name": "eth0", "mac": "52:54:00:12:34:56", "driver": "virtio_net", "virtual": false }
+//!   ]
+//! }
+//! ```
+//!
+//! Sizes are expressed in bytes. The memory and the disk sizes are set to `0` when they cannot be
+//! determined, while the rest of unknown values are set to `null`. The disks and the network
+//! interfaces are sorted by name.
+//!
+//! The provider also gives the output of `lshw -json` (the `lshw` key of `hw.libsonnet`), which
+//! is kept for the profiles written before the `hardware` key existed.
+
+use crate::error::ProfileError;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Size of the sectors used by /sys/block/*/size.
+const SECTOR_SIZE: u64 = 512;
+
+/// Keys that contain the CPU model in /proc/cpuinfo, depending on the architecture.
+const CPU_MODEL_KEYS: [&str; 3] = ["model name", "cpu", "Model"];
+
+/// Hardware facts available to the profiles.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HardwareFacts {
+    /// Machine architecture (e.g., "x86_64" or "aarch64").
+    pub arch: String,
+    /// Total memory in bytes (`0` if unknown).
+    pub memory: u64,
+    pub cpu: CpuFacts,
+    pub dmi: DmiFacts,
+    pub disks: Vec<DiskFacts>,
+    pub nics: Vec<NicFacts>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CpuFacts {
+    pub model: Option<String>,
+    /// Number of logical CPUs.
+    pub count: u32,
+}
+
+/// System information from the firmware (DMI/SMBIOS).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DmiFacts {
+    pub vendor: Option<String>,
+    pub product: Option<String>,
+    pub version: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskFacts {
+    /// Kernel name (e.g., "sda").
+    pub name: String,
+    /// Device path (e.g., "/dev/sda").
+    pub path: String,
+    /// Size in bytes (`0` if unknown).
+    pub size: u64,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub removable: bool,
+    pub rotational: bool,
+    /// Persistent paths under /dev/disk/by-id.
+    pub by_id: Vec<String>,
+}
+
+/// Network interface.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NicFacts {
+    pub name: String,
+    pub mac: Option<String>,
+    /// Kernel driver (e.g., "e1000e").
+    pub driver: Option<String>,
+    /// Whether it is a software interface (e.g., a bridge or a VLAN).
+    #[serde(rename = "virtual")]
+    pub is_virtual: bool,
+}
+
+/// Source of the hardware facts.
+pub trait HardwareProvider {
+    fn facts(&self) -> Result<HardwareFacts, ProfileError>;
+
+    /// Returns the output of `lshw -json`, or `None` if it is not available.
+    fn lshw(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Reads the hardware facts from sysfs and /proc.
+///
+/// The facts do not require any external tool. The lshw output is only available if the tool
+/// is installed.
+pub struct SysfsHardwareProvider {
+    root: PathBuf,
+}
+
+impl Default for SysfsHardwareProvider {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl SysfsHardwareProvider {
+    /// Creates a provider that reads the files below the given directory instead of "/".
+    ///
+    /// * `root`: directory containing the "sys", "proc" and "dev" directories.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Reads a file, returning its trimmed content unless it is empty or does not exist.
+    fn read(&self, path: impl AsRef<Path>) -> Option<String> {
+        let content = fs::read_to_string(self.root.join(path)).ok()?;
+        let content = content.trim();
+        (!content.is_empty()).then(|| content.to_string())
+    }
+
+    /// Returns the names of the entries of a directory, sorted (empty if it does not exist).
+    fn entries(&self, path: impl AsRef<Path>) -> io::Result<Vec<String>> {
+        let dir = match fs::read_dir(self.root.join(path)) {
+            Ok(dir) => dir,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error),
+        };
+        let mut names = vec![];
+        for entry in dir {
+            names.push(entry?.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the name of the file a symlink points to.
+    fn link_name(&self, path: impl AsRef<Path>) -> Option<String> {
+        let target = fs::read_link(self.root.join(path)).ok()?;
+        Some(target.file_name()?.to_string_lossy().to_string())
+    }
+
+    fn arch(&self) -> String {
+        self.read("proc/sys/kernel/arch")
+            .unwrap_or_else(|| std::env::consts::ARCH.to_string())
+    }
+
+    fn memory(&self) -> u64 {
+        let meminfo = self.read("proc/meminfo").unwrap_or_default();
+        meminfo
+            .lines()
+            .find_map(|l| l.strip_prefix("MemTotal:"))
+            .and_then(|v| v.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+            .unwrap_or_default()
+    }
+
+    fn cpu(&self) -> CpuFacts {
+        let cpuinfo = self.read("proc/cpuinfo").unwrap_or_default();
+        let fields: Vec<(&str, &str)> = cpuinfo
+            .lines()
+            .filter_map(|l| l.split_once(':'))
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .collect();
+        let count = fields.iter().filter(|(k, _)| *k == "processor").count();
+        let model = CPU_MODEL_KEYS.iter().find_map(|key| {
+            fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.to_string())
+        });
+        CpuFacts {
+            model,
+            count: count as u32,
+        }
+    }
+
+    fn dmi(&self) -> DmiFacts {
+        DmiFacts {
+            vendor: self.read("sys/class/dmi/id/sys_vendor"),
+            product: self.read("sys/class/dmi/id/product_name"),
+            version: self.read("sys/class/dmi/id/product_version"),
+        }
+    }
+
+    fn disks(&self) -> io::Result<Vec<DiskFacts>> {
+        let by_id = self.entries("dev/disk/by-id")?;
+        let mut disks = vec![];
+        for name in self.entries("sys/block")? {
+            let dir = Path::new("sys/block").join(&name);
+            // skip loop, RAM and device-mapper devices
+            if !self.root.join(&dir).join("device").exists() {
+                continue;
+            }
+            let sectors: u64 = self
+                .read(dir.join("size"))
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_default();
+            let links = by_id
+                .iter()
+                .filter(|l| {
+                    self.link_name(Path::new("dev/disk/by-id").join(l)).as_ref() == Some(&name)
+                })
+                .map(|l| format!("/dev/disk/by-id/{}", l))
+                .collect();
+            disks.push(DiskFacts {
+                path: format!("/dev/{}", name),
+                size: sectors * SECTOR_SIZE,
+                vendor: self.read(dir.join("device/vendor")),
+                model: self.read(dir.join("device/model")),
+                removable: self.read(dir.join("removable")).as_deref() == Some("1"),
+                rotational: self.read(dir.join("queue/rotational")).as_deref() == Some("1"),
+                by_id: links,
+                name,
+            });
+        }
+        Ok(disks)
+    }
+
+    fn nics(&self) -> io::Result<Vec<NicFacts>> {
+        let mut nics = vec![];
+        for name in self.entries("sys/class/net")? {
+            if name == "lo" {
+                continue;
+            }
+            let dir = Path::new("sys/class/net").join(&name);
+            nics.push(NicFacts {
+                mac: self.read(dir.join("address")),
+                driver: self.link_name(dir.join("device/driver")),
+                is_virtual: !self.root.join(&dir).join("device").exists(),
+                name,
+            });
+        }
+        Ok(nics)
+    }
+}
+
+impl HardwareProvider for SysfsHardwareProvider {
+    fn facts(&self) -> Result<HardwareFacts, ProfileError> {
+        Ok(HardwareFacts {
+            arch: self.arch(),
+            memory: self.memory(),
+            cpu: self.cpu(),
+            dmi: self.dmi(),
+            disks: self.disks()?,
+            nics: self.nics()?,
+        })
+    }
+
+    fn lshw(&self) -> Option<String> {
+        match Command::new("/usr/sbin/lshw").args(["-json"]).output() {
+            Ok(output) if output.status.success() => String::from_utf8(output.stdout).ok(),
+            _ => {
+                log::warn!("Could not run lshw, its hardware information will not be available");
+                None
+            }
+        }
+    }
+}
+
+/// Reads the hardware facts from a JSON file.
+///
+/// It is useful for checking how a profile behaves on a different system. It does not run any
+/// tool, so the lshw output is not available.
+pub struct FixtureHardwareProvider {
+    path: PathBuf,
+}
+
+impl FixtureHardwareProvider {
+    /// * `path`: JSON file following the [HardwareFacts] schema.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl HardwareProvider for FixtureHardwareProvider {
+    fn facts(&self) -> Result<HardwareFacts, ProfileError> {
+        let content = fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FixtureHardwareProvider, HardwareProvider, SysfsHardwareProvider};
+    use std::{
+        fs,
+        os::unix::fs::symlink,
+        path::{Path, PathBuf},
+    };
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_sysfs_facts() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        write(root, "proc/sys/kernel/arch", "aarch64\n");
+        write(
+            root,
+            "proc/meminfo",
+            "MemTotal:        2048 kB\nMemFree: 1 kB\n",
+        );
+        write(
+            root,
+            "proc/cpuinfo",
+            "processor\t: 0\nmodel name\t: Test CPU\n\nprocessor\t: 1\nmodel name\t: Test CPU\n",
+        );
+        write(root, "sys/class/dmi/id/sys_vendor", "ACME\n");
+        write(root, "sys/block/vda/size", "2048\n");
+        write(root, "sys/block/vda/removable", "0\n");
+        write(root, "sys/block/vda/queue/rotational", "1\n");
+        write(root, "sys/block/vda/device/model", "Disk  \n");
+        write(root, "sys/block/loop0/size", "100\n");
+        fs::create_dir_all(root.join("dev/disk/by-id")).unwrap();
+        symlink("../../vda", root.join("dev/disk/by-id/virtio-disk1")).unwrap();
+        write(root, "sys/class/net/eth0/address", "52:54:00:12:34:56\n");
+        fs::create_dir_all(root.join("sys/class/net/eth0/device")).unwrap();
+        symlink(
+            "../../bus/virtio/drivers/virtio_net",
+            root.join("sys/class/net/eth0/device/driver"),
+        )
+        .unwrap();
+        write(root, "sys/class/net/br0/address", "52:54:00:12:34:57\n");
+        write(root, "sys/class/net/lo/address", "00:00:00:00:00:00\n");
+
+        let facts = SysfsHardwareProvider::new(root).facts().unwrap();
+        assert_eq!(facts.arch, "aarch64");
+        assert_eq!(facts.memory, 2048 * 1024);
+        assert_eq!(facts.cpu.count, 2);
+        assert_eq!(facts.cpu.model.as_deref(), Some("Test CPU"));
+        assert_eq!(facts.dmi.vendor.as_deref(), Some("ACME"));
+        assert_eq!(facts.dmi.product, None);
+
+        assert_eq!(facts.disks.len(), 1);
+        let disk = &facts.disks[0];
+        assert_eq!(disk.path, "/dev/vda");
+        assert_eq!(disk.size, 2048 * 512);
+        assert_eq!(disk.model.as_deref(), Some("Disk"));
+        assert!(disk.rotational);
+        assert!(!disk.removable);
+        assert_eq!(disk.by_id, vec!["/dev/disk/by-id/virtio-disk1"]);
+
+        let nics: Vec<_> = facts
+            .nics
+            .iter()
+            .map(|n| (n.name.as_str(), n.driver.as_deref(), n.is_virtual))
+            .collect();
+        assert_eq!(
+            nics,
+            vec![("br0", None, true), ("eth0", Some("virtio_net"), false)]
+        );
+    }
+
+    #[test]
+    fn test_fixture_facts() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let provider = FixtureHardwareProvider::new(root.join("share/examples/hardware.json"));
+
+        let facts = provider.facts().unwrap();
+        assert_eq!(facts.memory, 4294967296);
+        assert_eq!(facts.disks.len(), 3);
+        assert_eq!(provider.lshw(), None);
+    }
+}
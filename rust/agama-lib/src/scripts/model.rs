@@ -28,7 +28,7 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
-use crate::utils::Transfer;
+use crate::utils::{Integrity, Transfer, TrustedKeys};
 
 use super::ScriptError;
 
@@ -52,20 +52,43 @@ pub struct BaseScript {
 }
 
 impl BaseScript {
-    fn write<P: AsRef<Path>>(&self, workdir: P) -> Result<(), ScriptError> {
+    /// Writes the script to the given work directory.
+    ///
+    /// The script is written to a temporary file first, which is moved into place only when
+    /// the content was retrieved (and verified, if needed). Otherwise, it is removed.
+    fn write<P: AsRef<Path>>(&self, workdir: P, keys: &TrustedKeys) -> Result<(), ScriptError> {
         let script_path = workdir.as_ref().join(&self.name);
         std::fs::create_dir_all(script_path.parent().unwrap())?;
 
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(script_path.file_name().unwrap_or_default());
+        tmp_name.push(".part");
+        let tmp_path = script_path.with_file_name(tmp_name);
+
+        if let Err(error) = self.write_content(&tmp_path, keys) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(error);
+        }
+        fs::rename(&tmp_path, &script_path)?;
+        Ok(())
+    }
+
+    fn write_content(&self, path: &Path, keys: &TrustedKeys) -> Result<(), ScriptError> {
         let mut file = fs::OpenOptions::new()
             .create(true)
             .write(true)
             .truncate(true)
             .mode(0o500)
-            .open(&script_path)?;
+            .open(path)?;
 
         match &self.source {
             ScriptSource::Text { body } => write!(file, "{}", &body)?,
-            ScriptSource::Remote { url } => Transfer::get(url, file)?,
+            ScriptSource::Remote { url, integrity } if integrity.is_empty() => {
+                Transfer::get(url, file)?
+            }
+            ScriptSource::Remote { url, integrity } => {
+                Transfer::get_verified(url, integrity, keys, file)?
+            }
         };
 
         Ok(())
@@ -78,7 +101,12 @@ pub enum ScriptSource {
     /// Script's body.
     Text { body: String },
     /// URL to get the script from.
-    Remote { url: String },
+    Remote {
+        url: String,
+        /// Expected checksum and signature of the script.
+        #[serde(flatten)]
+        integrity: Integrity,
+    },
 }
 
 /// Represents a script to run as part of the installation process.
@@ -111,9 +139,12 @@ impl Script {
     /// Writes the script to the given work directory.
     ///
     /// The name of the script depends on the work directory and the script's group.
-    pub fn write<P: AsRef<Path>>(&self, workdir: P) -> Result<(), ScriptError> {
+    ///
+    /// * `workdir`: where to write the script.
+    /// * `keys`: keys that are trusted to sign the remote scripts.
+    pub fn write<P: AsRef<Path>>(&self, workdir: P, keys: &TrustedKeys) -> Result<(), ScriptError> {
         let path = workdir.as_ref().join(self.group().to_string());
-        self.base().write(&path)
+        self.base().write(&path, keys)
     }
 
     /// Script's group.
@@ -282,6 +313,7 @@ impl WithRunner for InitScript {
 /// It offers an API to add and execute installation scripts.
 pub struct ScriptsRepository {
     workdir: PathBuf,
+    keys: TrustedKeys,
     pub scripts: Vec<Script>,
 }
 
@@ -296,11 +328,19 @@ impl ScriptsRepository {
         }
     }
 
+    /// Sets the keys that are trusted to sign the remote scripts.
+    ///
+    /// * `keys`: trusted keys (by default, the ones in [crate::utils::TRUSTED_KEYS_DIR]).
+    pub fn with_trusted_keys(mut self, keys: TrustedKeys) -> Self {
+        self.keys = keys;
+        self
+    }
+
     /// Adds a new script to the repository.
     ///
     /// * `script`: script to add.
     pub fn add(&mut self, script: Script) -> Result<(), ScriptError> {
-        script.write(&self.workdir)?;
+        script.write(&self.workdir, &self.keys)?;
         self.scripts.push(script);
         Ok(())
     }
@@ -337,6 +377,7 @@ impl Default for ScriptsRepository {
     fn default() -> Self {
         Self {
             workdir: PathBuf::from("/run/agama/scripts"),
+            keys: TrustedKeys::default(),
             scripts: vec![],
         }
     }
@@ -384,10 +425,30 @@ mod test {
     use tempfile::TempDir;
     use tokio::test;
 
-    use crate::scripts::{BaseScript, PreScript, Script, ScriptSource};
+    use crate::scripts::{BaseScript, PreScript, Script, ScriptError, ScriptSource};
+    use crate::utils::{Integrity, TransferError, TrustedKeys, VerificationError};
 
     use super::{ScriptsGroup, ScriptsRepository};
 
+    const BODY: &str = "#!/bin/sh\necho hello\n";
+    const BODY_SHA256: &str = "bfdeaeb08cffb6a36438bcd12dda25417e3cdd36f1e7e482a2849d539225288b";
+
+    /// Adds a remote script, served from the given directory, to a repository.
+    fn add_remote_script(dir: &TempDir, integrity: Integrity) -> Result<(), ScriptError> {
+        std::fs::write(dir.path().join("script.sh"), BODY).unwrap();
+        let keys = TrustedKeys::new(dir.path().join("keys"));
+        let mut repo = ScriptsRepository::new(dir.path().join("scripts")).with_trusted_keys(keys);
+
+        let base = BaseScript {
+            name: "test".to_string(),
+            source: ScriptSource::Remote {
+                url: format!("file://{}", dir.path().join("script.sh").display()),
+                integrity,
+            },
+        };
+        repo.add(Script::Pre(PreScript { base }))
+    }
+
     #[test]
     async fn test_add_script() {
         let tmp_dir = TempDir::with_prefix("scripts-").expect("a temporary directory");
@@ -409,6 +470,66 @@ mod test {
         assert!(script_path.exists());
     }
 
+    #[test]
+    async fn test_add_remote_script_checksum() {
+        let tmp_dir = TempDir::with_prefix("scripts-").expect("a temporary directory");
+        let integrity = Integrity {
+            sha256: Some("0".repeat(64)),
+            ..Default::default()
+        };
+        let result = add_remote_script(&tmp_dir, integrity);
+        assert!(matches!(
+            result,
+            Err(ScriptError::Unreachable(TransferError::VerificationFailed(
+                _,
+                VerificationError::ChecksumMismatch { .. }
+            )))
+        ));
+        // nothing is left in the work directory
+        let pre_dir = tmp_dir.path().join("scripts/pre");
+        assert_eq!(std::fs::read_dir(&pre_dir).unwrap().count(), 0);
+
+        let integrity = Integrity {
+            sha256: Some(BODY_SHA256.to_string()),
+            ..Default::default()
+        };
+        add_remote_script(&tmp_dir, integrity).unwrap();
+        let content = std::fs::read_to_string(pre_dir.join("test")).unwrap();
+        assert_eq!(content, BODY);
+    }
+
+    #[test]
+    async fn test_add_remote_script_bad_signature() {
+        let tmp_dir = TempDir::with_prefix("scripts-").expect("a temporary directory");
+        std::fs::create_dir(tmp_dir.path().join("keys")).unwrap();
+        std::fs::write(tmp_dir.path().join("script.sh.sig"), "not a signature").unwrap();
+        let integrity = Integrity {
+            signature: Some("script.sh.sig".to_string()),
+            ..Default::default()
+        };
+
+        // no trusted keyrings
+        let result = add_remote_script(&tmp_dir, integrity.clone());
+        assert!(matches!(
+            result,
+            Err(ScriptError::Unreachable(TransferError::VerificationFailed(
+                _,
+                VerificationError::NoTrustedKeys(_)
+            )))
+        ));
+
+        // the signature is checked with gpgv
+        std::fs::write(tmp_dir.path().join("keys/agama.gpg"), "").unwrap();
+        let result = add_remote_script(&tmp_dir, integrity);
+        assert!(matches!(
+            result,
+            Err(ScriptError::Unreachable(TransferError::VerificationFailed(
+                _,
+                VerificationError::InvalidSignature | VerificationError::IO(_)
+            )))
+        ));
+    }
+
     #[test]
     async fn test_run_scripts() {
         let tmp_dir = TempDir::with_prefix("scripts-").expect("a temporary directory");
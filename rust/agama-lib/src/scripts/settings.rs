@@ -22,7 +22,7 @@ use serde::{Deserialize, Serialize};
 
 use super::{InitScript, PostPartitioningScript, PostScript, PreScript};
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ScriptsConfig {
     /// User-defined pre-installation scripts
@@ -24,13 +24,38 @@
 use crate::base_http_client::BaseHTTPClient;
 use crate::bootloader::store::BootloaderStore;
 use crate::error::ServiceError;
-use crate::install_settings::InstallSettings;
+use crate::install_settings::{InstallSettings, Section};
 use crate::manager::{InstallationPhase, ManagerHTTPClient};
 use crate::scripts::{ScriptsClient, ScriptsGroup};
 use crate::{
     localization::LocalizationStore, network::NetworkStore, product::ProductStore,
     scripts::ScriptsStore, software::SoftwareStore, storage::StorageStore, users::UsersStore,
 };
+use std::fmt;
+
+/// Outcome of restoring the previous settings after a failure.
+#[derive(Debug, Default)]
+pub struct Rollback {
+    /// Sections that were restored.
+    pub restored: Vec<Section>,
+    /// Sections that could not be restored, including the reason.
+    pub failed: Vec<(Section, String)>,
+}
+
+impl fmt::Display for Rollback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.restored.is_empty() && self.failed.is_empty() {
+            return write!(f, "nothing was rolled back");
+        }
+
+        let restored: Vec<String> = self.restored.iter().map(|s| s.to_string()).collect();
+        write!(f, "rolled back: [{}]", restored.join(", "))?;
+        for (section, error) in &self.failed {
+            write!(f, "; could not roll back {}: {}", section, error)?;
+        }
+        Ok(())
+    }
+}
 
 /// Struct that loads/stores the settings from/to the D-Bus services.
 ///
@@ -69,22 +94,38 @@ impl Store {
 
     /// Loads the installation settings from the HTTP interface.
     pub async fn load(&self) -> Result<InstallSettings, ServiceError> {
-        let mut settings = InstallSettings {
-            bootloader: Some(self.bootloader.load().await?),
-            network: Some(self.network.load().await?),
-            software: Some(self.software.load().await?),
-            user: Some(self.users.load().await?),
-            product: Some(self.product.load().await?),
-            localization: Some(self.localization.load().await?),
-            scripts: Some(self.scripts.load().await?),
-            ..Default::default()
-        };
+        self.load_sections(&Section::ALL).await
+    }
 
-        let storage_settings = self.storage.load().await?;
-        settings.storage = storage_settings.storage;
-        settings.storage_autoyast = storage_settings.storage_autoyast;
+    /// Loads only the given sections of the installation settings
+    ///
+    /// * `sections`: sections to load.
+    pub async fn load_sections(
+        &self,
+        sections: &[Section],
+    ) -> Result<InstallSettings, ServiceError> {
+        let mut settings = InstallSettings::default();
 
         // TODO: use try_join here
+        for section in sections {
+            match section {
+                Section::Bootloader => settings.bootloader = Some(self.bootloader.load().await?),
+                Section::Network => settings.network = Some(self.network.load().await?),
+                Section::Software => settings.software = Some(self.software.load().await?),
+                Section::Users => settings.user = Some(self.users.load().await?),
+                Section::Product => settings.product = Some(self.product.load().await?),
+                Section::Localization => {
+                    settings.localization = Some(self.localization.load().await?)
+                }
+                Section::Scripts => settings.scripts = Some(self.scripts.load().await?),
+                Section::Storage => {
+                    let storage_settings = self.storage.load().await?;
+                    settings.storage = storage_settings.storage;
+                    settings.storage_autoyast = storage_settings.storage_autoyast;
+                }
+            }
+        }
+
         Ok(settings)
     }
 
@@ -94,46 +135,166 @@ impl Store {
     /// "config". It causes the storage proposal to be reset. This behavior should be revisited in
     /// the future but it might be the storage service the responsible for dealing with this.
     ///
+    /// The settings are stored as a whole: if any section fails, the sections that were already
+    /// written (including the failing one) are restored to their previous values. See
+    /// [Store::store_sections] for further details.
+    ///
     /// * `settings`: installation settings.
     pub async fn store(&self, settings: &InstallSettings) -> Result<(), ServiceError> {
-        if let Some(scripts) = &settings.scripts {
-            self.scripts.store(scripts).await?;
+        self.store_sections(settings, &Section::ALL).await
+    }
 
-            if scripts.pre.as_ref().is_some_and(|s| !s.is_empty()) {
-                self.run_pre_scripts().await?;
+    /// Stores only the given sections of the installation settings
+    ///
+    /// The rest of the sections are ignored even if they are present in the settings. The
+    /// pre-scripts (and the probe that they trigger) only run when the scripts section is
+    /// included.
+    ///
+    /// Before writing anything, the current values of the affected sections are loaded. If
+    /// storing a section fails, the previous values of the already written sections are stored
+    /// again and a [ServiceError::StoreSettings] error is returned. Note that the side effects
+    /// (e.g., running the pre-scripts or registering the product) cannot be undone. If the
+    /// current values cannot be loaded, the settings are stored anyway, but they cannot be
+    /// restored.
+    ///
+    /// * `settings`: installation settings.
+    /// * `sections`: sections to store.
+    pub async fn store_sections(
+        &self,
+        settings: &InstallSettings,
+        sections: &[Section],
+    ) -> Result<(), ServiceError> {
+        let sections: Vec<Section> = Section::ALL
+            .into_iter()
+            .filter(|s| sections.contains(s) && settings.has_section(*s))
+            .collect();
+        let previous = match self.load_sections(&sections).await {
+            Ok(previous) => previous,
+            Err(error) => {
+                log::warn!(
+                    "Could not load the current settings, rollback is not possible: {error}"
+                );
+                InstallSettings::default()
             }
-        }
+        };
 
-        // import the users (esp. the root password) before initializing software,
-        // if software fails the Web UI would be stuck in the root password dialog
-        if let Some(user) = &settings.user {
-            self.users.store(user).await?;
-        }
-        if let Some(network) = &settings.network {
-            self.network.store(network).await?;
-        }
-        // order is important here as network can be critical for connection
-        // to registration server and selecting product is important for rest
-        if let Some(product) = &settings.product {
-            self.product.store(product).await?;
-        }
-        // ordering: localization after product as some product may miss some locales
-        if let Some(localization) = &settings.localization {
-            self.localization.store(localization).await?;
-        }
-        if let Some(software) = &settings.software {
-            self.software.store(software).await?;
-        }
-        if settings.storage.is_some() || settings.storage_autoyast.is_some() {
-            self.storage.store(&settings.into()).await?
-        }
-        if let Some(bootloader) = &settings.bootloader {
-            self.bootloader.store(bootloader).await?;
+        for (index, section) in sections.iter().enumerate() {
+            if let Err(error) = self.store_section(settings, *section, true).await {
+                let rollback = self
+                    .rollback(&previous, settings, &sections[..=index])
+                    .await;
+                return Err(ServiceError::StoreSettings {
+                    section: *section,
+                    source: Box::new(error),
+                    rollback,
+                });
+            }
         }
 
         Ok(())
     }
 
+    /// Stores a single section of the installation settings
+    ///
+    /// * `settings`: installation settings.
+    /// * `section`: section to store.
+    /// * `run_pre_scripts`: whether to run the pre-scripts (if any) after storing them.
+    async fn store_section(
+        &self,
+        settings: &InstallSettings,
+        section: Section,
+        run_pre_scripts: bool,
+    ) -> Result<(), ServiceError> {
+        match section {
+            Section::Scripts => {
+                if let Some(scripts) = &settings.scripts {
+                    self.scripts.store(scripts).await?;
+
+                    if run_pre_scripts && scripts.pre.as_ref().is_some_and(|s| !s.is_empty()) {
+                        self.run_pre_scripts().await?;
+                    }
+                }
+            }
+            Section::Users => {
+                if let Some(user) = &settings.user {
+                    self.users.store(user).await?;
+                }
+            }
+            Section::Network => {
+                if let Some(network) = &settings.network {
+                    self.network.store(network).await?;
+                }
+            }
+            Section::Product => {
+                if let Some(product) = &settings.product {
+                    self.product.store(product).await?;
+                }
+            }
+            Section::Localization => {
+                if let Some(localization) = &settings.localization {
+                    self.localization.store(localization).await?;
+                }
+            }
+            Section::Software => {
+                if let Some(software) = &settings.software {
+                    self.software.store(software).await?;
+                }
+            }
+            Section::Storage => {
+                if settings.has_section(Section::Storage) {
+                    self.storage.store(&settings.into()).await?;
+                }
+            }
+            Section::Bootloader => {
+                if let Some(bootloader) = &settings.bootloader {
+                    self.bootloader.store(bootloader).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Restores the previous values of the given sections
+    ///
+    /// It tries to restore all the sections, even if some of them fail. The network
+    /// connections added by the new settings are removed. The product is selected again, but it
+    /// is not registered (see [ProductStore::rollback]).
+    ///
+    /// * `previous`: settings before storing the new ones.
+    /// * `settings`: settings that could not be stored.
+    /// * `sections`: sections to restore.
+    async fn rollback(
+        &self,
+        previous: &InstallSettings,
+        settings: &InstallSettings,
+        sections: &[Section],
+    ) -> Rollback {
+        let mut rollback = Rollback::default();
+        for section in sections {
+            if !previous.has_section(*section) {
+                rollback
+                    .failed
+                    .push((*section, "there are no previous settings".to_string()));
+                continue;
+            }
+            let result = match (section, &previous.network, &settings.network) {
+                (Section::Network, Some(previous), Some(network)) => {
+                    self.network.rollback(previous, network).await
+                }
+                (Section::Product, _, _) => match &previous.product {
+                    Some(product) => self.product.rollback(product).await,
+                    None => Ok(()),
+                },
+                _ => self.store_section(previous, *section, false).await,
+            };
+            match result {
+                Ok(()) => rollback.restored.push(*section),
+                Err(error) => rollback.failed.push((*section, error.to_string())),
+            }
+        }
+        rollback
+    }
+
     /// Runs the pre-installation scripts and forces a probe if the installation phase is "config".
     async fn run_pre_scripts(&self) -> Result<(), ServiceError> {
         let scripts_client = ScriptsClient::new(self.http_client.clone());
@@ -146,3 +307,302 @@ impl Store {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use httpmock::prelude::*;
+    use httpmock::Method::PATCH;
+    use std::error::Error;
+    use tokio::test; // without this, "error: async functions cannot be used for tests"
+
+    async fn store(mock_server_url: String) -> Result<Store, ServiceError> {
+        let mut bhc = BaseHTTPClient::default();
+        bhc.base_url = mock_server_url;
+        Store::new(bhc).await
+    }
+
+    #[test]
+    async fn test_store_selected_sections() -> Result<(), Box<dyn Error>> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/api/software/config");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"patterns": {"base": true}, "product": "Tumbleweed"}"#);
+        });
+        let software_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/api/software/config")
+                .body(r#"{"patterns":{"xfce":true},"product":null}"#);
+            then.status(200);
+        });
+        let url = server.url("/api");
+
+        let settings: InstallSettings = serde_json::from_str(
+            r#"{
+                "software": { "patterns": ["xfce"] },
+                "localization": { "language": "en_US.UTF-8" },
+                "scripts": { "pre": [{ "name": "hello", "body": "echo hello" }] }
+            }"#,
+        )?;
+
+        // any other request fails because it is not mocked
+        let store = store(url).await?;
+        store
+            .store_sections(&settings, &[Section::Software, Section::Storage])
+            .await?;
+
+        software_mock.assert();
+        Ok(())
+    }
+
+    #[test]
+    async fn test_store_rollback() -> Result<(), Box<dyn Error>> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/api/software/config");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"patterns": {"base": true}, "product": "Tumbleweed"}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/api/l10n/config");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"locales": ["en_US.UTF-8"], "keymap": "us", "timezone": "UTC"}"#);
+        });
+        let l10n_mock = server.mock(|when, then| {
+            when.method(PATCH)
+                .path("/api/l10n/config")
+                .body_contains("es_ES.UTF-8");
+            then.status(204);
+        });
+        let l10n_rollback_mock = server.mock(|when, then| {
+            when.method(PATCH)
+                .path("/api/l10n/config")
+                .body_contains("en_US.UTF-8");
+            then.status(204);
+        });
+        let software_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/api/software/config")
+                .body(r#"{"patterns":{"unknown":true},"product":null}"#);
+            then.status(400)
+                .body(r#"{"error":"Failed to find these patterns: [\"unknown\"]"}"#);
+        });
+        let software_rollback_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/api/software/config")
+                .body(r#"{"patterns":{"base":true},"product":null}"#);
+            then.status(200);
+        });
+        let url = server.url("/api");
+
+        let settings: InstallSettings = serde_json::from_str(
+            r#"{
+                "software": { "patterns": ["unknown"] },
+                "localization": { "language": "es_ES.UTF-8" }
+            }"#,
+        )?;
+
+        let store = store(url).await?;
+        let result = store.store(&settings).await;
+
+        let Err(ServiceError::StoreSettings {
+            section, rollback, ..
+        }) = result
+        else {
+            panic!("Unexpected result: {:?}", result);
+        };
+        assert_eq!(section, Section::Software);
+        assert_eq!(
+            rollback.restored,
+            vec![Section::Localization, Section::Software]
+        );
+        assert!(rollback.failed.is_empty());
+
+        l10n_mock.assert();
+        l10n_rollback_mock.assert();
+        software_mock.assert();
+        software_rollback_mock.assert();
+        Ok(())
+    }
+
+    #[test]
+    async fn test_store_network_rollback() -> Result<(), Box<dyn Error>> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/api/software/config");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"patterns": {"base": true}, "product": "Tumbleweed"}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/api/network/connections");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"[{"id": "eth0", "method4": "auto"}]"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/api/network/connections/eth0");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"id": "eth0", "method4": "auto"}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/api/network/connections/eth1");
+            then.status(404);
+        });
+        let add_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/api/network/connections")
+                .body_contains("eth1");
+            then.status(200);
+        });
+        let restore_mock = server.mock(|when, then| {
+            when.method(PUT).path("/api/network/connections/eth0");
+            then.status(200);
+        });
+        let remove_mock = server.mock(|when, then| {
+            when.method(DELETE).path("/api/network/connections/eth1");
+            then.status(200);
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/api/network/system/apply");
+            then.status(200);
+        });
+        server.mock(|when, then| {
+            when.method(PUT)
+                .path("/api/software/config")
+                .body_contains("unknown");
+            then.status(400)
+                .body(r#"{"error":"Failed to find these patterns: [\"unknown\"]"}"#);
+        });
+        server.mock(|when, then| {
+            when.method(PUT)
+                .path("/api/software/config")
+                .body_contains("base");
+            then.status(200);
+        });
+        let url = server.url("/api");
+
+        let settings: InstallSettings = serde_json::from_str(
+            r#"{
+                "network": { "connections": [{ "id": "eth1", "method4": "auto" }] },
+                "software": { "patterns": ["unknown"] }
+            }"#,
+        )?;
+
+        let store = store(url).await?;
+        let result = store.store(&settings).await;
+
+        let Err(ServiceError::StoreSettings { rollback, .. }) = result else {
+            panic!("Unexpected result: {:?}", result);
+        };
+        assert_eq!(rollback.restored, vec![Section::Network, Section::Software]);
+        assert!(rollback.failed.is_empty());
+
+        add_mock.assert();
+        remove_mock.assert();
+        restore_mock.assert();
+        Ok(())
+    }
+
+    #[test]
+    async fn test_store_product_rollback() -> Result<(), Box<dyn Error>> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/api/software/config");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"patterns": {"base": true}, "product": ""}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/api/software/registration");
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(r#"{"key": "OLD-CODE", "email": ""}"#);
+        });
+        let select_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/api/software/config")
+                .body_contains("SLES");
+            then.status(200);
+        });
+        let empty_product_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/api/software/config")
+                .body_contains(r#""product":"""#);
+            then.status(200);
+        });
+        let register_mock = server.mock(|when, then| {
+            when.method(POST).path("/api/software/registration");
+            then.status(200);
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/api/manager/probe_sync");
+            then.status(200);
+        });
+        server.mock(|when, then| {
+            when.method(PUT)
+                .path("/api/software/config")
+                .body_contains("unknown");
+            then.status(400)
+                .body(r#"{"error":"Failed to find these patterns: [\"unknown\"]"}"#);
+        });
+        server.mock(|when, then| {
+            when.method(PUT)
+                .path("/api/software/config")
+                .body_contains("base");
+            then.status(200);
+        });
+        let url = server.url("/api");
+
+        let settings: InstallSettings = serde_json::from_str(
+            r#"{
+                "product": { "id": "SLES", "registrationCode": "NEW-CODE" },
+                "software": { "patterns": ["unknown"] }
+            }"#,
+        )?;
+
+        let store = store(url).await?;
+        let result = store.store(&settings).await;
+
+        let Err(ServiceError::StoreSettings { rollback, .. }) = result else {
+            panic!("Unexpected result: {:?}", result);
+        };
+        assert_eq!(rollback.restored, vec![Section::Product, Section::Software]);
+        assert!(rollback.failed.is_empty());
+
+        select_mock.assert();
+        register_mock.assert_hits(1);
+        empty_product_mock.assert_hits(0);
+        Ok(())
+    }
+
+    #[test]
+    async fn test_store_without_previous_settings() -> Result<(), Box<dyn Error>> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/api/l10n/config");
+            then.status(500);
+        });
+        let l10n_mock = server.mock(|when, then| {
+            when.method(PATCH)
+                .path("/api/l10n/config")
+                .body_contains("es_ES.UTF-8");
+            then.status(204);
+        });
+        let url = server.url("/api");
+
+        let settings: InstallSettings =
+            serde_json::from_str(r#"{ "localization": { "language": "es_ES.UTF-8" } }"#)?;
+
+        let store = store(url).await?;
+        store.store(&settings).await?;
+
+        l10n_mock.assert();
+        Ok(())
+    }
+}
@@ -50,7 +50,7 @@ pub struct FirstUserSettings {
 /// Root user settings
 ///
 /// Holds the settings for the root user.
-#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct RootUserSettings {
     /// Root's password (in clear text)
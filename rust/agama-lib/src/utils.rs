@@ -21,7 +21,11 @@
 //! Utility module for Agama.
 
 mod file_format;
+mod integrity;
+mod secrets;
 mod transfer;
 
 pub use file_format::*;
+pub use integrity::*;
+pub use secrets::*;
 pub use transfer::*;
@@ -22,11 +22,10 @@
 //!
 //! It implements a simple API to detect the file formats that are relevent for Agama.
 
-use std::{
-    io::Write,
-    path::Path,
-    process::{Command, Stdio},
-};
+use std::path::Path;
+
+/// Keywords a Jsonnet file usually starts with.
+const JSONNET_KEYWORDS: [&str; 5] = ["local", "import", "importstr", "importbin", "function"];
 
 /// Relevant file formats for Agama.
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -37,8 +36,6 @@ pub enum FileFormat {
     Unknown,
 }
 
-const JSONNETFMT_BIN: &str = "jsonnetfmt";
-
 impl FileFormat {
     /// Tries to guess the file format from the content of a file.
     pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self, std::io::Error> {
@@ -69,29 +66,27 @@ impl FileFormat {
 
     /// Whether the format is Jsonnet.
     ///
-    /// It tries to process the content with the jsonnetfmt tool and returns `true` if it succeeds.
+    /// It returns `true` if the content starts with a "jsonnet" shebang or, skipping the
+    /// comments, with an object, an array or one of the [JSONNET_KEYWORDS] (`local`, `import`,
+    /// `importstr`, `importbin` or `function`). The content is not required to be valid, so
+    /// syntax errors are reported when evaluating it.
     fn is_jsonnet(content: &str) -> bool {
-        let child = Command::new(JSONNETFMT_BIN)
-            .args(["-"])
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn();
-
-        let Ok(mut child) = child else {
-            return false;
-        };
-
-        let Some(mut stdin) = child.stdin.take() else {
-            return false;
-        };
-
-        stdin
-            .write_all(content.as_bytes())
-            .expect("Failed to write to stdin");
-        drop(stdin);
+        if content.starts_with("#!") {
+            return content
+                .lines()
+                .next()
+                .is_some_and(|l| l.contains("jsonnet"));
+        }
 
-        child.wait().is_ok_and(|s| s.success())
+        let code = skip_comments(content);
+        if code.starts_with(['{', '[']) {
+            return true;
+        }
+        let keyword: String = code
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        JSONNET_KEYWORDS.contains(&keyword.as_str())
     }
 
     /// Whether is is a script.
@@ -107,6 +102,20 @@ impl FileFormat {
     }
 }
 
+/// Returns the content after the leading whitespace and comments.
+fn skip_comments(mut content: &str) -> &str {
+    loop {
+        content = content.trim_start();
+        if content.starts_with("//") || content.starts_with('#') {
+            content = content.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(rest) = content.strip_prefix("/*") {
+            content = rest.split_once("*/").map_or("", |(_, rest)| rest);
+        } else {
+            return content;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::FileFormat;
@@ -130,6 +139,17 @@ mod tests {
             print
         "#;
         assert_eq!(FileFormat::from_string(content), FileFormat::Jsonnet);
+
+        let content = r#"
+            // Agama profile
+            /* generated */
+            local hw = import 'hw.libsonnet';
+            { product: { id: 'Tumbleweed' } }
+            "#;
+        assert_eq!(FileFormat::from_string(content), FileFormat::Jsonnet);
+
+        let content = "{ product: { id: 'Tumbleweed' }";
+        assert_eq!(FileFormat::from_string(content), FileFormat::Jsonnet);
     }
 
     #[test]
@@ -154,5 +174,8 @@ mod tests {
             Some text content.
         "#;
         assert_eq!(FileFormat::from_string(text), FileFormat::Unknown);
+
+        let identifier = "localhost\n";
+        assert_eq!(FileFormat::from_string(identifier), FileFormat::Unknown);
     }
 }
@@ -0,0 +1,256 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Integrity checks for downloaded files.
+//!
+//! Profiles and remote scripts can declare the expected SHA-256 checksum of their content and/or
+//! a detached signature. The signature format is inferred from the extension of its URL:
+//!
+//! * `.minisig`: [minisign](https://jedisct1.github.io/minisign/) signature. The trusted public
+//!   keys are read from the `*.pub` files in the trusted keys directory.
+//! * Anything else (e.g., `.sig` or `.asc`): GPG signature, verified with `gpgv` against the
+//!   `*.gpg` keyrings in the trusted keys directory.
+
+use minisign_verify::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tempfile::NamedTempFile;
+use thiserror::Error;
+
+/// Default directory containing the trusted keys.
+pub const TRUSTED_KEYS_DIR: &str = "/etc/agama.d/trusted-keys";
+
+#[derive(Error, Debug)]
+pub enum VerificationError {
+    #[error("Checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("The signature is not valid or it was not made with a trusted key")]
+    InvalidSignature,
+    #[error("There are no trusted keys in {0}")]
+    NoTrustedKeys(String),
+    #[error("Could not read the trusted key {0}: {1}")]
+    InvalidKey(String, String),
+    #[error("Could not read the signature: {0}")]
+    MalformedSignature(String),
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+}
+
+/// Expected checksum and/or signature of a file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Integrity {
+    /// Expected SHA-256 checksum (hexadecimal).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// URL of the detached signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl Integrity {
+    /// Whether there is anything to check.
+    pub fn is_empty(&self) -> bool {
+        self.sha256.is_none() && self.signature.is_none()
+    }
+
+    /// Verifies the checksum of the given data.
+    pub fn verify_checksum(&self, data: &[u8]) -> Result<(), VerificationError> {
+        let Some(expected) = &self.sha256 else {
+            return Ok(());
+        };
+
+        let actual = format!("{:x}", Sha256::digest(data));
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(VerificationError::ChecksumMismatch {
+                expected: expected.to_string(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Signature formats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignatureFormat {
+    Minisign,
+    Gpg,
+}
+
+impl SignatureFormat {
+    /// Infers the signature format from its URL.
+    pub fn from_url(url: &str) -> Self {
+        if url.ends_with(".minisig") {
+            Self::Minisign
+        } else {
+            Self::Gpg
+        }
+    }
+}
+
+/// Keys that are trusted to sign profiles and scripts.
+pub struct TrustedKeys {
+    dir: PathBuf,
+}
+
+impl Default for TrustedKeys {
+    fn default() -> Self {
+        Self::new(TRUSTED_KEYS_DIR)
+    }
+}
+
+impl TrustedKeys {
+    /// * `dir`: directory containing the minisign public keys (`*.pub`) and the GPG keyrings
+    ///   (`*.gpg`).
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Verifies a detached signature.
+    ///
+    /// * `data`: signed data.
+    /// * `signature`: content of the signature file.
+    /// * `format`: signature format.
+    pub fn verify(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        format: SignatureFormat,
+    ) -> Result<(), VerificationError> {
+        match format {
+            SignatureFormat::Minisign => self.verify_minisign(data, signature),
+            SignatureFormat::Gpg => self.verify_gpg(data, signature),
+        }
+    }
+
+    fn verify_minisign(&self, data: &[u8], signature: &[u8]) -> Result<(), VerificationError> {
+        let signature = String::from_utf8_lossy(signature);
+        let signature = Signature::decode(&signature)
+            .map_err(|e| VerificationError::MalformedSignature(e.to_string()))?;
+
+        let keys = self.files("pub")?;
+        for path in &keys {
+            let key = PublicKey::from_file(path).map_err(|e| {
+                VerificationError::InvalidKey(path.display().to_string(), e.to_string())
+            })?;
+            if key.verify(data, &signature, false).is_ok() {
+                return Ok(());
+            }
+        }
+        Err(VerificationError::InvalidSignature)
+    }
+
+    fn verify_gpg(&self, data: &[u8], signature: &[u8]) -> Result<(), VerificationError> {
+        let keyrings = self.files("gpg")?;
+        let mut data_file = NamedTempFile::new()?;
+        data_file.write_all(data)?;
+        let mut signature_file = NamedTempFile::new()?;
+        signature_file.write_all(signature)?;
+
+        let mut command = Command::new("gpgv");
+        for keyring in &keyrings {
+            command.arg("--keyring").arg(keyring);
+        }
+        let output = command
+            .arg(signature_file.path())
+            .arg(data_file.path())
+            .output()?;
+        if !output.status.success() {
+            log::warn!("gpgv failed: {}", String::from_utf8_lossy(&output.stderr));
+            return Err(VerificationError::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    /// Returns the files with the given extension, failing if there are none.
+    fn files(&self, extension: &str) -> Result<Vec<PathBuf>, VerificationError> {
+        let no_keys = || VerificationError::NoTrustedKeys(self.dir.display().to_string());
+        let entries = fs::read_dir(&self.dir).map_err(|_| no_keys())?;
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == extension))
+            .collect();
+        if files.is_empty() {
+            return Err(no_keys());
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Integrity, SignatureFormat, TrustedKeys, VerificationError};
+    use std::fs;
+    use tempfile::TempDir;
+
+    const SCRIPT: &str = "#!/bin/sh\necho hello\n";
+    const PUBLIC_KEY: &str = "untrusted comment: agama test key
+RWS5shAeaM7zS9RiLG6P2ZEDPmdNjoTQLKgUqsCKeqIxGxjx3ZSWbcGJ
+";
+    const SIGNATURE: &str = "untrusted comment: signature from agama test key
+RUS5shAeaM7zS2VpPujm7GHhv3awIUvau3J4r7Q8oDuSyS/r0sZink03hRl7chA0gdNySdSFWC4mOXi91Yu5TCkEpVU5oBNbCQo=
+trusted comment: agama test
+RG3BX/L6knmOd5wSMXtOeIQB4CVneKL20Q60GMGwIcGHd/uqhYBq55UTlUe7Hbe9UZTSkOxTV9AQdUnbzRJrAg==
+";
+
+    #[test]
+    fn test_verify_checksum() {
+        let integrity = Integrity {
+            sha256: Some(
+                "BFDEAEB08CFFB6A36438BCD12DDA25417E3CDD36F1E7E482A2849D539225288B".to_string(),
+            ),
+            ..Default::default()
+        };
+        assert!(integrity.verify_checksum(SCRIPT.as_bytes()).is_ok());
+        assert!(matches!(
+            integrity.verify_checksum(b"echo bye"),
+            Err(VerificationError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_minisign() {
+        let dir = TempDir::new().unwrap();
+        let keys = TrustedKeys::new(dir.path());
+        let format = SignatureFormat::from_url("http://example.net/script.sh.minisig");
+        assert!(matches!(
+            keys.verify(SCRIPT.as_bytes(), SIGNATURE.as_bytes(), format),
+            Err(VerificationError::NoTrustedKeys(_))
+        ));
+
+        fs::write(dir.path().join("agama.pub"), PUBLIC_KEY).unwrap();
+        assert!(keys
+            .verify(SCRIPT.as_bytes(), SIGNATURE.as_bytes(), format)
+            .is_ok());
+        assert!(matches!(
+            keys.verify(b"echo bye", SIGNATURE.as_bytes(), format),
+            Err(VerificationError::InvalidSignature)
+        ));
+    }
+}
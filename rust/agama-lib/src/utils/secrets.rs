@@ -0,0 +1,117 @@
+// Copyright (c) [2025] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Detection of the secret values (e.g., passwords or private keys) in JSON documents.
+//!
+//! A value is considered secret when its key contains any of the [SENSITIVE_WORDS], no matter
+//! the case (e.g., `privateKey` or `caCertPassword`). It is used to keep the secrets out of the
+//! audit log, the events sent to clients with limited access and the settings reports.
+
+use serde_json::Value;
+
+/// Keys containing any of these words (in lowercase) are secret.
+pub const SENSITIVE_WORDS: [&str; 8] = [
+    "password",
+    "passphrase",
+    "key",
+    "psk",
+    "regcode",
+    "registrationcode",
+    "secret",
+    "token",
+];
+/// Keys (in lowercase) that contain a sensitive word but are not secret.
+const NON_SENSITIVE_KEYS: [&str; 5] = ["keyboard", "keymap", "keymaps", "pkey", "p_key"];
+
+/// Whether the given key holds a secret value.
+///
+/// * `key`: key of the value.
+pub fn is_sensitive_key(key: &str) -> bool {
+    let lowercase = key.to_lowercase();
+    SENSITIVE_WORDS.iter().any(|w| lowercase.contains(w))
+        && !NON_SENSITIVE_KEYS.contains(&lowercase.as_str())
+}
+
+/// Whether the given value must be masked.
+///
+/// The flags (e.g., `hashedPassword`) and the null values do not reveal anything, so they are
+/// not masked.
+///
+/// * `key`: key of the value.
+/// * `value`: value to check.
+pub fn is_secret(key: &str, value: &Value) -> bool {
+    is_sensitive_key(key) && !matches!(value, Value::Null | Value::Bool(_))
+}
+
+/// Replaces the secret values of a JSON document.
+///
+/// * `value`: JSON document.
+/// * `masked`: text to use instead of the secret values.
+pub fn mask_secrets(value: Value, masked: &str) -> Value {
+    match value {
+        Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| {
+                if is_secret(&key, &value) {
+                    (key, Value::String(masked.to_string()))
+                } else {
+                    (key, mask_secrets(value, masked))
+                }
+            })
+            .collect(),
+        Value::Array(items) => items
+            .into_iter()
+            .map(|item| mask_secrets(item, masked))
+            .collect(),
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_sensitive_key, mask_secrets};
+    use serde_json::json;
+
+    #[test]
+    fn test_is_sensitive_key() {
+        assert!(is_sensitive_key("password"));
+        assert!(is_sensitive_key("caCertPassword"));
+        assert!(is_sensitive_key("privateKey"));
+        assert!(is_sensitive_key("registrationCode"));
+        assert!(!is_sensitive_key("keyboard"));
+        assert!(!is_sensitive_key("pKey"));
+        assert!(!is_sensitive_key("userName"));
+    }
+
+    #[test]
+    fn test_mask_secrets() {
+        let value = json!({
+            "user": { "userName": "tux", "password": "fish", "hashedPassword": false },
+            "connections": [{ "id": "wg0", "privateKey": "abc", "peers": [{ "presharedKey": "xyz" }] }]
+        });
+        assert_eq!(
+            mask_secrets(value, "***"),
+            json!({
+                "user": { "userName": "tux", "password": "***", "hashedPassword": false },
+                "connections": [{ "id": "wg0", "privateKey": "***", "peers": [{ "presharedKey": "***" }] }]
+            })
+        );
+    }
+}
@@ -26,34 +26,388 @@
 //! information.
 //!
 //! At this point, it only supports those schemes supported by CURL.
+//!
+//! The transfers can be tuned through [TransferOptions]. By default, they are read from the
+//! following environment variables:
+//!
+//! * `AGAMA_TRANSFER_RETRIES`: number of retries after a transient error (default: 3).
+//! * `AGAMA_TRANSFER_CONNECT_TIMEOUT`: connection timeout, in seconds (default: 30).
+//! * `AGAMA_TRANSFER_TIMEOUT`: timeout for the whole transfer, in seconds (default: none).
+//! * `AGAMA_TRANSFER_PROXY`: proxy URL. CURL also honors the usual `http_proxy`, `https_proxy`
+//!   and `no_proxy` variables.
+//! * `AGAMA_TRANSFER_CA_BUNDLE`: path of the CA bundle to verify the server certificates.
 
-use std::io::Write;
+use std::{
+    env,
+    fs::File,
+    io::{self, Seek, Write},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
 
 use curl::easy::Easy;
 use thiserror::Error;
+use url::Url;
+
+use super::integrity::{Integrity, SignatureFormat, TrustedKeys, VerificationError};
 
 #[derive(Error, Debug)]
-#[error(transparent)]
-pub struct TransferError(#[from] curl::Error);
+pub enum TransferError {
+    #[error(transparent)]
+    CurlError(#[from] curl::Error),
+    #[error("Could not get {url}: the server returned HTTP {code}")]
+    HTTPError { url: String, code: u32 },
+    #[error("Could not verify {0}: {1}")]
+    VerificationFailed(String, VerificationError),
+    #[error("Could not write the data: {0}")]
+    IO(#[from] std::io::Error),
+}
 pub type TransferResult<T> = Result<T, TransferError>;
 
+/// Maximum delay between two retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Settings for the file transfers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferOptions {
+    /// Number of retries after a transient error (e.g., a timeout or a 503 response).
+    pub retries: u32,
+    /// Delay before the first retry. It is doubled after each attempt.
+    pub retry_delay: Duration,
+    pub connect_timeout: Option<Duration>,
+    /// Timeout for the whole transfer (per attempt).
+    pub timeout: Option<Duration>,
+    pub proxy: Option<String>,
+    /// CA bundle to verify the server certificates.
+    pub ca_bundle: Option<PathBuf>,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            retry_delay: Duration::from_secs(1),
+            connect_timeout: Some(Duration::from_secs(30)),
+            timeout: None,
+            proxy: None,
+            ca_bundle: None,
+        }
+    }
+}
+
+impl TransferOptions {
+    /// Reads the options from the environment (see the [module documentation](self)).
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Builds the options from the given variables.
+    ///
+    /// * `var`: returns the value of a variable, if defined.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let mut options = Self::default();
+        let seconds = |name| {
+            var(name)
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs)
+        };
+        if let Some(retries) = var("AGAMA_TRANSFER_RETRIES").and_then(|v| v.parse().ok()) {
+            options.retries = retries;
+        }
+        if let Some(timeout) = seconds("AGAMA_TRANSFER_CONNECT_TIMEOUT") {
+            options.connect_timeout = Some(timeout);
+        }
+        if let Some(timeout) = seconds("AGAMA_TRANSFER_TIMEOUT") {
+            options.timeout = Some(timeout);
+        }
+        if let Some(proxy) = var("AGAMA_TRANSFER_PROXY") {
+            options.proxy = Some(proxy);
+        }
+        if let Some(ca_bundle) = var("AGAMA_TRANSFER_CA_BUNDLE") {
+            options.ca_bundle = Some(PathBuf::from(ca_bundle));
+        }
+        options
+    }
+}
+
+/// Function to report the progress of a transfer.
+///
+/// It receives the downloaded bytes and the total size (0 if unknown).
+pub type ProgressFn = Box<dyn Fn(u64, u64) + Send + Sync>;
+
 /// File transfer API
-pub struct Transfer {}
+pub struct Transfer {
+    options: TransferOptions,
+    progress: Option<ProgressFn>,
+}
+
+impl Default for Transfer {
+    fn default() -> Self {
+        Self::new(TransferOptions::from_env())
+    }
+}
 
 impl Transfer {
+    /// * `options`: transfer settings.
+    pub fn new(options: TransferOptions) -> Self {
+        Self {
+            options,
+            progress: None,
+        }
+    }
+
+    /// Sets a function to report the progress of the transfers.
+    pub fn with_progress(mut self, progress: impl Fn(u64, u64) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Retrieves and writes the data from an URL using the default options
+    ///
+    /// * `url`: URL to get the data from.
+    /// * `out_fd`: where to write the data.
+    pub fn get(url: &str, out_fd: impl Write) -> TransferResult<()> {
+        Self::default().fetch(url, out_fd)
+    }
+
+    /// Retrieves the data from an URL, checking its integrity, using the default options
+    ///
+    /// See [Transfer::fetch_verified].
+    pub fn get_verified(
+        url: &str,
+        integrity: &Integrity,
+        keys: &TrustedKeys,
+        out_fd: impl Write,
+    ) -> TransferResult<()> {
+        Self::default().fetch_verified(url, integrity, keys, out_fd)
+    }
+
     /// Retrieves and writes the data from an URL
     ///
+    /// Transient errors are retried. The data is written only when the transfer succeeds, so a
+    /// failed attempt does not leave partial data in `out_fd`.
+    ///
+    /// * `url`: URL to get the data from.
+    /// * `out_fd`: where to write the data.
+    pub fn fetch(&self, url: &str, mut out_fd: impl Write) -> TransferResult<()> {
+        let mut file = tempfile::tempfile()?;
+        let mut delay = self.options.retry_delay;
+        let mut attempt = 0;
+        loop {
+            file.set_len(0)?;
+            file.rewind()?;
+            match self.perform(url, &mut file) {
+                Ok(()) => break,
+                Err(error) if attempt < self.options.retries && is_transient(&error) => {
+                    attempt += 1;
+                    log::warn!(
+                        "Could not get {} ({}), retrying in {:?} ({}/{})",
+                        url,
+                        error,
+                        delay,
+                        attempt,
+                        self.options.retries
+                    );
+                    thread::sleep(delay);
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        file.rewind()?;
+        io::copy(&mut file, &mut out_fd)?;
+        Ok(())
+    }
+
+    /// Retrieves the data from an URL, checking its integrity before writing it
+    ///
+    /// Nothing is written if the checksum or the signature do not match.
+    ///
     /// * `url`: URL to get the data from.
+    /// * `integrity`: expected checksum and signature. A relative signature URL is resolved
+    ///   against `url`.
+    /// * `keys`: keys that are trusted to sign the data.
     /// * `out_fd`: where to write the data.
-    pub fn get(url: &str, mut out_fd: impl Write) -> TransferResult<()> {
+    pub fn fetch_verified(
+        &self,
+        url: &str,
+        integrity: &Integrity,
+        keys: &TrustedKeys,
+        mut out_fd: impl Write,
+    ) -> TransferResult<()> {
+        let failed = |e| TransferError::VerificationFailed(url.to_string(), e);
+        let mut data = vec![];
+        self.fetch(url, &mut data)?;
+        integrity.verify_checksum(&data).map_err(failed)?;
+
+        if let Some(signature_url) = &integrity.signature {
+            let signature_url = Url::parse(url)
+                .and_then(|u| u.join(signature_url))
+                .map(|u| u.to_string())
+                .unwrap_or_else(|_| signature_url.to_string());
+            let mut signature = vec![];
+            self.fetch(&signature_url, &mut signature)?;
+            let format = SignatureFormat::from_url(&signature_url);
+            keys.verify(&data, &signature, format).map_err(failed)?;
+        }
+
+        out_fd.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Performs a single attempt to get the data.
+    fn perform(&self, url: &str, file: &mut File) -> TransferResult<()> {
         let mut handle = Easy::new();
         handle.follow_location(true)?;
         handle.fail_on_error(true)?;
         handle.url(url)?;
+        if let Some(timeout) = self.options.connect_timeout {
+            handle.connect_timeout(timeout)?;
+        }
+        if let Some(timeout) = self.options.timeout {
+            handle.timeout(timeout)?;
+        }
+        if let Some(proxy) = &self.options.proxy {
+            handle.proxy(proxy)?;
+        }
+        if let Some(ca_bundle) = &self.options.ca_bundle {
+            handle.cainfo(ca_bundle)?;
+        }
+        handle.progress(self.progress.is_some())?;
 
-        let mut transfer = handle.transfer();
-        transfer.write_function(|buf| Ok(out_fd.write(buf).unwrap()))?;
-        transfer.perform()?;
-        Ok(())
+        // keep the write error, as CURL only reports that the write failed
+        let mut write_error = None;
+        let result = {
+            let mut transfer = handle.transfer();
+            transfer.write_function(|buf| match file.write_all(buf) {
+                Ok(()) => Ok(buf.len()),
+                Err(error) => {
+                    write_error = Some(error);
+                    Ok(0)
+                }
+            })?;
+            if let Some(progress) = &self.progress {
+                transfer.progress_function(|total, now, _, _| {
+                    progress(now as u64, total as u64);
+                    true
+                })?;
+            }
+            transfer.perform()
+        };
+
+        if let Some(error) = write_error {
+            return Err(TransferError::IO(error));
+        }
+        match result {
+            Err(error) if error.is_http_returned_error() => Err(TransferError::HTTPError {
+                url: url.to_string(),
+                code: handle.response_code()?,
+            }),
+            Err(error) => Err(error.into()),
+            Ok(()) => Ok(()),
+        }
+    }
+}
+
+/// Whether the error is worth retrying.
+fn is_transient(error: &TransferError) -> bool {
+    let error = match error {
+        TransferError::HTTPError { code, .. } => return *code == 429 || *code >= 500,
+        TransferError::CurlError(error) => error,
+        _ => return false,
+    };
+    error.is_couldnt_resolve_proxy()
+        || error.is_couldnt_resolve_host()
+        || error.is_couldnt_connect()
+        || error.is_operation_timedout()
+        || error.is_partial_file()
+        || error.is_recv_error()
+        || error.is_send_error()
+        || error.is_got_nothing()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Transfer, TransferError, TransferOptions};
+    use httpmock::prelude::*;
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    fn transfer(retries: u32) -> Transfer {
+        Transfer::new(TransferOptions {
+            retries,
+            retry_delay: Duration::from_millis(1),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_options_from_vars() {
+        let options = TransferOptions::from_vars(|name| match name {
+            "AGAMA_TRANSFER_RETRIES" => Some("5".to_string()),
+            "AGAMA_TRANSFER_TIMEOUT" => Some("60".to_string()),
+            "AGAMA_TRANSFER_PROXY" => Some("http://proxy:3128".to_string()),
+            _ => None,
+        });
+        assert_eq!(options.retries, 5);
+        assert_eq!(options.timeout, Some(Duration::from_secs(60)));
+        assert_eq!(options.connect_timeout, Some(Duration::from_secs(30)));
+        assert_eq!(options.proxy.as_deref(), Some("http://proxy:3128"));
+    }
+
+    #[test]
+    fn test_fetch() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/profile.json");
+            then.status(200).body("{ \"software\": {} }");
+        });
+
+        let downloaded = Arc::new(AtomicU64::new(0));
+        let counter = downloaded.clone();
+        let transfer =
+            transfer(0).with_progress(move |now, _| counter.store(now, Ordering::SeqCst));
+        let mut data = vec![];
+        transfer
+            .fetch(&server.url("/profile.json"), &mut data)
+            .unwrap();
+        assert_eq!(String::from_utf8(data).unwrap(), "{ \"software\": {} }");
+        assert_eq!(downloaded.load(Ordering::SeqCst), 18);
+    }
+
+    #[test]
+    fn test_fetch_retries() {
+        let server = MockServer::start();
+        let unavailable = server.mock(|when, then| {
+            when.method(GET).path("/unavailable.json");
+            then.status(503);
+        });
+        let missing = server.mock(|when, then| {
+            when.method(GET).path("/missing.json");
+            then.status(404);
+        });
+
+        let mut data = vec![];
+        let result = transfer(2).fetch(&server.url("/unavailable.json"), &mut data);
+        assert!(matches!(
+            result,
+            Err(TransferError::HTTPError { code: 503, .. })
+        ));
+        unavailable.assert_hits(3);
+
+        let result = transfer(2).fetch(&server.url("/missing.json"), &mut data);
+        assert!(matches!(
+            result,
+            Err(TransferError::HTTPError { code: 404, .. })
+        ));
+        missing.assert_hits(1);
+        assert!(data.is_empty());
     }
 }
@@ -19,21 +19,29 @@
 // find current contact information at www.suse.com.
 
 use std::{
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
     path::{Path, PathBuf},
     pin::Pin,
     process::{ExitCode, Termination},
+    sync::{Arc, RwLock},
+    time::Duration,
 };
 
-use agama_lib::{auth::AuthToken, connection_to};
+use agama_lib::{auth::TokenClaims, connection_to};
 use agama_server::{
-    cert::Certificate,
+    cert::{self as certificate, Certificate, KeyType},
     l10n::helpers,
     logs::init_logging,
-    web::{self, run_monitor},
+    web::{
+        self,
+        keys::{KeyStore, SigningAlgorithm, TokenKeys},
+        run_monitor, ClientCertificate,
+    },
 };
 use anyhow::Context;
 use axum::{
-    extract::Request as AxumRequest,
+    extract::{ConnectInfo, Request as AxumRequest},
     http::{Request, Response},
     Router,
 };
@@ -42,13 +50,18 @@ use futures_util::pin_mut;
 use hyper::body::Incoming;
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::server::conn::auto::Builder;
-use openssl::ssl::{Ssl, SslAcceptor, SslMethod};
+use openssl::{
+    pkey::PKey,
+    ssl::{Ssl, SslAcceptor, SslMethod, SslVerifyMode},
+    x509::X509,
+};
 use tokio::sync::broadcast::channel;
 use tokio_openssl::SslStream;
 use tower::Service;
 
 const DEFAULT_WEB_UI_DIR: &str = "/usr/share/agama/web_ui";
 const TOKEN_FILE: &str = "/run/agama/token";
+const CERTIFICATE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Subcommand, Debug)]
 enum Commands {
@@ -57,6 +70,60 @@ enum Commands {
     /// This command starts the server in the given ports. The secondary port, if enabled, uses SSL.
     /// If no certificate is specified, agama-web-server generates a self-signed one.
     Serve(ServeArgs),
+    /// Manages the keys to sign the authentication tokens.
+    ///
+    /// The keys live in the directory set in the "jwt_keys_dir" option of the configuration
+    /// (/etc/agama.d/jwt by default). The server reads them when it starts.
+    #[command(subcommand)]
+    Keys(KeysCommands),
+    /// Manages the SSL certificate.
+    #[command(subcommand)]
+    Certificate(CertificateCommands),
+}
+
+#[derive(Subcommand, Debug)]
+enum CertificateCommands {
+    /// Creates a certificate signing request (CSR) to get the certificate signed by a CA.
+    ///
+    /// The request uses the given private key, which is generated if it does not exist. Once the
+    /// certificate is signed, copy it to /etc/agama.d/ssl/cert.pem: the server reloads it
+    /// automatically.
+    Csr {
+        /// Private key.
+        #[arg(long, default_value = "/etc/agama.d/ssl/key.pem")]
+        key: PathBuf,
+        /// Type of the key to generate.
+        #[arg(long, value_enum, default_value_t)]
+        key_type: KeyType,
+        /// File to write the request to (standard output by default).
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Prints the SHA-256 fingerprint of the certificate.
+    Fingerprint {
+        #[arg(long, default_value = "/etc/agama.d/ssl/cert.pem")]
+        cert: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum KeysCommands {
+    /// Lists the keys, marking the one that signs the new tokens with an asterisk.
+    List,
+    /// Generates a new key to sign the tokens.
+    ///
+    /// The previous keys are kept to verify the tokens signed with them. Remove them when those
+    /// tokens should not be accepted anymore.
+    Rotate {
+        /// Algorithm of the new key (the one from the configuration by default).
+        #[arg(long, value_enum)]
+        algorithm: Option<SigningAlgorithm>,
+    },
+    /// Removes a key, so the tokens signed with it are not valid anymore.
+    Remove {
+        /// Key identifier.
+        id: String,
+    },
 }
 
 /// Manage Agama's HTTP/JSON API.
@@ -103,6 +170,15 @@ struct ServeArgs {
     #[arg(long, default_value = "/etc/agama.d/ssl/cert.pem")]
     cert: Option<PathBuf>,
 
+    /// Type of the key when generating a self-signed certificate
+    #[arg(long, value_enum, default_value_t)]
+    key_type: KeyType,
+
+    /// CA to verify the client certificates (it overrides the "client_certs.ca" option of the
+    /// configuration)
+    #[arg(long)]
+    client_ca: Option<PathBuf>,
+
     // Agama D-Bus address
     #[arg(long, default_value = "unix:path=/run/agama/bus")]
     dbus_address: String,
@@ -133,7 +209,7 @@ impl ServeArgs {
             Certificate::read(cert.as_path(), key.as_path())
         } else {
             // ask for self-signed certificate
-            let certificate = Certificate::new()?;
+            let certificate = Certificate::generate(self.key_type)?;
 
             // write the certificate for the later use
             // for now do not care if writing self generated certificate failed or not, in the
@@ -146,7 +222,13 @@ impl ServeArgs {
 }
 
 /// Builds an SSL acceptor using a provided SSL certificate or generates a self-signed one
-fn ssl_acceptor(certificate: &Certificate) -> Result<SslAcceptor, openssl::error::ErrorStack> {
+///
+/// If `client_ca` is not empty, the clients are asked for a certificate signed by one of those
+/// CAs. The certificate is optional, but the handshake fails if it cannot be verified.
+fn ssl_acceptor(
+    certificate: &Certificate,
+    client_ca: &[X509],
+) -> Result<SslAcceptor, openssl::error::ErrorStack> {
     let mut tls_builder = SslAcceptor::mozilla_modern_v5(SslMethod::tls_server())?;
 
     tls_builder.set_private_key(&certificate.key)?;
@@ -155,6 +237,16 @@ fn ssl_acceptor(certificate: &Certificate) -> Result<SslAcceptor, openssl::error
     // check that the key belongs to the certificate
     tls_builder.check_private_key()?;
 
+    if !client_ca.is_empty() {
+        for ca in client_ca {
+            tls_builder.cert_store_mut().add_cert(ca.clone())?;
+            tls_builder.add_client_ca(ca)?;
+        }
+        tls_builder.set_verify(SslVerifyMode::PEER);
+        // required to resume the sessions when verifying the clients
+        tls_builder.set_session_id_context(b"agama")?;
+    }
+
     Ok(tls_builder.build())
 }
 
@@ -238,8 +330,21 @@ async fn handle_https_stream(
     if let Err(err) = SslStream::accept(Pin::new(&mut tls_stream)).await {
         tracing::error!("Error during TSL handshake from {}: {}", addr, err);
     } else {
+        // the certificate was already verified during the handshake
+        let client_cert = tls_stream
+            .ssl()
+            .peer_certificate()
+            .and_then(|c| ClientCertificate::from_x509(&c).ok());
+        if let Some(client_cert) = &client_cert {
+            tracing::info!("Client certificate from {}: {}", addr, client_cert.subject);
+        }
+
         let stream = TokioIo::new(tls_stream);
-        let hyper_service = hyper::service::service_fn(move |request: Request<Incoming>| {
+        let hyper_service = hyper::service::service_fn(move |mut request: Request<Incoming>| {
+            request.extensions_mut().insert(ConnectInfo(addr));
+            if let Some(client_cert) = &client_cert {
+                request.extensions_mut().insert(client_cert.clone());
+            }
             service.clone().call(request)
         });
 
@@ -261,7 +366,8 @@ async fn handle_http_stream(
     redirector_service: axum::Router,
 ) {
     let stream = TokioIo::new(tcp_stream);
-    let hyper_service = hyper::service::service_fn(move |request: Request<Incoming>| {
+    let hyper_service = hyper::service::service_fn(move |mut request: Request<Incoming>| {
+        request.extensions_mut().insert(ConnectInfo(addr));
         // check if it is local connection or external
         // the to_canonical() converts IPv4-mapped IPv6 addresses
         // to plain IPv4, then is_loopback() works correctly for the IPv4 connections
@@ -284,7 +390,9 @@ async fn handle_http_stream(
 }
 
 /// Starts the web server
-async fn start_server(address: String, service: Router, ssl_acceptor: SslAcceptor) {
+///
+/// `ssl_acceptor` is shared, so it can be replaced when the certificate changes.
+async fn start_server(address: String, service: Router, ssl_acceptor: Arc<RwLock<SslAcceptor>>) {
     tracing::info!("Starting Agama web server at {}", address);
 
     // see https://github.com/tokio-rs/axum/blob/main/examples/low-level-openssl/src/main.rs
@@ -304,7 +412,7 @@ async fn start_server(address: String, service: Router, ssl_acceptor: SslAccepto
     loop {
         let tower_service = service.clone();
         let redirector_service = redirector.clone();
-        let tls_acceptor = ssl_acceptor.clone();
+        let tls_acceptor = ssl_acceptor.read().unwrap().clone();
 
         // Wait for a new tcp connection; if it fails we cannot do much, so print an error and die
         let (tcp_stream, addr) = listener
@@ -324,6 +432,17 @@ async fn start_server(address: String, service: Router, ssl_acceptor: SslAccepto
     }
 }
 
+/// Prints the fingerprint of the certificate, so the users can verify it in the browser.
+fn print_fingerprint(certificate: &Certificate) {
+    match certificate.fingerprint() {
+        Ok(fingerprint) => {
+            println!("SSL certificate fingerprint (SHA-256): {}", fingerprint);
+            tracing::info!("SSL certificate fingerprint (SHA-256): {}", fingerprint);
+        }
+        Err(error) => tracing::warn!("Could not calculate the certificate fingerprint: {}", error),
+    }
+}
+
 /// Start serving the API.
 /// `options`: command-line arguments.
 async fn serve_command(args: ServeArgs) -> anyhow::Result<()> {
@@ -333,20 +452,46 @@ async fn serve_command(args: ServeArgs) -> anyhow::Result<()> {
     let (tx, _) = channel(16);
     run_monitor(tx.clone()).await?;
 
-    let config = web::ServiceConfig::load()?;
+    let mut config = web::ServiceConfig::load()?;
+    if args.client_ca.is_some() {
+        config.client_certs.ca = args.client_ca.clone();
+    }
+    let client_ca = config
+        .client_certs
+        .read_ca()
+        .context("could not read the client certificates CA")?;
+    let keys = config.token_keys();
 
-    write_token(TOKEN_FILE, &config.jwt_secret).context("could not create the token file")?;
+    write_token(TOKEN_FILE, &keys).context("could not create the token file")?;
 
     let dbus = connection_to(&args.dbus_address).await?;
     let web_ui_dir = args.web_ui_dir.clone().unwrap_or(find_web_ui_dir());
-    let service = web::service(config, tx, dbus, web_ui_dir).await?;
+    let service = web::service(config, keys, tx, dbus, web_ui_dir).await?;
     // TODO: Move elsewhere? Use a singleton? (It would be nice to use the same
     // generated self-signed certificate on both ports.)
-    let ssl_acceptor = if let Ok(ssl_acceptor) = ssl_acceptor(&args.to_certificate()?) {
-        ssl_acceptor
+    let certificate = args.to_certificate()?;
+    let ssl_acceptor = if let Ok(ssl_acceptor) = ssl_acceptor(&certificate, &client_ca) {
+        Arc::new(RwLock::new(ssl_acceptor))
     } else {
         return Err(anyhow::anyhow!("SSL initialization failed"));
     };
+    print_fingerprint(&certificate);
+
+    if let (Some(cert), Some(key)) = (args.cert.clone(), args.key.clone()) {
+        let acceptor = Arc::clone(&ssl_acceptor);
+        tokio::spawn(certificate::watch(
+            cert,
+            key,
+            CERTIFICATE_CHECK_INTERVAL,
+            move |certificate| match self::ssl_acceptor(&certificate, &client_ca) {
+                Ok(new_acceptor) => {
+                    *acceptor.write().unwrap() = new_acceptor;
+                    print_fingerprint(&certificate);
+                }
+                Err(error) => tracing::warn!("Could not use the new SSL certificate: {}", error),
+            },
+        ));
+    }
 
     let mut addresses = vec![args.address];
 
@@ -379,11 +524,79 @@ async fn serve_command(args: ServeArgs) -> anyhow::Result<()> {
 async fn run_command(cli: Cli) -> anyhow::Result<()> {
     match cli.command {
         Commands::Serve(options) => serve_command(options).await,
+        Commands::Keys(subcommand) => keys_command(subcommand),
+        Commands::Certificate(subcommand) => certificate_command(subcommand),
     }
 }
 
-fn write_token(path: &str, secret: &str) -> anyhow::Result<()> {
-    let token = AuthToken::generate(secret)?;
+/// Manages the SSL certificate.
+/// `subcommand`: certificate subcommand.
+fn certificate_command(subcommand: CertificateCommands) -> anyhow::Result<()> {
+    match subcommand {
+        CertificateCommands::Csr {
+            key,
+            key_type,
+            output,
+        } => {
+            let private_key = if key.exists() {
+                let pem = std::fs::read(&key)
+                    .with_context(|| format!("could not read the key {}", key.display()))?;
+                PKey::private_key_from_pem(&pem)?
+            } else {
+                let private_key = key_type.generate()?;
+                if let Some(dir) = key.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::OpenOptions::new()
+                    .create_new(true)
+                    .write(true)
+                    .mode(0o400)
+                    .open(&key)?
+                    .write_all(&private_key.private_key_to_pem_pkcs8()?)?;
+                eprintln!("The private key was written to {}", key.display());
+                private_key
+            };
+
+            let request = certificate::signing_request(&private_key)?.to_pem()?;
+            match output {
+                Some(path) => std::fs::write(path, request)?,
+                None => std::io::stdout().write_all(&request)?,
+            }
+        }
+        CertificateCommands::Fingerprint { cert } => {
+            let pem = std::fs::read(&cert)
+                .with_context(|| format!("could not read the certificate {}", cert.display()))?;
+            let cert = X509::from_pem(&pem)?;
+            println!("{}", certificate::fingerprint(&cert)?);
+        }
+    }
+    Ok(())
+}
+
+/// Manages the keys to sign the tokens.
+/// `subcommand`: keys subcommand.
+fn keys_command(subcommand: KeysCommands) -> anyhow::Result<()> {
+    let config = web::ServiceConfig::load()?;
+    let store = KeyStore::new(&config.jwt_keys_dir);
+
+    match subcommand {
+        KeysCommands::List => {
+            for key in store.list()? {
+                let mark = if key.current { "*" } else { " " };
+                println!("{} {} {:?}", mark, key.id, key.algorithm);
+            }
+        }
+        KeysCommands::Rotate { algorithm } => {
+            let id = store.rotate(algorithm.unwrap_or(config.jwt_algorithm))?;
+            println!("{}", id);
+        }
+        KeysCommands::Remove { id } => store.remove(&id)?,
+    }
+    Ok(())
+}
+
+fn write_token(path: &str, keys: &TokenKeys) -> anyhow::Result<()> {
+    let token = keys.ring.sign(&TokenClaims::default())?;
     Ok(token.write(path)?)
 }
 
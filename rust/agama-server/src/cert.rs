@@ -22,19 +22,48 @@ use anyhow;
 use gethostname::gethostname;
 use openssl::asn1::Asn1Time;
 use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
 use openssl::hash::MessageDigest;
-use openssl::pkey::{PKey, Private};
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, PKeyRef, Private};
 use openssl::rsa::Rsa;
+use openssl::stack::Stack;
 use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName, SubjectKeyIdentifier};
-use openssl::x509::{X509NameBuilder, X509};
+use openssl::x509::{X509Extension, X509Name, X509NameBuilder, X509Ref, X509Req, X509};
 use std::{
     fs,
     io::{self, Write},
+    net::IpAddr,
     os::unix::fs::OpenOptionsExt,
-    path::Path,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 
-const DEFAULT_CERT_DIR: &str = "/etc/agama.d/ssl";
+pub const DEFAULT_CERT_DIR: &str = "/etc/agama.d/ssl";
+
+/// Type of the key to generate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum KeyType {
+    /// 2048-bit RSA key.
+    #[default]
+    Rsa,
+    /// ECDSA key using the P-256 curve.
+    Ecdsa,
+}
+
+impl KeyType {
+    /// Generates a new private key.
+    pub fn generate(&self) -> anyhow::Result<PKey<Private>> {
+        let key = match self {
+            Self::Rsa => PKey::from_rsa(Rsa::generate(2048)?)?,
+            Self::Ecdsa => {
+                let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+                PKey::from_ec_key(EcKey::generate(&group)?)?
+            }
+        };
+        Ok(key)
+    }
+}
 
 /// Structure to handle and store certificate and private key which is later
 /// used for establishing HTTPS connection
@@ -46,16 +75,22 @@ pub struct Certificate {
 impl Certificate {
     /// Writes cert, key to (for now well known) location(s)
     pub fn write(&self) -> anyhow::Result<()> {
-        // check and create default dir if needed
-        if !Path::new(DEFAULT_CERT_DIR).is_dir() {
-            std::fs::create_dir_all(DEFAULT_CERT_DIR)?;
+        self.write_to(DEFAULT_CERT_DIR)
+    }
+
+    /// Writes cert, key to the given directory (as `cert.pem` and `key.pem`)
+    pub fn write_to<T: AsRef<Path>>(&self, dir: T) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        // check and create the dir if needed
+        if !dir.is_dir() {
+            std::fs::create_dir_all(dir)?;
         }
 
         if let Ok(bytes) = self.cert.to_pem() {
-            write_and_restrict(Path::new(DEFAULT_CERT_DIR).join("cert.pem"), &bytes)?;
+            write_and_restrict(dir.join("cert.pem"), &bytes)?;
         }
         if let Ok(bytes) = self.key.private_key_to_pem_pkcs8() {
-            write_and_restrict(Path::new(DEFAULT_CERT_DIR).join("key.pem"), &bytes)?;
+            write_and_restrict(dir.join("key.pem"), &bytes)?;
         }
 
         Ok(())
@@ -77,16 +112,16 @@ impl Certificate {
 
     /// Creates a self-signed certificate
     pub fn new() -> anyhow::Result<Self> {
-        let rsa = Rsa::generate(2048)?;
-        let key = PKey::from_rsa(rsa)?;
+        Self::generate(KeyType::default())
+    }
 
-        let hostname = gethostname()
-            .into_string()
-            .unwrap_or(String::from("localhost"));
-        let mut x509_name = X509NameBuilder::new()?;
-        x509_name.append_entry_by_text("O", "Agama")?;
-        x509_name.append_entry_by_text("CN", hostname.as_str())?;
-        let x509_name = x509_name.build();
+    /// Creates a self-signed certificate using the given type of key
+    ///
+    /// The certificate is valid for the host name, the default Agama names and all the local IP
+    /// addresses.
+    pub fn generate(key_type: KeyType) -> anyhow::Result<Self> {
+        let key = key_type.generate()?;
+        let x509_name = subject_name()?;
 
         let mut builder = X509::builder()?;
         builder.set_version(2)?;
@@ -107,17 +142,9 @@ impl Certificate {
 
         builder.append_extension(BasicConstraints::new().critical().ca().build()?)?;
 
-        builder.append_extension(
-            SubjectAlternativeName::new()
-                // use the default Agama host name
-                // TODO: use the gethostname crate and use the current real hostname
-                .dns("agama")
-                // use the default name for the mDNS/Avahi
-                // TODO: check which name is actually used by mDNS, to avoid
-                // conflicts it might actually use something like agama-2.local
-                .dns("agama.local")
-                .build(&builder.x509v3_context(None, None))?,
-        )?;
+        let subject_alt_name =
+            subject_alt_name(&local_addresses()).build(&builder.x509v3_context(None, None))?;
+        builder.append_extension(subject_alt_name)?;
 
         let subject_key_identifier =
             SubjectKeyIdentifier::new().build(&builder.x509v3_context(None, None))?;
@@ -128,6 +155,134 @@ impl Certificate {
 
         Ok(Certificate { cert, key })
     }
+
+    /// Returns the SHA-256 fingerprint of the certificate (e.g., "AB:CD:...")
+    pub fn fingerprint(&self) -> anyhow::Result<String> {
+        fingerprint(&self.cert)
+    }
+}
+
+/// Returns the SHA-256 fingerprint of the given certificate (e.g., "AB:CD:...")
+pub fn fingerprint(cert: &X509Ref) -> anyhow::Result<String> {
+    let digest = cert.digest(MessageDigest::sha256())?;
+    let hex: Vec<_> = digest.iter().map(|b| format!("{:02X}", b)).collect();
+    Ok(hex.join(":"))
+}
+
+/// Creates a certificate signing request (CSR) for the given key
+///
+/// The request includes the same subject and alternative names as the self-signed certificates,
+/// so the certificate can be signed by an internal CA instead.
+pub fn signing_request(key: &PKeyRef<Private>) -> anyhow::Result<X509Req> {
+    let mut builder = X509Req::builder()?;
+    builder.set_version(0)?;
+    let x509_name = subject_name()?;
+    builder.set_subject_name(&x509_name)?;
+    builder.set_pubkey(key)?;
+
+    let mut extensions: Stack<X509Extension> = Stack::new()?;
+    let subject_alt_name =
+        subject_alt_name(&local_addresses()).build(&builder.x509v3_context(None))?;
+    extensions.push(subject_alt_name)?;
+    builder.add_extensions(&extensions)?;
+
+    builder.sign(key, MessageDigest::sha256())?;
+    Ok(builder.build())
+}
+
+/// Watches the certificate and the key files, reading them again when they change.
+///
+/// It checks the modification time of the files at the given interval and calls `on_change`
+/// with the new certificate. If the new files cannot be read (e.g., only one of them was
+/// updated yet), it keeps trying.
+///
+/// * `cert`: path to the certificate.
+/// * `key`: path to the private key.
+/// * `interval`: time between checks.
+/// * `on_change`: function to call with the new certificate.
+pub async fn watch<F>(cert: PathBuf, key: PathBuf, interval: Duration, on_change: F)
+where
+    F: Fn(Certificate),
+{
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut last: (Option<SystemTime>, Option<SystemTime>) = (modified(&cert), modified(&key));
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let current = (modified(&cert), modified(&key));
+        if current == last {
+            continue;
+        }
+
+        match Certificate::read(&cert, &key) {
+            Ok(certificate) => {
+                tracing::info!("Reloading the SSL certificate from {}", cert.display());
+                on_change(certificate);
+                last = current;
+            }
+            Err(error) => tracing::warn!("Could not reload the SSL certificate: {}", error),
+        }
+    }
+}
+
+/// Builds the subject name ("O=Agama,CN=<hostname>")
+fn subject_name() -> anyhow::Result<X509Name> {
+    let hostname = gethostname()
+        .into_string()
+        .unwrap_or(String::from("localhost"));
+    let mut x509_name = X509NameBuilder::new()?;
+    x509_name.append_entry_by_text("O", "Agama")?;
+    x509_name.append_entry_by_text("CN", hostname.as_str())?;
+    Ok(x509_name.build())
+}
+
+/// Builds the subject alternative names extension
+///
+/// * `addresses`: IP addresses to include.
+fn subject_alt_name(addresses: &[IpAddr]) -> SubjectAlternativeName {
+    let mut names = SubjectAlternativeName::new();
+    if let Ok(hostname) = gethostname().into_string() {
+        names.dns(&hostname);
+    }
+    // use the default Agama host name
+    names.dns("agama");
+    // use the default name for the mDNS/Avahi
+    // TODO: check which name is actually used by mDNS, to avoid
+    // conflicts it might actually use something like agama-2.local
+    names.dns("agama.local");
+    names.dns("localhost");
+    for address in addresses {
+        names.ip(&address.to_string());
+    }
+    names
+}
+
+/// Returns the IP addresses of the local network interfaces
+///
+/// The IPv6 link-local addresses are skipped, as they cannot be used without the zone index.
+pub fn local_addresses() -> Vec<IpAddr> {
+    let Ok(interfaces) = nix::ifaddrs::getifaddrs() else {
+        tracing::warn!("Could not read the local IP addresses");
+        return vec![];
+    };
+
+    let mut addresses: Vec<IpAddr> = interfaces
+        .filter_map(|i| i.address)
+        .filter_map(|a| {
+            if let Some(ipv4) = a.as_sockaddr_in() {
+                Some(IpAddr::V4(ipv4.ip()))
+            } else {
+                a.as_sockaddr_in6().map(|ipv6| IpAddr::V6(ipv6.ip()))
+            }
+        })
+        .filter(|a| match a {
+            IpAddr::V6(ipv6) => (ipv6.segments()[0] & 0xffc0) != 0xfe80,
+            IpAddr::V4(_) => true,
+        })
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+    addresses
 }
 
 /// Writes buf into a file at path and sets the file permissions for the root only access
@@ -143,3 +298,52 @@ fn write_and_restrict<T: AsRef<Path>>(path: T, buf: &[u8]) -> io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{local_addresses, signing_request, Certificate, KeyType};
+    use openssl::pkey::Id;
+    use std::net::IpAddr;
+
+    #[test]
+    fn test_generate_ecdsa_certificate() {
+        let certificate = Certificate::generate(KeyType::Ecdsa).unwrap();
+        assert_eq!(certificate.key.id(), Id::EC);
+
+        let names = certificate.cert.subject_alt_names().unwrap();
+        assert!(names.iter().any(|n| n.dnsname() == Some("agama.local")));
+        let addresses: Vec<_> = names.iter().filter_map(|n| n.ipaddress()).collect();
+        assert_eq!(addresses.len(), local_addresses().len());
+
+        let fingerprint = certificate.fingerprint().unwrap();
+        assert_eq!(fingerprint.len(), 32 * 3 - 1);
+    }
+
+    #[test]
+    fn test_signing_request() {
+        let key = KeyType::Ecdsa.generate().unwrap();
+        let request = signing_request(&key).unwrap();
+        assert!(request.verify(&key).unwrap());
+        assert_eq!(request.extensions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let certificate = Certificate::generate(KeyType::Rsa).unwrap();
+        certificate.write_to(tmp_dir.path()).unwrap();
+
+        let read = Certificate::read(
+            tmp_dir.path().join("cert.pem"),
+            tmp_dir.path().join("key.pem"),
+        )
+        .unwrap();
+        assert_eq!(
+            read.fingerprint().unwrap(),
+            certificate.fingerprint().unwrap()
+        );
+        assert!(local_addresses()
+            .iter()
+            .all(|a| !matches!(a, IpAddr::V6(v6) if v6.segments()[0] == 0xfe80)));
+    }
+}
@@ -63,11 +63,12 @@ mod adapter;
 pub mod error;
 pub mod model;
 mod nm;
+pub mod nmstate;
 pub mod system;
 pub mod web;
 
 pub use action::Action;
 pub use adapter::{Adapter, NetworkAdapterError};
 pub use model::NetworkState;
-pub use nm::NetworkManagerAdapter;
+pub use nm::{KeyfileAdapter, NetworkManagerAdapter};
 pub use system::NetworkSystem;
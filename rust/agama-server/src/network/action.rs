@@ -23,7 +23,9 @@ use agama_lib::network::types::DeviceType;
 use tokio::sync::oneshot;
 use uuid::Uuid;
 
-use super::{error::NetworkStateError, model::GeneralState, NetworkAdapterError};
+use super::{
+    error::NetworkStateError, model::GeneralState, nmstate::NmstateState, NetworkAdapterError,
+};
 
 pub type Responder<T> = oneshot::Sender<T>;
 pub type ControllerConnection = (Connection, Vec<String>);
@@ -77,6 +79,11 @@ pub enum Action {
     RefreshScan(Responder<Result<(), NetworkAdapterError>>),
     /// Remove the connection with the given Uuid.
     RemoveConnection(String, Responder<Result<(), NetworkStateError>>),
+    /// Gets the network configuration in nmstate format and the IDs of the connections that
+    /// cannot be represented in that format
+    GetNmstate(Responder<(NmstateState, Vec<String>)>),
+    /// Applies a nmstate desired state to the network configuration
+    ApplyNmstate(Box<NmstateState>, Responder<Result<(), NetworkStateError>>),
     /// Apply the current configuration.
     Apply(Responder<Result<(), NetworkAdapterError>>),
 }
@@ -18,7 +18,7 @@
 // To contact SUSE LLC about this file by physical or electronic mail, you may
 // find current contact information at www.suse.com.
 
-use crate::network::{model::StateConfig, Action, NetworkState};
+use crate::network::{error::NetworkStateError, model::StateConfig, Action, NetworkState};
 use agama_lib::error::ServiceError;
 use async_trait::async_trait;
 use thiserror::Error;
@@ -34,6 +34,8 @@ pub enum NetworkAdapterError {
     Checkpoint(ServiceError), // only relevant for adapters that implement a checkpoint mechanism
     #[error("The network watcher cannot run: {0}")]
     Watcher(ServiceError),
+    #[error("Invalid network configuration: {0}")]
+    Validation(NetworkStateError),
 }
 
 /// A trait for the ability to read/write from/to a network service.
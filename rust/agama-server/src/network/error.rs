@@ -70,6 +70,28 @@ pub enum NetworkStateError {
     InvalidWirelessBand(String),
     #[error("Invalid bssid: '{0}'")]
     InvalidBssid(String),
+    #[error("Invalid VLAN protocol: '{0}'")]
+    InvalidVlanProtocol(String),
+    #[error("Invalid InfiniBand transport mode: '{0}'")]
+    InvalidInfinibandTransportMode(String),
+    #[error("Invalid TUN mode: '{0}'")]
+    InvalidTunMode(String),
+    #[error("Invalid route to '{0}': {1}")]
+    InvalidRoute(String, String),
+    #[error("Next hop '{0}' is not reachable from connection '{1}'")]
+    UnreachableNextHop(String, String),
+    #[error("Overlapping routes to '{0}' in connections '{1}' and '{2}'")]
+    OverlappingRoutes(String, String, String),
+    #[error("Invalid routing rule: {0}")]
+    InvalidRoutingRule(String),
+    #[error("Overlapping routing rules with priority {0} in connections '{1}' and '{2}'")]
+    OverlappingRoutingRules(u32, String, String),
+    #[error("Invalid WireGuard key: {0}")]
+    InvalidWireguardKey(String),
+    #[error("Invalid WireGuard endpoint: '{0}'")]
+    InvalidWireguardEndpoint(String),
+    #[error("Invalid nmstate document: {0}")]
+    InvalidNmstate(String),
 }
 
 impl From<NetworkStateError> for zbus::fdo::Error {
@@ -24,11 +24,14 @@
 //!   agnostic from the real network service (e.g., NetworkManager).
 use crate::network::error::NetworkStateError;
 use agama_lib::network::settings::{
-    BondSettings, IEEE8021XSettings, NetworkConnection, WirelessSettings,
+    BondSettings, BridgePortSettings, BridgeSettings, IEEE8021XSettings, InfinibandSettings,
+    NetworkConnection, RouteSettings, RoutingRuleSettings, TunSettings, VlanSettings,
+    WireguardPeerSettings, WireguardSettings, WirelessSettings,
 };
 use agama_lib::network::types::{BondMode, DeviceState, DeviceType, Status, SSID};
 use agama_lib::openapi::schemas;
-use cidr::IpInet;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use cidr::{IpCidr, IpInet};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, skip_serializing_none, DisplayFromStr};
 use std::{
@@ -222,7 +225,7 @@ impl NetworkState {
         controller: &Connection,
         ports: Vec<String>,
     ) -> Result<(), NetworkStateError> {
-        if let ConnectionConfig::Bond(_) = &controller.config {
+        if let ConnectionConfig::Bond(_) | ConnectionConfig::Bridge(_) = &controller.config {
             let mut controlled = vec![];
             for port in ports {
                 let connection = self
@@ -246,6 +249,80 @@ impl NetworkState {
             ))
         }
     }
+
+    /// Checks the routing configuration before writing it.
+    ///
+    /// It reports routes whose next hop is not reachable from the connection, routes defined
+    /// more than once (same destination, table and metric) and routing rules with the same
+    /// priority and selectors.
+    ///
+    /// Routes without an explicit metric are only compared within the same connection, as
+    /// NetworkManager assigns a different default metric to each device.
+    pub fn validate(&self) -> Result<(), NetworkStateError> {
+        // (destination, table, metric, connection ID when the metric is not set)
+        type RouteKey<'a> = (IpCidr, u32, Option<u32>, Option<&'a str>);
+        // (IPv6, priority, from, to)
+        type RuleKey = (bool, u32, Option<IpCidr>, Option<IpCidr>);
+
+        let mut routes: HashMap<RouteKey, &str> = HashMap::new();
+        let mut rules: HashMap<RuleKey, &str> = HashMap::new();
+
+        for conn in self.connections.iter().filter(|c| !c.is_removed()) {
+            let ip_config = &conn.ip_config;
+            for route in ip_config.routes4.iter().chain(&ip_config.routes6) {
+                let destination = route.destination.network();
+                if let Some(next_hop) = &route.next_hop {
+                    if next_hop.is_ipv4() != destination.is_ipv4() {
+                        return Err(NetworkStateError::InvalidRoute(
+                            destination.to_string(),
+                            format!("next hop '{next_hop}' belongs to a different IP family"),
+                        ));
+                    }
+                    if !ip_config.is_reachable(next_hop) {
+                        return Err(NetworkStateError::UnreachableNextHop(
+                            next_hop.to_string(),
+                            conn.id.to_string(),
+                        ));
+                    }
+                }
+
+                let owner = route.metric.is_none().then_some(conn.id.as_str());
+                let key = (
+                    destination,
+                    route.table.unwrap_or_default(),
+                    route.metric,
+                    owner,
+                );
+                if let Some(other) = routes.insert(key, &conn.id) {
+                    return Err(NetworkStateError::OverlappingRoutes(
+                        destination.to_string(),
+                        other.to_string(),
+                        conn.id.to_string(),
+                    ));
+                }
+            }
+
+            let rules4 = ip_config.routing_rules4.iter().map(|r| (false, r));
+            let rules6 = ip_config.routing_rules6.iter().map(|r| (true, r));
+            for (ipv6, rule) in rules4.chain(rules6) {
+                let key = (
+                    ipv6,
+                    rule.priority,
+                    rule.from.map(|n| n.network()),
+                    rule.to.map(|n| n.network()),
+                );
+                if let Some(other) = rules.insert(key, &conn.id) {
+                    return Err(NetworkStateError::OverlappingRoutingRules(
+                        rule.priority,
+                        other.to_string(),
+                        conn.id.to_string(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -450,6 +527,343 @@ mod tests {
             NetworkStateError::NotControllerConnection(_),
         ));
     }
+
+    #[test]
+    fn test_set_bridge_ports() {
+        let mut state = NetworkState::default();
+        let eth0 = Connection {
+            id: "eth0".to_string(),
+            ..Default::default()
+        };
+        let br0 = Connection {
+            id: "br0".to_string(),
+            config: ConnectionConfig::Bridge(Default::default()),
+            ..Default::default()
+        };
+        state.add_connection(eth0).unwrap();
+        state.add_connection(br0.clone()).unwrap();
+
+        state.set_ports(&br0, vec!["eth0".to_string()]).unwrap();
+        let eth0_found = state.get_connection("eth0").unwrap();
+        assert_eq!(eth0_found.controller, Some(br0.uuid));
+    }
+
+    #[test]
+    fn test_virtual_devices_from_settings() {
+        let vlan = NetworkConnection {
+            id: "bond0.10".to_string(),
+            vlan: Some(VlanSettings {
+                parent: "bond0".to_string(),
+                id: 10,
+                protocol: Some("802.1ad".to_string()),
+            }),
+            ..Default::default()
+        };
+        let conn = Connection::try_from(vlan).unwrap();
+        assert_eq!(
+            conn.config,
+            ConnectionConfig::Vlan(VlanConfig {
+                parent: "bond0".to_string(),
+                id: 10,
+                protocol: VlanProtocol::IEEE802_1ad
+            })
+        );
+        let settings = NetworkConnection::try_from(conn).unwrap();
+        assert_eq!(settings.device_type(), DeviceType::Vlan);
+        assert_eq!(settings.vlan.unwrap().protocol, Some("802.1ad".to_string()));
+
+        let bridge_port = NetworkConnection {
+            id: "eth0".to_string(),
+            bridge_port: Some(BridgePortSettings {
+                priority: Some(10),
+                path_cost: Some(100),
+            }),
+            ..Default::default()
+        };
+        let conn = Connection::try_from(bridge_port).unwrap();
+        assert_eq!(
+            conn.port_config,
+            PortConfig::Bridge(BridgePortConfig {
+                priority: Some(10),
+                path_cost: Some(100)
+            })
+        );
+
+        let tap = NetworkConnection {
+            id: "tap0".to_string(),
+            tun: Some(TunSettings {
+                mode: "tap".to_string(),
+                owner: Some("1000".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let conn = Connection::try_from(tap).unwrap();
+        assert!(matches!(
+            conn.config,
+            ConnectionConfig::Tun(TunConfig {
+                mode: TunMode::Tap,
+                ..
+            })
+        ));
+        let settings = NetworkConnection::try_from(conn).unwrap();
+        assert_eq!(settings.tun.unwrap().mode, "tap");
+
+        let infiniband = NetworkConnection {
+            id: "ib0".to_string(),
+            infiniband: Some(InfinibandSettings {
+                transport_mode: Some("bogus".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let error = Connection::try_from(infiniband).unwrap_err();
+        assert!(matches!(
+            error,
+            NetworkStateError::InvalidInfinibandTransportMode(_)
+        ));
+    }
+
+    #[test]
+    fn test_routes_from_settings() {
+        let settings = NetworkConnection {
+            id: "eth0".to_string(),
+            routes: vec![
+                RouteSettings {
+                    destination: "10.0.0.0/8".parse().unwrap(),
+                    next_hop: Some("192.168.1.254".parse().unwrap()),
+                    metric: Some(100),
+                    table: Some(100),
+                },
+                RouteSettings {
+                    destination: "2001:db8:1::/48".parse().unwrap(),
+                    next_hop: None,
+                    metric: None,
+                    table: None,
+                },
+            ],
+            routing_rules: vec![
+                RoutingRuleSettings {
+                    priority: 100,
+                    family: None,
+                    from: Some("192.168.1.0/24".parse().unwrap()),
+                    to: None,
+                    table: 100,
+                },
+                RoutingRuleSettings {
+                    priority: 100,
+                    family: Some("ipv6".to_string()),
+                    from: None,
+                    to: None,
+                    table: 200,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let conn = Connection::try_from(settings.clone()).unwrap();
+        let ip_config = &conn.ip_config;
+        assert_eq!(ip_config.routes4.len(), 1);
+        assert_eq!(ip_config.routes4[0].table, Some(100));
+        assert_eq!(ip_config.routes6.len(), 1);
+        assert_eq!(ip_config.routing_rules4.len(), 1);
+        assert_eq!(ip_config.routing_rules6[0].table, 200);
+
+        let new_settings = NetworkConnection::try_from(conn).unwrap();
+        assert_eq!(new_settings.routes, settings.routes);
+        assert_eq!(new_settings.routing_rules, settings.routing_rules);
+
+        let mixed = NetworkConnection {
+            id: "eth0".to_string(),
+            routing_rules: vec![RoutingRuleSettings {
+                priority: 100,
+                family: Some("ipv6".to_string()),
+                from: Some("192.168.1.0/24".parse().unwrap()),
+                to: None,
+                table: 100,
+            }],
+            ..Default::default()
+        };
+        let error = Connection::try_from(mixed).unwrap_err();
+        assert!(matches!(error, NetworkStateError::InvalidRoutingRule(_)));
+    }
+
+    #[test]
+    fn test_wireguard_from_settings() {
+        let private_key = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".to_string();
+        let public_key = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".to_string();
+        let peer = WireguardPeerSettings {
+            public_key: public_key.clone(),
+            allowed_ips: vec!["10.0.0.0/8".parse().unwrap()],
+            endpoint: Some("[2001:db8::1]:51820".to_string()),
+            persistent_keepalive: Some(25),
+            preshared_key: None,
+        };
+        let settings = NetworkConnection {
+            id: "wg0".to_string(),
+            wireguard: Some(WireguardSettings {
+                private_key: Some(private_key.clone()),
+                listen_port: Some(51820),
+                peers: vec![peer.clone()],
+            }),
+            ..Default::default()
+        };
+
+        let conn = Connection::try_from(settings.clone()).unwrap();
+        let ConnectionConfig::Wireguard(config) = &conn.config else {
+            panic!("Unexpected connection config: {:?}", conn.config);
+        };
+        assert_eq!(config.private_key, Some(private_key));
+        assert_eq!(config.peers[0].public_key, public_key);
+        let new_settings = NetworkConnection::try_from(conn).unwrap();
+        assert_eq!(new_settings.device_type(), DeviceType::Wireguard);
+        assert_eq!(new_settings.wireguard.unwrap().listen_port, Some(51820));
+
+        let mut invalid = settings.clone();
+        invalid.wireguard.as_mut().unwrap().private_key = Some("secret".to_string());
+        let error = Connection::try_from(invalid).unwrap_err();
+        assert!(matches!(error, NetworkStateError::InvalidWireguardKey(_)));
+        // the private key is not included in the error message
+        assert!(!error.to_string().contains("secret"));
+
+        let mut invalid = settings;
+        invalid.wireguard.as_mut().unwrap().peers[0].endpoint = Some("vpn.example.net".to_string());
+        let error = Connection::try_from(invalid).unwrap_err();
+        assert!(matches!(
+            error,
+            NetworkStateError::InvalidWireguardEndpoint(_)
+        ));
+    }
+
+    #[test]
+    fn test_wireguard_keep_secrets() {
+        let peer = WireguardPeer {
+            public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".to_string(),
+            preshared_key: Some("FpCyhws9cxwWoV4xELtfJvjJN+zQVRPISllRWgeopVE=".to_string()),
+            ..Default::default()
+        };
+        let original = WireguardConfig {
+            private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".to_string()),
+            listen_port: Some(51820),
+            peers: vec![peer.clone()],
+        };
+
+        let mut config = WireguardConfig {
+            peers: vec![WireguardPeer {
+                preshared_key: None,
+                ..peer
+            }],
+            ..Default::default()
+        };
+        config.keep_secrets(&original);
+        assert_eq!(config.private_key, original.private_key);
+        assert_eq!(config.peers, original.peers);
+        assert_eq!(config.listen_port, None);
+    }
+
+    fn static_connection(id: &str, address: &str, routes: Vec<IpRoute>) -> Connection {
+        let mut conn = Connection::new(id.to_string(), DeviceType::Ethernet);
+        conn.ip_config.method4 = Ipv4Method::Manual;
+        conn.ip_config.addresses = vec![address.parse().unwrap()];
+        conn.ip_config.routes4 = routes;
+        conn
+    }
+
+    fn route(destination: &str, next_hop: &str, metric: Option<u32>) -> IpRoute {
+        IpRoute {
+            destination: destination.parse().unwrap(),
+            next_hop: Some(next_hop.parse().unwrap()),
+            metric,
+            table: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_next_hops() {
+        let eth0 = static_connection(
+            "eth0",
+            "192.168.1.10/24",
+            vec![route("10.0.0.0/8", "192.168.1.254", None)],
+        );
+        let mut state = NetworkState::new(GeneralState::default(), vec![], vec![], vec![eth0]);
+        assert!(state.validate().is_ok());
+
+        let eth1 = static_connection(
+            "eth1",
+            "192.168.2.10/24",
+            vec![route("172.16.0.0/12", "192.168.1.254", None)],
+        );
+        state.add_connection(eth1).unwrap();
+        let error = state.validate().unwrap_err();
+        assert!(matches!(error, NetworkStateError::UnreachableNextHop(_, ref id) if id == "eth1"));
+
+        // the next hop cannot be checked when using DHCP
+        let eth1 = state.get_connection_mut("eth1").unwrap();
+        eth1.ip_config.method4 = Ipv4Method::Auto;
+        assert!(state.validate().is_ok());
+
+        let eth1 = state.get_connection_mut("eth1").unwrap();
+        eth1.ip_config.routes4 = vec![route("172.16.0.0/12", "2001:db8::1", None)];
+        let error = state.validate().unwrap_err();
+        assert!(matches!(error, NetworkStateError::InvalidRoute(_, _)));
+    }
+
+    #[test]
+    fn test_validate_overlapping_routes() {
+        let eth0 = static_connection(
+            "eth0",
+            "192.168.1.10/24",
+            vec![route("10.0.0.0/8", "192.168.1.254", None)],
+        );
+        let eth1 = static_connection(
+            "eth1",
+            "192.168.2.10/24",
+            vec![route("10.0.0.0/8", "192.168.2.254", None)],
+        );
+        let mut state =
+            NetworkState::new(GeneralState::default(), vec![], vec![], vec![eth0, eth1]);
+        assert!(state.validate().is_ok());
+
+        let eth0 = state.get_connection_mut("eth0").unwrap();
+        eth0.ip_config
+            .routes4
+            .push(route("10.1.0.1/8", "192.168.1.253", None));
+        let error = state.validate().unwrap_err();
+        assert!(matches!(
+            error,
+            NetworkStateError::OverlappingRoutes(_, _, _)
+        ));
+
+        let eth0 = state.get_connection_mut("eth0").unwrap();
+        eth0.ip_config.routes4 = vec![route("10.0.0.0/8", "192.168.1.254", Some(100))];
+        let eth1 = state.get_connection_mut("eth1").unwrap();
+        eth1.ip_config.routes4 = vec![route("10.0.0.0/8", "192.168.2.254", Some(100))];
+        let error = state.validate().unwrap_err();
+        assert!(matches!(
+            error,
+            NetworkStateError::OverlappingRoutes(_, ref a, ref b) if a == "eth0" && b == "eth1"
+        ));
+
+        let eth1 = state.get_connection_mut("eth1").unwrap();
+        eth1.ip_config.routes4[0].table = Some(100);
+        assert!(state.validate().is_ok());
+
+        let rule = RoutingRule {
+            priority: 100,
+            from: Some("192.168.2.0/24".parse().unwrap()),
+            to: None,
+            table: 100,
+        };
+        let eth0 = state.get_connection_mut("eth0").unwrap();
+        eth0.ip_config.routing_rules4 = vec![rule.clone()];
+        let eth1 = state.get_connection_mut("eth1").unwrap();
+        eth1.ip_config.routing_rules4 = vec![rule];
+        let error = state.validate().unwrap_err();
+        assert!(matches!(
+            error,
+            NetworkStateError::OverlappingRoutingRules(100, _, _)
+        ));
+    }
 }
 
 /// Network state
@@ -527,6 +941,9 @@ impl Connection {
             DeviceType::Bond => ConnectionConfig::Bond(Default::default()),
             DeviceType::Vlan => ConnectionConfig::Vlan(Default::default()),
             DeviceType::Bridge => ConnectionConfig::Bridge(Default::default()),
+            DeviceType::Infiniband => ConnectionConfig::Infiniband(Default::default()),
+            DeviceType::Tun => ConnectionConfig::Tun(Default::default()),
+            DeviceType::Wireguard => ConnectionConfig::Wireguard(Default::default()),
         };
         Self {
             id,
@@ -626,6 +1043,35 @@ impl TryFrom<NetworkConnection> for Connection {
             connection.config = config.into();
         }
 
+        if let Some(vlan_config) = conn.vlan {
+            let config = VlanConfig::try_from(vlan_config)?;
+            connection.config = config.into();
+        }
+
+        if let Some(bridge_config) = conn.bridge {
+            let config = BridgeConfig::try_from(bridge_config)?;
+            connection.config = config.into();
+        }
+
+        if let Some(infiniband_config) = conn.infiniband {
+            let config = InfinibandConfig::try_from(infiniband_config)?;
+            connection.config = config.into();
+        }
+
+        if let Some(tun_config) = conn.tun {
+            let config = TunConfig::try_from(tun_config)?;
+            connection.config = config.into();
+        }
+
+        if let Some(wireguard_config) = conn.wireguard {
+            let config = WireguardConfig::try_from(wireguard_config)?;
+            connection.config = config.into();
+        }
+
+        if let Some(bridge_port_config) = conn.bridge_port {
+            connection.port_config = PortConfig::Bridge(bridge_port_config.into());
+        }
+
         if let Some(ieee_8021x_config) = conn.ieee_8021x {
             connection.ieee_8021x_config = Some(IEEE8021XConfig::try_from(ieee_8021x_config)?);
         }
@@ -635,6 +1081,22 @@ impl TryFrom<NetworkConnection> for Connection {
         connection.ip_config.dns_searchlist = conn.dns_searchlist;
         connection.ip_config.gateway4 = conn.gateway4;
         connection.ip_config.gateway6 = conn.gateway6;
+        for route in conn.routes {
+            let route = IpRoute::from(route);
+            if route.destination.is_ipv4() {
+                connection.ip_config.routes4.push(route);
+            } else {
+                connection.ip_config.routes6.push(route);
+            }
+        }
+        for rule in conn.routing_rules {
+            let (rule, ipv6) = RoutingRule::from_settings(rule)?;
+            if ipv6 {
+                connection.ip_config.routing_rules6.push(rule);
+            } else {
+                connection.ip_config.routing_rules4.push(rule);
+            }
+        }
         connection.interface = conn.interface;
         connection.mtu = conn.mtu;
         connection.autoconnect = conn.autoconnect;
@@ -658,6 +1120,25 @@ impl TryFrom<Connection> for NetworkConnection {
         let addresses = conn.ip_config.addresses;
         let gateway4 = conn.ip_config.gateway4;
         let gateway6 = conn.ip_config.gateway6;
+        let routes = conn
+            .ip_config
+            .routes4
+            .into_iter()
+            .chain(conn.ip_config.routes6)
+            .map(RouteSettings::from)
+            .collect();
+        let routing_rules = conn
+            .ip_config
+            .routing_rules4
+            .iter()
+            .map(|rule| rule.to_settings(false))
+            .chain(
+                conn.ip_config
+                    .routing_rules6
+                    .iter()
+                    .map(|rule| rule.to_settings(true)),
+            )
+            .collect();
         let interface = conn.interface;
         let status = Some(conn.status);
         let mtu = conn.mtu;
@@ -676,6 +1157,8 @@ impl TryFrom<Connection> for NetworkConnection {
             nameservers,
             dns_searchlist,
             ignore_auto_dns,
+            routes,
+            routing_rules,
             mac_address,
             interface,
             addresses,
@@ -692,9 +1175,28 @@ impl TryFrom<Connection> for NetworkConnection {
             ConnectionConfig::Bond(config) => {
                 connection.bond = Some(BondSettings::try_from(config)?);
             }
+            ConnectionConfig::Vlan(config) => {
+                connection.vlan = Some(config.into());
+            }
+            ConnectionConfig::Bridge(config) => {
+                connection.bridge = Some(config.into());
+            }
+            ConnectionConfig::Infiniband(config) => {
+                connection.infiniband = Some(config.into());
+            }
+            ConnectionConfig::Tun(config) => {
+                connection.tun = Some(config.into());
+            }
+            ConnectionConfig::Wireguard(config) => {
+                connection.wireguard = Some(config.into());
+            }
             _ => {}
         }
 
+        if let PortConfig::Bridge(config) = conn.port_config {
+            connection.bridge_port = Some(config.into());
+        }
+
         Ok(connection)
     }
 }
@@ -711,6 +1213,7 @@ pub enum ConnectionConfig {
     Bridge(BridgeConfig),
     Infiniband(InfinibandConfig),
     Tun(TunConfig),
+    Wireguard(WireguardConfig),
 }
 
 #[derive(Default, Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
@@ -732,6 +1235,36 @@ impl From<WirelessConfig> for ConnectionConfig {
     }
 }
 
+impl From<VlanConfig> for ConnectionConfig {
+    fn from(value: VlanConfig) -> Self {
+        Self::Vlan(value)
+    }
+}
+
+impl From<BridgeConfig> for ConnectionConfig {
+    fn from(value: BridgeConfig) -> Self {
+        Self::Bridge(value)
+    }
+}
+
+impl From<InfinibandConfig> for ConnectionConfig {
+    fn from(value: InfinibandConfig) -> Self {
+        Self::Infiniband(value)
+    }
+}
+
+impl From<TunConfig> for ConnectionConfig {
+    fn from(value: TunConfig) -> Self {
+        Self::Tun(value)
+    }
+}
+
+impl From<WireguardConfig> for ConnectionConfig {
+    fn from(value: WireguardConfig) -> Self {
+        Self::Wireguard(value)
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("Invalid MAC address: {0}")]
 pub struct InvalidMacAddress(String);
@@ -820,6 +1353,39 @@ pub struct IpConfig {
     pub routes4: Vec<IpRoute>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub routes6: Vec<IpRoute>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub routing_rules4: Vec<RoutingRule>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub routing_rules6: Vec<RoutingRule>,
+}
+
+impl IpConfig {
+    /// Determines whether the next hop is reachable using the configured addresses.
+    ///
+    /// When the addresses are set automatically (e.g., using DHCP) it is not possible to know in
+    /// advance, so the next hop is considered reachable. The same applies to link-local next hops.
+    ///
+    /// * `next_hop`: next hop to check.
+    pub fn is_reachable(&self, next_hop: &IpAddr) -> bool {
+        let (is_static, is_link_local) = match next_hop {
+            IpAddr::V4(ip) => (
+                matches!(self.method4, Ipv4Method::Manual | Ipv4Method::Disabled),
+                ip.is_link_local(),
+            ),
+            IpAddr::V6(ip) => (
+                matches!(self.method6, Ipv6Method::Manual | Ipv6Method::Disabled),
+                (ip.segments()[0] & 0xffc0) == 0xfe80,
+            ),
+        };
+
+        if !is_static || is_link_local {
+            return true;
+        }
+
+        self.addresses
+            .iter()
+            .any(|address| address.network().contains(next_hop))
+    }
 }
 
 #[skip_serializing_none]
@@ -933,6 +1499,8 @@ pub struct IpRoute {
     pub next_hop: Option<IpAddr>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metric: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<u32>,
 }
 
 impl From<&IpRoute> for HashMap<&str, Value<'_>> {
@@ -950,10 +1518,109 @@ impl From<&IpRoute> for HashMap<&str, Value<'_>> {
         if let Some(metric) = route.metric {
             map.insert("metric", Value::new(metric));
         }
+        if let Some(table) = route.table {
+            map.insert("table", Value::new(table));
+        }
         map
     }
 }
 
+impl From<RouteSettings> for IpRoute {
+    fn from(settings: RouteSettings) -> Self {
+        IpRoute {
+            destination: settings.destination,
+            next_hop: settings.next_hop,
+            metric: settings.metric,
+            table: settings.table,
+        }
+    }
+}
+
+impl From<IpRoute> for RouteSettings {
+    fn from(route: IpRoute) -> Self {
+        RouteSettings {
+            destination: route.destination,
+            next_hop: route.next_hop,
+            metric: route.metric,
+            table: route.table,
+        }
+    }
+}
+
+/// Routing policy rule which selects the routing table to look up.
+///
+/// The IP family is not included because it is implied by the list the rule belongs to (see
+/// [IpConfig]).
+#[derive(Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingRule {
+    pub priority: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(schema_with = schemas::ip_inet_ref)]
+    pub from: Option<IpInet>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(schema_with = schemas::ip_inet_ref)]
+    pub to: Option<IpInet>,
+    pub table: u32,
+}
+
+impl RoutingRule {
+    /// Builds a rule from its settings representation.
+    ///
+    /// Returns the rule and whether it is an IPv6 one. The family is inferred from the source and
+    /// destination networks, using the `family` setting when they are not set (IPv4 by default).
+    ///
+    /// * `settings`: routing rule settings.
+    fn from_settings(settings: RoutingRuleSettings) -> Result<(Self, bool), NetworkStateError> {
+        let family = match settings.family.as_deref() {
+            None => None,
+            Some("ipv4") => Some(false),
+            Some("ipv6") => Some(true),
+            Some(family) => {
+                return Err(NetworkStateError::InvalidRoutingRule(format!(
+                    "unknown IP family '{family}'"
+                )))
+            }
+        };
+
+        let mut families = [settings.from, settings.to]
+            .into_iter()
+            .flatten()
+            .map(|network| network.is_ipv6())
+            .chain(family);
+        let ipv6 = families.next().unwrap_or_default();
+        if families.any(|f| f != ipv6) {
+            return Err(NetworkStateError::InvalidRoutingRule(format!(
+                "mixed IP families in the rule with priority {}",
+                settings.priority
+            )));
+        }
+
+        let rule = RoutingRule {
+            priority: settings.priority,
+            from: settings.from,
+            to: settings.to,
+            table: settings.table,
+        };
+        Ok((rule, ipv6))
+    }
+
+    /// Converts the rule into its settings representation.
+    ///
+    /// * `ipv6`: whether it is an IPv6 rule.
+    fn to_settings(&self, ipv6: bool) -> RoutingRuleSettings {
+        // the family is only needed when it cannot be inferred from the networks
+        let family = (ipv6 && self.from.is_none() && self.to.is_none()).then(|| "ipv6".to_string());
+        RoutingRuleSettings {
+            priority: self.priority,
+            family,
+            from: self.from,
+            to: self.to,
+            table: self.table,
+        }
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone, Serialize, utoipa::ToSchema)]
 pub enum VlanProtocol {
     #[default]
@@ -994,6 +1661,33 @@ pub struct VlanConfig {
     pub protocol: VlanProtocol,
 }
 
+impl TryFrom<VlanSettings> for VlanConfig {
+    type Error = NetworkStateError;
+
+    fn try_from(settings: VlanSettings) -> Result<Self, Self::Error> {
+        let mut config = VlanConfig {
+            parent: settings.parent,
+            id: settings.id,
+            ..Default::default()
+        };
+        if let Some(protocol) = settings.protocol {
+            config.protocol = VlanProtocol::from_str(&protocol)
+                .map_err(|_| NetworkStateError::InvalidVlanProtocol(protocol))?;
+        }
+        Ok(config)
+    }
+}
+
+impl From<VlanConfig> for VlanSettings {
+    fn from(vlan: VlanConfig) -> Self {
+        VlanSettings {
+            parent: vlan.parent,
+            id: vlan.id,
+            protocol: Some(vlan.protocol.to_string()),
+        }
+    }
+}
+
 #[serde_as]
 #[derive(Debug, Default, PartialEq, Clone, Serialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -1509,6 +2203,36 @@ pub struct BridgeConfig {
     pub ageing_time: Option<u32>,
 }
 
+impl TryFrom<BridgeSettings> for BridgeConfig {
+    type Error = NetworkStateError;
+
+    fn try_from(settings: BridgeSettings) -> Result<Self, Self::Error> {
+        Ok(BridgeConfig {
+            // NetworkManager enables STP by default
+            stp: settings.stp.unwrap_or(true),
+            priority: settings.priority,
+            forward_delay: settings.forward_delay,
+            hello_time: settings.hello_time,
+            max_age: settings.max_age,
+            ageing_time: settings.ageing_time,
+        })
+    }
+}
+
+impl From<BridgeConfig> for BridgeSettings {
+    fn from(bridge: BridgeConfig) -> Self {
+        BridgeSettings {
+            stp: Some(bridge.stp),
+            priority: bridge.priority,
+            forward_delay: bridge.forward_delay,
+            hello_time: bridge.hello_time,
+            max_age: bridge.max_age,
+            ageing_time: bridge.ageing_time,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone, Serialize, utoipa::ToSchema)]
 pub struct BridgePortConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -1517,6 +2241,24 @@ pub struct BridgePortConfig {
     pub path_cost: Option<u32>,
 }
 
+impl From<BridgePortSettings> for BridgePortConfig {
+    fn from(settings: BridgePortSettings) -> Self {
+        BridgePortConfig {
+            priority: settings.priority,
+            path_cost: settings.path_cost,
+        }
+    }
+}
+
+impl From<BridgePortConfig> for BridgePortSettings {
+    fn from(port: BridgePortConfig) -> Self {
+        BridgePortSettings {
+            priority: port.priority,
+            path_cost: port.path_cost,
+        }
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
 pub struct InfinibandConfig {
     pub p_key: Option<i32>,
@@ -1524,6 +2266,33 @@ pub struct InfinibandConfig {
     pub transport_mode: InfinibandTransportMode,
 }
 
+impl TryFrom<InfinibandSettings> for InfinibandConfig {
+    type Error = NetworkStateError;
+
+    fn try_from(settings: InfinibandSettings) -> Result<Self, Self::Error> {
+        let mut config = InfinibandConfig {
+            p_key: settings.p_key,
+            parent: settings.parent,
+            ..Default::default()
+        };
+        if let Some(mode) = settings.transport_mode {
+            config.transport_mode = InfinibandTransportMode::from_str(&mode)
+                .map_err(|_| NetworkStateError::InvalidInfinibandTransportMode(mode))?;
+        }
+        Ok(config)
+    }
+}
+
+impl From<InfinibandConfig> for InfinibandSettings {
+    fn from(infiniband: InfinibandConfig) -> Self {
+        InfinibandSettings {
+            p_key: infiniband.p_key,
+            parent: infiniband.parent,
+            transport_mode: Some(infiniband.transport_mode.to_string()),
+        }
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
 pub enum InfinibandTransportMode {
     #[default]
@@ -1564,6 +2333,28 @@ pub enum TunMode {
     Tap = 2,
 }
 
+impl FromStr for TunMode {
+    type Err = NetworkStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tun" => Ok(Self::Tun),
+            "tap" => Ok(Self::Tap),
+            _ => Err(NetworkStateError::InvalidTunMode(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TunMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match &self {
+            TunMode::Tun => "tun",
+            TunMode::Tap => "tap",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
 pub struct TunConfig {
     pub mode: TunMode,
@@ -1571,6 +2362,179 @@ pub struct TunConfig {
     pub owner: Option<String>,
 }
 
+impl TryFrom<TunSettings> for TunConfig {
+    type Error = NetworkStateError;
+
+    fn try_from(settings: TunSettings) -> Result<Self, Self::Error> {
+        Ok(TunConfig {
+            mode: TunMode::from_str(&settings.mode)?,
+            group: settings.group,
+            owner: settings.owner,
+        })
+    }
+}
+
+impl From<TunConfig> for TunSettings {
+    fn from(tun: TunConfig) -> Self {
+        TunSettings {
+            mode: tun.mode.to_string(),
+            group: tun.group,
+            owner: tun.owner,
+        }
+    }
+}
+
+const WIREGUARD_KEY_LENGTH: usize = 32;
+
+#[derive(Default, Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WireguardConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_port: Option<u16>,
+    pub peers: Vec<WireguardPeer>,
+}
+
+impl WireguardConfig {
+    /// Copies the secrets (private and pre-shared keys) which are not set from another config.
+    ///
+    /// NetworkManager does not expose the secrets, so they are lost when a connection is updated
+    /// using the settings read from the system.
+    ///
+    /// * `other`: configuration to copy the secrets from.
+    pub fn keep_secrets(&mut self, other: &WireguardConfig) {
+        if self.private_key.is_none() {
+            self.private_key.clone_from(&other.private_key);
+        }
+
+        for peer in self.peers.iter_mut().filter(|p| p.preshared_key.is_none()) {
+            if let Some(other_peer) = other.peers.iter().find(|p| p.public_key == peer.public_key) {
+                peer.preshared_key.clone_from(&other_peer.preshared_key);
+            }
+        }
+    }
+}
+
+#[derive(Default, Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WireguardPeer {
+    pub public_key: String,
+    #[schema(schema_with = schemas::ip_inet_array)]
+    pub allowed_ips: Vec<IpInet>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistent_keepalive: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preshared_key: Option<String>,
+}
+
+/// Determines whether the value is a WireGuard key (32 bytes encoded in base64).
+fn is_wireguard_key(value: &str) -> bool {
+    STANDARD
+        .decode(value)
+        .is_ok_and(|key| key.len() == WIREGUARD_KEY_LENGTH)
+}
+
+/// Determines whether the value is a valid WireGuard endpoint (`host:port` or `[ipv6]:port`).
+fn is_wireguard_endpoint(value: &str) -> bool {
+    let Some((host, port)) = value.rsplit_once(':') else {
+        return false;
+    };
+    !host.is_empty() && port.parse::<u16>().is_ok()
+}
+
+impl TryFrom<WireguardSettings> for WireguardConfig {
+    type Error = NetworkStateError;
+
+    fn try_from(settings: WireguardSettings) -> Result<Self, Self::Error> {
+        if settings
+            .private_key
+            .as_deref()
+            .is_some_and(|k| !is_wireguard_key(k))
+        {
+            return Err(NetworkStateError::InvalidWireguardKey(
+                "private key".to_string(),
+            ));
+        }
+
+        let peers = settings
+            .peers
+            .into_iter()
+            .map(WireguardPeer::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(WireguardConfig {
+            private_key: settings.private_key,
+            listen_port: settings.listen_port,
+            peers,
+        })
+    }
+}
+
+impl From<WireguardConfig> for WireguardSettings {
+    fn from(wireguard: WireguardConfig) -> Self {
+        WireguardSettings {
+            private_key: wireguard.private_key,
+            listen_port: wireguard.listen_port,
+            peers: wireguard.peers.into_iter().map(|p| p.into()).collect(),
+        }
+    }
+}
+
+impl TryFrom<WireguardPeerSettings> for WireguardPeer {
+    type Error = NetworkStateError;
+
+    fn try_from(settings: WireguardPeerSettings) -> Result<Self, Self::Error> {
+        if !is_wireguard_key(&settings.public_key) {
+            return Err(NetworkStateError::InvalidWireguardKey(format!(
+                "public key '{}'",
+                settings.public_key
+            )));
+        }
+
+        if settings
+            .preshared_key
+            .as_deref()
+            .is_some_and(|k| !is_wireguard_key(k))
+        {
+            return Err(NetworkStateError::InvalidWireguardKey(format!(
+                "pre-shared key of peer '{}'",
+                settings.public_key
+            )));
+        }
+
+        if let Some(endpoint) = &settings.endpoint {
+            if !is_wireguard_endpoint(endpoint) {
+                return Err(NetworkStateError::InvalidWireguardEndpoint(
+                    endpoint.to_string(),
+                ));
+            }
+        }
+
+        Ok(WireguardPeer {
+            public_key: settings.public_key,
+            allowed_ips: settings.allowed_ips,
+            endpoint: settings.endpoint,
+            persistent_keepalive: settings.persistent_keepalive,
+            preshared_key: settings.preshared_key,
+        })
+    }
+}
+
+impl From<WireguardPeer> for WireguardPeerSettings {
+    fn from(peer: WireguardPeer) -> Self {
+        WireguardPeerSettings {
+            public_key: peer.public_key,
+            allowed_ips: peer.allowed_ips,
+            endpoint: peer.endpoint,
+            persistent_keepalive: peer.persistent_keepalive,
+            preshared_key: peer.preshared_key,
+        }
+    }
+}
+
 /// Represents a network change.
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -23,16 +23,22 @@
 //! This module defines [a NetworkManager client](client::NetworkManagerClient) and a set of
 //! structs and enums to work with NetworkManager configuration. It is intended to be used
 //! internally, so the API is focused on Agama's use cases.
+//!
+//! Additionally, it offers [an adapter](KeyfileAdapter) that works directly on the
+//! NetworkManager keyfiles, so it does not need NetworkManager to be running.
 
 mod adapter;
 mod builder;
 mod client;
 mod dbus;
 mod error;
+mod keyfile;
+mod keyfile_adapter;
 mod model;
 mod proxies;
 mod watcher;
 
 pub use adapter::NetworkManagerAdapter;
 pub use client::NetworkManagerClient;
+pub use keyfile_adapter::KeyfileAdapter;
 pub use watcher::NetworkManagerWatcher;
@@ -216,6 +216,7 @@ impl<'a> DeviceFromProxyBuilder<'a> {
             destination,
             next_hop: None,
             metric: None,
+            table: None,
         };
 
         if let Some(next_hop) = route_data.get("next-hop") {
@@ -226,6 +227,10 @@ impl<'a> DeviceFromProxyBuilder<'a> {
             let metric: u32 = metric.downcast_ref().ok()?;
             new_route.metric = Some(metric);
         }
+        if let Some(table) = route_data.get("table") {
+            let table: u32 = table.downcast_ref().ok()?;
+            new_route.table = Some(table);
+        }
 
         Some(new_route)
     }
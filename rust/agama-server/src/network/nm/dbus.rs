@@ -45,7 +45,11 @@ const BRIDGE_KEY: &str = "bridge";
 const BRIDGE_PORT_KEY: &str = "bridge-port";
 const INFINIBAND_KEY: &str = "infiniband";
 const TUN_KEY: &str = "tun";
+const WIREGUARD_KEY: &str = "wireguard";
 const IEEE_8021X_KEY: &str = "802-1x";
+// Address families used by the routing rules.
+const AF_INET: i32 = 2;
+const AF_INET6: i32 = 10;
 
 /// Converts a connection struct into a HashMap that can be sent over D-Bus.
 ///
@@ -149,6 +153,15 @@ pub fn connection_to_dbus<'a>(
             connection_dbus.insert("type", TUN_KEY.into());
             result.insert(TUN_KEY, tun_config_to_dbus(tun));
         }
+        ConnectionConfig::Wireguard(wireguard) => {
+            connection_dbus.insert("type", WIREGUARD_KEY.into());
+            if !connection_dbus.contains_key("interface-name") {
+                connection_dbus.insert("interface-name", conn.id.as_str().into());
+            }
+            let mut wireguard_dbus = wireguard_config_to_dbus(wireguard);
+            wireguard_dbus.insert("mtu", Value::new(conn.mtu));
+            result.insert(WIREGUARD_KEY, wireguard_dbus);
+        }
         _ => {}
     }
 
@@ -211,6 +224,14 @@ pub fn connection_from_dbus(conn: OwnedNestedHash) -> Result<Connection, NmError
         return Ok(connection);
     }
 
+    if let Some(wireguard_config) = wireguard_config_from_dbus(&conn)? {
+        if let Some(mtu) = get_optional_property(&conn[WIREGUARD_KEY], "mtu")? {
+            connection.mtu = mtu;
+        }
+        connection.config = ConnectionConfig::Wireguard(wireguard_config);
+        return Ok(connection);
+    }
+
     if conn.contains_key(DUMMY_KEY) {
         connection.config = ConnectionConfig::Dummy;
         return Ok(connection);
@@ -344,6 +365,18 @@ fn ip_config_to_ipv4_dbus(ip_config: &IpConfig) -> HashMap<&str, zvariant::Value
         );
     }
 
+    if !ip_config.routing_rules4.is_empty() {
+        ipv4_dbus.insert(
+            "routing-rules",
+            ip_config
+                .routing_rules4
+                .iter()
+                .map(|rule| routing_rule_to_dbus(rule, AF_INET))
+                .collect::<Vec<HashMap<&str, Value>>>()
+                .into(),
+        );
+    }
+
     if let Some(gateway) = &ip_config.gateway4 {
         ipv4_dbus.insert("gateway", gateway.to_string().into());
     }
@@ -392,12 +425,47 @@ fn ip_config_to_ipv6_dbus(ip_config: &IpConfig) -> HashMap<&str, zvariant::Value
         );
     }
 
+    if !ip_config.routing_rules6.is_empty() {
+        ipv6_dbus.insert(
+            "routing-rules",
+            ip_config
+                .routing_rules6
+                .iter()
+                .map(|rule| routing_rule_to_dbus(rule, AF_INET6))
+                .collect::<Vec<HashMap<&str, Value>>>()
+                .into(),
+        );
+    }
+
     if let Some(gateway) = &ip_config.gateway6 {
         ipv6_dbus.insert("gateway", gateway.to_string().into());
     }
     ipv6_dbus
 }
 
+/// Converts a routing rule into a HashMap that can be sent over D-Bus.
+///
+/// * `rule`: routing rule to convert.
+/// * `family`: address family of the rule (`AF_INET` or `AF_INET6`).
+fn routing_rule_to_dbus(rule: &RoutingRule, family: i32) -> HashMap<&str, Value<'_>> {
+    let mut rule_dbus = HashMap::from([
+        ("family", Value::new(family)),
+        ("priority", Value::new(rule.priority)),
+        ("table", Value::new(rule.table)),
+    ]);
+
+    if let Some(from) = &rule.from {
+        rule_dbus.insert("from", Value::new(from.address().to_string()));
+        rule_dbus.insert("from-len", Value::new(from.network_length()));
+    }
+
+    if let Some(to) = &rule.to {
+        rule_dbus.insert("to", Value::new(to.address().to_string()));
+        rule_dbus.insert("to-len", Value::new(to.network_length()));
+    }
+    rule_dbus
+}
+
 fn wireless_config_to_dbus(config: &'_ WirelessConfig) -> NestedHash<'_> {
     let mut wireless: HashMap<&str, zvariant::Value> = HashMap::from([
         ("mode", Value::new(config.mode.to_string())),
@@ -543,7 +611,7 @@ fn bridge_port_config_from_dbus(
 
     Ok(Some(BridgePortConfig {
         priority: get_optional_property(bridge_port, "priority")?,
-        path_cost: get_optional_property(bridge_port, "path_cost")?,
+        path_cost: get_optional_property(bridge_port, "path-cost")?,
     }))
 }
 
@@ -615,6 +683,89 @@ fn tun_config_from_dbus(conn: &OwnedNestedHash) -> Result<Option<TunConfig>, NmE
     }))
 }
 
+fn wireguard_config_to_dbus(config: &WireguardConfig) -> HashMap<&str, zvariant::Value<'_>> {
+    let peers: Vec<HashMap<&str, Value>> = config
+        .peers
+        .iter()
+        .map(|peer| {
+            let allowed_ips: Vec<String> = peer
+                .allowed_ips
+                .iter()
+                .map(|ip| format!("{}/{}", ip.address(), ip.network_length()))
+                .collect();
+            let mut peer_dbus = HashMap::from([
+                ("public-key", Value::new(peer.public_key.as_str())),
+                ("allowed-ips", Value::new(allowed_ips)),
+            ]);
+            if let Some(endpoint) = &peer.endpoint {
+                peer_dbus.insert("endpoint", endpoint.into());
+            }
+            if let Some(keepalive) = peer.persistent_keepalive {
+                peer_dbus.insert("persistent-keepalive", keepalive.into());
+            }
+            if let Some(preshared_key) = &peer.preshared_key {
+                peer_dbus.insert("preshared-key", preshared_key.into());
+                peer_dbus.insert("preshared-key-flags", 0_u32.into());
+            }
+            peer_dbus
+        })
+        .collect();
+
+    let mut wireguard_config: HashMap<&str, zvariant::Value> =
+        HashMap::from([("peers", Value::new(peers))]);
+
+    if let Some(private_key) = &config.private_key {
+        wireguard_config.insert("private-key", private_key.into());
+        wireguard_config.insert("private-key-flags", 0_u32.into());
+    }
+
+    if let Some(listen_port) = config.listen_port {
+        wireguard_config.insert("listen-port", (listen_port as u32).into());
+    }
+
+    wireguard_config
+}
+
+fn wireguard_config_from_dbus(conn: &OwnedNestedHash) -> Result<Option<WireguardConfig>, NmError> {
+    let Some(wireguard) = conn.get(WIREGUARD_KEY) else {
+        return Ok(None);
+    };
+
+    let mut peers = vec![];
+    if let Some(peers_data) = wireguard.get("peers") {
+        let peers_data = peers_data.downcast_ref::<zbus::zvariant::Array>()?;
+        for peer in peers_data.iter() {
+            let dict = peer.downcast_ref::<zvariant::Dict>()?;
+            let map = <HashMap<String, zvariant::Value<'_>>>::try_from(dict)?;
+            let map = to_owned_hash(&map)?;
+            let allowed_ips: Vec<String> =
+                get_optional_property(&map, "allowed-ips")?.unwrap_or_default();
+            let allowed_ips = allowed_ips
+                .iter()
+                .map(|ip| ip.parse())
+                .collect::<Result<Vec<IpInet>, _>>()?;
+            peers.push(WireguardPeer {
+                public_key: get_property(&map, "public-key")?,
+                allowed_ips,
+                endpoint: get_optional_property(&map, "endpoint")?,
+                persistent_keepalive: get_optional_property::<u32>(&map, "persistent-keepalive")?
+                    .filter(|k| *k != 0),
+                preshared_key: get_optional_property(&map, "preshared-key")?,
+            });
+        }
+    }
+
+    let listen_port = get_optional_property::<u32>(wireguard, "listen-port")?
+        .filter(|p| *p != 0)
+        .map(|p| p as u16);
+
+    Ok(Some(WireguardConfig {
+        private_key: get_optional_property(wireguard, "private-key")?,
+        listen_port,
+        peers,
+    }))
+}
+
 /// Converts a MatchConfig struct into a HashMap that can be sent over D-Bus.
 ///
 /// * `match_config`: MatchConfig to convert.
@@ -759,6 +910,10 @@ fn ip_config_from_dbus(conn: &OwnedNestedHash) -> Result<IpConfig, NmError> {
             ip_config.routes4 = routes_from_dbus(route_data)?;
         }
 
+        if let Some(rules_data) = ipv4.get("routing-rules") {
+            ip_config.routing_rules4 = routing_rules_from_dbus(rules_data)?;
+        }
+
         if let Ok(gateway) = get_property::<String>(ipv4, "gateway") {
             ip_config.gateway4 = gateway.parse().ok();
         }
@@ -798,6 +953,10 @@ fn ip_config_from_dbus(conn: &OwnedNestedHash) -> Result<IpConfig, NmError> {
             ip_config.routes6 = routes_from_dbus(route_data)?;
         }
 
+        if let Some(rules_data) = ipv6.get("routing-rules") {
+            ip_config.routing_rules6 = routing_rules_from_dbus(rules_data)?;
+        }
+
         if let Ok(gateway) = get_property::<String>(ipv6, "gateway") {
             ip_config.gateway6 = gateway.parse().ok();
         }
@@ -836,16 +995,63 @@ fn routes_from_dbus(route_data: &OwnedValue) -> Result<Vec<IpRoute>, NmError> {
             destination,
             next_hop: None,
             metric: None,
+            table: None,
         };
         if let Some(next_hop) = get_optional_property::<String>(&map, "next-hop")? {
             new_route.next_hop = Some(IpAddr::from_str(next_hop.as_str()).unwrap());
         }
         new_route.metric = get_optional_property(&map, "metric")?;
+        new_route.table = get_optional_property(&map, "table")?;
         routes.push(new_route)
     }
     Ok(routes)
 }
 
+fn routing_rules_from_dbus(rules_data: &OwnedValue) -> Result<Vec<RoutingRule>, NmError> {
+    let rules_data = rules_data.downcast_ref::<zbus::zvariant::Array>()?;
+    let mut rules: Vec<RoutingRule> = vec![];
+    for rule in rules_data.iter() {
+        let dict = rule.downcast_ref::<zvariant::Dict>()?;
+        let map = <HashMap<String, zvariant::Value<'_>>>::try_from(dict)?;
+        let map = to_owned_hash(&map)?;
+
+        // only the rules that look up a routing table ("to-table" action) are supported
+        let action: Option<u8> = get_optional_property(&map, "action")?;
+        let table: Option<u32> = get_optional_property(&map, "table")?;
+        let (None | Some(1), Some(table)) = (action, table) else {
+            log::warn!("Ignoring unsupported routing rule: {:?}", map);
+            continue;
+        };
+
+        rules.push(RoutingRule {
+            priority: get_property(&map, "priority")?,
+            from: network_from_dbus(&map, "from", "from-len")?,
+            to: network_from_dbus(&map, "to", "to-len")?,
+            table,
+        });
+    }
+    Ok(rules)
+}
+
+/// Returns the network defined by an address and a prefix length, if any.
+///
+/// * `map`: D-Bus dictionary containing the properties.
+/// * `address_key`: name of the address property.
+/// * `length_key`: name of the prefix length property.
+fn network_from_dbus(
+    map: &HashMap<String, OwnedValue>,
+    address_key: &str,
+    length_key: &str,
+) -> Result<Option<IpInet>, NmError> {
+    let Some(address) = get_optional_property::<String>(map, address_key)? else {
+        return Ok(None);
+    };
+    let address: IpAddr = address.parse()?;
+    let max_length = if address.is_ipv4() { 32 } else { 128 };
+    let length: u8 = get_optional_property(map, length_key)?.unwrap_or(max_length);
+    Ok(Some(IpInet::new(address, length)?))
+}
+
 fn nameservers_from_dbus(dns_data: &OwnedValue) -> Result<Vec<IpAddr>, NmError> {
     let dns_data = dns_data.downcast_ref::<zbus::zvariant::Array>()?;
     let mut servers: Vec<IpAddr> = vec![];
@@ -1196,7 +1402,10 @@ mod test {
     use crate::network::{
         model::*,
         nm::{
-            dbus::{BOND_KEY, ETHERNET_KEY, INFINIBAND_KEY, WIRELESS_KEY, WIRELESS_SECURITY_KEY},
+            dbus::{
+                BOND_KEY, ETHERNET_KEY, INFINIBAND_KEY, WIREGUARD_KEY, WIRELESS_KEY,
+                WIRELESS_SECURITY_KEY,
+            },
             error::NmError,
         },
     };
@@ -1233,8 +1442,25 @@ mod test {
             ("prefix".to_string(), Value::new(24_u32)),
             ("next-hop".to_string(), Value::new("192.168.0.1")),
             ("metric".to_string(), Value::new(100_u32)),
+            ("table".to_string(), Value::new(100_u32)),
         ])];
 
+        let rules_v4_data = vec![
+            HashMap::from([
+                hi("family", 2_i32)?,
+                hi("priority", 100_u32)?,
+                hi("from", "192.168.0.0")?,
+                hi("from-len", 24_u8)?,
+                hi("table", 100_u32)?,
+            ]),
+            // unsupported action ("blackhole")
+            HashMap::from([
+                hi("family", 2_i32)?,
+                hi("priority", 200_u32)?,
+                hi("action", 6_u8)?,
+            ]),
+        ];
+
         let ipv4_section = HashMap::from([
             hi("method", "auto")?,
             hi("address-data", address_v4_data)?,
@@ -1243,6 +1469,7 @@ mod test {
             hi("dns-search", vec!["suse.com", "example.com"])?,
             hi("ignore-auto-dns", true)?,
             hi("route-data", route_v4_data)?,
+            hi("routing-rules", rules_v4_data)?,
         ]);
 
         let address_v6_data = vec![HashMap::from([
@@ -1315,7 +1542,17 @@ mod test {
             vec![IpRoute {
                 destination: IpInet::new("192.168.0.0".parse().unwrap(), 24_u8).unwrap(),
                 next_hop: Some(IpAddr::from_str("192.168.0.1").unwrap()),
-                metric: Some(100)
+                metric: Some(100),
+                table: Some(100)
+            }]
+        );
+        assert_eq!(
+            ip_config.routing_rules4,
+            vec![RoutingRule {
+                priority: 100,
+                from: Some(IpInet::new("192.168.0.0".parse().unwrap(), 24_u8).unwrap()),
+                to: None,
+                table: 100
             }]
         );
         assert_eq!(
@@ -1323,7 +1560,8 @@ mod test {
             vec![IpRoute {
                 destination: IpInet::new("2001:db8::".parse().unwrap(), 64_u8).unwrap(),
                 next_hop: Some(IpAddr::from_str("2001:db8::1").unwrap()),
-                metric: Some(100)
+                metric: Some(100),
+                table: None
             }]
         );
         assert!(!connection.autoconnect);
@@ -1453,6 +1691,55 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_connection_from_dbus_wireguard() -> anyhow::Result<()> {
+        let uuid = Uuid::new_v4().to_string();
+        let connection_section = HashMap::from([hi("id", "wg0")?, hi("uuid", uuid)?]);
+
+        let peers = vec![HashMap::from([
+            (
+                "public-key".to_string(),
+                Value::new("xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg="),
+            ),
+            (
+                "allowed-ips".to_string(),
+                Value::new(vec!["10.0.0.0/8", "fd00::/64"]),
+            ),
+            ("endpoint".to_string(), Value::new("vpn.example.net:51820")),
+            ("persistent-keepalive".to_string(), Value::new(25_u32)),
+        ])];
+        let wireguard_section = HashMap::from([
+            hi("listen-port", 51820_u32)?,
+            hi("mtu", 1420_u32)?,
+            hi("peers", peers)?,
+        ]);
+
+        let dbus_conn = HashMap::from([
+            ("connection".to_string(), connection_section),
+            (WIREGUARD_KEY.to_string(), wireguard_section),
+        ]);
+
+        let connection = connection_from_dbus(dbus_conn).unwrap();
+        assert_eq!(connection.mtu, 1420);
+        let ConnectionConfig::Wireguard(wireguard) = &connection.config else {
+            panic!("Wrong connection type")
+        };
+        assert_eq!(wireguard.private_key, None);
+        assert_eq!(wireguard.listen_port, Some(51820));
+        assert_eq!(
+            wireguard.peers,
+            vec![WireguardPeer {
+                public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".to_string(),
+                allowed_ips: vec!["10.0.0.0/8".parse()?, "fd00::/64".parse()?],
+                endpoint: Some("vpn.example.net:51820".to_string()),
+                persistent_keepalive: Some(25),
+                preshared_key: None,
+            }]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_connection_from_dbus_ieee_8021x() -> anyhow::Result<()> {
         let connection_section = HashMap::from([
@@ -1547,6 +1834,61 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_dbus_from_wireguard_connection() -> anyhow::Result<()> {
+        let config = WireguardConfig {
+            private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".to_string()),
+            listen_port: Some(51820),
+            peers: vec![WireguardPeer {
+                public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".to_string(),
+                allowed_ips: vec!["0.0.0.0/0".parse()?],
+                endpoint: Some("192.0.2.1:51820".to_string()),
+                persistent_keepalive: None,
+                preshared_key: None,
+            }],
+        };
+        let wireguard = Connection {
+            id: "wg0".to_string(),
+            config: ConnectionConfig::Wireguard(config),
+            ..Default::default()
+        };
+        let wireguard_dbus = connection_to_dbus(&wireguard, None);
+
+        let connection = wireguard_dbus.get("connection").unwrap();
+        let type_: &str = connection.get("type").unwrap().downcast_ref()?;
+        assert_eq!(type_, "wireguard");
+        let interface: &str = connection.get("interface-name").unwrap().downcast_ref()?;
+        assert_eq!(interface, "wg0");
+        assert!(!wireguard_dbus.contains_key(ETHERNET_KEY));
+
+        let wireguard = wireguard_dbus.get(WIREGUARD_KEY).unwrap();
+        let private_key: &str = wireguard.get("private-key").unwrap().downcast_ref()?;
+        assert_eq!(private_key, "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=");
+        let listen_port = wireguard
+            .get("listen-port")
+            .unwrap()
+            .downcast_ref::<u32>()?;
+        assert_eq!(listen_port, 51820);
+
+        let peers: Array = wireguard
+            .get("peers")
+            .unwrap()
+            .downcast_ref::<Value>()?
+            .try_into()?;
+        assert_eq!(peers.len(), 1);
+        let peer: Dict = peers.get(0)?.unwrap();
+        let peer: HashMap<String, Value> = peer.try_into()?;
+        assert_eq!(
+            peer["public-key"],
+            Value::from("xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=")
+        );
+        assert_eq!(peer["endpoint"], Value::from("192.0.2.1:51820"));
+        assert_eq!(peer["allowed-ips"], Value::from(vec!["0.0.0.0/0"]));
+        assert!(!peer.contains_key("preshared-key"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_dbus_from_wireless_connection() -> anyhow::Result<()> {
         let config = WirelessConfig {
@@ -1899,11 +2241,19 @@ mod test {
                 destination: IpInet::new("192.168.0.0".parse().unwrap(), 24_u8).unwrap(),
                 next_hop: Some(IpAddr::from_str("192.168.0.1").unwrap()),
                 metric: Some(100),
+                table: Some(100),
             }],
             routes6: vec![IpRoute {
                 destination: IpInet::new("2001:db8::".parse().unwrap(), 64_u8).unwrap(),
                 next_hop: Some(IpAddr::from_str("2001:db8::1").unwrap()),
                 metric: Some(100),
+                table: None,
+            }],
+            routing_rules6: vec![RoutingRule {
+                priority: 100,
+                from: None,
+                to: Some(IpInet::new("2001:db8::".parse().unwrap(), 64_u8).unwrap()),
+                table: 100,
             }],
             dns_searchlist: vec!["suse.com".to_string(), "suse.de".to_string()],
             ..Default::default()
@@ -1969,7 +2319,9 @@ mod test {
             assert_eq!(route4_hashmap["next-hop"], Value::from("192.168.0.1"));
             assert!(route4_hashmap.contains_key("metric"));
             assert_eq!(route4_hashmap["metric"], Value::from(100_u32));
+            assert_eq!(route4_hashmap["table"], Value::from(100_u32));
         }
+        assert!(ipv4_dbus.get("routing-rules").is_none());
         let dns_searchlist_array: Array = ipv4_dbus
             .get("dns-search")
             .unwrap()
@@ -2011,6 +2363,25 @@ mod test {
             assert_eq!(route6_hashmap["next-hop"], Value::from("2001:db8::1"));
             assert!(route6_hashmap.contains_key("metric"));
             assert_eq!(route6_hashmap["metric"], Value::from(100_u32));
+            assert!(!route6_hashmap.contains_key("table"));
+        }
+        let rules6_array: Array = ipv6_dbus
+            .get("routing-rules")
+            .unwrap()
+            .downcast_ref::<Value>()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(rules6_array.len(), 1);
+        for rule6 in rules6_array.iter() {
+            let rule6_dict: Dict = rule6.downcast_ref::<Value>().unwrap().try_into().unwrap();
+            let rule6_hashmap: HashMap<String, Value> = rule6_dict.try_into().unwrap();
+            assert_eq!(rule6_hashmap["family"], Value::from(10_i32));
+            assert_eq!(rule6_hashmap["priority"], Value::from(100_u32));
+            assert_eq!(rule6_hashmap["to"], Value::from("2001:db8::"));
+            assert_eq!(rule6_hashmap["to-len"], Value::from(64_u8));
+            assert_eq!(rule6_hashmap["table"], Value::from(100_u32));
+            assert!(!rule6_hashmap.contains_key("from"));
         }
         let dns_searchlist_array: Array = ipv6_dbus
             .get("dns-search")
@@ -20,7 +20,7 @@
 
 //! NetworkManager error types
 use crate::network::error::NetworkStateError;
-use cidr::errors::NetworkLengthTooLongError;
+use cidr::errors::{NetworkLengthTooLongError, NetworkParseError};
 use std::net::AddrParseError;
 use thiserror::Error;
 
@@ -60,6 +60,8 @@ pub enum NmError {
     InvalidNetworkPrefix(#[from] NetworkLengthTooLongError),
     #[error("Invalid network address: '{0}'")]
     InvalidNetworkAddress(#[from] AddrParseError),
+    #[error("Invalid network: '{0}'")]
+    InvalidNetwork(#[from] NetworkParseError),
 }
 
 impl From<NmError> for NetworkStateError {
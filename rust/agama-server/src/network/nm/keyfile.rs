@@ -0,0 +1,1443 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! This module implements some functions to convert from/to NetworkManager keyfiles
+//!
+//! Keyfiles are the INI-like files (`*.nmconnection`) that NetworkManager uses to store the
+//! connections. They use the same settings than the D-Bus API (see [super::dbus]), although some
+//! of them are written in a different way (e.g., IP addresses, routes or bonding options).
+use crate::network::model::*;
+use agama_lib::network::types::{BondMode, SSID};
+use cidr::IpInet;
+use std::{collections::HashMap, fmt, net::IpAddr, str::FromStr};
+use thiserror::Error;
+use uuid::Uuid;
+
+const CONNECTION_GROUP: &str = "connection";
+const ETHERNET_GROUP: &str = "ethernet";
+const WIRELESS_GROUP: &str = "wifi";
+const WIRELESS_SECURITY_GROUP: &str = "wifi-security";
+const BOND_GROUP: &str = "bond";
+const VLAN_GROUP: &str = "vlan";
+const BRIDGE_GROUP: &str = "bridge";
+const BRIDGE_PORT_GROUP: &str = "bridge-port";
+const INFINIBAND_GROUP: &str = "infiniband";
+const TUN_GROUP: &str = "tun";
+const WIREGUARD_GROUP: &str = "wireguard";
+const WIREGUARD_PEER_GROUP_PREFIX: &str = "wireguard-peer.";
+const IEEE_8021X_GROUP: &str = "802-1x";
+const MATCH_GROUP: &str = "match";
+const IPV4_GROUP: &str = "ipv4";
+const IPV6_GROUP: &str = "ipv6";
+
+#[derive(Error, Debug)]
+pub enum KeyfileError {
+    #[error("Invalid line {0}: '{1}'")]
+    InvalidLine(usize, String),
+    #[error("Missing '{0}' key")]
+    MissingKey(String),
+    #[error("Invalid value for '{0}': '{1}'")]
+    InvalidValue(String, String),
+    #[error("Connection type not supported: '{0}'")]
+    UnsupportedConnectionType(String),
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+}
+
+/// NetworkManager keyfile.
+///
+/// It keeps the groups and the keys in the same order they were read or added, so the written
+/// files look like the ones written by NetworkManager.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Keyfile {
+    groups: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl Keyfile {
+    /// Parses the content of a keyfile.
+    ///
+    /// * `content`: keyfile content.
+    pub fn parse(content: &str) -> Result<Self, KeyfileError> {
+        let mut keyfile = Keyfile::default();
+        let mut group: Option<String> = None;
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                group = Some(name.to_string());
+                keyfile.add_group(name);
+                continue;
+            }
+
+            let (Some(group), Some((key, value))) = (&group, line.split_once('=')) else {
+                return Err(KeyfileError::InvalidLine(number + 1, line.to_string()));
+            };
+            keyfile.set(group, key.trim_end(), unescape(value.trim_start()));
+        }
+
+        Ok(keyfile)
+    }
+
+    /// Determines whether the keyfile contains the given group.
+    ///
+    /// * `group`: group name.
+    pub fn has_group(&self, group: &str) -> bool {
+        self.group(group).is_some()
+    }
+
+    /// Returns the value of a key.
+    ///
+    /// * `group`: group name.
+    /// * `key`: key name.
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.group(group)?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the value of a key, which is a list of values separated by semicolons.
+    ///
+    /// * `group`: group name.
+    /// * `key`: key name.
+    pub fn get_list(&self, group: &str, key: &str) -> Vec<String> {
+        self.get(group, key).map(split_list).unwrap_or_default()
+    }
+
+    /// Returns the names of the groups.
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.groups.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the keys and values of a group.
+    ///
+    /// * `group`: group name.
+    pub fn entries(&self, group: &str) -> &[(String, String)] {
+        self.group(group).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Sets the value of a key, adding the group if it does not exist.
+    ///
+    /// * `group`: group name.
+    /// * `key`: key name.
+    /// * `value`: value to set.
+    pub fn set<T: ToString>(&mut self, group: &str, key: &str, value: T) {
+        let value = value.to_string();
+        let entries = self.add_group(group);
+        if let Some(entry) = entries.iter_mut().find(|(k, _)| k == key) {
+            entry.1 = value;
+        } else {
+            entries.push((key.to_string(), value));
+        }
+    }
+
+    /// Sets the value of a key to a list of values.
+    ///
+    /// * `group`: group name.
+    /// * `key`: key name.
+    /// * `values`: values to set.
+    pub fn set_list<T: ToString>(&mut self, group: &str, key: &str, values: &[T]) {
+        let values: Vec<String> = values
+            .iter()
+            .map(|v| format!("{};", v.to_string().replace(';', "\\;")))
+            .collect();
+        self.set(group, key, values.concat());
+    }
+
+    /// Sets the value of a key only if it is not empty.
+    ///
+    /// * `group`: group name.
+    /// * `key`: key name.
+    /// * `values`: values to set.
+    fn set_non_empty_list<T: ToString>(&mut self, group: &str, key: &str, values: &[T]) {
+        if !values.is_empty() {
+            self.set_list(group, key, values);
+        }
+    }
+
+    fn group(&self, group: &str) -> Option<&Vec<(String, String)>> {
+        self.groups
+            .iter()
+            .find(|(name, _)| name == group)
+            .map(|(_, entries)| entries)
+    }
+
+    fn add_group(&mut self, group: &str) -> &mut Vec<(String, String)> {
+        let index = match self.groups.iter().position(|(name, _)| name == group) {
+            Some(index) => index,
+            None => {
+                self.groups.push((group.to_string(), vec![]));
+                self.groups.len() - 1
+            }
+        };
+        &mut self.groups[index].1
+    }
+
+    /// Returns the value of a key converted to the given type.
+    fn parse_value<T: FromStr>(&self, group: &str, key: &str) -> Result<Option<T>, KeyfileError> {
+        let Some(value) = self.get(group, key) else {
+            return Ok(None);
+        };
+        let parsed = value
+            .parse()
+            .map_err(|_| KeyfileError::InvalidValue(key.to_string(), value.to_string()))?;
+        Ok(Some(parsed))
+    }
+
+    /// Returns the values of a list converted to the given type.
+    fn parse_list<T: FromStr>(&self, group: &str, key: &str) -> Result<Vec<T>, KeyfileError> {
+        self.get_list(group, key)
+            .iter()
+            .map(|v| {
+                v.parse()
+                    .map_err(|_| KeyfileError::InvalidValue(key.to_string(), v.to_string()))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Keyfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (group, entries)) in self.groups.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", group)?;
+            for (key, value) in entries {
+                writeln!(f, "{}={}", key, escape(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escapes a value following the GLib key file rules.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (index, c) in value.char_indices() {
+        match c {
+            ' ' if index == 0 => escaped.push_str("\\s"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' if !value[index..].starts_with("\\;") => escaped.push_str("\\\\"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Unescapes a value following the GLib key file rules.
+///
+/// Escaped list separators (`\;`) are kept so the value can be split later.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits a list of values separated by semicolons.
+fn split_list(value: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut current = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&';') => {
+                current.push(';');
+                chars.next();
+            }
+            ';' => values.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        values.push(current);
+    }
+    values
+}
+
+/// Converts a connection into a keyfile.
+///
+/// * `conn`: connection to convert.
+/// * `controller`: controller of the connection, if any.
+pub fn connection_to_keyfile(conn: &Connection, controller: Option<&Connection>) -> Keyfile {
+    let mut keyfile = Keyfile::default();
+
+    keyfile.set(CONNECTION_GROUP, "id", &conn.id);
+    keyfile.set(CONNECTION_GROUP, "uuid", conn.uuid);
+    keyfile.set(CONNECTION_GROUP, "type", connection_type(&conn.config));
+    if let Some(interface) = &conn.interface {
+        keyfile.set(CONNECTION_GROUP, "interface-name", interface);
+    }
+    if !conn.autoconnect {
+        keyfile.set(CONNECTION_GROUP, "autoconnect", false);
+    }
+    if let Some(zone) = &conn.firewall_zone {
+        keyfile.set(CONNECTION_GROUP, "zone", zone);
+    }
+    if let Some(controller) = controller {
+        match controller.config {
+            ConnectionConfig::Bond(_) => keyfile.set(CONNECTION_GROUP, "slave-type", BOND_GROUP),
+            ConnectionConfig::Bridge(_) => {
+                keyfile.set(CONNECTION_GROUP, "slave-type", BRIDGE_GROUP)
+            }
+            _ => log::error!("Controller {} has unhandled config type", controller.id),
+        }
+        keyfile.set(CONNECTION_GROUP, "master", controller.uuid);
+    }
+
+    match &conn.config {
+        ConnectionConfig::Wireless(wireless) => {
+            wireless_config_to_keyfile(&mut keyfile, conn, wireless)
+        }
+        ConnectionConfig::Bond(bond) => {
+            ethernet_config_to_keyfile(&mut keyfile, conn);
+            bond_config_to_keyfile(&mut keyfile, bond);
+        }
+        ConnectionConfig::Vlan(vlan) => {
+            ethernet_config_to_keyfile(&mut keyfile, conn);
+            keyfile.set(VLAN_GROUP, "id", vlan.id);
+            keyfile.set(VLAN_GROUP, "parent", &vlan.parent);
+            keyfile.set(VLAN_GROUP, "protocol", &vlan.protocol);
+        }
+        ConnectionConfig::Bridge(bridge) => {
+            ethernet_config_to_keyfile(&mut keyfile, conn);
+            bridge_config_to_keyfile(&mut keyfile, bridge);
+        }
+        ConnectionConfig::Infiniband(infiniband) => {
+            keyfile.set(
+                INFINIBAND_GROUP,
+                "transport-mode",
+                &infiniband.transport_mode,
+            );
+            if let Some(p_key) = infiniband.p_key {
+                keyfile.set(INFINIBAND_GROUP, "p-key", p_key);
+            }
+            if let Some(parent) = &infiniband.parent {
+                keyfile.set(INFINIBAND_GROUP, "parent", parent);
+            }
+            if conn.mtu != 0 {
+                keyfile.set(INFINIBAND_GROUP, "mtu", conn.mtu);
+            }
+        }
+        ConnectionConfig::Tun(tun) => {
+            keyfile.set(TUN_GROUP, "mode", tun.mode.clone() as u32);
+            if let Some(group) = &tun.group {
+                keyfile.set(TUN_GROUP, "group", group);
+            }
+            if let Some(owner) = &tun.owner {
+                keyfile.set(TUN_GROUP, "owner", owner);
+            }
+        }
+        ConnectionConfig::Wireguard(wireguard) => {
+            wireguard_config_to_keyfile(&mut keyfile, conn, wireguard)
+        }
+        ConnectionConfig::Ethernet | ConnectionConfig::Loopback | ConnectionConfig::Dummy => {
+            ethernet_config_to_keyfile(&mut keyfile, conn)
+        }
+    }
+
+    if let PortConfig::Bridge(port) = &conn.port_config {
+        keyfile.add_group(BRIDGE_PORT_GROUP);
+        if let Some(priority) = port.priority {
+            keyfile.set(BRIDGE_PORT_GROUP, "priority", priority);
+        }
+        if let Some(path_cost) = port.path_cost {
+            keyfile.set(BRIDGE_PORT_GROUP, "path-cost", path_cost);
+        }
+    }
+
+    if let Some(ieee_8021x) = &conn.ieee_8021x_config {
+        ieee_8021x_config_to_keyfile(&mut keyfile, ieee_8021x);
+    }
+
+    match_config_to_keyfile(&mut keyfile, &conn.match_config);
+    ip_config_to_keyfile(&mut keyfile, &conn.ip_config);
+
+    keyfile
+}
+
+/// Returns the NetworkManager connection type for the given configuration.
+fn connection_type(config: &ConnectionConfig) -> &'static str {
+    match config {
+        ConnectionConfig::Ethernet => ETHERNET_GROUP,
+        ConnectionConfig::Wireless(_) => WIRELESS_GROUP,
+        ConnectionConfig::Loopback => "loopback",
+        ConnectionConfig::Dummy => "dummy",
+        ConnectionConfig::Bond(_) => BOND_GROUP,
+        ConnectionConfig::Vlan(_) => VLAN_GROUP,
+        ConnectionConfig::Bridge(_) => BRIDGE_GROUP,
+        ConnectionConfig::Infiniband(_) => INFINIBAND_GROUP,
+        ConnectionConfig::Tun(_) => TUN_GROUP,
+        ConnectionConfig::Wireguard(_) => WIREGUARD_GROUP,
+    }
+}
+
+fn ethernet_config_to_keyfile(keyfile: &mut Keyfile, conn: &Connection) {
+    if matches!(conn.config, ConnectionConfig::Ethernet) {
+        keyfile.add_group(ETHERNET_GROUP);
+    }
+    if conn.mac_address != MacAddress::Unset {
+        keyfile.set(ETHERNET_GROUP, "cloned-mac-address", &conn.mac_address);
+    }
+    if conn.mtu != 0 {
+        keyfile.set(ETHERNET_GROUP, "mtu", conn.mtu);
+    }
+}
+
+fn wireguard_config_to_keyfile(keyfile: &mut Keyfile, conn: &Connection, config: &WireguardConfig) {
+    keyfile.add_group(WIREGUARD_GROUP);
+    if let Some(private_key) = &config.private_key {
+        keyfile.set(WIREGUARD_GROUP, "private-key", private_key);
+    }
+    if let Some(listen_port) = config.listen_port {
+        keyfile.set(WIREGUARD_GROUP, "listen-port", listen_port);
+    }
+    if conn.mtu != 0 {
+        keyfile.set(WIREGUARD_GROUP, "mtu", conn.mtu);
+    }
+
+    for peer in &config.peers {
+        let group = format!("{WIREGUARD_PEER_GROUP_PREFIX}{}", peer.public_key);
+        keyfile.add_group(&group);
+        if let Some(endpoint) = &peer.endpoint {
+            keyfile.set(&group, "endpoint", endpoint);
+        }
+        let allowed_ips: Vec<_> = peer
+            .allowed_ips
+            .iter()
+            .map(|ip| format!("{}/{}", ip.address(), ip.network_length()))
+            .collect();
+        keyfile.set_non_empty_list(&group, "allowed-ips", &allowed_ips);
+        if let Some(keepalive) = peer.persistent_keepalive {
+            keyfile.set(&group, "persistent-keepalive", keepalive);
+        }
+        if let Some(preshared_key) = &peer.preshared_key {
+            keyfile.set(&group, "preshared-key", preshared_key);
+            keyfile.set(&group, "preshared-key-flags", 0);
+        }
+    }
+}
+
+fn wireless_config_to_keyfile(keyfile: &mut Keyfile, conn: &Connection, config: &WirelessConfig) {
+    keyfile.set(WIRELESS_GROUP, "mode", config.mode);
+    match std::str::from_utf8(config.ssid.to_vec()) {
+        Ok(ssid) if !ssid.contains(';') => keyfile.set(WIRELESS_GROUP, "ssid", ssid),
+        _ => keyfile.set_list(WIRELESS_GROUP, "ssid", config.ssid.to_vec()),
+    }
+    if config.hidden {
+        keyfile.set(WIRELESS_GROUP, "hidden", true);
+    }
+    if let Some(band) = &config.band {
+        keyfile.set(WIRELESS_GROUP, "band", band);
+        keyfile.set(WIRELESS_GROUP, "channel", config.channel);
+    }
+    if let Some(bssid) = &config.bssid {
+        keyfile.set(WIRELESS_GROUP, "bssid", bssid);
+    }
+    if conn.mac_address != MacAddress::Unset {
+        keyfile.set(WIRELESS_GROUP, "cloned-mac-address", &conn.mac_address);
+    }
+    if conn.mtu != 0 {
+        keyfile.set(WIRELESS_GROUP, "mtu", conn.mtu);
+    }
+
+    if config.security == SecurityProtocol::WEP
+        && config.password.is_none()
+        && config.wep_security.is_none()
+    {
+        return;
+    }
+
+    keyfile.set(WIRELESS_SECURITY_GROUP, "key-mgmt", config.security);
+    if let Some(password) = &config.password {
+        keyfile.set(WIRELESS_SECURITY_GROUP, "psk", password);
+    }
+    keyfile.set_non_empty_list(WIRELESS_SECURITY_GROUP, "group", &config.group_algorithms);
+    keyfile.set_non_empty_list(
+        WIRELESS_SECURITY_GROUP,
+        "pairwise",
+        &config.pairwise_algorithms,
+    );
+    keyfile.set_non_empty_list(
+        WIRELESS_SECURITY_GROUP,
+        "proto",
+        &config.wpa_protocol_versions,
+    );
+    if config.pmf != 0 {
+        keyfile.set(WIRELESS_SECURITY_GROUP, "pmf", config.pmf);
+    }
+    if let Some(wep_security) = &config.wep_security {
+        keyfile.set(
+            WIRELESS_SECURITY_GROUP,
+            "wep-key-type",
+            wep_security.wep_key_type.clone() as u32,
+        );
+        if wep_security.auth_alg != WEPAuthAlg::Unset {
+            keyfile.set(WIRELESS_SECURITY_GROUP, "auth-alg", &wep_security.auth_alg);
+        }
+        for (i, key) in wep_security.keys.iter().take(4).enumerate() {
+            keyfile.set(WIRELESS_SECURITY_GROUP, &format!("wep-key{i}"), key);
+        }
+        keyfile.set(
+            WIRELESS_SECURITY_GROUP,
+            "wep-tx-keyidx",
+            wep_security.wep_key_index,
+        );
+    }
+}
+
+fn bond_config_to_keyfile(keyfile: &mut Keyfile, config: &BondConfig) {
+    keyfile.set(BOND_GROUP, "mode", config.mode);
+    let mut options: Vec<_> = config.options.0.iter().collect();
+    options.sort();
+    for (key, value) in options {
+        keyfile.set(BOND_GROUP, key, value);
+    }
+}
+
+fn bridge_config_to_keyfile(keyfile: &mut Keyfile, config: &BridgeConfig) {
+    keyfile.set(BRIDGE_GROUP, "stp", config.stp);
+    let values = [
+        ("priority", config.priority),
+        ("forward-delay", config.forward_delay),
+        ("hello-time", config.hello_time),
+        ("max-age", config.max_age),
+        ("ageing-time", config.ageing_time),
+    ];
+    for (key, value) in values {
+        if let Some(value) = value {
+            keyfile.set(BRIDGE_GROUP, key, value);
+        }
+    }
+}
+
+fn ieee_8021x_config_to_keyfile(keyfile: &mut Keyfile, config: &IEEE8021XConfig) {
+    keyfile.set_list(IEEE_8021X_GROUP, "eap", &config.eap);
+    if let Some(phase2_auth) = &config.phase2_auth {
+        keyfile.set(IEEE_8021X_GROUP, "phase2-auth", phase2_auth);
+    }
+    let values = [
+        ("identity", &config.identity),
+        ("password", &config.password),
+        ("ca-cert", &config.ca_cert),
+        ("ca-cert-password", &config.ca_cert_password),
+        ("client-cert", &config.client_cert),
+        ("client-cert-password", &config.client_cert_password),
+        ("private-key", &config.private_key),
+        ("private-key-password", &config.private_key_password),
+        ("anonymous-identity", &config.anonymous_identity),
+        ("phase1-peapver", &config.peap_version),
+    ];
+    for (key, value) in values {
+        if let Some(value) = value {
+            keyfile.set(IEEE_8021X_GROUP, key, value);
+        }
+    }
+    if config.peap_label {
+        keyfile.set(IEEE_8021X_GROUP, "phase1-peaplabel", 1);
+    }
+}
+
+fn match_config_to_keyfile(keyfile: &mut Keyfile, config: &MatchConfig) {
+    keyfile.set_non_empty_list(MATCH_GROUP, "driver", &config.driver);
+    keyfile.set_non_empty_list(MATCH_GROUP, "interface-name", &config.interface);
+    keyfile.set_non_empty_list(MATCH_GROUP, "path", &config.path);
+    keyfile.set_non_empty_list(MATCH_GROUP, "kernel-command-line", &config.kernel);
+}
+
+fn ip_config_to_keyfile(keyfile: &mut Keyfile, config: &IpConfig) {
+    let families = [
+        (
+            IPV4_GROUP,
+            config.method4.to_string(),
+            config.gateway4,
+            &config.routes4,
+            &config.routing_rules4,
+        ),
+        (
+            IPV6_GROUP,
+            config.method6.to_string(),
+            config.gateway6,
+            &config.routes6,
+            &config.routing_rules6,
+        ),
+    ];
+
+    for (group, method, gateway, routes, rules) in families {
+        let is_family = |ip: &IpAddr| ip.is_ipv4() == (group == IPV4_GROUP);
+
+        keyfile.set(group, "method", method);
+
+        let addresses = config
+            .addresses
+            .iter()
+            .filter(|ip| is_family(&ip.address()));
+        for (i, address) in addresses.enumerate() {
+            let address = format!("{}/{}", address.address(), address.network_length());
+            keyfile.set(group, &format!("address{}", i + 1), address);
+        }
+        if let Some(gateway) = gateway {
+            keyfile.set(group, "gateway", gateway);
+        }
+
+        let nameservers: Vec<_> = config
+            .nameservers
+            .iter()
+            .filter(|ip| is_family(ip))
+            .collect();
+        keyfile.set_non_empty_list(group, "dns", &nameservers);
+        keyfile.set_non_empty_list(group, "dns-search", &config.dns_searchlist);
+        if config.ignore_auto_dns {
+            keyfile.set(group, "ignore-auto-dns", true);
+        }
+
+        for (i, route) in routes.iter().enumerate() {
+            keyfile.set(group, &format!("route{}", i + 1), route_to_keyfile(route));
+            if let Some(table) = route.table {
+                keyfile.set(
+                    group,
+                    &format!("route{}_options", i + 1),
+                    format!("table={table}"),
+                );
+            }
+        }
+
+        for (i, rule) in rules.iter().enumerate() {
+            let key = format!("routing-rule{}", i + 1);
+            keyfile.set(group, &key, routing_rule_to_keyfile(rule));
+        }
+    }
+}
+
+/// Returns the routing rule in the `priority N [from NETWORK] [to NETWORK] table N` format.
+fn routing_rule_to_keyfile(rule: &RoutingRule) -> String {
+    let mut value = format!("priority {}", rule.priority);
+    if let Some(from) = &rule.from {
+        value.push_str(&format!(
+            " from {}/{}",
+            from.address(),
+            from.network_length()
+        ));
+    }
+    if let Some(to) = &rule.to {
+        value.push_str(&format!(" to {}/{}", to.address(), to.network_length()));
+    }
+    value.push_str(&format!(" table {}", rule.table));
+    value
+}
+
+/// Returns the route in the `destination/prefix[,next-hop[,metric]]` format.
+fn route_to_keyfile(route: &IpRoute) -> String {
+    let value = format!(
+        "{}/{}",
+        route.destination.address(),
+        route.destination.network_length()
+    );
+    match (route.next_hop, route.metric) {
+        (Some(next_hop), Some(metric)) => format!("{value},{next_hop},{metric}"),
+        (Some(next_hop), None) => format!("{value},{next_hop}"),
+        (None, Some(metric)) => {
+            let unspecified = if route.destination.is_ipv4() {
+                "0.0.0.0"
+            } else {
+                "::"
+            };
+            format!("{value},{unspecified},{metric}")
+        }
+        (None, None) => value,
+    }
+}
+
+/// Converts a keyfile into a connection.
+///
+/// The controller is not set because it requires knowing the rest of the connections. Use
+/// [controller_from_keyfile] to find out the controller's reference (a UUID, an interface name or
+/// a connection ID).
+///
+/// * `keyfile`: keyfile to convert.
+pub fn connection_from_keyfile(keyfile: &Keyfile) -> Result<Connection, KeyfileError> {
+    if !keyfile.has_group(CONNECTION_GROUP) {
+        return Err(KeyfileError::MissingKey(CONNECTION_GROUP.to_string()));
+    }
+
+    let id = required(keyfile, CONNECTION_GROUP, "id")?;
+    let uuid = required(keyfile, CONNECTION_GROUP, "uuid")?;
+    let uuid = Uuid::from_str(uuid).map_err(|_| invalid_value("uuid", uuid))?;
+    let type_ = required(keyfile, CONNECTION_GROUP, "type")?;
+
+    let mut conn = Connection {
+        id: id.to_string(),
+        uuid,
+        interface: keyfile
+            .get(CONNECTION_GROUP, "interface-name")
+            .map(str::to_string),
+        firewall_zone: keyfile.get(CONNECTION_GROUP, "zone").map(str::to_string),
+        autoconnect: keyfile
+            .parse_value(CONNECTION_GROUP, "autoconnect")?
+            .unwrap_or(true),
+        ..Default::default()
+    };
+
+    let hw_group = match type_ {
+        "ethernet" | "802-3-ethernet" => {
+            conn.config = ConnectionConfig::Ethernet;
+            ETHERNET_GROUP
+        }
+        "wifi" | "802-11-wireless" => {
+            conn.config = ConnectionConfig::Wireless(wireless_config_from_keyfile(keyfile)?);
+            WIRELESS_GROUP
+        }
+        "loopback" => {
+            conn.config = ConnectionConfig::Loopback;
+            ETHERNET_GROUP
+        }
+        "dummy" => {
+            conn.config = ConnectionConfig::Dummy;
+            ETHERNET_GROUP
+        }
+        "bond" => {
+            conn.config = ConnectionConfig::Bond(bond_config_from_keyfile(keyfile)?);
+            ETHERNET_GROUP
+        }
+        "vlan" => {
+            conn.config = ConnectionConfig::Vlan(vlan_config_from_keyfile(keyfile)?);
+            ETHERNET_GROUP
+        }
+        "bridge" => {
+            conn.config = ConnectionConfig::Bridge(bridge_config_from_keyfile(keyfile)?);
+            ETHERNET_GROUP
+        }
+        "infiniband" => {
+            conn.config = ConnectionConfig::Infiniband(infiniband_config_from_keyfile(keyfile)?);
+            INFINIBAND_GROUP
+        }
+        "tun" => {
+            conn.config = ConnectionConfig::Tun(tun_config_from_keyfile(keyfile)?);
+            TUN_GROUP
+        }
+        "wireguard" => {
+            conn.config = ConnectionConfig::Wireguard(wireguard_config_from_keyfile(keyfile)?);
+            WIREGUARD_GROUP
+        }
+        _ => return Err(KeyfileError::UnsupportedConnectionType(type_.to_string())),
+    };
+
+    if let Some(mac_address) = keyfile.get(hw_group, "cloned-mac-address") {
+        conn.mac_address = MacAddress::from_str(mac_address)
+            .map_err(|_| invalid_value("cloned-mac-address", mac_address))?;
+    }
+    conn.mtu = keyfile.parse_value(hw_group, "mtu")?.unwrap_or(0);
+
+    if keyfile.has_group(BRIDGE_PORT_GROUP) {
+        conn.port_config = PortConfig::Bridge(BridgePortConfig {
+            priority: keyfile.parse_value(BRIDGE_PORT_GROUP, "priority")?,
+            path_cost: keyfile.parse_value(BRIDGE_PORT_GROUP, "path-cost")?,
+        });
+    }
+
+    if keyfile.has_group(IEEE_8021X_GROUP) {
+        conn.ieee_8021x_config = Some(ieee_8021x_config_from_keyfile(keyfile)?);
+    }
+
+    conn.match_config = MatchConfig {
+        driver: keyfile.get_list(MATCH_GROUP, "driver"),
+        interface: keyfile.get_list(MATCH_GROUP, "interface-name"),
+        path: keyfile.get_list(MATCH_GROUP, "path"),
+        kernel: keyfile.get_list(MATCH_GROUP, "kernel-command-line"),
+    };
+    conn.ip_config = ip_config_from_keyfile(keyfile)?;
+
+    Ok(conn)
+}
+
+/// Returns the reference to the controller of a connection (`master` key), if any.
+///
+/// * `keyfile`: keyfile to get the controller from.
+pub fn controller_from_keyfile(keyfile: &Keyfile) -> Option<String> {
+    keyfile
+        .get(CONNECTION_GROUP, "master")
+        .filter(|m| !m.is_empty())
+        .map(str::to_string)
+}
+
+fn required<'a>(keyfile: &'a Keyfile, group: &str, key: &str) -> Result<&'a str, KeyfileError> {
+    keyfile
+        .get(group, key)
+        .ok_or_else(|| KeyfileError::MissingKey(format!("{group}.{key}")))
+}
+
+fn invalid_value(key: &str, value: &str) -> KeyfileError {
+    KeyfileError::InvalidValue(key.to_string(), value.to_string())
+}
+
+fn wireless_config_from_keyfile(keyfile: &Keyfile) -> Result<WirelessConfig, KeyfileError> {
+    let mut config = WirelessConfig::default();
+
+    if let Some(mode) = keyfile.get(WIRELESS_GROUP, "mode") {
+        config.mode = WirelessMode::try_from(mode).map_err(|_| invalid_value("mode", mode))?;
+    }
+
+    let ssid = required(keyfile, WIRELESS_GROUP, "ssid")?;
+    config.ssid = match keyfile.parse_list::<u8>(WIRELESS_GROUP, "ssid") {
+        Ok(bytes) if ssid.ends_with(';') => SSID(bytes),
+        _ => SSID(ssid.as_bytes().to_vec()),
+    };
+
+    config.hidden = keyfile
+        .parse_value(WIRELESS_GROUP, "hidden")?
+        .unwrap_or(false);
+    if let Some(band) = keyfile.get(WIRELESS_GROUP, "band") {
+        config.band = Some(WirelessBand::try_from(band).map_err(|_| invalid_value("band", band))?);
+    }
+    config.channel = keyfile.parse_value(WIRELESS_GROUP, "channel")?.unwrap_or(0);
+    config.bssid = keyfile.parse_value(WIRELESS_GROUP, "bssid")?;
+
+    if !keyfile.has_group(WIRELESS_SECURITY_GROUP) {
+        return Ok(config);
+    }
+
+    if let Some(key_mgmt) = keyfile.get(WIRELESS_SECURITY_GROUP, "key-mgmt") {
+        config.security = SecurityProtocol::try_from(key_mgmt)
+            .map_err(|_| invalid_value("key-mgmt", key_mgmt))?;
+    }
+    config.password = keyfile
+        .get(WIRELESS_SECURITY_GROUP, "psk")
+        .map(str::to_string);
+    config.group_algorithms = keyfile.parse_list(WIRELESS_SECURITY_GROUP, "group")?;
+    config.pairwise_algorithms = keyfile.parse_list(WIRELESS_SECURITY_GROUP, "pairwise")?;
+    config.wpa_protocol_versions = keyfile.parse_list(WIRELESS_SECURITY_GROUP, "proto")?;
+    config.pmf = keyfile
+        .parse_value(WIRELESS_SECURITY_GROUP, "pmf")?
+        .unwrap_or(0);
+
+    let wep_key_type: Option<u32> = keyfile.parse_value(WIRELESS_SECURITY_GROUP, "wep-key-type")?;
+    if let Some(wep_key_type) = wep_key_type {
+        let auth_alg = keyfile
+            .get(WIRELESS_SECURITY_GROUP, "auth-alg")
+            .unwrap_or_default();
+        let keys = (0..4)
+            .map_while(|i| keyfile.get(WIRELESS_SECURITY_GROUP, &format!("wep-key{i}")))
+            .map(str::to_string)
+            .collect();
+        config.wep_security = Some(WEPSecurity {
+            wep_key_type: WEPKeyType::try_from(wep_key_type).unwrap_or_default(),
+            auth_alg: WEPAuthAlg::try_from(auth_alg).unwrap_or_default(),
+            keys,
+            wep_key_index: keyfile
+                .parse_value(WIRELESS_SECURITY_GROUP, "wep-tx-keyidx")?
+                .unwrap_or(0),
+        });
+    }
+
+    Ok(config)
+}
+
+fn bond_config_from_keyfile(keyfile: &Keyfile) -> Result<BondConfig, KeyfileError> {
+    let mut config = BondConfig::default();
+    let mut options = HashMap::new();
+
+    for (key, value) in keyfile.entries(BOND_GROUP) {
+        if key == "mode" {
+            config.mode =
+                BondMode::try_from(value.as_str()).map_err(|_| invalid_value("mode", value))?;
+        } else {
+            options.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    config.options = BondOptions(options);
+    Ok(config)
+}
+
+fn vlan_config_from_keyfile(keyfile: &Keyfile) -> Result<VlanConfig, KeyfileError> {
+    let id = required(keyfile, VLAN_GROUP, "id")?;
+    Ok(VlanConfig {
+        id: id.parse().map_err(|_| invalid_value("id", id))?,
+        parent: required(keyfile, VLAN_GROUP, "parent")?.to_string(),
+        protocol: keyfile
+            .parse_value(VLAN_GROUP, "protocol")?
+            .unwrap_or_default(),
+    })
+}
+
+fn bridge_config_from_keyfile(keyfile: &Keyfile) -> Result<BridgeConfig, KeyfileError> {
+    Ok(BridgeConfig {
+        // NetworkManager enables STP by default
+        stp: keyfile.parse_value(BRIDGE_GROUP, "stp")?.unwrap_or(true),
+        priority: keyfile.parse_value(BRIDGE_GROUP, "priority")?,
+        forward_delay: keyfile.parse_value(BRIDGE_GROUP, "forward-delay")?,
+        hello_time: keyfile.parse_value(BRIDGE_GROUP, "hello-time")?,
+        max_age: keyfile.parse_value(BRIDGE_GROUP, "max-age")?,
+        ageing_time: keyfile.parse_value(BRIDGE_GROUP, "ageing-time")?,
+    })
+}
+
+fn infiniband_config_from_keyfile(keyfile: &Keyfile) -> Result<InfinibandConfig, KeyfileError> {
+    Ok(InfinibandConfig {
+        p_key: keyfile.parse_value(INFINIBAND_GROUP, "p-key")?,
+        parent: keyfile.get(INFINIBAND_GROUP, "parent").map(str::to_string),
+        transport_mode: keyfile
+            .parse_value(INFINIBAND_GROUP, "transport-mode")?
+            .unwrap_or_default(),
+    })
+}
+
+fn tun_config_from_keyfile(keyfile: &Keyfile) -> Result<TunConfig, KeyfileError> {
+    let mode = match keyfile.parse_value::<u32>(TUN_GROUP, "mode")? {
+        Some(2) => TunMode::Tap,
+        _ => TunMode::Tun,
+    };
+
+    Ok(TunConfig {
+        mode,
+        group: keyfile.get(TUN_GROUP, "group").map(str::to_string),
+        owner: keyfile.get(TUN_GROUP, "owner").map(str::to_string),
+    })
+}
+
+fn wireguard_config_from_keyfile(keyfile: &Keyfile) -> Result<WireguardConfig, KeyfileError> {
+    let mut peers = vec![];
+    for group in keyfile.groups() {
+        let Some(public_key) = group.strip_prefix(WIREGUARD_PEER_GROUP_PREFIX) else {
+            continue;
+        };
+        peers.push(WireguardPeer {
+            public_key: public_key.to_string(),
+            allowed_ips: keyfile.parse_list(group, "allowed-ips")?,
+            endpoint: keyfile.get(group, "endpoint").map(str::to_string),
+            persistent_keepalive: keyfile.parse_value(group, "persistent-keepalive")?,
+            preshared_key: keyfile.get(group, "preshared-key").map(str::to_string),
+        });
+    }
+
+    Ok(WireguardConfig {
+        private_key: keyfile
+            .get(WIREGUARD_GROUP, "private-key")
+            .map(str::to_string),
+        listen_port: keyfile.parse_value(WIREGUARD_GROUP, "listen-port")?,
+        peers,
+    })
+}
+
+fn ieee_8021x_config_from_keyfile(keyfile: &Keyfile) -> Result<IEEE8021XConfig, KeyfileError> {
+    let get = |key: &str| keyfile.get(IEEE_8021X_GROUP, key).map(str::to_string);
+
+    Ok(IEEE8021XConfig {
+        eap: keyfile.parse_list(IEEE_8021X_GROUP, "eap")?,
+        phase2_auth: keyfile.parse_value(IEEE_8021X_GROUP, "phase2-auth")?,
+        identity: get("identity"),
+        password: get("password"),
+        ca_cert: get("ca-cert"),
+        ca_cert_password: get("ca-cert-password"),
+        client_cert: get("client-cert"),
+        client_cert_password: get("client-cert-password"),
+        private_key: get("private-key"),
+        private_key_password: get("private-key-password"),
+        anonymous_identity: get("anonymous-identity"),
+        peap_version: get("phase1-peapver"),
+        peap_label: keyfile.get(IEEE_8021X_GROUP, "phase1-peaplabel") == Some("1"),
+    })
+}
+
+fn ip_config_from_keyfile(keyfile: &Keyfile) -> Result<IpConfig, KeyfileError> {
+    let mut config = IpConfig::default();
+
+    if let Some(method) = keyfile.get(IPV4_GROUP, "method") {
+        config.method4 =
+            Ipv4Method::from_str(method).map_err(|_| invalid_value("method", method))?;
+    }
+    if let Some(method) = keyfile.get(IPV6_GROUP, "method") {
+        config.method6 =
+            Ipv6Method::from_str(method).map_err(|_| invalid_value("method", method))?;
+    }
+
+    for group in [IPV4_GROUP, IPV6_GROUP] {
+        let mut gateway: Option<IpAddr> = keyfile.parse_value(group, "gateway")?;
+
+        for (key, value) in numbered_entries(keyfile, group, &["address", "addresses"]) {
+            for item in split_list(value) {
+                let (address, address_gateway) = match item.split_once(',') {
+                    Some((address, gateway)) => (address, Some(gateway)),
+                    None => (item.as_str(), None),
+                };
+                let address = IpInet::from_str(address).map_err(|_| invalid_value(key, address))?;
+                config.addresses.push(address);
+                if let (None, Some(address_gateway)) = (gateway, address_gateway) {
+                    gateway = Some(
+                        address_gateway
+                            .parse()
+                            .map_err(|_| invalid_value(key, address_gateway))?,
+                    );
+                }
+            }
+        }
+
+        let mut routes = vec![];
+        for (key, value) in numbered_entries(keyfile, group, &["route", "routes"]) {
+            let mut route = route_from_keyfile(value).ok_or_else(|| invalid_value(key, value))?;
+            let options_key = format!("{key}_options");
+            if let Some(options) = keyfile.get(group, &options_key) {
+                if let Some(table) = options.split(',').find_map(|o| o.strip_prefix("table=")) {
+                    route.table = Some(
+                        table
+                            .parse()
+                            .map_err(|_| invalid_value(&options_key, options))?,
+                    );
+                }
+            }
+            routes.push(route);
+        }
+
+        let mut rules = vec![];
+        for (_, value) in numbered_entries(keyfile, group, &["routing-rule"]) {
+            match routing_rule_from_keyfile(value) {
+                Some(rule) => rules.push(rule),
+                None => log::warn!("Ignoring unsupported routing rule: '{}'", value),
+            }
+        }
+
+        let mut nameservers: Vec<IpAddr> = keyfile.parse_list(group, "dns")?;
+        config.nameservers.append(&mut nameservers);
+
+        for domain in keyfile.get_list(group, "dns-search") {
+            if !config.dns_searchlist.contains(&domain) {
+                config.dns_searchlist.push(domain);
+            }
+        }
+
+        if let Some(ignore_auto_dns) = keyfile.parse_value(group, "ignore-auto-dns")? {
+            config.ignore_auto_dns = ignore_auto_dns;
+        }
+
+        if group == IPV4_GROUP {
+            config.gateway4 = gateway;
+            config.routes4 = routes;
+            config.routing_rules4 = rules;
+        } else {
+            config.gateway6 = gateway;
+            config.routes6 = routes;
+            config.routing_rules6 = rules;
+        }
+    }
+
+    Ok(config)
+}
+
+/// Returns the keys which consist of one of the given prefixes and a number (e.g., `address1`),
+/// sorted by that number.
+fn numbered_entries<'a>(
+    keyfile: &'a Keyfile,
+    group: &str,
+    prefixes: &[&str],
+) -> Vec<(&'a str, &'a str)> {
+    let mut entries: Vec<(u32, &str, &str)> = keyfile
+        .entries(group)
+        .iter()
+        .filter_map(|(key, value)| {
+            let number = prefixes
+                .iter()
+                .find_map(|p| key.strip_prefix(p)?.parse::<u32>().ok())?;
+            Some((number, key.as_str(), value.as_str()))
+        })
+        .collect();
+    entries.sort_by_key(|(number, _, _)| *number);
+    entries
+        .into_iter()
+        .map(|(_, key, value)| (key, value))
+        .collect()
+}
+
+/// Parses a route in the `destination/prefix[,next-hop[,metric]]` format.
+fn route_from_keyfile(value: &str) -> Option<IpRoute> {
+    let mut parts = value.split(',');
+    let destination = IpInet::from_str(parts.next()?).ok()?;
+    let next_hop = match parts.next().filter(|n| !n.is_empty()) {
+        Some(next_hop) => Some(IpAddr::from_str(next_hop).ok()?),
+        None => None,
+    };
+    let metric = match parts.next().filter(|m| !m.is_empty()) {
+        Some(metric) => Some(metric.parse().ok()?),
+        None => None,
+    };
+
+    Some(IpRoute {
+        destination,
+        next_hop: next_hop.filter(|ip| !ip.is_unspecified()),
+        metric,
+        table: None,
+    })
+}
+
+/// Parses a routing rule in the `priority N [from NETWORK] [to NETWORK] table N` format.
+///
+/// It returns `None` if the rule is not valid or it uses unsupported selectors or actions.
+fn routing_rule_from_keyfile(value: &str) -> Option<RoutingRule> {
+    let mut priority = None;
+    let mut table = None;
+    let mut from = None;
+    let mut to = None;
+
+    let mut tokens = value.split_whitespace();
+    while let Some(keyword) = tokens.next() {
+        let argument = tokens.next()?;
+        let network = || match argument {
+            "all" => Some(None),
+            _ => IpInet::from_str(argument).ok().map(Some),
+        };
+        match keyword {
+            "priority" => priority = Some(argument.parse().ok()?),
+            "table" => table = Some(argument.parse().ok()?),
+            "from" => from = network()?,
+            "to" => to = network()?,
+            _ => return None,
+        }
+    }
+
+    Some(RoutingRule {
+        priority: priority?,
+        from,
+        to,
+        table: table?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{connection_from_keyfile, connection_to_keyfile, controller_from_keyfile, Keyfile};
+    use crate::network::model::*;
+    use agama_lib::network::types::{BondMode, SSID};
+    use cidr::IpInet;
+    use std::{collections::HashMap, net::IpAddr, str::FromStr};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_parse_keyfile() -> anyhow::Result<()> {
+        let content = r#"
+# written by NetworkManager
+[connection]
+id=Wired connection 1
+uuid=a1b2c3d4-e5f6-4a5b-8c9d-0e1f2a3b4c5d
+type=ethernet
+interface-name=eth0
+
+[ipv4]
+method=manual
+address1=192.168.122.100/24,192.168.122.1
+dns=192.168.122.1;1.1.1.1;
+dns-search=example.com;
+route1=10.0.0.0/8,192.168.122.254,100
+
+[ipv6]
+method=auto
+"#;
+
+        let keyfile = Keyfile::parse(content)?;
+        let conn = connection_from_keyfile(&keyfile)?;
+        assert_eq!(conn.id, "Wired connection 1");
+        assert_eq!(conn.interface, Some("eth0".to_string()));
+        assert_eq!(conn.config, ConnectionConfig::Ethernet);
+        assert!(conn.autoconnect);
+
+        let ip_config = &conn.ip_config;
+        assert_eq!(ip_config.method4, Ipv4Method::Manual);
+        assert_eq!(ip_config.method6, Ipv6Method::Auto);
+        assert_eq!(
+            ip_config.addresses,
+            vec![IpInet::from_str("192.168.122.100/24")?]
+        );
+        assert_eq!(ip_config.gateway4, Some(IpAddr::from_str("192.168.122.1")?));
+        assert_eq!(
+            ip_config.nameservers,
+            vec![
+                IpAddr::from_str("192.168.122.1")?,
+                IpAddr::from_str("1.1.1.1")?
+            ]
+        );
+        assert_eq!(ip_config.dns_searchlist, vec!["example.com".to_string()]);
+        assert_eq!(
+            ip_config.routes4,
+            vec![IpRoute {
+                destination: IpInet::from_str("10.0.0.0/8")?,
+                next_hop: Some(IpAddr::from_str("192.168.122.254")?),
+                metric: Some(100),
+                table: None
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_keyfile() {
+        assert!(Keyfile::parse("id=eth0").is_err());
+        assert!(Keyfile::parse("[connection]\nid").is_err());
+
+        let keyfile = Keyfile::parse("[connection]\nid=eth0\ntype=ethernet").unwrap();
+        assert!(connection_from_keyfile(&keyfile).is_err());
+
+        let keyfile = Keyfile::parse(
+            "[connection]\nid=vpn\nuuid=a1b2c3d4-e5f6-4a5b-8c9d-0e1f2a3b4c5d\ntype=vpn",
+        )
+        .unwrap();
+        assert!(connection_from_keyfile(&keyfile).is_err());
+    }
+
+    #[test]
+    fn test_escape_values() -> anyhow::Result<()> {
+        let mut keyfile = Keyfile::default();
+        keyfile.set("wifi-security", "psk", " pass\\word\n");
+        keyfile.set_list("match", "path", &["pci-0000:00:1f.6", "a;b"]);
+        let content = keyfile.to_string();
+        assert_eq!(
+            content,
+            "[wifi-security]\npsk=\\spass\\\\word\\n\n\n[match]\npath=pci-0000:00:1f.6;a\\;b;\n"
+        );
+
+        let keyfile = Keyfile::parse(&content)?;
+        assert_eq!(keyfile.get("wifi-security", "psk"), Some(" pass\\word\n"));
+        assert_eq!(
+            keyfile.get_list("match", "path"),
+            vec!["pci-0000:00:1f.6".to_string(), "a;b".to_string()]
+        );
+        Ok(())
+    }
+
+    fn round_trip(conn: &Connection, controller: Option<&Connection>) -> Connection {
+        let keyfile = connection_to_keyfile(conn, controller);
+        let keyfile = Keyfile::parse(&keyfile.to_string()).unwrap();
+        connection_from_keyfile(&keyfile).unwrap()
+    }
+
+    #[test]
+    fn test_ethernet_round_trip() -> anyhow::Result<()> {
+        let conn = Connection {
+            id: "eth0".to_string(),
+            interface: Some("eth0".to_string()),
+            mac_address: MacAddress::from_str("FD:CB:A9:87:65:43")?,
+            mtu: 9000,
+            firewall_zone: Some("public".to_string()),
+            autoconnect: false,
+            ip_config: IpConfig {
+                method4: Ipv4Method::Manual,
+                method6: Ipv6Method::Manual,
+                addresses: vec![
+                    IpInet::from_str("192.168.1.10/24")?,
+                    IpInet::from_str("2001:db8::10/64")?,
+                ],
+                nameservers: vec![
+                    IpAddr::from_str("192.168.1.1")?,
+                    IpAddr::from_str("2001:db8::1")?,
+                ],
+                dns_searchlist: vec!["example.com".to_string()],
+                ignore_auto_dns: true,
+                gateway4: Some(IpAddr::from_str("192.168.1.1")?),
+                gateway6: Some(IpAddr::from_str("2001:db8::1")?),
+                routes4: vec![IpRoute {
+                    destination: IpInet::from_str("10.0.0.0/8")?,
+                    next_hop: None,
+                    metric: Some(50),
+                    table: Some(100),
+                }],
+                routes6: vec![IpRoute {
+                    destination: IpInet::from_str("2001:db8:1::/48")?,
+                    next_hop: Some(IpAddr::from_str("2001:db8::2")?),
+                    metric: None,
+                    table: None,
+                }],
+                routing_rules4: vec![RoutingRule {
+                    priority: 100,
+                    from: Some(IpInet::from_str("192.168.1.0/24")?),
+                    to: None,
+                    table: 100,
+                }],
+                routing_rules6: vec![RoutingRule {
+                    priority: 200,
+                    from: None,
+                    to: None,
+                    table: 200,
+                }],
+            },
+            match_config: MatchConfig {
+                driver: vec!["e1000e".to_string()],
+                path: vec!["pci-0000:00:19.0".to_string()],
+                ..Default::default()
+            },
+            ieee_8021x_config: Some(IEEE8021XConfig {
+                eap: vec![EAPMethod::PEAP],
+                phase2_auth: Some(Phase2AuthMethod::MSCHAPV2),
+                identity: Some("user".to_string()),
+                password: Some("secret".to_string()),
+                ca_cert: Some("/etc/ssl/ca.pem".to_string()),
+                peap_label: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(round_trip(&conn, None), conn);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wireless_round_trip() -> anyhow::Result<()> {
+        let conn = Connection {
+            id: "wlan0".to_string(),
+            config: ConnectionConfig::Wireless(WirelessConfig {
+                mode: WirelessMode::Infra,
+                ssid: SSID("agama; network".as_bytes().to_vec()),
+                password: Some("wpa-password".to_string()),
+                security: SecurityProtocol::WPA2,
+                band: Some(WirelessBand::A),
+                channel: 36,
+                bssid: Some(macaddr::MacAddr6::from_str("12:34:56:78:9A:BC")?),
+                hidden: true,
+                group_algorithms: vec![GroupAlgorithm::Ccmp],
+                pairwise_algorithms: vec![PairwiseAlgorithm::Ccmp],
+                wpa_protocol_versions: vec![WPAProtocolVersion::Rsn],
+                pmf: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(round_trip(&conn, None), conn);
+
+        let conn = Connection {
+            id: "wep".to_string(),
+            config: ConnectionConfig::Wireless(WirelessConfig {
+                ssid: SSID("wep-network".as_bytes().to_vec()),
+                wep_security: Some(WEPSecurity {
+                    wep_key_type: WEPKeyType::Key,
+                    auth_alg: WEPAuthAlg::Open,
+                    keys: vec!["abcde".to_string(), "fghij".to_string()],
+                    wep_key_index: 1,
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(round_trip(&conn, None), conn);
+        Ok(())
+    }
+
+    #[test]
+    fn test_virtual_devices_round_trip() {
+        let bond = Connection {
+            id: "bond0".to_string(),
+            interface: Some("bond0".to_string()),
+            config: ConnectionConfig::Bond(BondConfig {
+                mode: BondMode::ActiveBackup,
+                options: BondOptions(HashMap::from([("primary".to_string(), "eth0".to_string())])),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(round_trip(&bond, None), bond);
+
+        let port = Connection {
+            id: "eth0".to_string(),
+            controller: Some(bond.uuid),
+            ..Default::default()
+        };
+        let keyfile = connection_to_keyfile(&port, Some(&bond));
+        assert_eq!(keyfile.get("connection", "slave-type"), Some("bond"));
+        assert_eq!(
+            controller_from_keyfile(&keyfile),
+            Some(bond.uuid.to_string())
+        );
+
+        let configs = vec![
+            ConnectionConfig::Loopback,
+            ConnectionConfig::Dummy,
+            ConnectionConfig::Vlan(VlanConfig {
+                parent: "eth0".to_string(),
+                id: 10,
+                protocol: VlanProtocol::IEEE802_1ad,
+            }),
+            ConnectionConfig::Bridge(BridgeConfig {
+                stp: false,
+                priority: Some(32768),
+                forward_delay: Some(15),
+                hello_time: Some(2),
+                max_age: Some(20),
+                ageing_time: Some(300),
+            }),
+            ConnectionConfig::Infiniband(InfinibandConfig {
+                p_key: Some(0x8001),
+                parent: Some("ib0".to_string()),
+                transport_mode: InfinibandTransportMode::Connected,
+            }),
+            ConnectionConfig::Tun(TunConfig {
+                mode: TunMode::Tap,
+                group: Some("users".to_string()),
+                owner: Some("1000".to_string()),
+            }),
+            ConnectionConfig::Wireguard(WireguardConfig {
+                private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".to_string()),
+                listen_port: Some(51820),
+                peers: vec![WireguardPeer {
+                    public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".to_string(),
+                    allowed_ips: vec![
+                        IpInet::from_str("10.0.0.0/8").unwrap(),
+                        IpInet::from_str("fd00::/64").unwrap(),
+                    ],
+                    endpoint: Some("vpn.example.net:51820".to_string()),
+                    persistent_keepalive: Some(25),
+                    preshared_key: None,
+                }],
+            }),
+        ];
+
+        for config in configs {
+            let conn = Connection {
+                id: "virtual".to_string(),
+                uuid: Uuid::new_v4(),
+                config,
+                port_config: PortConfig::Bridge(BridgePortConfig {
+                    priority: Some(10),
+                    path_cost: Some(100),
+                }),
+                ..Default::default()
+            };
+            assert_eq!(round_trip(&conn, None), conn);
+        }
+    }
+}
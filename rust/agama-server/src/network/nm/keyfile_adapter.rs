@@ -0,0 +1,232 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+use crate::network::{
+    model::{Connection, NetworkState, StateConfig},
+    nm::keyfile::{
+        connection_from_keyfile, connection_to_keyfile, controller_from_keyfile, Keyfile,
+        KeyfileError,
+    },
+    Adapter, NetworkAdapterError,
+};
+use agama_lib::error::ServiceError;
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+/// Directory, relative to the root, containing the NetworkManager connections.
+const CONNECTIONS_DIR: &str = "etc/NetworkManager/system-connections";
+const KEYFILE_EXTENSION: &str = "nmconnection";
+
+/// An adapter that reads and writes NetworkManager keyfiles
+///
+/// It does not need NetworkManager to be running, so it can be used to write the configuration
+/// into the target system (using its mount point as the root directory) or for testing purposes.
+/// As there is no running service, it does not report any device, access point or status change.
+pub struct KeyfileAdapter {
+    root: PathBuf,
+}
+
+impl KeyfileAdapter {
+    /// Returns an adapter that works on the given root directory.
+    ///
+    /// * `root`: root directory (e.g., `/` or `/mnt`). The connections are read from and written
+    ///   to `etc/NetworkManager/system-connections` below this directory.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the directory containing the connections.
+    pub fn connections_dir(&self) -> PathBuf {
+        self.root.join(CONNECTIONS_DIR)
+    }
+
+    /// Reads the connections, returning also the file where each one is stored.
+    ///
+    /// Unsupported or invalid files are skipped.
+    fn read_connections(&self) -> Result<Vec<(PathBuf, Connection)>, KeyfileError> {
+        let dir = self.connections_dir();
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut paths: Vec<PathBuf> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == KEYFILE_EXTENSION))
+            .collect();
+        paths.sort();
+
+        let mut connections = vec![];
+        let mut controlled_by: HashMap<Uuid, String> = HashMap::new();
+        for path in paths {
+            let content = fs::read_to_string(&path)?;
+            let result = Keyfile::parse(&content)
+                .and_then(|keyfile| Ok((connection_from_keyfile(&keyfile)?, keyfile)));
+            match result {
+                Ok((connection, keyfile)) => {
+                    if let Some(controller) = controller_from_keyfile(&keyfile) {
+                        controlled_by.insert(connection.uuid, controller);
+                    }
+                    connections.push((path, connection));
+                }
+                Err(e) => {
+                    tracing::warn!("Could not process connection {}: {}", path.display(), e);
+                }
+            }
+        }
+
+        let controllers: Vec<(Uuid, String, Option<String>)> = connections
+            .iter()
+            .map(|(_, c)| (c.uuid, c.id.clone(), c.interface.clone()))
+            .collect();
+        for (_, conn) in connections.iter_mut() {
+            let Some(reference) = controlled_by.get(&conn.uuid) else {
+                continue;
+            };
+
+            // the controller can be referenced by its UUID, its interface name or its ID
+            let controller = controllers.iter().find(|(uuid, id, interface)| {
+                &uuid.to_string() == reference
+                    || interface.as_ref() == Some(reference)
+                    || id == reference
+            });
+            if let Some((uuid, _, _)) = controller {
+                conn.controller = Some(*uuid);
+            } else {
+                log::warn!(
+                    "Could not found a connection for the controller '{}' (required by connection '{}')",
+                    reference,
+                    conn.id
+                );
+            }
+        }
+
+        Ok(connections)
+    }
+
+    /// Writes the connections, removing the files of the deleted ones.
+    ///
+    /// * `network`: network model.
+    fn write_connections(&self, network: &NetworkState) -> Result<(), KeyfileError> {
+        let dir = self.connections_dir();
+        let mut paths: HashMap<Uuid, PathBuf> = self
+            .read_connections()?
+            .into_iter()
+            .map(|(path, conn)| (conn.uuid, path))
+            .collect();
+
+        for conn in &network.connections {
+            let old_path = paths.remove(&conn.uuid);
+            if conn.is_removed() {
+                if let Some(old_path) = old_path {
+                    log::info!("Removing connection {} ({})", conn.id, conn.uuid);
+                    fs::remove_file(old_path)?;
+                }
+                continue;
+            }
+
+            let controller = conn
+                .controller
+                .and_then(|uuid| network.get_connection_by_uuid(uuid));
+            let keyfile = connection_to_keyfile(conn, controller);
+            let path = dir.join(keyfile_name(conn));
+
+            log::info!(
+                "Writing connection {} ({}) to {}",
+                conn.id,
+                conn.uuid,
+                path.display()
+            );
+            fs::create_dir_all(&dir)?;
+            // the keyfiles may contain secrets and NetworkManager ignores them if they are
+            // readable by other users
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&path)?;
+            file.write_all(keyfile.to_string().as_bytes())?;
+
+            if let Some(old_path) = old_path.filter(|p| p != &path) {
+                fs::remove_file(old_path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Adapter for KeyfileAdapter {
+    async fn read(&self, config: StateConfig) -> Result<NetworkState, NetworkAdapterError> {
+        let mut state = NetworkState::default();
+
+        if config.connections {
+            state.connections = self
+                .read_connections()
+                .map_err(|e| NetworkAdapterError::Read(e.into()))?
+                .into_iter()
+                .map(|(_, conn)| conn)
+                .collect();
+        }
+
+        Ok(state)
+    }
+
+    async fn write(&self, network: &NetworkState) -> Result<(), NetworkAdapterError> {
+        self.write_connections(network)
+            .map_err(|e| NetworkAdapterError::Write(e.into()))
+    }
+}
+
+impl From<KeyfileError> for ServiceError {
+    fn from(value: KeyfileError) -> Self {
+        ServiceError::Anyhow(value.into())
+    }
+}
+
+/// Returns the name of the file to store the connection.
+///
+/// It is based on the connection ID, replacing the characters that are not allowed in a file
+/// name.
+///
+/// * `conn`: connection to get the file name for.
+fn keyfile_name(conn: &Connection) -> String {
+    let name: String = conn
+        .id
+        .chars()
+        .map(|c| if c == '/' || c.is_control() { '_' } else { c })
+        .collect();
+    let name = if name.is_empty() || name.starts_with('.') {
+        conn.uuid.to_string()
+    } else {
+        name
+    };
+    format!("{name}.{KEYFILE_EXTENSION}")
+}
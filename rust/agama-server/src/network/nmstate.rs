@@ -0,0 +1,1437 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! This module implements the conversion from/to the [nmstate](https://nmstate.io/) desired state
+//! format.
+//!
+//! nmstate describes the network configuration using a YAML document with four sections:
+//! `interfaces`, `routes`, `route-rules` and `dns-resolver`. Each interface is mapped to a
+//! connection, using the interface name to find the existing one. Only a subset of the format is
+//! supported: ethernet, bond, bridge, VLAN, InfiniBand, dummy and loopback interfaces. Other
+//! connections (e.g., wireless, WireGuard or TUN ones) cannot be represented, so they are left out
+//! when exporting the configuration (see [NetworkState::nmstate_skipped_connections]).
+//!
+//! Routing rules are not bound to an interface in nmstate. When applying a document, each rule is
+//! added to the connection that has routes in the rule's table.
+//!
+//! nmstate handles DNS globally, so the name servers and search domains are set on every
+//! connection which is not a port of a bond or a bridge.
+use super::{error::NetworkStateError, model::*, NetworkState};
+use agama_lib::network::types::BondMode;
+use cidr::IpInet;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::{collections::BTreeMap, net::IpAddr, str::FromStr};
+use yaml_rust::{yaml::Hash, Yaml, YamlEmitter, YamlLoader};
+
+/// nmstate desired state.
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateState {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interfaces: Vec<NmstateInterface>,
+    pub routes: Option<NmstateRoutes>,
+    pub route_rules: Option<NmstateRouteRules>,
+    pub dns_resolver: Option<NmstateDnsResolver>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateInterface {
+    pub name: String,
+    /// Interface type (e.g., "ethernet", "bond" or "linux-bridge"). It can be omitted when the
+    /// interface already exists.
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    #[serde(default)]
+    pub state: NmstateInterfaceState,
+    pub mtu: Option<u32>,
+    pub mac_address: Option<String>,
+    pub ipv4: Option<NmstateIpConfig>,
+    pub ipv6: Option<NmstateIpConfig>,
+    pub link_aggregation: Option<NmstateBondConfig>,
+    pub bridge: Option<NmstateBridgeConfig>,
+    pub vlan: Option<NmstateVlanConfig>,
+    pub infiniband: Option<NmstateInfinibandConfig>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NmstateInterfaceState {
+    #[default]
+    Up,
+    Down,
+    Absent,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateIpConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    pub dhcp: Option<bool>,
+    pub autoconf: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub address: Vec<NmstateIpAddress>,
+    pub auto_dns: Option<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateIpAddress {
+    pub ip: IpAddr,
+    pub prefix_length: u8,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateBondConfig {
+    pub mode: Option<String>,
+    pub port: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, serde_json::Value>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateBridgeConfig {
+    pub options: Option<NmstateBridgeOptions>,
+    pub port: Option<Vec<NmstateBridgePort>>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateBridgeOptions {
+    pub mac_ageing_time: Option<u32>,
+    pub stp: Option<NmstateStpOptions>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateStpOptions {
+    pub enabled: Option<bool>,
+    pub priority: Option<u32>,
+    pub forward_delay: Option<u32>,
+    pub hello_time: Option<u32>,
+    pub max_age: Option<u32>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateBridgePort {
+    pub name: String,
+    pub stp_priority: Option<u32>,
+    pub stp_path_cost: Option<u32>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateVlanConfig {
+    pub base_iface: String,
+    pub id: u32,
+    pub protocol: Option<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateInfinibandConfig {
+    pub base_iface: Option<String>,
+    pub mode: Option<String>,
+    pub pkey: Option<NmstateInfinibandPkey>,
+}
+
+/// InfiniBand partition key, which can be written as a number or as an hexadecimal string
+/// (e.g., "0x8001").
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NmstateInfinibandPkey {
+    Number(i32),
+    Text(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NmstateRoutes {
+    #[serde(default)]
+    pub config: Vec<NmstateRoute>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateRoute {
+    pub destination: IpInet,
+    pub next_hop_interface: Option<String>,
+    pub next_hop_address: Option<IpAddr>,
+    pub metric: Option<u32>,
+    pub table_id: Option<u32>,
+    pub state: Option<NmstateRouteState>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NmstateRouteState {
+    Absent,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NmstateRouteRules {
+    #[serde(default)]
+    pub config: Vec<NmstateRouteRule>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NmstateRouteRule {
+    pub family: Option<NmstateRouteRuleFamily>,
+    pub ip_from: Option<IpInet>,
+    pub ip_to: Option<IpInet>,
+    pub priority: Option<u32>,
+    pub route_table: Option<u32>,
+    pub state: Option<NmstateRouteState>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NmstateRouteRuleFamily {
+    Ipv4,
+    Ipv6,
+}
+
+/// Routing table used by nmstate when a rule does not set one.
+const MAIN_ROUTE_TABLE: u32 = 254;
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NmstateDnsResolver {
+    pub config: Option<NmstateDnsConfig>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NmstateDnsConfig {
+    #[serde(default)]
+    pub server: Vec<IpAddr>,
+    #[serde(default)]
+    pub search: Vec<String>,
+}
+
+impl NmstateState {
+    /// Reads the desired state from a YAML document.
+    ///
+    /// * `text`: YAML document.
+    pub fn from_yaml(text: &str) -> Result<Self, NetworkStateError> {
+        let docs = YamlLoader::load_from_str(text)
+            .map_err(|e| NetworkStateError::InvalidNmstate(e.to_string()))?;
+        let value = match docs.into_iter().next() {
+            None | Some(Yaml::Null) => return Ok(Self::default()),
+            Some(doc) => yaml_to_json(doc)?,
+        };
+        serde_json::from_value(value).map_err(|e| NetworkStateError::InvalidNmstate(e.to_string()))
+    }
+
+    /// Reads the desired state from a JSON document.
+    ///
+    /// * `text`: JSON document.
+    pub fn from_json(text: &str) -> Result<Self, NetworkStateError> {
+        serde_json::from_str(text).map_err(|e| NetworkStateError::InvalidNmstate(e.to_string()))
+    }
+
+    /// Writes the desired state as a YAML document.
+    pub fn to_yaml(&self) -> Result<String, NetworkStateError> {
+        let value = serde_json::to_value(self)
+            .map_err(|e| NetworkStateError::InvalidNmstate(e.to_string()))?;
+        let mut output = String::new();
+        YamlEmitter::new(&mut output)
+            .dump(&json_to_yaml(&value))
+            .map_err(|e| NetworkStateError::InvalidNmstate(e.to_string()))?;
+        output.push('\n');
+        Ok(output)
+    }
+}
+
+impl NetworkState {
+    /// Returns the network configuration in nmstate format.
+    ///
+    /// Connections that cannot be represented in that format (e.g., wireless ones) are ignored.
+    /// Use [NetworkState::nmstate_skipped_connections] to find out which ones.
+    pub fn to_nmstate(&self) -> NmstateState {
+        let connections: Vec<&Connection> = self
+            .connections
+            .iter()
+            .filter(|c| !c.is_removed())
+            .collect();
+
+        let mut interfaces = vec![];
+        let mut routes = vec![];
+        let mut rules = vec![];
+        let mut servers: Vec<IpAddr> = vec![];
+        let mut search: Vec<String> = vec![];
+        let port_ip_config = IpConfig::default();
+
+        for conn in &connections {
+            let Some(type_) = nmstate_type(&conn.config) else {
+                continue;
+            };
+            let name = interface_name(conn);
+            let ports: Vec<&Connection> = connections
+                .iter()
+                .filter(|c| c.controller == Some(conn.uuid))
+                .copied()
+                .collect();
+            // ports do not have IP settings
+            let ip_config = if conn.controller.is_some() {
+                &port_ip_config
+            } else {
+                &conn.ip_config
+            };
+
+            let mut iface = NmstateInterface {
+                name: name.to_string(),
+                type_: Some(type_.to_string()),
+                state: if conn.is_up() {
+                    NmstateInterfaceState::Up
+                } else {
+                    NmstateInterfaceState::Down
+                },
+                mtu: (conn.mtu > 0).then_some(conn.mtu),
+                ipv4: Some(ipv4_to_nmstate(ip_config)),
+                ipv6: Some(ipv6_to_nmstate(ip_config)),
+                ..Default::default()
+            };
+            if let MacAddress::MacAddress(mac) = &conn.mac_address {
+                iface.mac_address = Some(mac.to_string());
+            }
+
+            match &conn.config {
+                ConnectionConfig::Bond(config) => {
+                    iface.link_aggregation = Some(bond_to_nmstate(config, &ports));
+                }
+                ConnectionConfig::Bridge(config) => {
+                    iface.bridge = Some(bridge_to_nmstate(config, &ports));
+                }
+                ConnectionConfig::Vlan(config) => {
+                    iface.vlan = Some(NmstateVlanConfig {
+                        base_iface: config.parent.clone(),
+                        id: config.id,
+                        protocol: Some(config.protocol.to_string().to_lowercase()),
+                    });
+                }
+                ConnectionConfig::Infiniband(config) => {
+                    iface.infiniband = Some(NmstateInfinibandConfig {
+                        base_iface: config.parent.clone(),
+                        mode: Some(config.transport_mode.to_string()),
+                        pkey: config
+                            .p_key
+                            .filter(|p| *p >= 0)
+                            .map(|p| NmstateInfinibandPkey::Text(format!("{:#06x}", p))),
+                    });
+                }
+                _ => {}
+            }
+            interfaces.push(iface);
+
+            if let Some(gateway) = ip_config.gateway4 {
+                routes.push(default_route_to_nmstate(name, gateway));
+            }
+            if let Some(gateway) = ip_config.gateway6 {
+                routes.push(default_route_to_nmstate(name, gateway));
+            }
+            for route in ip_config.routes4.iter().chain(ip_config.routes6.iter()) {
+                routes.push(NmstateRoute {
+                    destination: route.destination,
+                    next_hop_interface: Some(name.to_string()),
+                    next_hop_address: route.next_hop,
+                    metric: route.metric,
+                    table_id: route.table,
+                    state: None,
+                });
+            }
+            let rules4 = ip_config
+                .routing_rules4
+                .iter()
+                .map(|r| (NmstateRouteRuleFamily::Ipv4, r));
+            let rules6 = ip_config
+                .routing_rules6
+                .iter()
+                .map(|r| (NmstateRouteRuleFamily::Ipv6, r));
+            for (family, rule) in rules4.chain(rules6) {
+                rules.push(NmstateRouteRule {
+                    family: Some(family),
+                    ip_from: rule.from,
+                    ip_to: rule.to,
+                    priority: Some(rule.priority),
+                    route_table: Some(rule.table),
+                    state: None,
+                });
+            }
+
+            for server in &ip_config.nameservers {
+                if !servers.contains(server) {
+                    servers.push(*server);
+                }
+            }
+            for domain in &ip_config.dns_searchlist {
+                if !search.contains(domain) {
+                    search.push(domain.clone());
+                }
+            }
+        }
+
+        let dns_resolver =
+            (!servers.is_empty() || !search.is_empty()).then_some(NmstateDnsResolver {
+                config: Some(NmstateDnsConfig {
+                    server: servers,
+                    search,
+                }),
+            });
+
+        NmstateState {
+            interfaces,
+            routes: (!routes.is_empty()).then_some(NmstateRoutes { config: routes }),
+            route_rules: (!rules.is_empty()).then_some(NmstateRouteRules { config: rules }),
+            dns_resolver,
+        }
+    }
+
+    /// Returns the IDs of the connections that cannot be represented in nmstate format.
+    ///
+    /// They are left out by [NetworkState::to_nmstate], so the caller can warn the user about
+    /// them.
+    pub fn nmstate_skipped_connections(&self) -> Vec<String> {
+        self.connections
+            .iter()
+            .filter(|c| !c.is_removed() && nmstate_type(&c.config).is_none())
+            .map(|c| c.id.clone())
+            .collect()
+    }
+
+    /// Applies a nmstate desired state.
+    ///
+    /// Interfaces are matched with the existing connections using the interface name (or the
+    /// connection ID). Settings which are not included in the document are kept. If something
+    /// goes wrong, the state is not modified at all.
+    ///
+    /// * `nmstate`: desired state.
+    pub fn apply_nmstate(&mut self, nmstate: NmstateState) -> Result<(), NetworkStateError> {
+        let mut state = self.clone();
+        let mut controllers: Vec<(String, Vec<String>)> = vec![];
+        let mut bridge_ports: Vec<(String, BridgePortConfig)> = vec![];
+        let mut updated: Vec<String> = vec![];
+
+        for iface in nmstate.interfaces {
+            let existing = state
+                .get_connection_by_interface(&iface.name)
+                .or_else(|| state.get_connection(&iface.name))
+                .cloned();
+
+            if iface.state == NmstateInterfaceState::Absent {
+                if let Some(conn) = existing {
+                    state.remove_connection(&conn.id)?;
+                }
+                continue;
+            }
+
+            let is_new = existing.is_none();
+            let mut conn = existing.unwrap_or_else(|| Connection {
+                id: iface.name.clone(),
+                interface: Some(iface.name.clone()),
+                ..Default::default()
+            });
+            conn.config = config_from_nmstate(&iface, conn.config)?;
+
+            if let Some(ports) = iface.link_aggregation.and_then(|b| b.port) {
+                controllers.push((conn.id.clone(), ports));
+            }
+            if let Some(ports) = iface.bridge.and_then(|b| b.port) {
+                let names = ports.iter().map(|p| p.name.clone()).collect();
+                controllers.push((conn.id.clone(), names));
+                for port in ports {
+                    if port.stp_priority.is_some() || port.stp_path_cost.is_some() {
+                        let config = BridgePortConfig {
+                            priority: port.stp_priority,
+                            path_cost: port.stp_path_cost,
+                        };
+                        bridge_ports.push((port.name, config));
+                    }
+                }
+            }
+
+            if let Some(mtu) = iface.mtu {
+                conn.mtu = mtu;
+            }
+            if let Some(mac) = &iface.mac_address {
+                conn.mac_address = MacAddress::from_str(mac).map_err(|_| {
+                    NetworkStateError::InvalidNmstate(format!("invalid MAC address '{mac}'"))
+                })?;
+            }
+            if let Some(ipv4) = &iface.ipv4 {
+                ipv4_from_nmstate(&mut conn.ip_config, ipv4)?;
+            }
+            if let Some(ipv6) = &iface.ipv6 {
+                ipv6_from_nmstate(&mut conn.ip_config, ipv6)?;
+            }
+            match iface.state {
+                NmstateInterfaceState::Down => conn.set_down(),
+                _ => conn.set_up(),
+            }
+
+            updated.push(conn.id.clone());
+            if is_new {
+                state.add_connection(conn)?;
+            } else {
+                state.update_connection(conn)?;
+            }
+        }
+
+        for (id, ports) in controllers {
+            for port in &ports {
+                if state.get_connection_by_interface(port).is_none()
+                    && state.get_connection(port).is_none()
+                {
+                    state.add_connection(Connection {
+                        id: port.clone(),
+                        interface: Some(port.clone()),
+                        ..Default::default()
+                    })?;
+                }
+            }
+            let controller = state
+                .get_connection(&id)
+                .cloned()
+                .ok_or(NetworkStateError::UnknownConnection(id))?;
+            state.set_ports(&controller, ports)?;
+        }
+
+        for (name, config) in bridge_ports {
+            if let Some(conn) = state.get_connection_by_interface_mut(&name) {
+                conn.port_config = PortConfig::Bridge(config);
+            }
+        }
+
+        if let Some(routes) = nmstate.routes {
+            for route in routes.config {
+                state.apply_nmstate_route(route)?;
+            }
+        }
+
+        if let Some(rules) = nmstate.route_rules {
+            for rule in rules.config {
+                state.apply_nmstate_route_rule(rule)?;
+            }
+        }
+
+        if let Some(config) = nmstate.dns_resolver.and_then(|d| d.config) {
+            for conn in state.connections.iter_mut() {
+                if conn.is_removed() || conn.is_loopback() || conn.controller.is_some() {
+                    continue;
+                }
+                if !updated.is_empty() && !updated.contains(&conn.id) {
+                    continue;
+                }
+                conn.ip_config.nameservers = config.server.clone();
+                conn.ip_config.dns_searchlist = config.search.clone();
+            }
+        }
+
+        *self = state;
+        Ok(())
+    }
+
+    fn get_connection_by_interface_mut(&mut self, name: &str) -> Option<&mut Connection> {
+        self.connections
+            .iter_mut()
+            .find(|c| c.interface.as_deref() == Some(name) || c.id == name)
+    }
+
+    /// Adds or removes a route.
+    ///
+    /// Default routes without a metric or a table are mapped to the connection gateway.
+    fn apply_nmstate_route(&mut self, route: NmstateRoute) -> Result<(), NetworkStateError> {
+        if route.state == Some(NmstateRouteState::Absent) {
+            for conn in self.connections.iter_mut() {
+                if route
+                    .next_hop_interface
+                    .as_ref()
+                    .is_some_and(|name| name.as_str() != interface_name(conn))
+                {
+                    continue;
+                }
+                let ip_config = &mut conn.ip_config;
+                ip_config
+                    .routes4
+                    .retain(|r| r.destination != route.destination);
+                ip_config
+                    .routes6
+                    .retain(|r| r.destination != route.destination);
+                if route.destination.network_length() == 0 {
+                    match route.destination {
+                        IpInet::V4(_) => ip_config.gateway4 = None,
+                        IpInet::V6(_) => ip_config.gateway6 = None,
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let Some(name) = &route.next_hop_interface else {
+            return Err(NetworkStateError::InvalidNmstate(format!(
+                "the route to '{}' has no next-hop-interface",
+                route.destination
+            )));
+        };
+        let conn = self
+            .get_connection_by_interface_mut(name)
+            .ok_or_else(|| NetworkStateError::UnknownConnection(name.clone()))?;
+        let ip_config = &mut conn.ip_config;
+
+        if let Some(next_hop) = route.next_hop_address {
+            if route.destination.network_length() == 0
+                && route.metric.is_none()
+                && route.table_id.is_none()
+            {
+                match (route.destination, next_hop) {
+                    (IpInet::V4(_), IpAddr::V4(_)) => ip_config.gateway4 = Some(next_hop),
+                    (IpInet::V6(_), IpAddr::V6(_)) => ip_config.gateway6 = Some(next_hop),
+                    _ => {
+                        return Err(NetworkStateError::InvalidRoute(
+                            route.destination.to_string(),
+                            format!("next hop '{next_hop}' belongs to a different IP family"),
+                        ))
+                    }
+                }
+                return Ok(());
+            }
+        }
+
+        let ip_route = IpRoute {
+            destination: route.destination,
+            next_hop: route.next_hop_address,
+            metric: route.metric,
+            table: route.table_id,
+        };
+        let routes = match route.destination {
+            IpInet::V4(_) => &mut ip_config.routes4,
+            IpInet::V6(_) => &mut ip_config.routes6,
+        };
+        if !routes.contains(&ip_route) {
+            routes.push(ip_route);
+        }
+        Ok(())
+    }
+
+    /// Adds or removes a routing rule.
+    ///
+    /// A new rule is added to the connection that has routes in the rule's table.
+    fn apply_nmstate_route_rule(
+        &mut self,
+        rule: NmstateRouteRule,
+    ) -> Result<(), NetworkStateError> {
+        let ipv6 = route_rule_family(&rule)? == NmstateRouteRuleFamily::Ipv6;
+
+        if rule.state == Some(NmstateRouteState::Absent) {
+            for conn in self.connections.iter_mut() {
+                routing_rules_mut(&mut conn.ip_config, ipv6).retain(|r| {
+                    rule.priority.is_some_and(|p| p != r.priority)
+                        || rule.route_table.is_some_and(|t| t != r.table)
+                        || rule.ip_from.is_some_and(|n| Some(n) != r.from)
+                        || rule.ip_to.is_some_and(|n| Some(n) != r.to)
+                });
+            }
+            return Ok(());
+        }
+
+        let Some(priority) = rule.priority else {
+            return Err(NetworkStateError::InvalidNmstate(
+                "a route rule has no priority".to_string(),
+            ));
+        };
+        let routing_rule = RoutingRule {
+            priority,
+            from: rule.ip_from,
+            to: rule.ip_to,
+            table: rule.route_table.unwrap_or(MAIN_ROUTE_TABLE),
+        };
+        let exists = self
+            .connections
+            .iter()
+            .filter(|c| !c.is_removed())
+            .any(|c| {
+                let ip_config = &c.ip_config;
+                let rules = if ipv6 {
+                    &ip_config.routing_rules6
+                } else {
+                    &ip_config.routing_rules4
+                };
+                rules.contains(&routing_rule)
+            });
+        if exists {
+            return Ok(());
+        }
+
+        let table = routing_rule.table;
+        let conn = self
+            .connections
+            .iter_mut()
+            .filter(|c| !c.is_removed())
+            .find(|c| {
+                let ip_config = &c.ip_config;
+                let routes = if ipv6 {
+                    &ip_config.routes6
+                } else {
+                    &ip_config.routes4
+                };
+                routes.iter().any(|r| r.table == Some(table))
+            })
+            .ok_or_else(|| {
+                NetworkStateError::InvalidRoutingRule(format!(
+                    "no interface has routes in the table {table}"
+                ))
+            })?;
+        routing_rules_mut(&mut conn.ip_config, ipv6).push(routing_rule);
+        Ok(())
+    }
+}
+
+/// Returns the IPv4 or the IPv6 routing rules of an IP configuration.
+fn routing_rules_mut(ip_config: &mut IpConfig, ipv6: bool) -> &mut Vec<RoutingRule> {
+    if ipv6 {
+        &mut ip_config.routing_rules6
+    } else {
+        &mut ip_config.routing_rules4
+    }
+}
+
+/// Returns the IP family of a routing rule.
+///
+/// The family is inferred from the source and destination networks, using the `family` setting
+/// when they are not set (IPv4 by default).
+fn route_rule_family(rule: &NmstateRouteRule) -> Result<NmstateRouteRuleFamily, NetworkStateError> {
+    let families: Vec<NmstateRouteRuleFamily> = [rule.ip_from, rule.ip_to]
+        .iter()
+        .flatten()
+        .map(|n| match n {
+            IpInet::V4(_) => NmstateRouteRuleFamily::Ipv4,
+            IpInet::V6(_) => NmstateRouteRuleFamily::Ipv6,
+        })
+        .chain(rule.family)
+        .collect();
+    match families.first() {
+        None => Ok(NmstateRouteRuleFamily::Ipv4),
+        Some(family) if families.iter().all(|f| f == family) => Ok(*family),
+        Some(_) => Err(NetworkStateError::InvalidRoutingRule(
+            "the rule mixes IPv4 and IPv6".to_string(),
+        )),
+    }
+}
+
+/// Returns the interface name of a connection, falling back to its ID.
+fn interface_name(conn: &Connection) -> &str {
+    conn.interface.as_deref().unwrap_or(&conn.id)
+}
+
+/// Returns the nmstate interface type for the given configuration, if it is supported.
+fn nmstate_type(config: &ConnectionConfig) -> Option<&'static str> {
+    let type_ = match config {
+        ConnectionConfig::Ethernet => "ethernet",
+        ConnectionConfig::Loopback => "loopback",
+        ConnectionConfig::Dummy => "dummy",
+        ConnectionConfig::Bond(_) => "bond",
+        ConnectionConfig::Bridge(_) => "linux-bridge",
+        ConnectionConfig::Vlan(_) => "vlan",
+        ConnectionConfig::Infiniband(_) => "infiniband",
+        _ => return None,
+    };
+    Some(type_)
+}
+
+fn ipv4_to_nmstate(config: &IpConfig) -> NmstateIpConfig {
+    let enabled = config.method4 != Ipv4Method::Disabled;
+    NmstateIpConfig {
+        enabled,
+        dhcp: enabled.then_some(config.method4 == Ipv4Method::Auto),
+        address: addresses_to_nmstate(&config.addresses, IpAddr::is_ipv4),
+        auto_dns: config.ignore_auto_dns.then_some(false),
+        ..Default::default()
+    }
+}
+
+fn ipv6_to_nmstate(config: &IpConfig) -> NmstateIpConfig {
+    let enabled = !matches!(config.method6, Ipv6Method::Disabled | Ipv6Method::Ignore);
+    NmstateIpConfig {
+        enabled,
+        dhcp: enabled.then_some(matches!(
+            config.method6,
+            Ipv6Method::Auto | Ipv6Method::Dhcp
+        )),
+        autoconf: enabled.then_some(config.method6 == Ipv6Method::Auto),
+        address: addresses_to_nmstate(&config.addresses, IpAddr::is_ipv6),
+        auto_dns: config.ignore_auto_dns.then_some(false),
+    }
+}
+
+fn addresses_to_nmstate(
+    addresses: &[IpInet],
+    filter: fn(&IpAddr) -> bool,
+) -> Vec<NmstateIpAddress> {
+    addresses
+        .iter()
+        .filter(|a| filter(&a.address()))
+        .map(|a| NmstateIpAddress {
+            ip: a.address(),
+            prefix_length: a.network_length(),
+        })
+        .collect()
+}
+
+fn default_route_to_nmstate(name: &str, gateway: IpAddr) -> NmstateRoute {
+    let destination = match gateway {
+        IpAddr::V4(_) => "0.0.0.0/0",
+        IpAddr::V6(_) => "::/0",
+    };
+    NmstateRoute {
+        destination: destination.parse().unwrap(),
+        next_hop_interface: Some(name.to_string()),
+        next_hop_address: Some(gateway),
+        metric: None,
+        table_id: None,
+        state: None,
+    }
+}
+
+fn bond_to_nmstate(config: &BondConfig, ports: &[&Connection]) -> NmstateBondConfig {
+    let options = config
+        .options
+        .0
+        .iter()
+        .map(|(key, value)| {
+            let value = match value.parse::<u64>() {
+                Ok(number) => number.into(),
+                Err(_) => serde_json::Value::String(value.clone()),
+            };
+            (key.clone(), value)
+        })
+        .collect();
+    NmstateBondConfig {
+        mode: Some(config.mode.to_string()),
+        port: Some(
+            ports
+                .iter()
+                .map(|p| interface_name(p).to_string())
+                .collect(),
+        ),
+        options,
+    }
+}
+
+fn bridge_to_nmstate(config: &BridgeConfig, ports: &[&Connection]) -> NmstateBridgeConfig {
+    let ports = ports
+        .iter()
+        .map(|p| {
+            let mut port = NmstateBridgePort {
+                name: interface_name(p).to_string(),
+                ..Default::default()
+            };
+            if let PortConfig::Bridge(config) = &p.port_config {
+                port.stp_priority = config.priority;
+                port.stp_path_cost = config.path_cost;
+            }
+            port
+        })
+        .collect();
+    NmstateBridgeConfig {
+        options: Some(NmstateBridgeOptions {
+            mac_ageing_time: config.ageing_time,
+            stp: Some(NmstateStpOptions {
+                enabled: Some(config.stp),
+                priority: config.priority,
+                forward_delay: config.forward_delay,
+                hello_time: config.hello_time,
+                max_age: config.max_age,
+            }),
+        }),
+        port: Some(ports),
+    }
+}
+
+/// Builds the connection configuration for an interface.
+///
+/// The current configuration is kept when the interface type does not change and the document
+/// does not include the type specific section.
+///
+/// * `iface`: nmstate interface.
+/// * `current`: current connection configuration.
+fn config_from_nmstate(
+    iface: &NmstateInterface,
+    current: ConnectionConfig,
+) -> Result<ConnectionConfig, NetworkStateError> {
+    let type_ = match &iface.type_ {
+        Some(type_) => type_.as_str(),
+        None => nmstate_type(&current).unwrap_or("ethernet"),
+    };
+
+    let config = match type_ {
+        "ethernet" => ConnectionConfig::Ethernet,
+        "loopback" => ConnectionConfig::Loopback,
+        "dummy" => ConnectionConfig::Dummy,
+        "bond" => {
+            let mut config = match current {
+                ConnectionConfig::Bond(config) => config,
+                _ => BondConfig::default(),
+            };
+            if let Some(bond) = &iface.link_aggregation {
+                if let Some(mode) = &bond.mode {
+                    config.mode = BondMode::try_from(mode.as_str())
+                        .map_err(|_| NetworkStateError::InvalidBondMode(mode.clone()))?;
+                }
+                if !bond.options.is_empty() {
+                    let options = bond
+                        .options
+                        .iter()
+                        .map(|(key, value)| {
+                            let value = match value {
+                                serde_json::Value::String(value) => value.clone(),
+                                value => value.to_string(),
+                            };
+                            (key.clone(), value)
+                        })
+                        .collect();
+                    config.options = BondOptions(options);
+                }
+            }
+            config.into()
+        }
+        "linux-bridge" => {
+            let mut config = match current {
+                ConnectionConfig::Bridge(config) => config,
+                // NetworkManager enables STP by default
+                _ => BridgeConfig {
+                    stp: true,
+                    ..Default::default()
+                },
+            };
+            if let Some(options) = iface.bridge.as_ref().and_then(|b| b.options.as_ref()) {
+                if options.mac_ageing_time.is_some() {
+                    config.ageing_time = options.mac_ageing_time;
+                }
+                if let Some(stp) = &options.stp {
+                    config.stp = stp.enabled.unwrap_or(config.stp);
+                    config.priority = stp.priority.or(config.priority);
+                    config.forward_delay = stp.forward_delay.or(config.forward_delay);
+                    config.hello_time = stp.hello_time.or(config.hello_time);
+                    config.max_age = stp.max_age.or(config.max_age);
+                }
+            }
+            config.into()
+        }
+        "vlan" => match (&iface.vlan, current) {
+            (Some(vlan), _) => {
+                let protocol = match vlan.protocol.as_deref() {
+                    None | Some("802.1q") => VlanProtocol::IEEE802_1Q,
+                    Some("802.1ad") => VlanProtocol::IEEE802_1ad,
+                    Some(protocol) => {
+                        return Err(NetworkStateError::InvalidVlanProtocol(protocol.to_string()))
+                    }
+                };
+                VlanConfig {
+                    parent: vlan.base_iface.clone(),
+                    id: vlan.id,
+                    protocol,
+                }
+                .into()
+            }
+            (None, ConnectionConfig::Vlan(config)) => config.into(),
+            (None, _) => {
+                return Err(NetworkStateError::InvalidNmstate(format!(
+                    "missing VLAN settings for '{}'",
+                    iface.name
+                )))
+            }
+        },
+        "infiniband" => {
+            let mut config = match current {
+                ConnectionConfig::Infiniband(config) => config,
+                _ => InfinibandConfig::default(),
+            };
+            if let Some(infiniband) = &iface.infiniband {
+                if infiniband.base_iface.is_some() {
+                    config.parent = infiniband.base_iface.clone();
+                }
+                if let Some(mode) = &infiniband.mode {
+                    config.transport_mode =
+                        InfinibandTransportMode::from_str(mode).map_err(|_| {
+                            NetworkStateError::InvalidInfinibandTransportMode(mode.clone())
+                        })?;
+                }
+                if let Some(pkey) = &infiniband.pkey {
+                    config.p_key = Some(pkey_from_nmstate(pkey)?);
+                }
+            }
+            config.into()
+        }
+        _ => {
+            return Err(NetworkStateError::InvalidNmstate(format!(
+                "unsupported interface type '{}' ('{}')",
+                type_, iface.name
+            )))
+        }
+    };
+
+    Ok(config)
+}
+
+fn pkey_from_nmstate(pkey: &NmstateInfinibandPkey) -> Result<i32, NetworkStateError> {
+    match pkey {
+        NmstateInfinibandPkey::Number(number) => Ok(*number),
+        NmstateInfinibandPkey::Text(text) => {
+            let number = match text.strip_prefix("0x") {
+                Some(hex) => i32::from_str_radix(hex, 16),
+                None => text.parse(),
+            };
+            number.map_err(|_| {
+                NetworkStateError::InvalidNmstate(format!("invalid InfiniBand pkey '{text}'"))
+            })
+        }
+    }
+}
+
+/// Updates the IPv4 settings.
+///
+/// nmstate does not have an equivalent to the "link-local" method, so an enabled configuration
+/// without DHCP nor addresses is considered as disabled.
+fn ipv4_from_nmstate(
+    ip_config: &mut IpConfig,
+    config: &NmstateIpConfig,
+) -> Result<(), NetworkStateError> {
+    ip_config.method4 = if !config.enabled {
+        Ipv4Method::Disabled
+    } else if config.dhcp.unwrap_or_default() {
+        Ipv4Method::Auto
+    } else if !config.address.is_empty() {
+        Ipv4Method::Manual
+    } else {
+        Ipv4Method::Disabled
+    };
+    update_addresses(ip_config, &config.address, IpAddr::is_ipv4)?;
+    if let Some(auto_dns) = config.auto_dns {
+        ip_config.ignore_auto_dns = !auto_dns;
+    }
+    Ok(())
+}
+
+/// Updates the IPv6 settings.
+fn ipv6_from_nmstate(
+    ip_config: &mut IpConfig,
+    config: &NmstateIpConfig,
+) -> Result<(), NetworkStateError> {
+    let dhcp = config.dhcp.unwrap_or_default();
+    let autoconf = config.autoconf.unwrap_or_default();
+    ip_config.method6 = if !config.enabled {
+        Ipv6Method::Disabled
+    } else if autoconf {
+        Ipv6Method::Auto
+    } else if dhcp {
+        Ipv6Method::Dhcp
+    } else if !config.address.is_empty() {
+        Ipv6Method::Manual
+    } else {
+        Ipv6Method::LinkLocal
+    };
+    update_addresses(ip_config, &config.address, IpAddr::is_ipv6)?;
+    if let Some(auto_dns) = config.auto_dns {
+        ip_config.ignore_auto_dns = !auto_dns;
+    }
+    Ok(())
+}
+
+/// Replaces the addresses of one IP family.
+///
+/// * `ip_config`: IP configuration to update.
+/// * `addresses`: new addresses.
+/// * `family`: function to select the addresses of the family to replace.
+fn update_addresses(
+    ip_config: &mut IpConfig,
+    addresses: &[NmstateIpAddress],
+    family: fn(&IpAddr) -> bool,
+) -> Result<(), NetworkStateError> {
+    let mut new_addresses = vec![];
+    for address in addresses {
+        if !family(&address.ip) {
+            return Err(NetworkStateError::InvalidIpAddr(address.ip.to_string()));
+        }
+        let inet = IpInet::new(address.ip, address.prefix_length).map_err(|_| {
+            NetworkStateError::InvalidIpAddr(format!("{}/{}", address.ip, address.prefix_length))
+        })?;
+        new_addresses.push(inet);
+    }
+    ip_config.addresses.retain(|a| !family(&a.address()));
+    ip_config.addresses.extend(new_addresses);
+    Ok(())
+}
+
+/// Converts a YAML document into a JSON value, so it can be deserialized using serde.
+fn yaml_to_json(yaml: Yaml) -> Result<serde_json::Value, NetworkStateError> {
+    let value = match yaml {
+        Yaml::Null => serde_json::Value::Null,
+        Yaml::Boolean(value) => value.into(),
+        Yaml::Integer(value) => value.into(),
+        Yaml::Real(value) => match value
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+        {
+            Some(number) => serde_json::Value::Number(number),
+            None => serde_json::Value::String(value),
+        },
+        Yaml::String(value) => value.into(),
+        Yaml::Array(items) => items
+            .into_iter()
+            .map(yaml_to_json)
+            .collect::<Result<Vec<_>, _>>()?
+            .into(),
+        Yaml::Hash(hash) => {
+            let mut map = serde_json::Map::new();
+            for (key, value) in hash {
+                let key = match key {
+                    Yaml::String(key) | Yaml::Real(key) => key,
+                    Yaml::Integer(key) => key.to_string(),
+                    Yaml::Boolean(key) => key.to_string(),
+                    key => {
+                        return Err(NetworkStateError::InvalidNmstate(format!(
+                            "unsupported key {key:?}"
+                        )))
+                    }
+                };
+                map.insert(key, yaml_to_json(value)?);
+            }
+            serde_json::Value::Object(map)
+        }
+        Yaml::Alias(_) | Yaml::BadValue => {
+            return Err(NetworkStateError::InvalidNmstate(
+                "unsupported YAML value".to_string(),
+            ))
+        }
+    };
+    Ok(value)
+}
+
+/// Keys that are written first, as nmstate does, to make the document easier to read.
+const LEADING_KEYS: [&str; 3] = ["name", "type", "state"];
+
+/// Converts a JSON value into a YAML document.
+fn json_to_yaml(value: &serde_json::Value) -> Yaml {
+    match value {
+        serde_json::Value::Null => Yaml::Null,
+        serde_json::Value::Bool(value) => Yaml::Boolean(*value),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(number) => Yaml::Integer(number),
+            None => Yaml::Real(number.to_string()),
+        },
+        serde_json::Value::String(value) => Yaml::String(value.clone()),
+        serde_json::Value::Array(items) => Yaml::Array(items.iter().map(json_to_yaml).collect()),
+        serde_json::Value::Object(map) => {
+            let mut hash = Hash::new();
+            let leading = LEADING_KEYS
+                .iter()
+                .filter_map(|key| map.get_key_value(*key));
+            let rest = map
+                .iter()
+                .filter(|(key, _)| !LEADING_KEYS.contains(&key.as_str()));
+            for (key, value) in leading.chain(rest) {
+                hash.insert(Yaml::String(key.clone()), json_to_yaml(value));
+            }
+            Yaml::Hash(hash)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::model::{Connection, ConnectionConfig, NetworkState};
+
+    fn build_state() -> NetworkState {
+        let eth0 = Connection {
+            id: "Wired connection 1".to_string(),
+            interface: Some("eth0".to_string()),
+            ip_config: IpConfig {
+                method4: Ipv4Method::Manual,
+                method6: Ipv6Method::Auto,
+                addresses: vec!["192.168.1.10/24".parse().unwrap()],
+                gateway4: Some("192.168.1.1".parse().unwrap()),
+                nameservers: vec!["192.168.1.1".parse().unwrap()],
+                dns_searchlist: vec!["example.net".to_string()],
+                routes4: vec![IpRoute {
+                    destination: "10.0.0.0/8".parse().unwrap(),
+                    next_hop: Some("192.168.1.254".parse().unwrap()),
+                    metric: Some(100),
+                    table: Some(100),
+                }],
+                routing_rules4: vec![RoutingRule {
+                    priority: 100,
+                    from: Some("192.168.1.0/24".parse().unwrap()),
+                    to: None,
+                    table: 100,
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let wlan0 = Connection {
+            id: "wlan0".to_string(),
+            config: ConnectionConfig::Wireless(Default::default()),
+            ..Default::default()
+        };
+        let tun0 = Connection {
+            id: "tun0".to_string(),
+            config: ConnectionConfig::Tun(Default::default()),
+            ..Default::default()
+        };
+        NetworkState::new(Default::default(), vec![], vec![], vec![eth0, wlan0, tun0])
+    }
+
+    #[test]
+    fn test_to_nmstate() {
+        let state = build_state();
+        let nmstate = state.to_nmstate();
+
+        assert_eq!(nmstate.interfaces.len(), 1);
+        let eth0 = &nmstate.interfaces[0];
+        assert_eq!(eth0.name, "eth0");
+        assert_eq!(eth0.type_.as_deref(), Some("ethernet"));
+        let ipv4 = eth0.ipv4.as_ref().unwrap();
+        assert!(ipv4.enabled);
+        assert_eq!(ipv4.dhcp, Some(false));
+        assert_eq!(
+            ipv4.address,
+            vec![NmstateIpAddress {
+                ip: "192.168.1.10".parse().unwrap(),
+                prefix_length: 24
+            }]
+        );
+        let ipv6 = eth0.ipv6.as_ref().unwrap();
+        assert_eq!(ipv6.autoconf, Some(true));
+
+        let routes = &nmstate.routes.as_ref().unwrap().config;
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].destination, "0.0.0.0/0".parse().unwrap());
+        assert_eq!(
+            routes[0].next_hop_address,
+            Some("192.168.1.1".parse().unwrap())
+        );
+        assert_eq!(routes[1].destination, "10.0.0.0/8".parse().unwrap());
+        assert_eq!(routes[1].metric, Some(100));
+
+        let rules = &nmstate.route_rules.as_ref().unwrap().config;
+        assert_eq!(
+            rules,
+            &vec![NmstateRouteRule {
+                family: Some(NmstateRouteRuleFamily::Ipv4),
+                ip_from: Some("192.168.1.0/24".parse().unwrap()),
+                priority: Some(100),
+                route_table: Some(100),
+                ..Default::default()
+            }]
+        );
+
+        let dns = nmstate.dns_resolver.unwrap().config.unwrap();
+        assert_eq!(dns.server, vec!["192.168.1.1".parse::<IpAddr>().unwrap()]);
+        assert_eq!(dns.search, vec!["example.net".to_string()]);
+
+        assert_eq!(
+            state.nmstate_skipped_connections(),
+            vec!["wlan0".to_string(), "tun0".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let mut state = build_state();
+        let yaml = state.to_nmstate().to_yaml().unwrap();
+        assert!(yaml.contains("  - name: eth0\n    type: ethernet\n    state: up\n"));
+        assert!(yaml.contains("prefix-length: 24"));
+        assert!(yaml.contains("next-hop-interface: eth0"));
+        assert!(yaml.contains("route-table: 100"));
+
+        let nmstate = NmstateState::from_yaml(&yaml).unwrap();
+        assert_eq!(nmstate, state.to_nmstate());
+
+        let original = state.clone();
+        state.apply_nmstate(nmstate).unwrap();
+        assert_eq!(state.connections, original.connections);
+    }
+
+    #[test]
+    fn test_apply_nmstate() {
+        let mut state = build_state();
+        let yaml = r#"
+interfaces:
+  - name: bond0
+    type: bond
+    state: up
+    ipv4:
+      enabled: true
+      dhcp: true
+    link-aggregation:
+      mode: active-backup
+      port:
+        - eth1
+        - eth2
+      options:
+        miimon: 100
+  - name: bond0.10
+    type: vlan
+    vlan:
+      base-iface: bond0
+      id: 10
+    ipv4:
+      enabled: true
+      address:
+        - ip: 10.10.0.2
+          prefix-length: 24
+  - name: eth0
+    state: absent
+routes:
+  config:
+    - destination: 0.0.0.0/0
+      next-hop-interface: bond0.10
+      next-hop-address: 10.10.0.1
+dns-resolver:
+  config:
+    server:
+      - 10.10.0.1
+"#;
+        let nmstate = NmstateState::from_yaml(yaml).unwrap();
+        state.apply_nmstate(nmstate).unwrap();
+
+        let eth0 = state.get_connection("Wired connection 1").unwrap();
+        assert!(eth0.is_removed());
+
+        let bond0 = state.get_connection("bond0").unwrap();
+        let ConnectionConfig::Bond(bond) = &bond0.config else {
+            panic!("Unexpected connection type");
+        };
+        assert_eq!(bond.mode, BondMode::ActiveBackup);
+        assert_eq!(bond.options.0.get("miimon"), Some(&"100".to_string()));
+        assert_eq!(bond0.ip_config.method4, Ipv4Method::Auto);
+        assert_eq!(
+            bond0.ip_config.nameservers,
+            vec!["10.10.0.1".parse::<IpAddr>().unwrap()]
+        );
+
+        let eth1 = state.get_connection_by_interface("eth1").unwrap();
+        assert_eq!(eth1.controller, Some(bond0.uuid));
+        assert!(eth1.ip_config.nameservers.is_empty());
+
+        let nmstate = state.to_nmstate();
+        let eth1 = nmstate
+            .interfaces
+            .iter()
+            .find(|i| i.name == "eth1")
+            .unwrap();
+        assert_eq!(eth1.ipv4, Some(NmstateIpConfig::default()));
+
+        let vlan = state.get_connection("bond0.10").unwrap();
+        let ConnectionConfig::Vlan(config) = &vlan.config else {
+            panic!("Unexpected connection type");
+        };
+        assert_eq!(config.parent, "bond0");
+        assert_eq!(config.id, 10);
+        assert_eq!(vlan.ip_config.method4, Ipv4Method::Manual);
+        assert_eq!(
+            vlan.ip_config.addresses,
+            vec!["10.10.0.2/24".parse::<IpInet>().unwrap()]
+        );
+        assert_eq!(vlan.ip_config.gateway4, Some("10.10.0.1".parse().unwrap()));
+
+        // wireless connections are not affected
+        let wlan0 = state.get_connection("wlan0").unwrap();
+        assert!(!wlan0.is_removed());
+    }
+
+    #[test]
+    fn test_apply_nmstate_route_rules() {
+        let mut state = build_state();
+        let yaml = r#"
+route-rules:
+  config:
+    - ip-from: 192.168.1.0/24
+      priority: 100
+      state: absent
+    - ip-to: 10.0.0.0/8
+      priority: 200
+      route-table: 100
+"#;
+        let nmstate = NmstateState::from_yaml(yaml).unwrap();
+        state.apply_nmstate(nmstate).unwrap();
+
+        let eth0 = state.get_connection("Wired connection 1").unwrap();
+        assert_eq!(
+            eth0.ip_config.routing_rules4,
+            vec![RoutingRule {
+                priority: 200,
+                from: None,
+                to: Some("10.0.0.0/8".parse().unwrap()),
+                table: 100,
+            }]
+        );
+
+        // there are no routes in the table 300
+        let original = state.clone();
+        let nmstate = NmstateState::from_yaml(
+            "route-rules:\n  config:\n    - priority: 300\n      route-table: 300\n",
+        )
+        .unwrap();
+        let error = state.apply_nmstate(nmstate).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid routing rule: no interface has routes in the table 300"
+        );
+        assert_eq!(state.connections, original.connections);
+    }
+
+    #[test]
+    fn test_apply_invalid_nmstate() {
+        let mut state = build_state();
+        let original = state.clone();
+        let nmstate = NmstateState::from_yaml(
+            "interfaces:\n  - name: eth1\n  - name: wg0\n    type: wireguard\n",
+        )
+        .unwrap();
+
+        let error = state.apply_nmstate(nmstate).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid nmstate document: unsupported interface type 'wireguard' ('wg0')"
+        );
+        assert_eq!(state.connections, original.connections);
+
+        assert!(NmstateState::from_yaml("interfaces: [{ name: eth0, state: unknown }]").is_err());
+    }
+}
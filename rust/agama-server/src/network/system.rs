@@ -21,6 +21,7 @@
 use super::{
     error::NetworkStateError,
     model::{AccessPoint, Device, NetworkChange, StateConfig},
+    nmstate::NmstateState,
     NetworkAdapterError,
 };
 use crate::network::{
@@ -213,6 +214,45 @@ impl NetworkSystemClient {
         Ok(result?)
     }
 
+    /// Sets the ports of a controller connection (a bond or a bridge).
+    ///
+    /// * `uuid`: controller UUID.
+    /// * `ports`: IDs or interface names of the ports.
+    pub async fn set_ports(
+        &self,
+        uuid: Uuid,
+        ports: Vec<String>,
+    ) -> Result<(), NetworkSystemError> {
+        let (tx, rx) = oneshot::channel();
+        self.actions
+            .send(Action::SetPorts(uuid, Box::new(ports), tx))?;
+        let result = rx.await?;
+        Ok(result?)
+    }
+
+    /// Returns the network configuration in nmstate format.
+    ///
+    /// It also returns the IDs of the connections that were left out because they cannot be
+    /// represented in that format.
+    pub async fn get_nmstate(&self) -> Result<(NmstateState, Vec<String>), NetworkSystemError> {
+        let (tx, rx) = oneshot::channel();
+        self.actions.send(Action::GetNmstate(tx))?;
+        Ok(rx.await?)
+    }
+
+    /// Applies a nmstate desired state.
+    ///
+    /// The changes are not written until the configuration is applied.
+    ///
+    /// * `nmstate`: desired state.
+    pub async fn apply_nmstate(&self, nmstate: NmstateState) -> Result<(), NetworkSystemError> {
+        let (tx, rx) = oneshot::channel();
+        self.actions
+            .send(Action::ApplyNmstate(Box::new(nmstate), tx))?;
+        let result = rx.await?;
+        Ok(result?)
+    }
+
     /// Applies the network configuration.
     pub async fn apply(&self) -> Result<(), NetworkSystemError> {
         let (tx, rx) = oneshot::channel();
@@ -353,6 +393,14 @@ impl<T: Adapter> NetworkSystemServer<T> {
 
                 tx.send(result).unwrap();
             }
+            Action::GetNmstate(tx) => {
+                let skipped = self.state.nmstate_skipped_connections();
+                tx.send((self.state.to_nmstate(), skipped)).unwrap();
+            }
+            Action::ApplyNmstate(nmstate, tx) => {
+                let result = self.state.apply_nmstate(*nmstate);
+                tx.send(result).unwrap();
+            }
             Action::Apply(tx) => {
                 let result = self.write().await;
                 tx.send(result).unwrap();
@@ -407,6 +455,9 @@ impl<T: Adapter> NetworkSystemServer<T> {
 
     /// Writes the network configuration.
     pub async fn write(&mut self) -> Result<(), NetworkAdapterError> {
+        self.state
+            .validate()
+            .map_err(NetworkAdapterError::Validation)?;
         self.adapter.write(&self.state).await?;
         self.state = self.adapter.read(StateConfig::default()).await?;
         Ok(())
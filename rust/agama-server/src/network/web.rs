@@ -27,7 +27,7 @@ use crate::{
 use anyhow::Context;
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::{delete, get, patch, post},
     Json, Router,
@@ -36,11 +36,15 @@ use axum::{
 use super::{
     error::NetworkStateError,
     model::{AccessPoint, GeneralState},
+    nmstate::NmstateState,
     system::{NetworkSystemClient, NetworkSystemError},
-    Adapter,
+    Adapter, NetworkAdapterError,
 };
 
-use crate::network::{model::Connection, model::Device, NetworkSystem};
+use crate::network::{
+    model::{Connection, ConnectionConfig, Device},
+    NetworkSystem,
+};
 use agama_lib::{error::ServiceError, network::settings::NetworkConnection};
 
 use serde_json::json;
@@ -124,6 +128,7 @@ pub async fn network_service<T: Adapter + Send + Sync + 'static>(
         .route("/connections/:id/connect", patch(connect))
         .route("/connections/:id/disconnect", patch(disconnect))
         .route("/devices", get(devices))
+        .route("/nmstate", get(nmstate).put(update_nmstate))
         .route("/system/apply", post(apply))
         .route("/wifi", get(wifi_networks))
         .with_state(state))
@@ -211,12 +216,38 @@ async fn connections(
     State(state): State<NetworkServiceState>,
 ) -> Result<Json<Vec<NetworkConnection>>, NetworkError> {
     let connections = state.network.get_connections().await?;
-    let connections = connections
+    let network_connections = connections
         .iter()
-        .map(|c| NetworkConnection::try_from(c.clone()).unwrap())
+        .map(|c| {
+            let mut conn = NetworkConnection::try_from(c.clone()).unwrap();
+            add_ports(&mut conn, c, &connections);
+            conn
+        })
         .collect();
 
-    Ok(Json(connections))
+    Ok(Json(network_connections))
+}
+
+/// Adds the ports (the connections it controls) to a bond or bridge connection.
+///
+/// * `network_connection`: connection to add the ports to.
+/// * `conn`: controller connection.
+/// * `connections`: all the connections.
+fn add_ports(
+    network_connection: &mut NetworkConnection,
+    conn: &Connection,
+    connections: &[Connection],
+) {
+    let ports = connections
+        .iter()
+        .filter(|c| c.controller == Some(conn.uuid))
+        .map(|c| c.interface.as_deref().unwrap_or(&c.id).to_string());
+
+    if let Some(bond) = &mut network_connection.bond {
+        bond.ports = ports.collect();
+    } else if let Some(bridge) = &mut network_connection.bridge {
+        bridge.ports = ports.collect();
+    }
 }
 
 #[utoipa::path(
@@ -231,13 +262,19 @@ async fn add_connection(
     State(state): State<NetworkServiceState>,
     Json(conn): Json<NetworkConnection>,
 ) -> Result<Json<Connection>, NetworkError> {
+    let ports = conn.ports().to_vec();
     let conn = Connection::try_from(conn)?;
     let id = conn.id.clone();
 
     state.network.add_connection(conn).await?;
     match state.network.get_connection(&id).await? {
         None => Err(NetworkError::CannotAddConnection(id.clone())),
-        Some(conn) => Ok(Json(conn)),
+        Some(conn) => {
+            if !ports.is_empty() {
+                state.network.set_ports(conn.uuid, ports).await?;
+            }
+            Ok(Json(conn))
+        }
     }
 }
 
@@ -258,10 +295,12 @@ async fn connection(
         .get_connection(&id)
         .await?
         .ok_or_else(|| NetworkError::UnknownConnection(id.clone()))?;
+    let connections = state.network.get_connections().await?;
 
-    let conn = NetworkConnection::try_from(conn)?;
+    let mut network_connection = NetworkConnection::try_from(conn.clone())?;
+    add_ports(&mut network_connection, &conn, &connections);
 
-    Ok(Json(conn))
+    Ok(Json(network_connection))
 }
 
 #[utoipa::path(
@@ -301,15 +340,28 @@ async fn update_connection(
         .get_connection(&id)
         .await?
         .ok_or_else(|| NetworkError::UnknownConnection(id.clone()))?;
+    let ports = conn.ports().to_vec();
     let mut conn = Connection::try_from(conn)?;
     if orig_conn.id != id {
         // FIXME: why?
         return Err(NetworkError::UnknownConnection(id));
     } else {
         conn.uuid = orig_conn.uuid;
+        // the ports are set through the controller
+        conn.controller = orig_conn.controller;
+    }
+
+    if let (ConnectionConfig::Wireguard(config), ConnectionConfig::Wireguard(orig_config)) =
+        (&mut conn.config, &orig_conn.config)
+    {
+        config.keep_secrets(orig_config);
     }
 
+    let uuid = conn.uuid;
     state.network.update_connection(conn).await?;
+    if !ports.is_empty() {
+        state.network.set_ports(uuid, ports).await?;
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -377,6 +429,64 @@ async fn disconnect(
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(
+    get,
+    path = "/nmstate",
+    context_path = "/api/network",
+    responses(
+      (status = 200, description = "Network configuration in nmstate format. The connections that cannot be represented in that format are listed in a comment at the top of the document.", body = String, content_type = "application/yaml")
+    )
+)]
+async fn nmstate(
+    State(state): State<NetworkServiceState>,
+) -> Result<impl IntoResponse, NetworkError> {
+    let (nmstate, skipped) = state.network.get_nmstate().await?;
+    let mut yaml = String::new();
+    if !skipped.is_empty() {
+        // quote the IDs so they cannot break the comment
+        let ids: Vec<String> = skipped.iter().map(|id| format!("{id:?}")).collect();
+        tracing::warn!(
+            "Connections left out of the nmstate document: {}",
+            ids.join(", ")
+        );
+        yaml.push_str(&format!(
+            "# Connections that cannot be represented in nmstate format: {}\n",
+            ids.join(", ")
+        ));
+    }
+    yaml.push_str(&nmstate.to_yaml()?);
+    Ok(([(header::CONTENT_TYPE, "application/yaml")], yaml))
+}
+
+#[utoipa::path(
+    put,
+    path = "/nmstate",
+    context_path = "/api/network",
+    request_body(content = String, description = "nmstate desired state (YAML or JSON)", content_type = "application/yaml"),
+    responses(
+      (status = 204, description = "The desired state was applied to the network configuration"),
+      (status = 400, description = "The desired state is not valid")
+    )
+)]
+async fn update_nmstate(
+    State(state): State<NetworkServiceState>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<impl IntoResponse, NetworkError> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let nmstate = if is_json {
+        NmstateState::from_json(&body)?
+    } else {
+        NmstateState::from_yaml(&body)?
+    };
+    state.network.apply_nmstate(nmstate).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[utoipa::path(
     post,
     path = "/system/apply",
@@ -388,11 +498,12 @@ async fn disconnect(
 async fn apply(
     State(state): State<NetworkServiceState>,
 ) -> Result<impl IntoResponse, NetworkError> {
-    state
-        .network
-        .apply()
-        .await
-        .map_err(|_| NetworkError::CannotApplyConfig)?;
+    state.network.apply().await.map_err(|e| match e {
+        NetworkSystemError::AdapterError(NetworkAdapterError::Validation(e)) => {
+            NetworkError::Error(e)
+        }
+        _ => NetworkError::CannotApplyConfig,
+    })?;
 
     Ok(StatusCode::NO_CONTENT)
 }
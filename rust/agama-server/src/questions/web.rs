@@ -24,6 +24,8 @@
 //!
 //! * `questions_service` which returns the Axum service.
 //! * `questions_stream` which offers an stream that emits questions related signals.
+//!
+//! Additionally, `open_questions` returns the number of questions waiting for an answer.
 
 use crate::{error::Error, web::Event};
 use agama_lib::{
@@ -282,6 +284,14 @@ pub async fn questions_stream(
     Ok(Box::pin(stream))
 }
 
+/// Returns the number of questions waiting for an answer.
+///
+/// * `dbus`: D-Bus connection.
+pub async fn open_questions(dbus: zbus::Connection) -> Result<usize, Error> {
+    let client = QuestionsClient::new(dbus).await?;
+    Ok(client.questions().await?.len())
+}
+
 /// Returns the list of questions that waits for answer.
 ///
 /// * `state`: service state.
@@ -29,8 +29,8 @@ use crate::{
     error::Error,
     l10n::web::l10n_service,
     manager::web::{manager_service, manager_stream},
-    network::{web::network_service, NetworkManagerAdapter},
-    questions::web::{questions_service, questions_stream},
+    network::{web::network_service, KeyfileAdapter, NetworkManagerAdapter},
+    questions::web::{open_questions, questions_service, questions_stream},
     scripts::web::scripts_service,
     software::web::{software_service, software_streams},
     storage::web::{storage_service, storage_streams},
@@ -39,31 +39,52 @@ use crate::{
 };
 use axum::Router;
 
+mod audit;
 mod auth;
+mod client_cert;
 pub mod common;
 mod config;
 pub mod docs;
 mod event;
 mod http;
+mod journal;
+pub mod keys;
+mod metrics;
 mod service;
+mod sessions;
+mod sse;
 mod state;
+mod throttle;
 mod ws;
 
-use agama_lib::{connection, error::ServiceError};
+use agama_lib::{connection, error::ServiceError, manager::ManagerClient};
+pub use audit::{AuditEntry, AuditLog};
+pub use client_cert::{ClientCertificate, ClientCertsConfig};
 pub use config::ServiceConfig;
 pub use event::{Event, EventsReceiver, EventsSender};
+pub use journal::{
+    EventFilter, EventsJournal, EventsQuery, JournalEntry, JournalMessage, Subscription,
+    DEFAULT_JOURNAL_CAPACITY,
+};
+use keys::TokenKeys;
+pub use metrics::Metrics;
 pub use service::MainServiceBuilder;
+pub use sessions::{Session, SessionsRegistry};
 use std::path::Path;
+pub use throttle::{LockoutStatus, LoginLimits};
+use tokio::sync::broadcast::error::RecvError;
 use tokio_stream::{StreamExt, StreamMap};
 
 /// Returns a service that implements the web-based Agama API.
 ///
 /// * `config`: service configuration.
+/// * `keys`: keys to sign and verify the tokens.
 /// * `events`: channel to send the events through the WebSocket.
 /// * `dbus`: D-Bus connection.
 /// * `web_ui_dir`: public directory containing the web UI.
 pub async fn service<P>(
     config: ServiceConfig,
+    keys: TokenKeys,
     events: EventsSender,
     dbus: zbus::Connection,
     web_ui_dir: P,
@@ -71,9 +92,21 @@ pub async fn service<P>(
 where
     P: AsRef<Path>,
 {
-    let network_adapter = NetworkManagerAdapter::from_system()
-        .await
-        .expect("Could not connect to NetworkManager to read the configuration");
+    let network = network_router(&config, events.clone()).await?;
+
+    let metrics = Metrics::new();
+    match ManagerClient::new(dbus.clone()).await {
+        Ok(manager) => match manager.current_installation_phase().await {
+            Ok(phase) => metrics.set_phase(phase),
+            Err(error) => tracing::warn!("Could not read the installation phase: {}", error),
+        },
+        Err(error) => tracing::warn!("Could not connect to the manager: {}", error),
+    }
+    tokio::spawn(monitor_questions(
+        dbus.clone(),
+        events.subscribe(),
+        metrics.clone(),
+    ));
 
     let router = MainServiceBuilder::new(events.clone(), web_ui_dir)
         .add_service("/l10n", l10n_service(dbus.clone(), events.clone()).await?)
@@ -81,15 +114,66 @@ where
         .add_service("/software", software_service(dbus.clone()).await?)
         .add_service("/storage", storage_service(dbus.clone()).await?)
         .add_service("/bootloader", bootloader_service(dbus.clone()).await?)
-        .add_service("/network", network_service(network_adapter, events).await?)
+        .add_service("/network", network)
         .add_service("/questions", questions_service(dbus.clone()).await?)
         .add_service("/users", users_service(dbus.clone()).await?)
         .add_service("/scripts", scripts_service().await?)
         .with_config(config)
+        .with_keys(keys)
+        .with_metrics(metrics)
         .build();
     Ok(router)
 }
 
+/// Returns the network service using the adapter given by the configuration.
+///
+/// If the keyfiles root directory is not set, it uses NetworkManager. When NetworkManager is not
+/// available, it falls back to the keyfiles in the running system.
+///
+/// * `config`: service configuration.
+/// * `events`: channel to send the events.
+async fn network_router(
+    config: &ServiceConfig,
+    events: EventsSender,
+) -> Result<Router, ServiceError> {
+    if let Some(root) = &config.network_keyfiles_root {
+        return network_service(KeyfileAdapter::new(root), events).await;
+    }
+
+    match NetworkManagerAdapter::from_system().await {
+        Ok(adapter) => network_service(adapter, events).await,
+        Err(error) => {
+            tracing::warn!(
+                "Could not connect to NetworkManager ({}), using the keyfiles instead",
+                error
+            );
+            network_service(KeyfileAdapter::new("/"), events).await
+        }
+    }
+}
+
+/// Keeps the number of open questions in the metrics up to date.
+///
+/// * `dbus`: D-Bus connection.
+/// * `events`: channel to listen for the questions changes.
+/// * `metrics`: metrics to update.
+async fn monitor_questions(dbus: zbus::Connection, mut events: EventsReceiver, metrics: Metrics) {
+    loop {
+        match open_questions(dbus.clone()).await {
+            Ok(count) => metrics.set_open_questions(count),
+            Err(error) => tracing::warn!("Could not count the open questions: {}", error),
+        }
+
+        loop {
+            match events.recv().await {
+                Ok(Event::QuestionsChanged) | Err(RecvError::Lagged(_)) => break,
+                Ok(_) => {}
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
 /// Starts monitoring the D-Bus service progress.
 ///
 /// The events are sent to the `events` channel.
@@ -0,0 +1,386 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Implements the audit log.
+//!
+//! Every request under `/api` that might change something (any method but `GET`, `HEAD` and
+//! `OPTIONS`) is recorded as a JSON document in its own line:
+//!
+//! ```json
+//! { "timestamp": "2024-10-18T12:00:00.000Z", "method": "PUT", "path": "/api/users/root",
+//!   "status": 200, "clientAddress": "192.168.122.1", "tokenId": "a2f4...", "scope": "full",
+//!   "body": { "password": "[redacted]", "hashedPassword": false } }
+//! ```
+//!
+//! The sensitive values of the request body (passwords, keys, registration codes, etc.) are masked.
+//! When the file is too big, it is rotated, keeping a few old files around.
+
+use super::{
+    auth::{ensure_full_access, verified_claims, AuthError},
+    state::ServiceState,
+    throttle::LockoutStatus,
+};
+use agama_lib::{
+    auth::{TokenClaims, TokenScope},
+    utils::mask_secrets,
+};
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{ConnectInfo, OriginalUri, Query, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::SocketAddr,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+pub const DEFAULT_AUDIT_LOG: &str = "/var/log/agama/audit.jsonl";
+/// Size of the file that triggers the rotation.
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+/// Number of rotated files to keep.
+const MAX_ROTATED_FILES: usize = 3;
+/// Bodies bigger than this size are not recorded.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const REDACTED: &str = "[redacted]";
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error("Could not read the audit log: {0}")]
+    IO(#[from] io::Error),
+}
+
+impl IntoResponse for AuditError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Auth(error) => error.into_response(),
+            Self::IO(_) => {
+                let body = json!({ "error": self.to_string() });
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+            }
+        }
+    }
+}
+
+/// Entry of the audit log.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// Time of the request (RFC 3339).
+    pub timestamp: String,
+    /// HTTP method.
+    pub method: String,
+    /// Request path.
+    pub path: String,
+    /// Response status code.
+    pub status: u16,
+    /// Address of the client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_address: Option<String>,
+    /// Identifier of the token used for the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<String>,
+    /// Scope of the token used for the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<TokenScope>,
+    /// Request body (JSON only), with the sensitive values masked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// Failed logins and lockout of the client (only for the failed or rejected logins).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lockout: Option<LockoutStatus>,
+}
+
+/// Audit log, written to a JSON-lines file.
+#[derive(Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    // serializes the writes and the rotation
+    lock: Arc<Mutex<()>>,
+}
+
+impl AuditLog {
+    /// * `path`: path of the log file.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Appends an entry to the log, rotating the file if needed.
+    ///
+    /// * `entry`: entry to record.
+    pub fn record(&self, entry: &AuditEntry) -> io::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let _guard = self.lock.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > MAX_FILE_SIZE {
+            self.rotate()?;
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())
+    }
+
+    /// Returns the entries, from the oldest to the newest one.
+    ///
+    /// * `limit`: return only the given number of entries (the newest ones).
+    pub fn entries(&self, limit: Option<usize>) -> io::Result<Vec<AuditEntry>> {
+        let _guard = self.lock.lock().unwrap();
+        let mut paths: Vec<_> = (1..=MAX_ROTATED_FILES)
+            .rev()
+            .map(|i| self.rotated_path(i))
+            .collect();
+        paths.push(self.path.clone());
+
+        let mut entries = vec![];
+        for path in paths.iter().filter(|p| p.exists()) {
+            let reader = BufReader::new(fs::File::open(path)?);
+            for line in reader.lines() {
+                match serde_json::from_str(&line?) {
+                    Ok(entry) => entries.push(entry),
+                    Err(error) => tracing::warn!("Skipping audit log entry: {}", error),
+                }
+            }
+        }
+
+        if let Some(limit) = limit {
+            let skip = entries.len().saturating_sub(limit);
+            entries.drain(..skip);
+        }
+        Ok(entries)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        for i in (1..MAX_ROTATED_FILES).rev() {
+            let path = self.rotated_path(i);
+            if path.exists() {
+                fs::rename(&path, self.rotated_path(i + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+}
+
+/// Records the mutating requests in the audit log.
+pub async fn audit_middleware(
+    State(state): State<ServiceState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(audit) = state.audit.clone() else {
+        return next.run(request).await;
+    };
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let claims: Option<TokenClaims> = verified_claims(&mut parts, &state).await.ok();
+    let path = match parts.extensions.get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path().to_string(),
+        None => parts.uri.path().to_string(),
+    };
+    let client_address = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_canonical().to_string());
+
+    // only small JSON bodies are recorded
+    let is_json = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let length = body.size_hint().upper();
+    let (request, body) = match length {
+        Some(length) if is_json && length <= MAX_BODY_SIZE as u64 => {
+            let bytes = to_bytes(body, MAX_BODY_SIZE).await.unwrap_or_default();
+            let value = serde_json::from_slice(&bytes).ok().map(redact);
+            (Request::from_parts(parts.clone(), Body::from(bytes)), value)
+        }
+        _ => (Request::from_parts(parts.clone(), body), None),
+    };
+
+    let mut entry = AuditEntry {
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        method: parts.method.to_string(),
+        path,
+        client_address,
+        token_id: claims.as_ref().and_then(|c| c.jti.clone()),
+        scope: claims.map(|c| c.scope),
+        body,
+        ..Default::default()
+    };
+
+    let response = next.run(request).await;
+    entry.status = response.status().as_u16();
+    entry.lockout = response.extensions().get::<LockoutStatus>().cloned();
+    if let Err(error) = audit.record(&entry) {
+        tracing::error!("Could not write to the audit log: {}", error);
+    }
+    response
+}
+
+/// Masks the sensitive values of a JSON document (see [mask_secrets]).
+///
+/// * `value`: JSON document.
+pub fn redact(value: Value) -> Value {
+    mask_secrets(value, REDACTED)
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct AuditQuery {
+    /// Return only the given number of entries (the newest ones).
+    limit: Option<usize>,
+}
+
+/// Returns the entries of the audit log, from the oldest to the newest one.
+#[utoipa::path(get,
+    path = "/audit",
+    context_path = "/api",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Audit log entries.", body = Vec<AuditEntry>),
+        (status = 403, description = "The current token does not allow reading the audit log.")
+    )
+)]
+pub async fn audit_handler(
+    State(state): State<ServiceState>,
+    claims: TokenClaims,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, AuditError> {
+    ensure_full_access(&claims)?;
+    let Some(audit) = state.audit else {
+        return Ok(Json(vec![]));
+    };
+    Ok(Json(audit.entries(query.limit)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redact, AuditEntry, AuditLog, MAX_ROTATED_FILES};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_redact() {
+        let body = json!({
+            "password": "nots3cr3t",
+            "hashedPassword": false,
+            "key": "INTERNAL-USE-ONLY-1234",
+            "keyboard": "us",
+            "connections": [
+                { "id": "wlan0", "wireless": { "password": "s3cr3t", "ssid": "agama" } },
+                {
+                    "id": "wg0",
+                    "wireguard": {
+                        "privateKey": "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=",
+                        "peers": [{
+                            "publicKey": "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=",
+                            "presharedKey": "FpCyhws9cxwWoV4xELtfJvjJN+zQVRPISllRWgeopVE=",
+                            "endpoint": "192.168.1.1:51820"
+                        }]
+                    }
+                }
+            ]
+        });
+        assert_eq!(
+            redact(body),
+            json!({
+                "password": "[redacted]",
+                "hashedPassword": false,
+                "key": "[redacted]",
+                "keyboard": "us",
+                "connections": [
+                    { "id": "wlan0", "wireless": { "password": "[redacted]", "ssid": "agama" } },
+                    {
+                        "id": "wg0",
+                        "wireguard": {
+                            "privateKey": "[redacted]",
+                            "peers": [{
+                                "publicKey": "[redacted]",
+                                "presharedKey": "[redacted]",
+                                "endpoint": "192.168.1.1:51820"
+                            }]
+                        }
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_record_and_rotate() {
+        let tmp_dir = tempdir().unwrap();
+        let log = AuditLog::new(tmp_dir.path().join("audit.jsonl"));
+        for status in [200, 201, 400, 403] {
+            let entry = AuditEntry {
+                method: "PUT".to_string(),
+                path: "/api/users/root".to_string(),
+                status,
+                ..Default::default()
+            };
+            log.record(&entry).unwrap();
+            log.rotate().unwrap();
+        }
+        log.record(&AuditEntry::default()).unwrap();
+
+        // the oldest entry is gone
+        let entries = log.entries(None).unwrap();
+        assert_eq!(entries.len(), MAX_ROTATED_FILES + 1);
+        assert_eq!(entries[0].status, 201);
+        assert_eq!(entries[2].status, 403);
+
+        let entries = log.entries(Some(2)).unwrap();
+        assert_eq!(entries[0].status, 403);
+    }
+}
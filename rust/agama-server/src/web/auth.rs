@@ -20,12 +20,12 @@
 
 //! Contains the code to handle access authorization.
 
-use super::state::ServiceState;
-use agama_lib::auth::{AuthToken, AuthTokenError, TokenClaims};
+use super::{state::ServiceState, ClientCertificate};
+use agama_lib::auth::{AuthToken, AuthTokenError, TokenClaims, TokenScope};
 use async_trait::async_trait;
 use axum::{
-    extract::FromRequestParts,
-    http::{request, StatusCode},
+    extract::{FromRequestParts, OriginalUri},
+    http::{header, request, Method, StatusCode},
     response::{IntoResponse, Response},
     Json, RequestPartsExt,
 };
@@ -49,6 +49,18 @@ pub enum AuthError {
     /// The authentication failed (most probably the password is wrong)
     #[error("Authentication via PAM failed: {0}")]
     Failed(#[from] PamError),
+    /// The token does not grant access to the resource.
+    #[error("The token scope ({0}) does not allow this operation")]
+    Forbidden(TokenScope),
+    /// The token was revoked.
+    #[error("The authentication token was revoked")]
+    RevokedToken,
+    /// The session does not exist.
+    #[error("Unknown session: {0}")]
+    UnknownSession(String),
+    /// The client must wait before trying to log in again.
+    #[error("Too many login attempts, try again in {0} seconds")]
+    TooManyAttempts(u64),
 }
 
 impl IntoResponse for AuthError {
@@ -56,7 +68,16 @@ impl IntoResponse for AuthError {
         let body = json!({
             "error": self.to_string()
         });
-        (StatusCode::BAD_REQUEST, Json(body)).into_response()
+        let status = match self {
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::UnknownSession(_) => StatusCode::NOT_FOUND,
+            Self::TooManyAttempts(seconds) => {
+                let headers = [(header::RETRY_AFTER, seconds.to_string())];
+                return (StatusCode::TOO_MANY_REQUESTS, headers, Json(body)).into_response();
+            }
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(body)).into_response()
     }
 }
 
@@ -68,24 +89,209 @@ impl FromRequestParts<ServiceState> for TokenClaims {
         parts: &mut request::Parts,
         state: &ServiceState,
     ) -> Result<Self, Self::Rejection> {
-        let token = match parts
-            .extract::<TypedHeader<headers::Authorization<Bearer>>>()
-            .await
-        {
-            Ok(TypedHeader(headers::Authorization(bearer))) => bearer.token().to_owned(),
-            Err(_) => {
-                let cookie = parts
-                    .extract::<TypedHeader<headers::Cookie>>()
-                    .await
-                    .map_err(|_| AuthError::MissingToken)?;
-                cookie
-                    .get("agamaToken")
-                    .ok_or(AuthError::MissingToken)?
-                    .to_owned()
-            }
+        let claims = verified_claims(parts, state).await?;
+
+        // nested routers strip the prefix from the URI, so use the original one
+        let path = match parts.extensions.get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.path(),
+            None => parts.uri.path(),
         };
+        if !scope_allows(claims.scope, &parts.method, path) {
+            return Err(AuthError::Forbidden(claims.scope));
+        }
+        Ok(claims)
+    }
+}
+
+/// Returns the claims of the token included in the request, without checking its scope.
+///
+/// The token is read from the `Authorization` header or, if it is not there, from the
+/// `agamaToken` cookie. Without a token, it falls back to the client certificate of the
+/// connection, if its subject is mapped to a scope.
+///
+/// * `parts`: request parts.
+/// * `state`: service state.
+pub async fn verified_claims(
+    parts: &mut request::Parts,
+    state: &ServiceState,
+) -> Result<TokenClaims, AuthError> {
+    let token = match parts
+        .extract::<TypedHeader<headers::Authorization<Bearer>>>()
+        .await
+    {
+        Ok(TypedHeader(headers::Authorization(bearer))) => bearer.token().to_owned(),
+        Err(_) => {
+            let cookie = parts
+                .extract::<TypedHeader<headers::Cookie>>()
+                .await
+                .ok()
+                .and_then(|TypedHeader(cookie)| cookie.get("agamaToken").map(str::to_owned));
+            match cookie {
+                Some(token) => token,
+                None => return certificate_claims(parts, state).ok_or(AuthError::MissingToken),
+            }
+        }
+    };
+
+    verify_token(&AuthToken::new(&token), state)
+}
+
+/// Verifies a token, rejecting it if it was revoked.
+///
+/// * `token`: token to verify.
+/// * `state`: service state, containing the keys and the sessions.
+pub fn verify_token(token: &AuthToken, state: &ServiceState) -> Result<TokenClaims, AuthError> {
+    let claims = state.keys.ring.verify(token)?;
+    if let Some(id) = &claims.jti {
+        if state.sessions.is_revoked(id) {
+            return Err(AuthError::RevokedToken);
+        }
+    }
+    Ok(claims)
+}
+
+/// Returns an error unless the token grants full access.
+///
+/// * `claims`: token claims.
+pub fn ensure_full_access(claims: &TokenClaims) -> Result<(), AuthError> {
+    if claims.scope != TokenScope::Full {
+        return Err(AuthError::Forbidden(claims.scope));
+    }
+    Ok(())
+}
+
+/// Returns the claims for the client certificate of the connection, if any.
+fn certificate_claims(parts: &request::Parts, state: &ServiceState) -> Option<TokenClaims> {
+    let certificate = parts.extensions.get::<ClientCertificate>()?;
+    let claims = state.config.client_certs.claims(certificate);
+    if claims.is_none() {
+        tracing::info!(
+            "The client certificate subject is not allowed: {}",
+            certificate.subject
+        );
+    }
+    claims
+}
+
+/// Resources that read-only tokens cannot get because they may contain secrets (e.g.,
+/// passwords, keys or the registration code). A resource is matched with all its subpaths.
+const SENSITIVE_RESOURCES: &[&str] = &[
+    "/audit",
+    "/auth/sessions",
+    "/manager/logs",
+    "/network/connections",
+    "/network/nmstate",
+    "/scripts",
+    "/software/registration",
+    "/storage/config",
+    "/storage/config_model",
+    "/users",
+];
+
+/// Determines whether a token with the given scope grants access to a resource.
+///
+/// * `scope`: token scope.
+/// * `method`: HTTP method of the request.
+/// * `path`: request path.
+pub fn scope_allows(scope: TokenScope, method: &Method, path: &str) -> bool {
+    let path = path.strip_prefix("/api").unwrap_or(path);
+    let is_read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+
+    // any token can check the session or log out
+    if path == "/auth" {
+        return true;
+    }
+
+    match scope {
+        TokenScope::Full => true,
+        TokenScope::ReadOnly => is_read && !is_sensitive(path),
+        TokenScope::Questions => {
+            let is_question = path == "/questions" || path.starts_with("/questions/");
+            let is_answer = is_question && path.ends_with("/answer");
+            (is_read && (is_question || matches!(path, "/ws" | "/events")))
+                || (*method == Method::PUT && is_answer)
+        }
+    }
+}
+
+/// Whether the resource may contain secrets (see [SENSITIVE_RESOURCES]).
+fn is_sensitive(path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    SENSITIVE_RESOURCES.iter().any(|resource| {
+        path.strip_prefix(resource)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scope_allows;
+    use agama_lib::auth::TokenScope;
+    use axum::http::Method;
+
+    #[test]
+    fn test_scope_allows() {
+        assert!(scope_allows(
+            TokenScope::Full,
+            &Method::PUT,
+            "/api/storage/config"
+        ));
+
+        assert!(scope_allows(
+            TokenScope::ReadOnly,
+            &Method::GET,
+            "/api/storage/devices/system"
+        ));
+        assert!(!scope_allows(
+            TokenScope::ReadOnly,
+            &Method::PUT,
+            "/api/storage/devices/system"
+        ));
+        assert!(scope_allows(
+            TokenScope::ReadOnly,
+            &Method::GET,
+            "/api/network/connections_summary"
+        ));
+        for path in [
+            "/api/software/registration",
+            "/api/network/connections",
+            "/api/network/connections/wlan0",
+            "/api/storage/config",
+            "/api/users/root",
+        ] {
+            assert!(!scope_allows(TokenScope::ReadOnly, &Method::GET, path));
+            assert!(scope_allows(TokenScope::Full, &Method::GET, path));
+        }
+        assert!(scope_allows(
+            TokenScope::ReadOnly,
+            &Method::DELETE,
+            "/api/auth"
+        ));
 
-        let token = AuthToken::new(&token);
-        Ok(token.claims(&state.config.jwt_secret)?)
+        assert!(scope_allows(
+            TokenScope::Questions,
+            &Method::PUT,
+            "/api/questions/1/answer"
+        ));
+        assert!(!scope_allows(
+            TokenScope::Questions,
+            &Method::POST,
+            "/api/questions"
+        ));
+        assert!(scope_allows(
+            TokenScope::Questions,
+            &Method::GET,
+            "/api/events"
+        ));
+        assert!(!scope_allows(
+            TokenScope::Questions,
+            &Method::GET,
+            "/api/storage/config"
+        ));
+        assert!(!scope_allows(
+            TokenScope::Questions,
+            &Method::POST,
+            "/api/auth/tokens"
+        ));
     }
 }
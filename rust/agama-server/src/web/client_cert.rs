@@ -0,0 +1,155 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Implements the authentication using TLS client certificates.
+//!
+//! When a trusted CA is configured, the server asks the clients for a certificate during the TLS
+//! handshake. The subject of a verified certificate is added to the requests (see
+//! [ClientCertificate]) and, if it is mapped to a scope in the configuration, the request is
+//! authenticated as if it included a token with that scope.
+
+use agama_lib::auth::{TokenClaims, TokenScope};
+use openssl::{
+    error::ErrorStack,
+    x509::{X509Ref, X509},
+};
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf};
+
+/// Client certificates configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ClientCertsConfig {
+    /// File containing the trusted CA certificates (PEM). The client certificates are not
+    /// requested if it is not set.
+    pub ca: Option<PathBuf>,
+    /// Scope granted to each certificate subject.
+    ///
+    /// The subject is written as a comma-separated list of attributes in the same order they
+    /// appear in the certificate (e.g., "O=Example,CN=provisioning"). It is also possible to use
+    /// just the common name (e.g., "CN=provisioning").
+    #[serde(default)]
+    pub subjects: HashMap<String, TokenScope>,
+}
+
+impl ClientCertsConfig {
+    /// Reads the trusted CA certificates.
+    ///
+    /// It returns an empty list if no CA is configured.
+    pub fn read_ca(&self) -> anyhow::Result<Vec<X509>> {
+        let Some(path) = &self.ca else {
+            return Ok(vec![]);
+        };
+        let pem = std::fs::read(path)?;
+        Ok(X509::stack_from_pem(&pem)?)
+    }
+
+    /// Returns the claims for the given certificate, if its subject is mapped to a scope.
+    ///
+    /// * `certificate`: verified client certificate.
+    pub fn claims(&self, certificate: &ClientCertificate) -> Option<TokenClaims> {
+        let scope = self.subjects.get(&certificate.subject).or_else(|| {
+            let common_name = certificate.common_name.as_ref()?;
+            self.subjects.get(&format!("CN={}", common_name))
+        })?;
+        Some(TokenClaims::new(*scope, TokenClaims::DEFAULT_TTL))
+    }
+}
+
+/// Verified client certificate of a TLS connection.
+///
+/// It is added to the extensions of the requests of that connection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientCertificate {
+    /// Certificate subject (e.g., "O=Example,CN=provisioning").
+    pub subject: String,
+    /// Common name of the subject, if any.
+    pub common_name: Option<String>,
+}
+
+impl ClientCertificate {
+    pub fn from_x509(certificate: &X509Ref) -> Result<Self, ErrorStack> {
+        let mut attributes = vec![];
+        let mut common_name = None;
+        for entry in certificate.subject_name().entries() {
+            let name = entry.object().nid().short_name()?;
+            let value = entry.data().as_utf8()?.to_string();
+            if name == "CN" {
+                common_name = Some(value.clone());
+            }
+            attributes.push(format!("{}={}", name, value));
+        }
+        Ok(Self {
+            subject: attributes.join(","),
+            common_name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientCertificate, ClientCertsConfig};
+    use agama_lib::auth::TokenScope;
+    use openssl::{
+        hash::MessageDigest,
+        pkey::PKey,
+        rsa::Rsa,
+        x509::{X509NameBuilder, X509},
+    };
+
+    fn build_certificate(organization: &str, common_name: &str) -> X509 {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("O", organization).unwrap();
+        name.append_entry_by_text("CN", common_name).unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn test_client_certificate_claims() {
+        let certificate = build_certificate("Example", "provisioning");
+        let client = ClientCertificate::from_x509(&certificate).unwrap();
+        assert_eq!(client.subject, "O=Example,CN=provisioning");
+        assert_eq!(client.common_name.as_deref(), Some("provisioning"));
+
+        let mut config = ClientCertsConfig::default();
+        assert!(config.claims(&client).is_none());
+
+        config
+            .subjects
+            .insert("CN=provisioning".to_string(), TokenScope::ReadOnly);
+        assert_eq!(config.claims(&client).unwrap().scope, TokenScope::ReadOnly);
+
+        config
+            .subjects
+            .insert("O=Example,CN=provisioning".to_string(), TokenScope::Full);
+        assert_eq!(config.claims(&client).unwrap().scope, TokenScope::Full);
+
+        let other = build_certificate("Example", "other");
+        let other = ClientCertificate::from_x509(&other).unwrap();
+        assert!(config.claims(&other).is_none());
+    }
+}
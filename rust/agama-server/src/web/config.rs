@@ -30,37 +30,108 @@
 //! All the settings are merged into a single configuration. The values in the latter locations
 //! take precedence.
 
+use super::{
+    audit::DEFAULT_AUDIT_LOG,
+    client_cert::ClientCertsConfig,
+    keys::{KeyStore, SigningAlgorithm, TokenKeys, DEFAULT_KEYS_DIR},
+    sessions::{SessionsRegistry, SESSIONS_FILE},
+    throttle::LoginLimits,
+};
 use config::{Config, ConfigError, File};
-use rand::distributions::{Alphanumeric, DistString};
 use serde::Deserialize;
+use std::path::PathBuf;
 
 /// Web service configuration.
 #[derive(Clone, Debug, Deserialize)]
 pub struct ServiceConfig {
-    /// Key to sign the JSON Web Tokens.
+    /// Key to sign the JSON Web Tokens. If it is empty, the keys are read from `jwt_keys_dir`.
+    #[serde(default)]
     pub jwt_secret: String,
+    /// Algorithm to sign the tokens when using the keys from `jwt_keys_dir`.
+    #[serde(default)]
+    pub jwt_algorithm: SigningAlgorithm,
+    /// Directory containing the keys to sign and verify the tokens.
+    #[serde(default = "default_keys_dir")]
+    pub jwt_keys_dir: PathBuf,
+    /// File to record the mutating requests (`/var/log/agama/audit.jsonl` by default, `None` to
+    /// disable the audit log).
+    #[serde(default = "default_audit_log")]
+    pub audit_log: Option<PathBuf>,
+    /// Limits of the password logins for each client address.
+    #[serde(default)]
+    pub login_limits: LoginLimits,
+    /// Authentication using TLS client certificates.
+    #[serde(default)]
+    pub client_certs: ClientCertsConfig,
+    /// Root directory to read and write the NetworkManager keyfiles instead of using
+    /// NetworkManager (e.g., `/mnt` to write the configuration into the target system).
+    #[serde(default)]
+    pub network_keyfiles_root: Option<PathBuf>,
+}
+
+fn default_audit_log() -> Option<PathBuf> {
+    Some(PathBuf::from(DEFAULT_AUDIT_LOG))
+}
+
+fn default_keys_dir() -> PathBuf {
+    PathBuf::from(DEFAULT_KEYS_DIR)
 }
 
 impl ServiceConfig {
     pub fn load() -> Result<Self, ConfigError> {
-        const JWT_SECRET_SIZE: usize = 30;
-        let jwt_secret: String =
-            Alphanumeric.sample_string(&mut rand::thread_rng(), JWT_SECRET_SIZE);
-
         let config = Config::builder()
-            .set_default("jwt_secret", jwt_secret)?
             .add_source(File::with_name("/usr/etc/agama.d/server").required(false))
             .add_source(File::with_name("/etc/agama.d/server").required(false))
             .add_source(File::with_name("etc/agama.d/server").required(false))
             .build()?;
         config.try_deserialize()
     }
+
+    /// Returns the keys to sign and verify the tokens.
+    ///
+    /// If `jwt_secret` is set, it is the only key. Otherwise, the keys are read from (or generated
+    /// in) `jwt_keys_dir`, so the tokens survive a restart of the server. If that directory is not
+    /// usable, it falls back to a random secret.
+    pub fn token_keys(&self) -> TokenKeys {
+        if !self.jwt_secret.is_empty() {
+            return TokenKeys::from_secret(&self.jwt_secret);
+        }
+
+        match KeyStore::new(&self.jwt_keys_dir).load_or_create(self.jwt_algorithm) {
+            Ok(keys) => keys,
+            Err(error) => {
+                tracing::warn!(
+                    "Could not use the keys from {}: {}. Using a random secret.",
+                    self.jwt_keys_dir.display(),
+                    error
+                );
+                TokenKeys::random()
+            }
+        }
+    }
+
+    /// Returns the registry of the sessions.
+    ///
+    /// When the keys are read from `jwt_keys_dir`, the sessions (including the revoked ones) are
+    /// kept in the same directory. Otherwise, they are kept only in memory.
+    pub fn sessions(&self) -> SessionsRegistry {
+        if !self.jwt_secret.is_empty() {
+            return SessionsRegistry::new();
+        }
+        SessionsRegistry::with_file(self.jwt_keys_dir.join(SESSIONS_FILE))
+    }
 }
 
 impl Default for ServiceConfig {
     fn default() -> Self {
         Self {
             jwt_secret: "".to_string(),
+            jwt_algorithm: SigningAlgorithm::default(),
+            jwt_keys_dir: default_keys_dir(),
+            audit_log: default_audit_log(),
+            login_limits: LoginLimits::default(),
+            client_certs: ClientCertsConfig::default(),
+            network_keyfiles_root: None,
         }
     }
 }
@@ -32,12 +32,24 @@ impl ApiDocBuilder for MiscApiDocBuilder {
     fn paths(&self) -> Paths {
         PathsBuilder::new()
             .path_from::<crate::web::http::__path_ping>()
+            .path_from::<crate::web::http::__path_create_token>()
+            .path_from::<crate::web::http::__path_public_keys>()
+            .path_from::<crate::web::audit::__path_audit_handler>()
+            .path_from::<crate::web::metrics::__path_metrics_handler>()
+            .path_from::<crate::web::http::__path_list_sessions>()
+            .path_from::<crate::web::http::__path_revoke_session>()
             .build()
     }
 
     fn components(&self) -> Components {
         ComponentsBuilder::new()
             .schema_from::<crate::web::http::PingResponse>()
+            .schema_from::<crate::web::http::AuthResponse>()
+            .schema_from::<crate::web::http::TokenRequest>()
+            .schema_from::<agama_lib::auth::TokenScope>()
+            .schema_from::<crate::web::Session>()
+            .schema_from::<crate::web::AuditEntry>()
+            .schema_from::<crate::web::LockoutStatus>()
             .build()
     }
 }
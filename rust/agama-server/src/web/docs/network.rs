@@ -41,8 +41,10 @@ impl ApiDocBuilder for NetworkApiDocBuilder {
             .path_from::<crate::network::web::__path_devices>()
             .path_from::<crate::network::web::__path_disconnect>()
             .path_from::<crate::network::web::__path_general_state>()
+            .path_from::<crate::network::web::__path_nmstate>()
             .path_from::<crate::network::web::__path_update_connection>()
             .path_from::<crate::network::web::__path_update_general_state>()
+            .path_from::<crate::network::web::__path_update_nmstate>()
             .path_from::<crate::network::web::__path_wifi_networks>()
             .build()
     }
@@ -50,11 +52,20 @@ impl ApiDocBuilder for NetworkApiDocBuilder {
     fn components(&self) -> Components {
         ComponentsBuilder::new()
             .schema_from::<agama_lib::network::settings::BondSettings>()
+            .schema_from::<agama_lib::network::settings::BridgePortSettings>()
+            .schema_from::<agama_lib::network::settings::BridgeSettings>()
             .schema_from::<agama_lib::network::settings::IEEE8021XSettings>()
+            .schema_from::<agama_lib::network::settings::InfinibandSettings>()
             .schema_from::<agama_lib::network::settings::MatchSettings>()
             .schema_from::<agama_lib::network::settings::NetworkConnection>()
             .schema_from::<agama_lib::network::settings::NetworkSettings>()
             .schema_from::<agama_lib::network::settings::NetworkSettings>()
+            .schema_from::<agama_lib::network::settings::RouteSettings>()
+            .schema_from::<agama_lib::network::settings::RoutingRuleSettings>()
+            .schema_from::<agama_lib::network::settings::TunSettings>()
+            .schema_from::<agama_lib::network::settings::VlanSettings>()
+            .schema_from::<agama_lib::network::settings::WireguardPeerSettings>()
+            .schema_from::<agama_lib::network::settings::WireguardSettings>()
             .schema_from::<agama_lib::network::settings::WirelessSettings>()
             .schema_from::<agama_lib::network::types::BondMode>()
             .schema_from::<agama_lib::network::types::DeviceState>()
@@ -84,6 +95,7 @@ impl ApiDocBuilder for NetworkApiDocBuilder {
             .schema_from::<crate::network::model::PairwiseAlgorithm>()
             .schema_from::<crate::network::model::Phase2AuthMethod>()
             .schema_from::<crate::network::model::PortConfig>()
+            .schema_from::<crate::network::model::RoutingRule>()
             .schema_from::<crate::network::model::SecurityProtocol>()
             .schema_from::<crate::network::model::TunConfig>()
             .schema_from::<crate::network::model::TunMode>()
@@ -93,6 +105,8 @@ impl ApiDocBuilder for NetworkApiDocBuilder {
             .schema_from::<crate::network::model::WEPKeyType>()
             .schema_from::<crate::network::model::WEPSecurity>()
             .schema_from::<crate::network::model::WPAProtocolVersion>()
+            .schema_from::<crate::network::model::WireguardConfig>()
+            .schema_from::<crate::network::model::WireguardPeer>()
             .schema_from::<crate::network::model::WirelessBand>()
             .schema_from::<crate::network::model::WirelessConfig>()
             .schema_from::<crate::network::model::WirelessMode>()
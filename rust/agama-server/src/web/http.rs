@@ -20,16 +20,22 @@
 
 //! Implements the basic handlers for the HTTP-based API (login, logout, ping, etc.).
 
-use super::{auth::AuthError, state::ServiceState};
-use agama_lib::auth::{AuthToken, TokenClaims};
+use super::{
+    auth::{ensure_full_access, verify_token, AuthError},
+    state::ServiceState,
+    Session,
+};
+use agama_lib::auth::{AuthToken, TokenClaims, TokenScope};
 use axum::{
-    extract::{Query, State},
+    extract::{ConnectInfo, Path, Query, State},
     http::{header, HeaderMap, HeaderValue, StatusCode},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
-use pam::Client;
+use jsonwebtoken::jwk::JwkSet;
+use pam::{Client, PamError};
 use serde::{Deserialize, Serialize};
+use std::{net::SocketAddr, num::NonZeroU32, time::Duration};
 use utoipa::ToSchema;
 
 #[derive(Serialize, ToSchema)]
@@ -68,20 +74,41 @@ pub struct LoginRequest {
     path = "/auth",
     context_path = "/api",
     responses(
-        (status = 200, description = "The user has been successfully authenticated.", body = AuthResponse)
+        (status = 200, description = "The user has been successfully authenticated.", body = AuthResponse),
+        (status = 400, description = "The authentication failed."),
+        (status = 429, description = "Too many login attempts. Check the Retry-After header.")
     )
 )]
 pub async fn login(
     State(state): State<ServiceState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
     Json(login): Json<LoginRequest>,
-) -> Result<impl IntoResponse, AuthError> {
-    let mut pam_client = Client::with_password("agama")?;
-    pam_client
-        .conversation_mut()
-        .set_credentials("root", login.password);
-    pam_client.authenticate()?;
+) -> Result<impl IntoResponse, Response> {
+    let client_address = connect_info.map(|ConnectInfo(addr)| addr);
+    let client_ip = client_address.map(|a| a.ip());
+    // the lockout status is included in the response so it gets recorded in the audit log
+    if let Err(status) = state.login_throttle.attempt(client_ip) {
+        let retry_after = status.retry_after.unwrap_or(1);
+        let mut response = AuthError::TooManyAttempts(retry_after).into_response();
+        response.extensions_mut().insert(status);
+        return Err(response);
+    }
+
+    if let Err(error) = authenticate(login.password) {
+        let status = state.login_throttle.failed(client_ip);
+        let mut response = AuthError::Failed(error).into_response();
+        response.extensions_mut().insert(status);
+        return Err(response);
+    }
+    state.login_throttle.succeeded(client_ip);
 
-    let token = AuthToken::generate(&state.config.jwt_secret)?;
+    let token = issue_token(
+        &state,
+        TokenScope::Full,
+        TokenClaims::DEFAULT_TTL,
+        client_address,
+    )
+    .map_err(IntoResponse::into_response)?;
     let content = Json(AuthResponse {
         token: token.to_string(),
     });
@@ -96,6 +123,130 @@ pub async fn login(
     Ok((headers, content))
 }
 
+/// Checks the root password using PAM.
+fn authenticate(password: String) -> Result<(), PamError> {
+    let mut pam_client = Client::with_password("agama")?;
+    pam_client
+        .conversation_mut()
+        .set_credentials("root", password);
+    pam_client.authenticate()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenRequest {
+    /// What the token grants access to
+    #[serde(default)]
+    pub scope: TokenScope,
+    /// Time to live of the token, in seconds (one day by default)
+    #[schema(value_type = Option<u32>)]
+    pub ttl: Option<NonZeroU32>,
+}
+
+/// Returns the public keys to verify the tokens as a JSON Web Key Set.
+///
+/// Only the asymmetric keys (EdDSA and RS256) are included.
+#[utoipa::path(get,
+    path = "/auth/keys",
+    context_path = "/api",
+    responses(
+        (status = 200, description = "JSON Web Key Set (RFC 7517).")
+    )
+)]
+pub async fn public_keys(State(state): State<ServiceState>) -> Json<JwkSet> {
+    Json(state.keys.jwks.clone())
+}
+
+/// Creates a new token, which can be restricted to a scope and expires after the given time.
+///
+/// Only tokens with full access can create new tokens.
+#[utoipa::path(post,
+    path = "/auth/tokens",
+    context_path = "/api",
+    request_body = TokenRequest,
+    responses(
+        (status = 200, description = "The token has been created.", body = AuthResponse),
+        (status = 403, description = "The current token does not allow creating tokens.")
+    )
+)]
+pub async fn create_token(
+    State(state): State<ServiceState>,
+    claims: TokenClaims,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(request): Json<TokenRequest>,
+) -> Result<Json<AuthResponse>, AuthError> {
+    ensure_full_access(&claims)?;
+
+    let ttl = request
+        .ttl
+        .map(|s| Duration::from_secs(s.get().into()))
+        .unwrap_or(TokenClaims::DEFAULT_TTL);
+    let client_address = connect_info.map(|ConnectInfo(addr)| addr);
+    let token = issue_token(&state, request.scope, ttl, client_address)?;
+    Ok(Json(AuthResponse {
+        token: token.to_string(),
+    }))
+}
+
+/// Lists the active sessions (the tokens issued by the server which did not expire).
+#[utoipa::path(get,
+    path = "/auth/sessions",
+    context_path = "/api",
+    responses(
+        (status = 200, description = "List of active sessions.", body = Vec<Session>),
+        (status = 403, description = "The current token does not allow listing the sessions.")
+    )
+)]
+pub async fn list_sessions(
+    State(state): State<ServiceState>,
+    claims: TokenClaims,
+) -> Result<Json<Vec<Session>>, AuthError> {
+    ensure_full_access(&claims)?;
+    Ok(Json(state.sessions.list()))
+}
+
+/// Revokes a session, so its token is not accepted anymore.
+#[utoipa::path(delete,
+    path = "/auth/sessions/{id}",
+    context_path = "/api",
+    params(
+        ("id" = String, Path, description = "Session identifier.")
+    ),
+    responses(
+        (status = 204, description = "The session has been revoked."),
+        (status = 403, description = "The current token does not allow revoking sessions."),
+        (status = 404, description = "The session does not exist.")
+    )
+)]
+pub async fn revoke_session(
+    State(state): State<ServiceState>,
+    claims: TokenClaims,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AuthError> {
+    ensure_full_access(&claims)?;
+    if !state.sessions.revoke(&id) {
+        return Err(AuthError::UnknownSession(id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Issues a new token, registering the session.
+///
+/// * `state`: service state.
+/// * `scope`: what the token grants access to.
+/// * `ttl`: time to live of the token.
+/// * `client_address`: address of the client that requested the token.
+fn issue_token(
+    state: &ServiceState,
+    scope: TokenScope,
+    ttl: Duration,
+    client_address: Option<SocketAddr>,
+) -> Result<AuthToken, AuthError> {
+    let mut claims = TokenClaims::new(scope, ttl);
+    state.sessions.register(&mut claims, client_address);
+    Ok(state.keys.ring.sign(&claims)?)
+}
+
 #[derive(Clone, Deserialize, utoipa::ToSchema)]
 pub struct LoginFromQueryParams {
     /// Token to use for authentication.
@@ -114,7 +265,7 @@ pub async fn login_from_query(
     let mut headers = HeaderMap::new();
 
     let token = AuthToken::new(&params.token);
-    if token.claims(&state.config.jwt_secret).is_ok() {
+    if verify_token(&token, &state).is_ok() {
         let cookie = auth_cookie_from_token(&token);
         headers.insert(
             header::SET_COOKIE,
@@ -145,7 +296,14 @@ pub async fn login_from_query(
 #[utoipa::path(delete, path = "/api/auth", responses(
     (status = 204, description = "The user has been logged out.")
 ))]
-pub async fn logout(_claims: TokenClaims) -> Result<impl IntoResponse, AuthError> {
+pub async fn logout(
+    State(state): State<ServiceState>,
+    claims: TokenClaims,
+) -> Result<impl IntoResponse, AuthError> {
+    if let Some(id) = &claims.jti {
+        state.sessions.revoke(id);
+    }
+
     let mut headers = HeaderMap::new();
     let cookie = "agamaToken=deleted; HttpOnly; Expires=Thu, 01 Jan 1970 00:00:00 GMT".to_string();
     headers.insert(
@@ -0,0 +1,401 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Implements a journal of the events.
+//!
+//! The journal keeps the latest events, so the clients that connect late or reconnect can get
+//! the ones they missed. Each event gets a sequence number, which is included in its JSON
+//! representation (`seq`):
+//!
+//! ```json
+//! { "type": "ProductChanged", "id": "Tumbleweed", "seq": 42 }
+//! ```
+//!
+//! When some events are not available (they are not in the journal anymore or the client could
+//! not keep up), the client gets a `Gap` message containing the range of lost sequence numbers:
+//!
+//! ```json
+//! { "type": "Gap", "from": 10, "to": 41 }
+//! ```
+//!
+//! Some events contain secrets (e.g., the `RootUserChanged` event includes the root password),
+//! so they are masked for the clients that do not have full access (see
+//! [Subscription::redacting_secrets]).
+
+use super::{audit::redact, Event, EventsSender};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Number of events to keep in the journal.
+pub const DEFAULT_JOURNAL_CAPACITY: usize = 1024;
+
+/// Event recorded in the journal.
+#[derive(Clone, Debug)]
+pub struct JournalEntry {
+    pub seq: u64,
+    /// JSON representation of the event, including the sequence number.
+    pub event: Arc<Value>,
+}
+
+impl JournalEntry {
+    /// Event type (e.g., "ProductChanged").
+    pub fn event_type(&self) -> Option<&str> {
+        self.event.get("type").and_then(Value::as_str)
+    }
+
+    /// Service that emitted the event, if it is included in the event.
+    pub fn service(&self) -> Option<&str> {
+        self.event.get("service").and_then(Value::as_str)
+    }
+}
+
+/// Message to send to the clients.
+#[derive(Clone, Debug)]
+pub enum JournalMessage {
+    Event(JournalEntry),
+    /// The events in the given range (both included) are not available.
+    Gap {
+        from: u64,
+        to: u64,
+    },
+}
+
+impl JournalMessage {
+    pub fn to_json(&self) -> String {
+        match self {
+            Self::Event(entry) => entry.event.to_string(),
+            Self::Gap { from, to } => json!({ "type": "Gap", "from": from, "to": to }).to_string(),
+        }
+    }
+}
+
+struct JournalState {
+    entries: VecDeque<JournalEntry>,
+    capacity: usize,
+    last_seq: u64,
+}
+
+/// Bounded journal of the events.
+#[derive(Clone)]
+pub struct EventsJournal {
+    state: Arc<Mutex<JournalState>>,
+    sender: broadcast::Sender<JournalEntry>,
+}
+
+impl EventsJournal {
+    /// * `capacity`: number of events to keep.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        let state = JournalState {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            last_seq: 0,
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            sender,
+        }
+    }
+
+    /// Records the events sent through the given channel.
+    ///
+    /// It spawns a task that runs until the channel is closed.
+    ///
+    /// * `events`: channel to get the events from.
+    pub fn record(&self, events: &EventsSender) {
+        let mut receiver = events.subscribe();
+        let journal = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => journal.push(&event),
+                    Err(RecvError::Lagged(lost)) => {
+                        tracing::warn!("The events journal lost {} events", lost);
+                        journal.skip(lost);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Adds an event to the journal.
+    pub fn push(&self, event: &Event) {
+        let mut value = match serde_json::to_value(event) {
+            Ok(value) => value,
+            Err(error) => {
+                tracing::error!("Could not serialize the event {:?}: {}", event, error);
+                return;
+            }
+        };
+
+        let mut state = self.state.lock().unwrap();
+        state.last_seq += 1;
+        if let Value::Object(object) = &mut value {
+            object.insert("seq".to_string(), state.last_seq.into());
+        }
+        let entry = JournalEntry {
+            seq: state.last_seq,
+            event: Arc::new(value),
+        };
+        if state.entries.len() == state.capacity {
+            state.entries.pop_front();
+        }
+        if state.capacity > 0 {
+            state.entries.push_back(entry.clone());
+        }
+        // sending while holding the lock keeps the order of the subscriptions consistent
+        _ = self.sender.send(entry);
+    }
+
+    /// Skips some sequence numbers (e.g., because the events were lost).
+    fn skip(&self, count: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.last_seq += count;
+    }
+
+    /// Sequence number of the last event.
+    pub fn last_seq(&self) -> u64 {
+        self.state.lock().unwrap().last_seq
+    }
+
+    /// Subscribes to the events.
+    ///
+    /// * `since`: sequence number of the last event the client got. The subscription starts
+    ///   with the journaled events after that one. If it is `None`, only the new events are
+    ///   included.
+    /// * `filter`: events to include.
+    pub fn subscribe(&self, since: Option<u64>, filter: EventFilter) -> Subscription {
+        let state = self.state.lock().unwrap();
+        let last_seq = since.unwrap_or(state.last_seq).min(state.last_seq);
+        let backlog = state
+            .entries
+            .iter()
+            .filter(|e| e.seq > last_seq)
+            .cloned()
+            .collect();
+        Subscription {
+            backlog,
+            receiver: self.sender.subscribe(),
+            filter,
+            last_seq,
+            redact_secrets: false,
+        }
+    }
+}
+
+/// Events to send to a client.
+///
+/// An empty list means no filtering.
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter {
+    /// Event types (e.g., "Progress").
+    pub types: HashSet<String>,
+    /// Services. The events that do not include the service are excluded.
+    pub services: HashSet<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, entry: &JournalEntry) -> bool {
+        let matches = |set: &HashSet<String>, value: Option<&str>| {
+            set.is_empty() || value.is_some_and(|v| set.contains(v))
+        };
+        matches(&self.types, entry.event_type()) && matches(&self.services, entry.service())
+    }
+}
+
+/// Query parameters to subscribe to the events.
+#[derive(Debug, Default, Deserialize)]
+pub struct EventsQuery {
+    /// Sequence number of the last event the client got.
+    pub since: Option<u64>,
+    /// Comma-separated list of event types.
+    pub types: Option<String>,
+    /// Comma-separated list of services.
+    pub services: Option<String>,
+}
+
+impl EventsQuery {
+    pub fn filter(&self) -> EventFilter {
+        let split = |list: &Option<String>| {
+            list.iter()
+                .flat_map(|l| l.split(','))
+                .map(str::trim)
+                .filter(|i| !i.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        EventFilter {
+            types: split(&self.types),
+            services: split(&self.services),
+        }
+    }
+}
+
+/// Subscription to the journal.
+pub struct Subscription {
+    backlog: VecDeque<JournalEntry>,
+    receiver: broadcast::Receiver<JournalEntry>,
+    filter: EventFilter,
+    last_seq: u64,
+    redact_secrets: bool,
+}
+
+impl Subscription {
+    /// Masks the secrets (passwords, keys, etc.) of the events.
+    ///
+    /// * `redact`: whether to mask the secrets.
+    pub fn redacting_secrets(mut self, redact: bool) -> Self {
+        self.redact_secrets = redact;
+        self
+    }
+
+    /// Waits for the next message.
+    ///
+    /// It returns `None` when the journal is gone.
+    pub async fn next(&mut self) -> Option<JournalMessage> {
+        loop {
+            let entry = match self.backlog.pop_front() {
+                Some(entry) => entry,
+                None => match self.receiver.recv().await {
+                    Ok(entry) => entry,
+                    // the next event reveals the gap
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                },
+            };
+
+            if entry.seq <= self.last_seq {
+                continue;
+            }
+
+            if entry.seq > self.last_seq + 1 {
+                let gap = JournalMessage::Gap {
+                    from: self.last_seq + 1,
+                    to: entry.seq - 1,
+                };
+                self.last_seq = entry.seq - 1;
+                self.backlog.push_front(entry);
+                return Some(gap);
+            }
+
+            self.last_seq = entry.seq;
+            if !self.filter.matches(&entry) {
+                continue;
+            }
+            if self.redact_secrets {
+                let event = redact(entry.event.as_ref().clone());
+                return Some(JournalMessage::Event(JournalEntry {
+                    seq: entry.seq,
+                    event: Arc::new(event),
+                }));
+            }
+            return Some(JournalMessage::Event(entry));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventsJournal, EventsQuery, JournalMessage};
+    use crate::web::Event;
+    use agama_lib::users::RootUser;
+
+    fn product_changed(id: &str) -> Event {
+        Event::ProductChanged { id: id.to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_since() {
+        let journal = EventsJournal::new(2);
+        for id in ["a", "b", "c"] {
+            journal.push(&product_changed(id));
+        }
+
+        let mut subscription = journal.subscribe(Some(0), Default::default());
+        let message = subscription.next().await.unwrap();
+        assert!(matches!(message, JournalMessage::Gap { from: 1, to: 1 }));
+        assert_eq!(
+            subscription.next().await.unwrap().to_json(),
+            r#"{"id":"b","seq":2,"type":"ProductChanged"}"#
+        );
+        assert!(matches!(
+            subscription.next().await.unwrap(),
+            JournalMessage::Event(entry) if entry.seq == 3
+        ));
+
+        journal.push(&product_changed("d"));
+        assert!(matches!(
+            subscription.next().await.unwrap(),
+            JournalMessage::Event(entry) if entry.seq == 4
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_filter() {
+        let journal = EventsJournal::new(10);
+        let query = EventsQuery {
+            types: Some("Progress, ServiceStatusChanged".to_string()),
+            services: Some("storage".to_string()),
+            ..Default::default()
+        };
+        let mut subscription = journal.subscribe(None, query.filter());
+
+        journal.push(&product_changed("a"));
+        journal.push(&Event::ServiceStatusChanged {
+            service: "software".to_string(),
+            status: 1,
+        });
+        journal.push(&Event::ServiceStatusChanged {
+            service: "storage".to_string(),
+            status: 0,
+        });
+        assert!(matches!(
+            subscription.next().await.unwrap(),
+            JournalMessage::Event(entry) if entry.seq == 3
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_redacting_secrets() {
+        let journal = EventsJournal::new(10);
+        journal.push(&Event::RootUserChanged(RootUser {
+            password: Some("nots3cr3t".to_string()),
+            hashed_password: Some(false),
+            ssh_public_key: None,
+        }));
+
+        let mut subscription = journal.subscribe(Some(0), Default::default());
+        let message = subscription.next().await.unwrap().to_json();
+        assert!(message.contains("nots3cr3t"));
+
+        let mut subscription = journal
+            .subscribe(Some(0), Default::default())
+            .redacting_secrets(true);
+        let message = subscription.next().await.unwrap().to_json();
+        assert!(!message.contains("nots3cr3t"));
+        assert!(message.contains(r#""hashedPassword":false"#));
+    }
+}
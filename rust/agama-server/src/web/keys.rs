@@ -0,0 +1,400 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Manages the keys to sign and verify the authentication tokens.
+//!
+//! The keys are kept in a directory (`/etc/agama.d/jwt` by default), so the tokens are still
+//! valid after restarting the server. Each key is stored in a file named after its identifier:
+//!
+//! * `<id>.secret`: HS256 secret.
+//! * `<id>.ed25519.pem`: EdDSA (Ed25519) private key. The public key is written to
+//!   `<id>.ed25519.pub.pem`.
+//! * `<id>.rsa.pem`: RS256 private key. The public key is written to `<id>.rsa.pub.pem`.
+//!
+//! The `current` file contains the identifier of the key that signs the new tokens. The rest of
+//! the keys are only used to verify the tokens that were signed before rotating the keys.
+//!
+//! The directory also contains the `sessions.json` file, which keeps the issued and the revoked
+//! tokens (see [crate::web::sessions]).
+//!
+//! The public keys are published as a JSON Web Key Set, so external tools can verify the tokens
+//! signed with the asymmetric keys.
+
+use agama_lib::auth::{AuthTokenError, KeyRing, TokenKey};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use openssl::{
+    pkey::{PKey, Private},
+    rsa::Rsa,
+};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Deserialize;
+use std::{
+    fs,
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use uuid::Uuid;
+
+pub const DEFAULT_KEYS_DIR: &str = "/etc/agama.d/jwt";
+const CURRENT_KEY_FILE: &str = "current";
+const SECRET_SIZE: usize = 64;
+const RSA_KEY_SIZE: u32 = 2048;
+
+#[derive(Error, Debug)]
+pub enum KeysError {
+    #[error("Could not read or write the keys: {0}")]
+    IO(#[from] io::Error),
+    #[error("Could not process the key: {0}")]
+    OpenSSL(#[from] openssl::error::ErrorStack),
+    #[error("Invalid key: {0}")]
+    InvalidKey(#[from] AuthTokenError),
+    #[error("Unknown key: '{0}'")]
+    UnknownKey(String),
+    #[error("The current key cannot be removed")]
+    CurrentKey,
+}
+
+/// Algorithm to sign the tokens.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+pub enum SigningAlgorithm {
+    /// HMAC using SHA-256 (symmetric).
+    #[default]
+    #[value(name = "HS256")]
+    HS256,
+    /// Ed25519 signature (asymmetric).
+    #[value(name = "EdDSA")]
+    EdDSA,
+    /// RSA signature using SHA-256 (asymmetric).
+    #[value(name = "RS256")]
+    RS256,
+}
+
+impl SigningAlgorithm {
+    /// Suffix of the files containing the keys.
+    fn suffix(&self) -> &'static str {
+        match self {
+            Self::HS256 => ".secret",
+            Self::EdDSA => ".ed25519.pem",
+            Self::RS256 => ".rsa.pem",
+        }
+    }
+
+    /// Suffix of the files containing the public keys, if any.
+    fn public_suffix(&self) -> Option<&'static str> {
+        match self {
+            Self::HS256 => None,
+            Self::EdDSA => Some(".ed25519.pub.pem"),
+            Self::RS256 => Some(".rsa.pub.pem"),
+        }
+    }
+
+    fn from_file_name(name: &str) -> Option<(&str, Self)> {
+        [Self::HS256, Self::EdDSA, Self::RS256]
+            .into_iter()
+            .find_map(|a| name.strip_suffix(a.suffix()).map(|id| (id, a)))
+    }
+}
+
+/// Keys to sign and verify the tokens.
+#[derive(Clone)]
+pub struct TokenKeys {
+    /// Keys to sign and verify the tokens.
+    pub ring: KeyRing,
+    /// Public keys (only for the asymmetric algorithms).
+    pub jwks: JwkSet,
+}
+
+impl TokenKeys {
+    /// Uses a single secret to sign and verify the tokens.
+    ///
+    /// * `secret`: secret.
+    pub fn from_secret(secret: &str) -> Self {
+        Self {
+            ring: KeyRing::from_secret(secret),
+            jwks: JwkSet { keys: vec![] },
+        }
+    }
+
+    /// Uses a random secret that only lives in memory.
+    pub fn random() -> Self {
+        let secret = Alphanumeric.sample_string(&mut rand::thread_rng(), SECRET_SIZE);
+        Self::from_secret(&secret)
+    }
+}
+
+/// Key stored in the keys directory.
+#[derive(Debug, PartialEq)]
+pub struct StoredKey {
+    pub id: String,
+    pub algorithm: SigningAlgorithm,
+    /// Whether it is the key that signs the new tokens.
+    pub current: bool,
+}
+
+/// Keeps the keys in a directory.
+pub struct KeyStore {
+    dir: PathBuf,
+}
+
+impl KeyStore {
+    /// * `dir`: directory to keep the keys.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Reads the keys, generating a new one if there is no key for the given algorithm.
+    ///
+    /// If the current key uses a different algorithm, it generates a new key, but the old ones
+    /// are kept to verify the existing tokens.
+    ///
+    /// * `algorithm`: algorithm to sign the new tokens.
+    pub fn load_or_create(&self, algorithm: SigningAlgorithm) -> Result<TokenKeys, KeysError> {
+        let current = self.list()?.into_iter().find(|k| k.current);
+        if !matches!(current, Some(k) if k.algorithm == algorithm) {
+            self.rotate(algorithm)?;
+        }
+        self.read()
+    }
+
+    /// Reads the keys.
+    pub fn read(&self) -> Result<TokenKeys, KeysError> {
+        let mut keys = self.list()?;
+        // the current key goes first, so it signs the tokens
+        keys.sort_by_key(|k| !k.current);
+
+        let mut ring = vec![];
+        let mut jwks = vec![];
+        for key in keys {
+            let (token_key, jwk) = self.read_key(&key)?;
+            ring.push(token_key);
+            jwks.extend(jwk);
+        }
+
+        Ok(TokenKeys {
+            ring: KeyRing::new(ring),
+            jwks: JwkSet { keys: jwks },
+        })
+    }
+
+    /// Lists the keys in the directory.
+    pub fn list(&self) -> Result<Vec<StoredKey>, KeysError> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let current = fs::read_to_string(self.dir.join(CURRENT_KEY_FILE)).unwrap_or_default();
+        let mut keys = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            let Some((id, algorithm)) = SigningAlgorithm::from_file_name(&name) else {
+                continue;
+            };
+            keys.push(StoredKey {
+                id: id.to_string(),
+                algorithm,
+                current: id == current.trim(),
+            });
+        }
+        keys.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(keys)
+    }
+
+    /// Generates a new key that becomes the current one.
+    ///
+    /// The previous keys are kept to verify the existing tokens. It returns the identifier of the
+    /// new key.
+    ///
+    /// * `algorithm`: algorithm of the new key.
+    pub fn rotate(&self, algorithm: SigningAlgorithm) -> Result<String, KeysError> {
+        fs::create_dir_all(&self.dir)?;
+        let id = Uuid::new_v4().simple().to_string()[..12].to_string();
+
+        match algorithm {
+            SigningAlgorithm::HS256 => {
+                let secret = Alphanumeric.sample_string(&mut rand::thread_rng(), SECRET_SIZE);
+                self.write_private(&id, algorithm, secret.as_bytes())?;
+            }
+            SigningAlgorithm::EdDSA => {
+                let key = PKey::generate_ed25519()?;
+                self.write_key_pair(&id, algorithm, &key)?;
+            }
+            SigningAlgorithm::RS256 => {
+                let key = PKey::from_rsa(Rsa::generate(RSA_KEY_SIZE)?)?;
+                self.write_key_pair(&id, algorithm, &key)?;
+            }
+        }
+
+        let tmp_path = self.dir.join(format!(".{}", CURRENT_KEY_FILE));
+        write_file(&tmp_path, id.as_bytes(), 0o644)?;
+        fs::rename(tmp_path, self.dir.join(CURRENT_KEY_FILE))?;
+        Ok(id)
+    }
+
+    /// Removes a key, so the tokens signed with it are not valid anymore.
+    ///
+    /// * `id`: key identifier.
+    pub fn remove(&self, id: &str) -> Result<(), KeysError> {
+        let key = self
+            .list()?
+            .into_iter()
+            .find(|k| k.id == id)
+            .ok_or_else(|| KeysError::UnknownKey(id.to_string()))?;
+        if key.current {
+            return Err(KeysError::CurrentKey);
+        }
+
+        fs::remove_file(self.key_path(&key.id, key.algorithm.suffix()))?;
+        if let Some(suffix) = key.algorithm.public_suffix() {
+            let path = self.key_path(&key.id, suffix);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_key(&self, key: &StoredKey) -> Result<(TokenKey, Option<Jwk>), KeysError> {
+        let content = fs::read(self.key_path(&key.id, key.algorithm.suffix()))?;
+        let id = Some(key.id.as_str());
+
+        if key.algorithm == SigningAlgorithm::HS256 {
+            let secret = String::from_utf8_lossy(&content);
+            return Ok((TokenKey::from_secret(id, secret.trim()), None));
+        }
+
+        let private = PKey::private_key_from_pem(&content)?;
+        let private_pem = private.private_key_to_pem_pkcs8()?;
+        let public_pem = private.public_key_to_pem()?;
+        let (token_key, algorithm, params) = if key.algorithm == SigningAlgorithm::EdDSA {
+            let token_key = TokenKey::from_ed_pem(id, Some(&private_pem), &public_pem)?;
+            let params = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(private.raw_public_key()?),
+            });
+            (token_key, KeyAlgorithm::EdDSA, params)
+        } else {
+            let token_key = TokenKey::from_rsa_pem(id, Some(&private_pem), &public_pem)?;
+            let rsa = private.rsa()?;
+            let params = AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+                e: URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+            });
+            (token_key, KeyAlgorithm::RS256, params)
+        };
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(algorithm),
+                key_id: Some(key.id.clone()),
+                ..Default::default()
+            },
+            algorithm: params,
+        };
+        Ok((token_key, Some(jwk)))
+    }
+
+    fn write_key_pair(
+        &self,
+        id: &str,
+        algorithm: SigningAlgorithm,
+        key: &PKey<Private>,
+    ) -> Result<(), KeysError> {
+        self.write_private(id, algorithm, &key.private_key_to_pem_pkcs8()?)?;
+        if let Some(suffix) = algorithm.public_suffix() {
+            write_file(self.key_path(id, suffix), &key.public_key_to_pem()?, 0o644)?;
+        }
+        Ok(())
+    }
+
+    fn write_private(&self, id: &str, algorithm: SigningAlgorithm, buf: &[u8]) -> io::Result<()> {
+        write_file(self.key_path(id, algorithm.suffix()), buf, 0o600)
+    }
+
+    fn key_path(&self, id: &str, suffix: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", id, suffix))
+    }
+}
+
+/// Writes buf into a file at path, setting the given permissions
+fn write_file<T: AsRef<Path>>(path: T, buf: &[u8], mode: u32) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .mode(mode)
+        .open(path)?;
+
+    file.write_all(buf)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyStore, KeysError, SigningAlgorithm};
+    use agama_lib::auth::TokenClaims;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rotate_keys() {
+        let tmp_dir = tempdir().unwrap();
+        let store = KeyStore::new(tmp_dir.path().join("jwt"));
+
+        let keys = store.load_or_create(SigningAlgorithm::HS256).unwrap();
+        let old_token = keys.ring.sign(&TokenClaims::default()).unwrap();
+        assert!(keys.jwks.keys.is_empty());
+
+        // reading the keys again does not generate a new one
+        let keys = store.load_or_create(SigningAlgorithm::HS256).unwrap();
+        assert!(keys.ring.verify(&old_token).is_ok());
+        assert_eq!(store.list().unwrap().len(), 1);
+
+        let keys = store.load_or_create(SigningAlgorithm::EdDSA).unwrap();
+        let new_token = keys.ring.sign(&TokenClaims::default()).unwrap();
+        assert!(keys.ring.verify(&old_token).is_ok());
+        assert!(keys.ring.verify(&new_token).is_ok());
+        assert_eq!(keys.jwks.keys.len(), 1);
+
+        let stored = store.list().unwrap();
+        let old = stored.iter().find(|k| !k.current).unwrap();
+        let current = stored.iter().find(|k| k.current).unwrap();
+        assert_eq!(current.algorithm, SigningAlgorithm::EdDSA);
+        assert!(matches!(
+            store.remove(&current.id),
+            Err(KeysError::CurrentKey)
+        ));
+
+        store.remove(&old.id).unwrap();
+        let keys = store.read().unwrap();
+        assert!(keys.ring.verify(&old_token).is_err());
+        assert!(keys.ring.verify(&new_token).is_ok());
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Implements the metrics of the web server and the installation process.
+//!
+//! The metrics are exposed at `/api/metrics` using the Prometheus text format, e.g.:
+//!
+//! ```text
+//! # HELP agama_installation_phase Current installation phase.
+//! # TYPE agama_installation_phase gauge
+//! agama_installation_phase{phase="config"} 1
+//! ```
+
+use super::{state::ServiceState, Event, EventsSender};
+use crate::error::Error;
+use agama_lib::manager::InstallationPhase;
+use axum::{
+    extract::State,
+    http::{header, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use prometheus_client::{
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Installation phases, as they are reported in the metrics.
+const PHASES: [InstallationPhase; 4] = [
+    InstallationPhase::Startup,
+    InstallationPhase::Config,
+    InstallationPhase::Install,
+    InstallationPhase::Finish,
+];
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct MethodLabels {
+    method: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct StatusLabels {
+    status: u16,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TransportLabels {
+    transport: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TypeLabels {
+    r#type: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PhaseLabels {
+    phase: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ServiceLabels {
+    service: String,
+}
+
+/// Metrics of the web server and the installation process.
+///
+/// Cloning the metrics is cheap: all the clones share the same values.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Arc<Registry>,
+    http_requests: Family<MethodLabels, Counter>,
+    http_responses: Family<StatusLabels, Counter>,
+    http_in_flight: Gauge,
+    http_duration: Histogram,
+    clients: Family<TransportLabels, Gauge>,
+    events: Family<TypeLabels, Counter>,
+    phase: Family<PhaseLabels, Gauge>,
+    progress_step: Family<ServiceLabels, Gauge>,
+    progress_steps: Family<ServiceLabels, Gauge>,
+    questions: Gauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("agama");
+
+        let http_requests = Family::<MethodLabels, Counter>::default();
+        registry.register(
+            "http_requests",
+            "Number of HTTP requests",
+            http_requests.clone(),
+        );
+        let http_responses = Family::<StatusLabels, Counter>::default();
+        registry.register(
+            "http_responses",
+            "Number of HTTP responses",
+            http_responses.clone(),
+        );
+        let http_in_flight = Gauge::default();
+        registry.register(
+            "http_requests_in_flight",
+            "Number of HTTP requests being processed",
+            http_in_flight.clone(),
+        );
+        let http_duration = Histogram::new(exponential_buckets(0.001, 4.0, 8));
+        registry.register(
+            "http_request_duration_seconds",
+            "Time to process the HTTP requests",
+            http_duration.clone(),
+        );
+        let clients = Family::<TransportLabels, Gauge>::default();
+        registry.register(
+            "event_clients",
+            "Number of clients listening to the events (WebSocket or Server-Sent Events)",
+            clients.clone(),
+        );
+        let events = Family::<TypeLabels, Counter>::default();
+        registry.register("events", "Number of emitted events", events.clone());
+        let phase = Family::<PhaseLabels, Gauge>::default();
+        registry.register(
+            "installation_phase",
+            "Current installation phase",
+            phase.clone(),
+        );
+        let progress_step = Family::<ServiceLabels, Gauge>::default();
+        registry.register(
+            "progress_step",
+            "Current progress step of each service",
+            progress_step.clone(),
+        );
+        let progress_steps = Family::<ServiceLabels, Gauge>::default();
+        registry.register(
+            "progress_steps",
+            "Number of progress steps of each service",
+            progress_steps.clone(),
+        );
+        let questions = Gauge::default();
+        registry.register(
+            "open_questions",
+            "Number of questions waiting for an answer",
+            questions.clone(),
+        );
+
+        let metrics = Self {
+            registry: Arc::new(registry),
+            http_requests,
+            http_responses,
+            http_in_flight,
+            http_duration,
+            clients,
+            events,
+            phase,
+            progress_step,
+            progress_steps,
+            questions,
+        };
+        metrics.set_phase(InstallationPhase::Startup);
+        metrics
+    }
+
+    /// Updates the metrics from the events sent through the given channel.
+    ///
+    /// It spawns a task that keeps listening for new events.
+    ///
+    /// * `events`: channel to listen for events.
+    pub fn record(&self, events: &EventsSender) {
+        let mut receiver = events.subscribe();
+        let metrics = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => metrics.push(&event),
+                    Err(RecvError::Lagged(lost)) => {
+                        tracing::warn!("The metrics lost {} events", lost);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Updates the metrics from an event.
+    pub fn push(&self, event: &Event) {
+        if let Ok(serde_json::Value::Object(object)) = serde_json::to_value(event) {
+            if let Some(serde_json::Value::String(event_type)) = object.get("type") {
+                self.events
+                    .get_or_create(&TypeLabels {
+                        r#type: event_type.to_string(),
+                    })
+                    .inc();
+            }
+        }
+
+        match event {
+            Event::InstallationPhaseChanged { phase } => self.set_phase(*phase),
+            Event::Progress { service, progress } => {
+                let labels = ServiceLabels {
+                    service: service.to_string(),
+                };
+                self.progress_step
+                    .get_or_create(&labels)
+                    .set(progress.current_step.into());
+                self.progress_steps
+                    .get_or_create(&labels)
+                    .set(progress.max_steps.into());
+            }
+            _ => {}
+        }
+    }
+
+    /// Sets the current installation phase.
+    pub fn set_phase(&self, phase: InstallationPhase) {
+        for candidate in PHASES {
+            let labels = PhaseLabels {
+                phase: format!("{:?}", candidate).to_lowercase(),
+            };
+            self.phase
+                .get_or_create(&labels)
+                .set((candidate == phase).into());
+        }
+    }
+
+    /// Sets the number of questions waiting for an answer.
+    pub fn set_open_questions(&self, count: usize) {
+        self.questions.set(count.try_into().unwrap_or(i64::MAX));
+    }
+
+    /// Registers an HTTP request.
+    pub fn request_started(&self, method: &Method) {
+        self.http_requests
+            .get_or_create(&MethodLabels {
+                method: method.to_string(),
+            })
+            .inc();
+        self.http_in_flight.inc();
+    }
+
+    /// Registers an HTTP response.
+    ///
+    /// * `status`: response status.
+    /// * `latency`: time spent processing the request.
+    pub fn request_finished(&self, status: StatusCode, latency: Duration) {
+        self.http_responses
+            .get_or_create(&StatusLabels {
+                status: status.as_u16(),
+            })
+            .inc();
+        self.http_in_flight.dec();
+        self.http_duration.observe(latency.as_secs_f64());
+    }
+
+    /// Registers a client listening to the events.
+    ///
+    /// The client is unregistered when the returned guard is dropped.
+    ///
+    /// * `transport`: "websocket" or "sse".
+    pub fn client_connected(&self, transport: &str) -> ClientGuard {
+        let gauge = self
+            .clients
+            .get_or_create(&TransportLabels {
+                transport: transport.to_string(),
+            })
+            .clone();
+        gauge.inc();
+        ClientGuard(gauge)
+    }
+
+    /// Returns the metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String, std::fmt::Error> {
+        let mut output = String::new();
+        encode(&mut output, &self.registry)?;
+        Ok(output)
+    }
+}
+
+/// Keeps an events client registered in the metrics while it is alive.
+pub struct ClientGuard(Gauge);
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Returns the metrics in the Prometheus text format.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    context_path = "/api",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", content_type = "application/openmetrics-text"),
+    )
+)]
+pub async fn metrics_handler(State(state): State<ServiceState>) -> Result<Response, Error> {
+    let body = state
+        .metrics
+        .encode()
+        .map_err(|e| Error::Anyhow(format!("could not encode the metrics: {}", e)))?;
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE))],
+        body,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Metrics;
+    use crate::web::Event;
+    use agama_lib::{manager::InstallationPhase, progress::Progress};
+    use axum::http::{Method, StatusCode};
+    use std::time::Duration;
+
+    #[test]
+    fn test_encode_metrics() {
+        let metrics = Metrics::new();
+        metrics.request_started(&Method::GET);
+        metrics.request_finished(StatusCode::OK, Duration::from_millis(5));
+        metrics.push(&Event::InstallationPhaseChanged {
+            phase: InstallationPhase::Install,
+        });
+        metrics.push(&Event::Progress {
+            service: "software".to_string(),
+            progress: Progress {
+                current_step: 2,
+                max_steps: 5,
+                current_title: "Installing".to_string(),
+                finished: false,
+            },
+        });
+        metrics.set_open_questions(1);
+        let guard = metrics.client_connected("websocket");
+
+        let output = metrics.encode().unwrap();
+        assert!(output.contains("agama_http_requests_total{method=\"GET\"} 1"));
+        assert!(output.contains("agama_http_responses_total{status=\"200\"} 1"));
+        assert!(output.contains("agama_http_requests_in_flight 0"));
+        assert!(output.contains("agama_events_total{type=\"InstallationPhaseChanged\"} 1"));
+        assert!(output.contains("agama_installation_phase{phase=\"install\"} 1"));
+        assert!(output.contains("agama_installation_phase{phase=\"config\"} 0"));
+        assert!(output.contains("agama_progress_step{service=\"software\"} 2"));
+        assert!(output.contains("agama_progress_steps{service=\"software\"} 5"));
+        assert!(output.contains("agama_open_questions 1"));
+        assert!(output.contains("agama_event_clients{transport=\"websocket\"} 1"));
+
+        drop(guard);
+        let output = metrics.encode().unwrap();
+        assert!(output.contains("agama_event_clients{transport=\"websocket\"} 0"));
+    }
+}
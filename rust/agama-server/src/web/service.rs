@@ -18,8 +18,18 @@
 // To contact SUSE LLC about this file by physical or electronic mail, you may
 // find current contact information at www.suse.com.
 
-use super::http::{login, login_from_query, logout, session};
-use super::{config::ServiceConfig, state::ServiceState, EventsSender};
+use super::http::{
+    create_token, list_sessions, login, login_from_query, logout, revoke_session, session,
+};
+use super::{
+    audit::{audit_handler, audit_middleware},
+    config::ServiceConfig,
+    keys::TokenKeys,
+    metrics::metrics_handler,
+    state::ServiceState,
+    throttle::LoginThrottle,
+    AuditLog, EventsJournal, EventsSender, Metrics, DEFAULT_JOURNAL_CAPACITY,
+};
 use agama_lib::auth::TokenClaims;
 use axum::http::HeaderValue;
 use axum::{
@@ -27,7 +37,7 @@ use axum::{
     extract::Request,
     middleware,
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use hyper::header::CACHE_CONTROL;
@@ -46,12 +56,17 @@ use tracing::Span;
 /// It is responsible for building an axum service which includes:
 ///
 /// * A static assets directory (`public_dir`).
-/// * A websocket at the `/ws` path.
-/// * An authentication endpoint at `/auth`.
+/// * A websocket at the `/ws` path, which replays the events from a journal.
+/// * A Server-Sent Events endpoint at the `/events` path, streaming the same events.
+/// * An authentication endpoint at `/auth`, which also allows managing the tokens and sessions.
 /// * A 'ping' endpoint at '/ping'.
 /// * A number of authenticated services that are added using the `add_service` function.
+/// * An audit log of the requests that might change something, available at `/audit`.
+/// * The metrics of the web server and the installation process, available at `/metrics`.
 pub struct MainServiceBuilder {
     config: ServiceConfig,
+    keys: Option<TokenKeys>,
+    metrics: Option<Metrics>,
     events: EventsSender,
     api_router: Router<ServiceState>,
     public_dir: PathBuf,
@@ -66,13 +81,17 @@ impl MainServiceBuilder {
     where
         P: AsRef<Path>,
     {
-        let api_router = Router::new().route("/ws", get(super::ws::ws_handler));
+        let api_router = Router::new()
+            .route("/ws", get(super::ws::ws_handler))
+            .route("/events", get(super::sse::sse_handler));
         let config = ServiceConfig::default();
 
         Self {
             events,
             api_router,
             config,
+            keys: None,
+            metrics: None,
             public_dir: PathBuf::from(public_dir.as_ref()),
         }
     }
@@ -81,6 +100,27 @@ impl MainServiceBuilder {
         Self { config, ..self }
     }
 
+    /// Sets the keys to sign and verify the tokens.
+    ///
+    /// If they are not set, it uses the `jwt_secret` from the configuration.
+    pub fn with_keys(self, keys: TokenKeys) -> Self {
+        Self {
+            keys: Some(keys),
+            ..self
+        }
+    }
+
+    /// Sets the metrics to update.
+    ///
+    /// It allows updating the metrics from outside the service (e.g., the number of questions). If
+    /// they are not set, the builder creates a new set of metrics.
+    pub fn with_metrics(self, metrics: Metrics) -> Self {
+        Self {
+            metrics: Some(metrics),
+            ..self
+        }
+    }
+
     /// Add an authenticated service.
     ///
     /// * `path`: Path to mount the service under `/api`.
@@ -98,22 +138,49 @@ impl MainServiceBuilder {
     }
 
     pub fn build(self) -> Router {
+        let journal = EventsJournal::new(DEFAULT_JOURNAL_CAPACITY);
+        journal.record(&self.events);
+        let keys = self
+            .keys
+            .unwrap_or_else(|| TokenKeys::from_secret(&self.config.jwt_secret));
+        let audit = self.config.audit_log.as_ref().map(AuditLog::new);
+        let metrics = self.metrics.unwrap_or_default();
+        metrics.record(&self.events);
+        let login_throttle = LoginThrottle::new(self.config.login_limits.clone());
+        let sessions = self.config.sessions();
         let state = ServiceState {
             config: self.config,
+            keys,
             events: self.events,
+            journal,
+            sessions,
+            audit,
+            metrics: metrics.clone(),
+            login_throttle,
             public_dir: self.public_dir.clone(),
         };
 
         let api_router = self
             .api_router
+            .route("/auth/tokens", post(create_token))
+            .route("/auth/sessions", get(list_sessions))
+            .route("/auth/sessions/:id", delete(revoke_session))
+            .route("/audit", get(audit_handler))
+            .route("/metrics", get(metrics_handler))
             .route_layer(middleware::from_extractor_with_state::<TokenClaims, _>(
                 state.clone(),
             ))
             .route("/ping", get(super::http::ping))
-            .route("/auth", post(login).get(session).delete(logout));
+            .route("/auth/keys", get(super::http::public_keys))
+            .route("/auth", post(login).get(session).delete(logout))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                audit_middleware,
+            ));
 
         tracing::info!("Serving static files from {}", self.public_dir.display());
         let serve = ServeDir::new(self.public_dir).precompressed_gzip();
+        let response_metrics = metrics.clone();
 
         Router::new()
             .nest_service("/", serve)
@@ -121,12 +188,14 @@ impl MainServiceBuilder {
             .nest("/api", api_router)
             .layer(
                 TraceLayer::new_for_http()
-                    .on_request(|request: &Request<Body>, _span: &Span| {
-                        tracing::info!("request: {} {}", request.method(), request.uri().path())
+                    .on_request(move |request: &Request<Body>, _span: &Span| {
+                        tracing::info!("request: {} {}", request.method(), request.uri().path());
+                        metrics.request_started(request.method());
                     })
                     .on_response(
-                        |response: &Response<Body>, latency: Duration, _span: &Span| {
-                            tracing::info!("response: {} {:?}", response.status(), latency)
+                        move |response: &Response<Body>, latency: Duration, _span: &Span| {
+                            tracing::info!("response: {} {:?}", response.status(), latency);
+                            response_metrics.request_finished(response.status(), latency);
                         },
                     ),
             )
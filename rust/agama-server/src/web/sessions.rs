@@ -0,0 +1,273 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Keeps track of the sessions (the issued tokens).
+//!
+//! Each token issued by the server includes an identifier (the `jti` claim), so it can be listed
+//! and revoked before it expires. The revoked identifiers are kept until the tokens expire.
+//!
+//! Tokens without an identifier (like the master token) are not tracked.
+//!
+//! When the keys are kept in a directory, the sessions and the revoked identifiers are written
+//! to a file in the same directory (see [SESSIONS_FILE]), so they survive a restart of the server.
+
+use agama_lib::auth::{TokenClaims, TokenScope};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    net::SocketAddr,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+/// Name of the file, in the keys directory, that keeps the sessions.
+pub const SESSIONS_FILE: &str = "sessions.json";
+
+/// Session (issued token).
+#[derive(Clone, Debug, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    /// Session identifier (the `jti` claim of the token).
+    pub id: String,
+    /// Issue time (seconds since the Unix epoch).
+    pub issued_at: i64,
+    /// Expiration time (seconds since the Unix epoch).
+    pub expires_at: i64,
+    /// Address of the client that requested the token.
+    pub client_address: Option<String>,
+    /// What the token grants access to.
+    pub scope: TokenScope,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct SessionsState {
+    sessions: HashMap<String, Session>,
+    /// Revoked identifiers and their expiration time.
+    revoked: HashMap<String, i64>,
+}
+
+impl SessionsState {
+    /// Forgets the sessions that already expired.
+    fn prune(&mut self) {
+        let now = now();
+        self.sessions.retain(|_, s| s.expires_at > now);
+        self.revoked.retain(|_, exp| *exp > now);
+    }
+}
+
+/// Registry of the sessions.
+#[derive(Clone, Default)]
+pub struct SessionsRegistry {
+    state: Arc<Mutex<SessionsState>>,
+    /// File to keep the sessions (`None` to keep them only in memory).
+    path: Option<PathBuf>,
+}
+
+impl SessionsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a registry that keeps the sessions in the given file.
+    ///
+    /// If the file cannot be read, it starts with no sessions.
+    ///
+    /// * `path`: file to keep the sessions.
+    pub fn with_file<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let state = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                tracing::warn!("Ignoring the sessions in {}: {}", path.display(), error);
+                SessionsState::default()
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => SessionsState::default(),
+            Err(error) => {
+                tracing::warn!(
+                    "Could not read the sessions from {}: {}",
+                    path.display(),
+                    error
+                );
+                SessionsState::default()
+            }
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            path: Some(path),
+        }
+    }
+
+    /// Registers a new session, setting the identifier of the given claims.
+    ///
+    /// * `claims`: claims of the token to issue.
+    /// * `client_address`: address of the client that requested the token.
+    pub fn register(
+        &self,
+        claims: &mut TokenClaims,
+        client_address: Option<SocketAddr>,
+    ) -> Session {
+        let id = Uuid::new_v4().to_string();
+        claims.jti = Some(id.clone());
+        let session = Session {
+            id: id.clone(),
+            issued_at: claims.iat,
+            expires_at: claims.exp,
+            client_address: client_address.map(|a| a.ip().to_canonical().to_string()),
+            scope: claims.scope,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        state.prune();
+        state.sessions.insert(id, session.clone());
+        self.save(&state);
+        session
+    }
+
+    /// Returns the active sessions, sorted by issue time.
+    pub fn list(&self) -> Vec<Session> {
+        let mut state = self.state.lock().unwrap();
+        state.prune();
+        let mut sessions: Vec<_> = state.sessions.values().cloned().collect();
+        sessions.sort_by_key(|s| (s.issued_at, s.id.clone()));
+        sessions
+    }
+
+    /// Revokes a session.
+    ///
+    /// It returns `false` if the session does not exist.
+    ///
+    /// * `id`: session identifier.
+    pub fn revoke(&self, id: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.prune();
+        let Some(session) = state.sessions.remove(id) else {
+            return false;
+        };
+        state.revoked.insert(session.id, session.expires_at);
+        self.save(&state);
+        true
+    }
+
+    /// Determines whether a session was revoked.
+    ///
+    /// * `id`: session identifier.
+    pub fn is_revoked(&self, id: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state.revoked.contains_key(id)
+    }
+
+    /// Writes the sessions to the file, if any.
+    ///
+    /// The file is replaced atomically, so a crash does not leave a truncated file behind.
+    fn save(&self, state: &SessionsState) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(error) = write_state(path, state) {
+            tracing::error!(
+                "Could not write the sessions to {}: {}",
+                path.display(),
+                error
+            );
+        }
+    }
+}
+
+fn write_state(path: &Path, state: &SessionsState) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .mode(0o600)
+        .open(&tmp_path)?;
+    file.write_all(&serde_json::to_vec(state)?)?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionsRegistry;
+    use agama_lib::auth::{TokenClaims, TokenScope};
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_register_and_revoke() {
+        let registry = SessionsRegistry::new();
+        let mut claims = TokenClaims::new(TokenScope::ReadOnly, Duration::from_secs(60));
+        let session =
+            registry.register(&mut claims, Some("[::ffff:10.0.0.1]:4242".parse().unwrap()));
+        assert_eq!(claims.jti.as_ref(), Some(&session.id));
+        assert_eq!(session.client_address.as_deref(), Some("10.0.0.1"));
+
+        let mut expired = TokenClaims {
+            exp: claims.iat - 1,
+            ..TokenClaims::default()
+        };
+        registry.register(&mut expired, None);
+
+        let sessions = registry.list();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].scope, TokenScope::ReadOnly);
+
+        assert!(!registry.is_revoked(&session.id));
+        assert!(registry.revoke(&session.id));
+        assert!(registry.is_revoked(&session.id));
+        assert!(registry.list().is_empty());
+        assert!(!registry.revoke("unknown"));
+    }
+
+    #[test]
+    fn test_persist_sessions() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("jwt/sessions.json");
+        let registry = SessionsRegistry::with_file(&path);
+        let mut active = TokenClaims::new(TokenScope::Full, Duration::from_secs(60));
+        let active = registry.register(&mut active, None);
+        let mut revoked = TokenClaims::new(TokenScope::ReadOnly, Duration::from_secs(60));
+        let revoked = registry.register(&mut revoked, None);
+        assert!(registry.revoke(&revoked.id));
+
+        // a new registry (e.g., after restarting the server) reads the same sessions
+        let registry = SessionsRegistry::with_file(&path);
+        let sessions = registry.list();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, active.id);
+        assert!(registry.is_revoked(&revoked.id));
+        assert!(!registry.is_revoked(&active.id));
+        assert!(registry.revoke(&active.id));
+    }
+}
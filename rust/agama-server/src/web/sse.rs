@@ -0,0 +1,73 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Implements the Server-Sent Events endpoint.
+//!
+//! It streams the same events as the websocket, including the `Gap` messages, and it accepts
+//! the same query parameters (see [EventsQuery]). Each event includes its sequence number as
+//! the SSE id, so the clients can resume the stream using the `Last-Event-ID` header. As in the
+//! websocket, the secrets are masked unless the token grants full access.
+
+use super::{state::ServiceState, EventsQuery, JournalMessage};
+use agama_lib::auth::{TokenClaims, TokenScope};
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream};
+use std::convert::Infallible;
+
+const LAST_EVENT_ID: &str = "Last-Event-ID";
+
+pub async fn sse_handler(
+    State(state): State<ServiceState>,
+    Query(query): Query<EventsQuery>,
+    claims: TokenClaims,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let last_event_id = headers
+        .get(LAST_EVENT_ID)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let subscription = state
+        .journal
+        .subscribe(last_event_id.or(query.since), query.filter())
+        .redacting_secrets(claims.scope != TokenScope::Full);
+    let client = state.metrics.client_connected("sse");
+    let events = stream::unfold(
+        (subscription, client),
+        |(mut subscription, client)| async move {
+            let message = subscription.next().await?;
+            Some((Ok(to_sse_event(&message)), (subscription, client)))
+        },
+    );
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+fn to_sse_event(message: &JournalMessage) -> SseEvent {
+    let event = SseEvent::default().data(message.to_json());
+    match message {
+        JournalMessage::Event(entry) => event
+            .id(entry.seq.to_string())
+            .event(entry.event_type().unwrap_or("message")),
+        JournalMessage::Gap { .. } => event.event("Gap"),
+    }
+}
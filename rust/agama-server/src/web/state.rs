@@ -20,15 +20,26 @@
 
 //! Implements the web service state.
 
-use super::{config::ServiceConfig, EventsSender};
+use super::{
+    config::ServiceConfig, keys::TokenKeys, throttle::LoginThrottle, AuditLog, EventsJournal,
+    EventsSender, Metrics, SessionsRegistry,
+};
 use std::path::PathBuf;
 
 /// Web service state.
 ///
-/// It holds the service configuration, the current D-Bus connection and a channel to send events.
+/// It holds the service configuration, the keys to sign the tokens, the current D-Bus connection,
+/// a channel to send events, the journal of the events, the active sessions, the audit log, the
+/// metrics and the login attempts of each client.
 #[derive(Clone)]
 pub struct ServiceState {
     pub config: ServiceConfig,
+    pub keys: TokenKeys,
     pub events: EventsSender,
+    pub journal: EventsJournal,
+    pub sessions: SessionsRegistry,
+    pub audit: Option<AuditLog>,
+    pub metrics: Metrics,
+    pub login_throttle: LoginThrottle,
     pub public_dir: PathBuf,
 }
@@ -0,0 +1,286 @@
+// Copyright (c) [2024] SUSE LLC
+//
+// All Rights Reserved.
+//
+// This program is free software; you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation; either version 2 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along
+// with this program; if not, contact SUSE LLC.
+//
+// To contact SUSE LLC about this file by physical or electronic mail, you may
+// find current contact information at www.suse.com.
+
+//! Limits the password logins to make brute-force attacks harder.
+//!
+//! Each client address can try to log in `max_attempts` times every `attempts_window` seconds.
+//! Additionally, after `max_failures` consecutive failed logins, the address is locked out for
+//! `lockout_time` seconds. In both cases, the server answers with a `429 Too Many Requests` status
+//! and a `Retry-After` header.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Limits of the password logins.
+///
+/// Setting `max_attempts` or `max_failures` to `0` disables the corresponding limit.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LoginLimits {
+    /// Maximum number of login attempts per client address in each window.
+    pub max_attempts: u32,
+    /// Length of the window to count the login attempts, in seconds.
+    pub attempts_window: u64,
+    /// Consecutive failed logins that lock out the client address.
+    pub max_failures: u32,
+    /// How long the client address is locked out, in seconds.
+    pub lockout_time: u64,
+}
+
+impl Default for LoginLimits {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            attempts_window: 60,
+            max_failures: 5,
+            lockout_time: 300,
+        }
+    }
+}
+
+/// Login status of a client, as recorded in the audit log.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LockoutStatus {
+    /// Number of consecutive failed logins.
+    pub failures: u32,
+    /// Seconds until the client is allowed to log in again, if it is locked out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Debug)]
+struct ClientState {
+    window_start: Instant,
+    attempts: u32,
+    failures: u32,
+    locked_until: Option<Instant>,
+}
+
+impl ClientState {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            attempts: 0,
+            failures: 0,
+            locked_until: None,
+        }
+    }
+}
+
+/// Keeps track of the login attempts of each client address.
+///
+/// Cloning the throttle is cheap: all the clones share the same state.
+#[derive(Clone, Debug)]
+pub struct LoginThrottle {
+    limits: LoginLimits,
+    clients: Arc<Mutex<HashMap<Option<IpAddr>, ClientState>>>,
+}
+
+impl LoginThrottle {
+    pub fn new(limits: LoginLimits) -> Self {
+        Self {
+            limits,
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Registers a login attempt.
+    ///
+    /// It returns the status of the client if it is not allowed to try to log in.
+    ///
+    /// * `address`: client address (`None` if it is unknown).
+    pub fn attempt(&self, address: Option<IpAddr>) -> Result<(), LockoutStatus> {
+        self.attempt_at(address, Instant::now())
+    }
+
+    /// Registers a failed login and returns the updated status of the client.
+    ///
+    /// * `address`: client address (`None` if it is unknown).
+    pub fn failed(&self, address: Option<IpAddr>) -> LockoutStatus {
+        self.failed_at(address, Instant::now())
+    }
+
+    /// Registers a successful login, resetting the failures count.
+    ///
+    /// * `address`: client address (`None` if it is unknown).
+    pub fn succeeded(&self, address: Option<IpAddr>) {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get_mut(&canonical(address)) {
+            client.failures = 0;
+        }
+    }
+
+    fn attempt_at(&self, address: Option<IpAddr>, now: Instant) -> Result<(), LockoutStatus> {
+        let mut clients = self.clients.lock().unwrap();
+        Self::prune(&mut clients, &self.limits, now);
+        let client = clients
+            .entry(canonical(address))
+            .or_insert_with(|| ClientState::new(now));
+
+        if let Some(until) = client.locked_until {
+            return Err(LockoutStatus {
+                failures: client.failures,
+                retry_after: Some(retry_after(until, now)),
+            });
+        }
+
+        let window = Duration::from_secs(self.limits.attempts_window);
+        if now.duration_since(client.window_start) >= window {
+            client.window_start = now;
+            client.attempts = 0;
+        }
+        if self.limits.max_attempts > 0 && client.attempts >= self.limits.max_attempts {
+            return Err(LockoutStatus {
+                failures: client.failures,
+                retry_after: Some(retry_after(client.window_start + window, now)),
+            });
+        }
+        client.attempts += 1;
+        Ok(())
+    }
+
+    fn failed_at(&self, address: Option<IpAddr>, now: Instant) -> LockoutStatus {
+        let mut clients = self.clients.lock().unwrap();
+        let client = clients
+            .entry(canonical(address))
+            .or_insert_with(|| ClientState::new(now));
+        client.failures += 1;
+
+        let mut status = LockoutStatus {
+            failures: client.failures,
+            retry_after: None,
+        };
+        if self.limits.max_failures > 0 && client.failures >= self.limits.max_failures {
+            let lockout = Duration::from_secs(self.limits.lockout_time);
+            tracing::warn!(
+                "Locking out {:?} for {:?} after {} failed logins",
+                address,
+                lockout,
+                client.failures
+            );
+            client.locked_until = Some(now + lockout);
+            status.retry_after = Some(lockout.as_secs());
+        }
+        status
+    }
+
+    /// Forgets the clients whose lockout and window are over, keeping their failures count only
+    /// while they are locked out.
+    fn prune(
+        clients: &mut HashMap<Option<IpAddr>, ClientState>,
+        limits: &LoginLimits,
+        now: Instant,
+    ) {
+        let window = Duration::from_secs(limits.attempts_window);
+        for client in clients.values_mut() {
+            if client.locked_until.is_some_and(|until| until <= now) {
+                client.locked_until = None;
+                client.failures = 0;
+            }
+        }
+        clients.retain(|_, client| {
+            client.locked_until.is_some()
+                || client.failures > 0
+                || now.duration_since(client.window_start) < window
+        });
+    }
+}
+
+fn canonical(address: Option<IpAddr>) -> Option<IpAddr> {
+    address.map(|a| a.to_canonical())
+}
+
+/// Returns the seconds until the given instant, rounded up (and at least one second).
+fn retry_after(until: Instant, now: Instant) -> u64 {
+    let remaining = until.saturating_duration_since(now);
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    seconds.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LockoutStatus, LoginLimits, LoginThrottle};
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::{Duration, Instant},
+    };
+
+    const CLIENT: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(192, 168, 122, 10)));
+    const OTHER: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(192, 168, 122, 20)));
+
+    #[test]
+    fn test_rate_limit() {
+        let throttle = LoginThrottle::new(LoginLimits {
+            max_attempts: 2,
+            attempts_window: 60,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert!(throttle.attempt_at(CLIENT, now).is_ok());
+        assert!(throttle.attempt_at(CLIENT, now).is_ok());
+        assert_eq!(
+            throttle.attempt_at(CLIENT, now + Duration::from_secs(20)),
+            Err(LockoutStatus {
+                failures: 0,
+                retry_after: Some(40)
+            })
+        );
+        assert!(throttle.attempt_at(OTHER, now).is_ok());
+        assert!(throttle
+            .attempt_at(CLIENT, now + Duration::from_secs(60))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_lockout() {
+        let throttle = LoginThrottle::new(LoginLimits {
+            max_failures: 2,
+            lockout_time: 300,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert!(throttle.attempt_at(CLIENT, now).is_ok());
+        assert_eq!(throttle.failed_at(CLIENT, now).retry_after, None);
+        assert!(throttle.attempt_at(CLIENT, now).is_ok());
+        assert_eq!(
+            throttle.failed_at(CLIENT, now),
+            LockoutStatus {
+                failures: 2,
+                retry_after: Some(300)
+            }
+        );
+
+        let status = throttle
+            .attempt_at(CLIENT, now + Duration::from_secs(100))
+            .unwrap_err();
+        assert_eq!(status.retry_after, Some(200));
+
+        assert!(throttle
+            .attempt_at(CLIENT, now + Duration::from_secs(300))
+            .is_ok());
+        assert_eq!(throttle.failed_at(CLIENT, now).failures, 1);
+    }
+}
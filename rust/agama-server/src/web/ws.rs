@@ -19,31 +19,40 @@
 // find current contact information at www.suse.com.
 
 //! Implements the websocket handling.
+//!
+//! The clients can resume the stream and filter the events using the query parameters (see
+//! [EventsQuery]), e.g., `/api/ws?since=42&types=Progress,IssuesChanged&services=storage`.
+//! The secrets included in the events are masked unless the token grants full access.
 
-use super::{state::ServiceState, EventsSender};
+use super::{state::ServiceState, EventsQuery, Metrics, Subscription};
+use agama_lib::auth::{TokenClaims, TokenScope};
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
+        Query, State, WebSocketUpgrade,
     },
     response::IntoResponse,
 };
 
 pub async fn ws_handler(
     State(state): State<ServiceState>,
+    Query(query): Query<EventsQuery>,
+    claims: TokenClaims,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state.events))
+    let subscription = state
+        .journal
+        .subscribe(query.since, query.filter())
+        .redacting_secrets(claims.scope != TokenScope::Full);
+    ws.on_upgrade(move |socket| handle_socket(socket, subscription, state.metrics))
 }
 
-async fn handle_socket(mut socket: WebSocket, events: EventsSender) {
-    let mut rx = events.subscribe();
-    while let Ok(msg) = rx.recv().await {
-        if let Ok(json) = serde_json::to_string(&msg) {
-            if socket.send(Message::Text(json)).await.is_err() {
-                tracing::info!("ws: client disconnected");
-                return;
-            }
+async fn handle_socket(mut socket: WebSocket, mut subscription: Subscription, metrics: Metrics) {
+    let _client = metrics.client_connected("websocket");
+    while let Some(message) = subscription.next().await {
+        if socket.send(Message::Text(message.to_json())).await.is_err() {
+            tracing::info!("ws: client disconnected");
+            return;
         }
     }
 }
@@ -21,13 +21,16 @@
 pub mod common;
 
 use agama_lib::error::ServiceError;
-use agama_lib::network::settings::{BondSettings, NetworkConnection};
+use agama_lib::network::settings::{
+    BondSettings, BridgeSettings, NetworkConnection, RouteSettings, VlanSettings,
+    WireguardPeerSettings, WireguardSettings,
+};
 use agama_lib::network::types::{DeviceType, SSID};
 use agama_server::network::web::network_service;
 use agama_server::network::{
     self,
     model::{self, AccessPoint, GeneralState, StateConfig},
-    Adapter, NetworkAdapterError, NetworkState,
+    Adapter, KeyfileAdapter, NetworkAdapterError, NetworkState,
 };
 
 use async_trait::async_trait;
@@ -40,6 +43,7 @@ use axum::{
 use common::body_to_string;
 use serde_json::to_string;
 use std::error::Error;
+use std::os::unix::fs::PermissionsExt;
 use tokio::{sync::broadcast, test};
 use tower::ServiceExt;
 
@@ -240,3 +244,304 @@ async fn test_add_bond_connection() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+async fn test_add_bridge_and_vlan_connections() -> Result<(), Box<dyn Error>> {
+    let state = build_state().await;
+    let network_service = build_service(state.clone()).await?;
+
+    let br0 = NetworkConnection {
+        id: "br0".to_string(),
+        interface: Some("br0".to_string()),
+        bridge: Some(BridgeSettings {
+            stp: Some(false),
+            ports: vec!["eth0".to_string()],
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let vlan = NetworkConnection {
+        id: "br0.10".to_string(),
+        vlan: Some(VlanSettings {
+            parent: "br0".to_string(),
+            id: 10,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    for conn in [br0, vlan] {
+        let request = Request::builder()
+            .uri("/connections")
+            .header("Content-Type", "application/json")
+            .method(Method::POST)
+            .body(serde_json::to_string(&conn)?)
+            .unwrap();
+        let response = network_service.clone().oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let request = Request::builder()
+        .uri("/connections")
+        .method(Method::GET)
+        .body(Body::empty())
+        .unwrap();
+
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_string(response.into_body()).await;
+    assert!(body.contains(r#""bridge":{"stp":false,"ports":["eth0"]}"#));
+    assert!(body.contains(r#""vlan":{"parent":"br0","id":10,"protocol":"802.1Q"}"#));
+
+    Ok(())
+}
+
+#[test]
+async fn test_apply_unreachable_route() -> Result<(), Box<dyn Error>> {
+    let state = build_state().await;
+    let network_service = build_service(state.clone()).await?;
+
+    let eth1 = NetworkConnection {
+        id: "eth1".to_string(),
+        method4: Some("manual".to_string()),
+        addresses: vec!["192.168.1.10/24".parse()?],
+        routes: vec![RouteSettings {
+            destination: "10.0.0.0/8".parse()?,
+            next_hop: Some("192.168.2.1".parse()?),
+            metric: Some(100),
+            table: Some(100),
+        }],
+        ..Default::default()
+    };
+    let request = Request::builder()
+        .uri("/connections")
+        .header("Content-Type", "application/json")
+        .method(Method::POST)
+        .body(serde_json::to_string(&eth1)?)
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri("/connections")
+        .method(Method::GET)
+        .body(Body::empty())
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_string(response.into_body()).await;
+    assert!(body.contains(
+        r#""routes":[{"destination":"10.0.0.0/8","nextHop":"192.168.2.1","metric":100,"table":100}]"#
+    ));
+
+    let request = Request::builder()
+        .uri("/system/apply")
+        .method(Method::POST)
+        .body(Body::empty())
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = body_to_string(response.into_body()).await;
+    assert!(body.contains("Next hop '192.168.2.1' is not reachable from connection 'eth1'"));
+
+    Ok(())
+}
+
+#[test]
+async fn test_add_wireguard_connection() -> Result<(), Box<dyn Error>> {
+    let state = build_state().await;
+    let network_service = build_service(state.clone()).await?;
+
+    let peer = WireguardPeerSettings {
+        public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".to_string(),
+        allowed_ips: vec!["10.0.0.0/8".parse()?],
+        endpoint: Some("vpn.example.net:51820".to_string()),
+        ..Default::default()
+    };
+    let mut wg0 = NetworkConnection {
+        id: "wg0".to_string(),
+        wireguard: Some(WireguardSettings {
+            private_key: Some("not-a-key".to_string()),
+            listen_port: Some(51820),
+            peers: vec![peer],
+        }),
+        ..Default::default()
+    };
+    let request = Request::builder()
+        .uri("/connections")
+        .header("Content-Type", "application/json")
+        .method(Method::POST)
+        .body(serde_json::to_string(&wg0)?)
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = body_to_string(response.into_body()).await;
+    assert!(body.contains("Invalid WireGuard key: private key"));
+    assert!(!body.contains("not-a-key"));
+
+    let wireguard = wg0.wireguard.as_mut().unwrap();
+    wireguard.private_key = Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".to_string());
+    let request = Request::builder()
+        .uri("/connections")
+        .header("Content-Type", "application/json")
+        .method(Method::POST)
+        .body(serde_json::to_string(&wg0)?)
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // updating the connection without the private key keeps the current one
+    let wireguard = wg0.wireguard.as_mut().unwrap();
+    wireguard.private_key = None;
+    wireguard.listen_port = Some(51821);
+    let request = Request::builder()
+        .uri("/connections/wg0")
+        .header("Content-Type", "application/json")
+        .method(Method::PUT)
+        .body(serde_json::to_string(&wg0)?)
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let request = Request::builder()
+        .uri("/connections/wg0")
+        .method(Method::GET)
+        .body(Body::empty())
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_string(response.into_body()).await;
+    assert!(body.contains(r#""privateKey":"yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=""#));
+    assert!(body.contains(r#""listenPort":51821"#));
+
+    Ok(())
+}
+
+#[test]
+async fn test_nmstate() -> Result<(), Box<dyn Error>> {
+    let mut state = build_state().await;
+    let wlan0 = model::Connection::new("wlan0".to_string(), DeviceType::Wireless);
+    state.add_connection(wlan0)?;
+    let network_service = build_service(state.clone()).await?;
+
+    let yaml = r#"
+interfaces:
+  - name: br0
+    type: linux-bridge
+    ipv4:
+      enabled: true
+      dhcp: true
+    bridge:
+      port:
+        - name: eth0
+"#;
+    let request = Request::builder()
+        .uri("/nmstate")
+        .header(header::CONTENT_TYPE, "application/yaml")
+        .method(Method::PUT)
+        .body(yaml.to_string())
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let json = r#"{ "dns-resolver": { "config": { "server": ["192.168.1.1"] } } }"#;
+    let request = Request::builder()
+        .uri("/nmstate")
+        .header(header::CONTENT_TYPE, "application/json")
+        .method(Method::PUT)
+        .body(json.to_string())
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let request = Request::builder()
+        .uri("/nmstate")
+        .method(Method::GET)
+        .body(Body::empty())
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/yaml"
+    );
+    let body = body_to_string(response.into_body()).await;
+    assert!(
+        body.starts_with("# Connections that cannot be represented in nmstate format: \"wlan0\"\n")
+    );
+    assert!(body.contains("  - name: br0\n    type: linux-bridge\n"));
+    assert!(body.contains("      port:\n        - name: eth0\n"));
+    assert!(body.contains("    server:\n      - 192.168.1.1\n"));
+
+    let request = Request::builder()
+        .uri("/nmstate")
+        .header(header::CONTENT_TYPE, "application/yaml")
+        .method(Method::PUT)
+        .body("interfaces:\n  - name: wlan0\n    type: wifi\n".to_string())
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = body_to_string(response.into_body()).await;
+    assert!(body.contains("unsupported interface type 'wifi' ('wlan0')"));
+
+    Ok(())
+}
+
+#[test]
+async fn test_keyfile_adapter() -> Result<(), Box<dyn Error>> {
+    let root = tempfile::tempdir()?;
+    let adapter = KeyfileAdapter::new(root.path());
+    let connections_dir = adapter.connections_dir();
+    std::fs::create_dir_all(&connections_dir)?;
+    std::fs::write(
+        connections_dir.join("eth0.nmconnection"),
+        "[connection]\nid=eth0\nuuid=a1b2c3d4-e5f6-4a5b-8c9d-0e1f2a3b4c5d\ntype=ethernet\n",
+    )?;
+
+    let (tx, _rx) = broadcast::channel(16);
+    let network_service = network_service(adapter, tx).await?;
+
+    let request = Request::builder()
+        .uri("/connections")
+        .method(Method::GET)
+        .body(Body::empty())
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_string(response.into_body()).await;
+    assert!(body.contains(r#""id":"eth0""#));
+
+    let eth1 = NetworkConnection {
+        id: "eth1".to_string(),
+        method4: Some("manual".to_string()),
+        addresses: vec!["192.168.1.10/24".parse()?],
+        interface: Some("eth1".to_string()),
+        ..Default::default()
+    };
+    let request = Request::builder()
+        .uri("/connections")
+        .header("Content-Type", "application/json")
+        .method(Method::POST)
+        .body(serde_json::to_string(&eth1)?)
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri("/system/apply")
+        .method(Method::POST)
+        .body(Body::empty())
+        .unwrap();
+    let response = network_service.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let path = connections_dir.join("eth1.nmconnection");
+    let content = std::fs::read_to_string(&path)?;
+    assert!(content.contains("interface-name=eth1"));
+    assert!(content.contains("address1=192.168.1.10/24"));
+    assert_eq!(
+        std::fs::metadata(&path)?.permissions().mode() & 0o777,
+        0o600
+    );
+    Ok(())
+}
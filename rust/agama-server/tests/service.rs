@@ -20,8 +20,15 @@
 
 pub mod common;
 
-use agama_lib::auth::AuthToken;
-use agama_server::web::{MainServiceBuilder, ServiceConfig};
+use agama_lib::{
+    auth::{AuthToken, TokenClaims, TokenScope},
+    manager::InstallationPhase,
+    users::{FirstUser, RootUser},
+};
+use agama_server::web::{
+    keys::{KeyStore, SigningAlgorithm},
+    ClientCertificate, Event, LoginLimits, MainServiceBuilder, ServiceConfig,
+};
 use axum::{
     body::Body,
     http::{Method, Request, StatusCode},
@@ -29,6 +36,7 @@ use axum::{
     routing::get,
 };
 use common::body_to_string;
+use http_body_util::BodyExt;
 use std::{error::Error, path::PathBuf};
 use tokio::{sync::broadcast::channel, test};
 use tower::ServiceExt;
@@ -66,6 +74,7 @@ async fn protected() -> String {
 async fn access_protected_route(token: &str, jwt_secret: &str) -> Response {
     let config = ServiceConfig {
         jwt_secret: jwt_secret.to_string(),
+        ..Default::default()
     };
     let (tx, _) = channel(16);
     let web_service = MainServiceBuilder::new(tx, public_dir())
@@ -103,3 +112,552 @@ async fn test_access_protected_route_failed() -> Result<(), Box<dyn Error>> {
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     Ok(())
 }
+
+#[test]
+async fn test_asymmetric_keys() -> Result<(), Box<dyn Error>> {
+    let tmp_dir = tempfile::tempdir()?;
+    let keys = KeyStore::new(tmp_dir.path()).load_or_create(SigningAlgorithm::EdDSA)?;
+    let token = keys.ring.sign(&TokenClaims::default())?;
+
+    let (tx, _) = channel(16);
+    let web_service = MainServiceBuilder::new(tx, public_dir())
+        .add_service("/protected", get(protected))
+        .with_keys(keys)
+        .build();
+
+    let request = Request::builder()
+        .uri("/api/protected")
+        .header("Authorization", format!("Bearer {}", token.as_str()))
+        .body(Body::empty())
+        .unwrap();
+    let response = web_service.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri("/api/auth/keys")
+        .body(Body::empty())
+        .unwrap();
+    let response = web_service.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_string(response.into_body()).await;
+    let jwks: serde_json::Value = serde_json::from_str(&body)?;
+    assert_eq!(jwks["keys"][0]["alg"], "EdDSA");
+    assert_eq!(jwks["keys"][0]["crv"], "Ed25519");
+    Ok(())
+}
+
+#[test]
+async fn test_scoped_tokens() -> Result<(), Box<dyn Error>> {
+    let config = ServiceConfig {
+        jwt_secret: "nots3cr3t".to_string(),
+        ..Default::default()
+    };
+    let (tx, _) = channel(16);
+    let web_service = MainServiceBuilder::new(tx, public_dir())
+        .add_service("/protected", get(protected).put(protected))
+        .add_service("/software/registration", get(protected))
+        .add_service("/network/connections", get(protected))
+        .with_config(config)
+        .build();
+
+    let request = |method: Method, uri: &str, token: &AuthToken, body: Body| {
+        Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("Authorization", format!("Bearer {}", token.as_str()))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .unwrap()
+    };
+
+    let token = AuthToken::generate("nots3cr3t")?;
+    let response = web_service
+        .clone()
+        .oneshot(request(
+            Method::POST,
+            "/api/auth/tokens",
+            &token,
+            Body::from("{\"scope\":\"read-only\",\"ttl\":60}"),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_string(response.into_body()).await;
+    let body: serde_json::Value = serde_json::from_str(&body)?;
+    let read_only = AuthToken::new(body["token"].as_str().unwrap());
+    let claims = read_only.claims("nots3cr3t")?;
+    assert_eq!(claims.scope, TokenScope::ReadOnly);
+
+    let response = web_service
+        .clone()
+        .oneshot(request(
+            Method::GET,
+            "/api/protected",
+            &read_only,
+            Body::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = web_service
+        .clone()
+        .oneshot(request(
+            Method::PUT,
+            "/api/protected",
+            &read_only,
+            Body::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // resources containing secrets are not readable
+    for uri in ["/api/software/registration", "/api/network/connections"] {
+        let response = web_service
+            .clone()
+            .oneshot(request(Method::GET, uri, &read_only, Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = web_service
+            .clone()
+            .oneshot(request(Method::GET, uri, &token, Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let expired = TokenClaims {
+        exp: claims.exp - 3600,
+        ..TokenClaims::default()
+    };
+    let expired = AuthToken::generate_with_claims("nots3cr3t", &expired)?;
+    let response = web_service
+        .oneshot(request(
+            Method::GET,
+            "/api/protected",
+            &expired,
+            Body::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[test]
+async fn test_sessions() -> Result<(), Box<dyn Error>> {
+    let config = ServiceConfig {
+        jwt_secret: "nots3cr3t".to_string(),
+        ..Default::default()
+    };
+    let (tx, _) = channel(16);
+    let web_service = MainServiceBuilder::new(tx, public_dir())
+        .add_service("/protected", get(protected))
+        .with_config(config)
+        .build();
+
+    let request = |method: Method, uri: &str, token: &str, body: Body| {
+        Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .unwrap()
+    };
+
+    let master = AuthToken::generate("nots3cr3t")?;
+    let response = web_service
+        .clone()
+        .oneshot(request(
+            Method::POST,
+            "/api/auth/tokens",
+            master.as_str(),
+            Body::from("{\"scope\":\"questions\"}"),
+        ))
+        .await
+        .unwrap();
+    let body = body_to_string(response.into_body()).await;
+    let body: serde_json::Value = serde_json::from_str(&body)?;
+    let token = body["token"].as_str().unwrap().to_string();
+
+    let response = web_service
+        .clone()
+        .oneshot(request(
+            Method::GET,
+            "/api/auth/sessions",
+            master.as_str(),
+            Body::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_string(response.into_body()).await;
+    let sessions: serde_json::Value = serde_json::from_str(&body)?;
+    assert_eq!(sessions.as_array().unwrap().len(), 1);
+    assert_eq!(sessions[0]["scope"], "questions");
+    let id = sessions[0]["id"].as_str().unwrap();
+
+    let response = web_service
+        .clone()
+        .oneshot(request(Method::GET, "/api/auth", &token, Body::empty()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let login = |token: &str| {
+        Request::builder()
+            .uri(format!("/login?token={}", token))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = web_service.clone().oneshot(login(&token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert!(response.headers().contains_key("set-cookie"));
+
+    let response = web_service
+        .clone()
+        .oneshot(request(
+            Method::DELETE,
+            &format!("/api/auth/sessions/{}", id),
+            master.as_str(),
+            Body::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = web_service
+        .clone()
+        .oneshot(request(Method::GET, "/api/auth", &token, Body::empty()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // a revoked token cannot be used to log into the web UI
+    let response = web_service.clone().oneshot(login(&token)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert!(!response.headers().contains_key("set-cookie"));
+
+    let response = web_service
+        .oneshot(request(
+            Method::DELETE,
+            &format!("/api/auth/sessions/{}", id),
+            master.as_str(),
+            Body::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[test]
+async fn test_audit_log() -> Result<(), Box<dyn Error>> {
+    let tmp_dir = tempfile::tempdir()?;
+    let config = ServiceConfig {
+        jwt_secret: "nots3cr3t".to_string(),
+        audit_log: Some(tmp_dir.path().join("audit.jsonl")),
+        ..Default::default()
+    };
+    let (tx, _) = channel(16);
+    let web_service = MainServiceBuilder::new(tx, public_dir())
+        .add_service("/protected", get(protected).put(protected))
+        .with_config(config)
+        .build();
+
+    let token = AuthToken::generate("nots3cr3t")?;
+    let request = |method: Method, uri: &str, body: Body| {
+        Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("Authorization", format!("Bearer {}", token.as_str()))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .unwrap()
+    };
+
+    let body = "{\"userName\":\"jane\",\"password\":\"nots3cr3t\"}";
+    let response = web_service
+        .clone()
+        .oneshot(request(Method::PUT, "/api/protected", Body::from(body)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = web_service
+        .oneshot(request(Method::GET, "/api/audit", Body::empty()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_string(response.into_body()).await;
+    let entries: serde_json::Value = serde_json::from_str(&body)?;
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+    assert_eq!(entries[0]["method"], "PUT");
+    assert_eq!(entries[0]["path"], "/api/protected");
+    assert_eq!(entries[0]["status"], 200);
+    assert_eq!(entries[0]["scope"], "full");
+    assert_eq!(
+        entries[0]["body"],
+        serde_json::json!({ "userName": "jane", "password": "[redacted]" })
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_login_lockout() -> Result<(), Box<dyn Error>> {
+    let tmp_dir = tempfile::tempdir()?;
+    let config = ServiceConfig {
+        jwt_secret: "nots3cr3t".to_string(),
+        audit_log: Some(tmp_dir.path().join("audit.jsonl")),
+        login_limits: LoginLimits {
+            max_failures: 1,
+            lockout_time: 60,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (tx, _) = channel(16);
+    let web_service = MainServiceBuilder::new(tx, public_dir())
+        .with_config(config)
+        .build();
+
+    let login = || {
+        Request::builder()
+            .uri("/api/auth")
+            .method(Method::POST)
+            .header("Content-Type", "application/json")
+            .body(Body::from("{\"password\":\"wrong\"}"))
+            .unwrap()
+    };
+
+    let response = web_service.clone().oneshot(login()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = web_service.clone().oneshot(login()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["Retry-After"].to_str()?.parse()?;
+    assert!(retry_after > 0 && retry_after <= 60);
+
+    let token = AuthToken::generate("nots3cr3t")?;
+    let request = Request::builder()
+        .uri("/api/audit")
+        .header("Authorization", format!("Bearer {}", token.as_str()))
+        .body(Body::empty())
+        .unwrap();
+    let response = web_service.oneshot(request).await.unwrap();
+    let body = body_to_string(response.into_body()).await;
+    let entries: serde_json::Value = serde_json::from_str(&body)?;
+    assert_eq!(entries[0]["status"], 400);
+    assert_eq!(entries[0]["lockout"]["failures"], 1);
+    assert_eq!(entries[1]["status"], 429);
+    assert!(entries[1]["lockout"]["retryAfter"].is_u64());
+    Ok(())
+}
+
+#[test]
+async fn test_client_certificate() -> Result<(), Box<dyn Error>> {
+    let mut config = ServiceConfig {
+        jwt_secret: "nots3cr3t".to_string(),
+        ..Default::default()
+    };
+    config
+        .client_certs
+        .subjects
+        .insert("CN=provisioning".to_string(), TokenScope::ReadOnly);
+    let (tx, _) = channel(16);
+    let web_service = MainServiceBuilder::new(tx, public_dir())
+        .add_service("/protected", get(protected).put(protected))
+        .with_config(config)
+        .build();
+
+    let request = |method: Method, common_name: &str| {
+        let mut request = Request::builder()
+            .uri("/api/protected")
+            .method(method)
+            .body(Body::empty())
+            .unwrap();
+        request.extensions_mut().insert(ClientCertificate {
+            subject: format!("O=Example,CN={}", common_name),
+            common_name: Some(common_name.to_string()),
+        });
+        request
+    };
+
+    let response = web_service
+        .clone()
+        .oneshot(request(Method::GET, "provisioning"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = web_service
+        .clone()
+        .oneshot(request(Method::PUT, "provisioning"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = web_service
+        .oneshot(request(Method::GET, "unknown"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[test]
+async fn test_metrics() -> Result<(), Box<dyn Error>> {
+    let config = ServiceConfig {
+        jwt_secret: "nots3cr3t".to_string(),
+        ..Default::default()
+    };
+    let (tx, _) = channel(16);
+    let web_service = MainServiceBuilder::new(tx.clone(), public_dir())
+        .add_service("/protected", get(protected))
+        .with_config(config)
+        .build();
+
+    tx.send(Event::InstallationPhaseChanged {
+        phase: InstallationPhase::Config,
+    })?;
+    // give the metrics a chance to record the events
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let token = AuthToken::generate("nots3cr3t")?;
+    let request = |uri: &str| {
+        Request::builder()
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", token.as_str()))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = web_service
+        .clone()
+        .oneshot(request("/api/protected"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = web_service.oneshot(request("/api/metrics")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_string(response.into_body()).await;
+    assert!(body.contains("agama_http_responses_total{status=\"200\"} 1"));
+    assert!(body.contains("agama_events_total{type=\"InstallationPhaseChanged\"} 1"));
+    assert!(body.contains("agama_installation_phase{phase=\"config\"} 1"));
+    Ok(())
+}
+
+#[test]
+async fn test_events_stream() -> Result<(), Box<dyn Error>> {
+    let config = ServiceConfig {
+        jwt_secret: "nots3cr3t".to_string(),
+        ..Default::default()
+    };
+    let (tx, _) = channel(16);
+    let web_service = MainServiceBuilder::new(tx.clone(), public_dir())
+        .with_config(config)
+        .build();
+
+    tx.send(Event::ProductChanged {
+        id: "Tumbleweed".to_string(),
+    })?;
+    tx.send(Event::RegistrationChanged)?;
+    // give the journal a chance to record the events
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let token = AuthToken::generate("nots3cr3t")?;
+    let request = Request::builder()
+        .uri("/api/events?types=RegistrationChanged")
+        .header("Authorization", format!("Bearer {}", token.as_str()))
+        .header("Last-Event-ID", "0")
+        .body(Body::empty())
+        .unwrap();
+    let response = web_service.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut body = response.into_body();
+    let frame = body.frame().await.unwrap()?.into_data().unwrap();
+    assert_eq!(
+        String::from_utf8(frame.to_vec())?,
+        "data: {\"seq\":2,\"type\":\"RegistrationChanged\"}\nid: 2\nevent: RegistrationChanged\n\n"
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_events_stream_secrets() -> Result<(), Box<dyn Error>> {
+    let config = ServiceConfig {
+        jwt_secret: "nots3cr3t".to_string(),
+        ..Default::default()
+    };
+    let (tx, _) = channel(16);
+    let web_service = MainServiceBuilder::new(tx.clone(), public_dir())
+        .with_config(config)
+        .build();
+
+    tx.send(Event::RootUserChanged(RootUser {
+        password: Some("r00tpassw0rd".to_string()),
+        hashed_password: Some(false),
+        ssh_public_key: None,
+    }))?;
+    tx.send(Event::FirstUserChanged(FirstUser {
+        full_name: "Tux".to_string(),
+        user_name: "tux".to_string(),
+        password: "tuxpassw0rd".to_string(),
+        hashed_password: false,
+    }))?;
+    // give the journal a chance to record the events
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let token = AuthToken::generate("nots3cr3t")?;
+    let request = Request::builder()
+        .uri("/api/auth/tokens")
+        .method(Method::POST)
+        .header("Authorization", format!("Bearer {}", token.as_str()))
+        .header("Content-Type", "application/json")
+        .body(Body::from("{\"scope\":\"read-only\",\"ttl\":60}"))
+        .unwrap();
+    let response = web_service.clone().oneshot(request).await.unwrap();
+    let body = body_to_string(response.into_body()).await;
+    let body: serde_json::Value = serde_json::from_str(&body)?;
+    let read_only = body["token"].as_str().unwrap().to_string();
+
+    let events = |token: String| {
+        Request::builder()
+            .uri("/api/events")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Last-Event-ID", "0")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // a read-only token never sees the passwords
+    let response = web_service
+        .clone()
+        .oneshot(events(read_only))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut body = response.into_body();
+    for event_type in ["RootUserChanged", "FirstUserChanged"] {
+        let frame = body.frame().await.unwrap()?.into_data().unwrap();
+        let frame = String::from_utf8(frame.to_vec())?;
+        assert!(frame.contains(event_type));
+        assert!(!frame.contains("passw0rd"));
+    }
+
+    // but a full access one does
+    let response = web_service
+        .oneshot(events(token.as_str().to_string()))
+        .await
+        .unwrap();
+    let mut body = response.into_body();
+    let frame = body.frame().await.unwrap()?.into_data().unwrap();
+    assert!(String::from_utf8(frame.to_vec())?.contains("r00tpassw0rd"));
+    Ok(())
+}
@@ -42,10 +42,28 @@ module Agama
         end.compact
       end
 
+      # Classifies the profile elements according to their support level.
+      #
+      # Unknown elements are not included.
+      #
+      # @param profile [Yast::ProfileHash] AutoYaST profile to check
+      # @return [Hash<String, Array<Hash>>] Supported ("supported"), partially supported
+      #   ("partial") and ignored ("ignored") elements, including their path, support level,
+      #   Agama equivalent and notes
+      def report(profile)
+        description = ProfileDescription.load
+        report = { "supported" => [], "partial" => [], "ignored" => [] }
+        report_elements(description.elements, profile, "", report)
+        report
+      end
+
     private
 
       # Returns the elements from the profile
       #
+      # Elements are identified by their full path, so an element nested in an unknown section is
+      # not mistaken for a top level one.
+      #
       # @return [Array<String>] List of element IDs (e.g., "networking/backend")
       def elements_from(profile, parent = "")
         return [] unless profile.is_a?(Hash)
@@ -55,15 +73,86 @@ module Agama
 
           children = if v.is_a?(Array)
             v.map.with_index do |e, i|
-              elements_from(e, "#{parent}#{ProfileDescription::SEPARATOR}#{k}[#{i}]")
+              elements_from(e, "#{current}[#{i}]")
             end
           else
-            elements_from(v, k)
+            elements_from(v, current)
           end
 
           [current, *children]
         end.flatten
       end
+
+      # Adds the known elements of a profile section to the report.
+      #
+      # @param elements [Array<ProfileElement>] Known elements of the section
+      # @param profile [Object] Profile section
+      # @param parent [String] Path of the section
+      # @param report [Hash<String, Array<Hash>>] Report to add the elements to
+      # @return [Array<Symbol>] Support levels of the elements included in the section
+      def report_elements(elements, profile, parent, report)
+        return [] unless profile.is_a?(Hash)
+
+        profile.flat_map do |key, value|
+          element = elements.find { |e| e.short_key.delete_suffix("[]") == key }
+          next [] unless element
+
+          path = parent.empty? ? key : "#{parent}#{ProfileDescription::SEPARATOR}#{key}"
+          support = report_support(element, value, path, report)
+          report[report_group(support)] << {
+            "path"    => path,
+            "support" => support.to_s,
+            "agama"   => element.agama,
+            "notes"   => element.notes
+          }.compact
+          [support]
+        end
+      end
+
+      # Returns the support level of an element for the report.
+      #
+      # Sections are considered as supported when they are known, so they are not reported as
+      # unsupported. However, the report infers their support level from the children included in
+      # the profile.
+      #
+      # @param element [ProfileElement]
+      # @param value [Object] Value of the element in the profile
+      # @param path [String] Path of the element
+      # @param report [Hash<String, Array<Hash>>] Report to add the children to
+      # @return [Symbol]
+      def report_support(element, value, path, report)
+        return element.support if element.children.empty?
+
+        nested = if value.is_a?(Array)
+          value.each_with_index.flat_map do |e, i|
+            report_elements(element.children, e, "#{path}[#{i}]", report)
+          end
+        else
+          report_elements(element.children, value, path, report)
+        end
+
+        nested.uniq!
+        return element.support if nested.empty?
+        return nested.first if nested.size == 1
+        return :partial if nested.include?(:yes) || nested.include?(:partial)
+
+        :no
+      end
+
+      # Returns the report group for the given support level.
+      #
+      # @param support [Symbol]
+      # @return [String]
+      def report_group(support)
+        case support
+        when :yes
+          "supported"
+        when :partial
+          "partial"
+        else
+          "ignored"
+        end
+      end
     end
   end
 end
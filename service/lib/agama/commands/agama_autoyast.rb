@@ -37,7 +37,8 @@ module Agama
     # Command to convert an AutoYaST profile to an Agama configuration.
     #
     # It fetches the profile, checks for unsupported elements and converts it to an Agama
-    # configuration file.
+    # configuration file. Additionally, it writes a conversion report (autoinst-report.json)
+    # listing the supported, partially supported and ignored elements.
     #
     # @param url [String] URL of the AutoYaST profile
     # @param dir [String] Directory to write the converted profile
@@ -55,6 +56,8 @@ module Agama
         return false unless report_unsupported(unsupported)
 
         write_agama_config(profile)
+        write_report(profile)
+        true
       end
 
     private
@@ -95,6 +98,16 @@ module Agama
         raise CouldNotWriteAgamaConfig
       end
 
+      def write_report(profile)
+        report = Agama::AutoYaST::ProfileChecker.new.report(profile)
+        File.write(
+          File.join(directory, "autoinst-report.json"),
+          JSON.pretty_generate(report)
+        )
+      rescue StandardError => e
+        logger.warn "Could not write the conversion report: #{e.message}"
+      end
+
       def questions_client
         @questions_client ||= Agama::DBus::Clients::Questions.new(logger: logger)
       end
@@ -76,5 +76,50 @@ describe Agama::AutoYaST::ProfileChecker do
         )
       end
     end
+
+    context "when a known element is nested in an unknown section" do
+      let(:profile) do
+        { "custom" => { "networking" => { "backend" => "wicked" } } }
+      end
+
+      it "does not report the element" do
+        expect(subject.find_unsupported(profile)).to eq([])
+      end
+    end
+  end
+
+  describe "#report" do
+    let(:profile) do
+      {
+        "software"     => { "products" => ["openSUSE"] },
+        "networking"   => {
+          "backend"    => "wicked",
+          "interfaces" => [{ "device" => "eth0" }]
+        },
+        "scripts"      => { "pre-scripts" => [{ "rerun" => true }] },
+        "iscsi-client" => {},
+        "unknown"      => {}
+      }
+    end
+
+    it "classifies the known elements according to their support level" do
+      report = subject.report(profile)
+      expect(report["supported"]).to contain_exactly(
+        { "path" => "software", "support" => "yes" },
+        { "path" => "software/products", "support" => "yes", "agama" => "software.id" },
+        a_hash_including("path" => "networking/interfaces", "support" => "yes"),
+        { "path" => "networking/interfaces[0]/device", "support" => "yes", "agama" => "interface" }
+      )
+      expect(report["partial"]).to contain_exactly(
+        { "path" => "networking", "support" => "partial" }
+      )
+      expect(report["ignored"]).to contain_exactly(
+        a_hash_including("path" => "networking/backend", "support" => "no"),
+        { "path" => "scripts", "support" => "no" },
+        { "path" => "scripts/pre-scripts", "support" => "no" },
+        { "path" => "scripts/pre-scripts[0]/rerun", "support" => "no" },
+        { "path" => "iscsi-client", "support" => "planned" }
+      )
+    end
   end
 end
@@ -64,6 +64,14 @@ describe Agama::Commands::AgamaAutoYaST do
       expect(autoinst).to include("openSUSE")
     end
 
+    it "writes the conversion report to the given directory" do
+      subject.run
+      report = JSON.parse(File.read(File.join(tmpdir, "autoinst-report.json")))
+      expect(report["supported"]).to include(
+        "path" => "software/products", "support" => "yes", "agama" => "software.id"
+      )
+    end
+
     context "when the profile includes unsupported elements" do
       let(:profile) do
         Yast::ProfileHash.new({ "networking" => { "backend" => "wicked" } })